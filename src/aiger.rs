@@ -0,0 +1,638 @@
+// Copyright (c) 2020 xhe
+
+//! Conversion between gate-level modules and And-Inverter Graphs in the
+//! AIGER format, binary (`aig`) or ASCII (`aag`).
+//!
+//! AIGER has a single implicit clock: only `$_FF_` flip-flops have a form
+//! in it, with their initial value taken from the `init` attribute of
+//! their output.
+
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+// What drives a net of the module being written.
+enum Driver<'a> {
+    Var(u32),
+    Cell(&'a Cell),
+}
+
+struct Aig<'a> {
+    wt: WireTable,
+    sigmap: SigMap,
+    drivers: HashMap<SigBit, Driver<'a>>,
+    lits: HashMap<SigBit, u32>,
+    visiting: HashSet<SigBit>,
+    strash: HashMap<(u32, u32), u32>,
+    ands: Vec<(u32, u32, u32)>,
+    next: u32,
+}
+
+impl<'a> Aig<'a> {
+    fn and(&mut self, a: u32, b: u32) -> u32 {
+        let (a, b) = if a > b { (a, b) } else { (b, a) };
+        if b == 0 || a == b ^ 1 {
+            return 0;
+        }
+        if b == 1 || a == b {
+            return a;
+        }
+        if let Some(l) = self.strash.get(&(a, b)) {
+            return *l;
+        }
+        let l = self.next * 2;
+        self.next += 1;
+        self.ands.push((l, a, b));
+        self.strash.insert((a, b), l);
+        l
+    }
+
+    fn or(&mut self, a: u32, b: u32) -> u32 {
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    fn xor(&mut self, a: u32, b: u32) -> u32 {
+        let x = self.and(a, b ^ 1);
+        let y = self.and(a ^ 1, b);
+        self.or(x, y)
+    }
+
+    fn mux(&mut self, a: u32, b: u32, s: u32) -> u32 {
+        let x = self.and(s, b);
+        let y = self.and(s ^ 1, a);
+        self.or(x, y)
+    }
+
+    fn port(&mut self, cell: &Cell, k: &str) -> Result<u32> {
+        let b = match cell.port(k).map(|s| self.wt.bits(s)).transpose()? {
+            Some(b) if b.len() == 1 => b,
+            _ => bail!("port `{}' of cell `{}' is not a single bit", k, cell.i2()),
+        };
+        self.lit(&b[0])
+    }
+
+    // Literal of `b`, building the cone of logic driving it.
+    fn lit(&mut self, b: &SigBit) -> Result<u32> {
+        let b = self.sigmap.map_bit(b);
+        if let Some(l) = self.lits.get(&b) {
+            return Ok(*l);
+        }
+        let cell = match self.drivers.get(&b) {
+            None => return Ok(matches!(b, SigBit::Const(State::S1)) as u32),
+            Some(Driver::Var(v)) => return Ok(v * 2),
+            Some(Driver::Cell(c)) => *c,
+        };
+        if !self.visiting.insert(b.clone()) {
+            bail!("combinational loop through `{}'", b);
+        }
+        let a = self.port(cell, "\\A")?;
+        let l = match cell.i1().as_str() {
+            "$_BUF_" => a,
+            "$_NOT_" => a ^ 1,
+            "$_MUX_" | "$_NMUX_" => {
+                let x = self.port(cell, "\\B")?;
+                let s = self.port(cell, "\\S")?;
+                self.mux(a, x, s) ^ (cell.i1() == "$_NMUX_") as u32
+            }
+            tp => {
+                let x = self.port(cell, "\\B")?;
+                match tp {
+                    "$_AND_" => self.and(a, x),
+                    "$_NAND_" => self.and(a, x) ^ 1,
+                    "$_OR_" => self.or(a, x),
+                    "$_NOR_" => self.or(a, x) ^ 1,
+                    "$_XOR_" => self.xor(a, x),
+                    "$_XNOR_" => self.xor(a, x) ^ 1,
+                    "$_ANDNOT_" => self.and(a, x ^ 1),
+                    _ => self.or(a, x ^ 1),
+                }
+            }
+        };
+        self.visiting.remove(&b);
+        self.lits.insert(b, l);
+        Ok(l)
+    }
+}
+
+const GATES: &[&str] = &[
+    "$_BUF_",
+    "$_NOT_",
+    "$_AND_",
+    "$_NAND_",
+    "$_OR_",
+    "$_NOR_",
+    "$_XOR_",
+    "$_XNOR_",
+    "$_ANDNOT_",
+    "$_ORNOT_",
+    "$_MUX_",
+    "$_NMUX_",
+];
+
+// Symbol of bit `i` of wire `w`: its name, indexed if it is wider than one
+// bit.
+fn symbol(wt: &WireTable, w: &str, i: i64) -> String {
+    let name = w.strip_prefix('\\').unwrap_or(w);
+    match wt.sigspec(&[SigBit::Wire((w.to_string(), i))]) {
+        SigSpec::Refer((_, Some((n, _)))) if wt.width(w) != Some(1) => format!("{}[{}]", name, n),
+        _ => name.to_string(),
+    }
+}
+
+// Binary AIGER number: 7 bits per byte, LSB first.
+fn encode<W: Write>(w: &mut W, mut n: u32) -> Result<()> {
+    while n >= 0x80 {
+        w.write_all(&[(n & 0x7f) as u8 | 0x80])?;
+        n >>= 7;
+    }
+    w.write_all(&[n as u8])?;
+    Ok(())
+}
+
+/// Write `module` as AIGER, binary if `binary` is set and ASCII otherwise.
+///
+/// The module must be made of the single-bit gates `$_AND_`, `$_OR_`,
+/// `$_MUX_` and friends, and of `$_FF_`. Input ports become inputs, and so
+/// do nets without a driver; output ports become outputs. Public names of
+/// inputs, outputs and latches are written to the symbol table.
+pub fn write_aiger<W: Write>(w: &mut W, module: &Module, binary: bool) -> Result<()> {
+    if !module.processes().is_empty() || !module.memories().is_empty() {
+        bail!(
+            "module `{}' has processes or memories, which have no AIGER form",
+            module.ident()
+        );
+    }
+    let wt = WireTable::new(module);
+    let mut sigmap = SigMap::new(module, &wt)?;
+    let mut ports: Vec<&Wire> = module.wires().iter().filter(|w| *w.port() > 0).collect();
+    ports.sort_by_key(|w| *w.port());
+    for w in ports.iter().filter(|w| *w.input()) {
+        for b in wt.bits(&SigSpec::wire(w.id()))? {
+            sigmap.promote(&b);
+        }
+    }
+
+    let mut drivers = HashMap::new();
+    let mut ffs = Vec::new();
+    let mut used = Vec::new();
+    for c in module.cells() {
+        let tp = c.i1().as_str();
+        let out = match tp {
+            "$_FF_" => {
+                ffs.push(c);
+                "\\Q"
+            }
+            _ if GATES.contains(&tp) => "\\Y",
+            _ => bail!("cell `{}' of type `{}' has no AIGER form", c.i2(), tp),
+        };
+        for (k, s) in c.connects() {
+            let bits = sigmap.map(&wt.bits(s)?);
+            if k == out {
+                for b in bits {
+                    drivers.insert(b, Driver::Cell(c));
+                }
+            } else {
+                used.extend(bits);
+            }
+        }
+    }
+
+    // inputs: the input ports, then undriven nets
+    let mut inputs: Vec<(SigBit, Option<String>)> = Vec::new();
+    for w in ports.iter().filter(|w| *w.input()) {
+        for (i, b) in wt.bits(&SigSpec::wire(w.id()))?.iter().enumerate() {
+            let b = sigmap.map_bit(b);
+            if !b.is_const() && !drivers.contains_key(&b) {
+                drivers.insert(b.clone(), Driver::Var(inputs.len() as u32 + 1));
+                inputs.push((b, Some(symbol(&wt, w.id(), i as i64))));
+            }
+        }
+    }
+    let mut outputs = Vec::new();
+    for w in ports.iter().filter(|w| *w.output()) {
+        for (i, b) in wt.bits(&SigSpec::wire(w.id()))?.into_iter().enumerate() {
+            used.push(sigmap.map_bit(&b));
+            outputs.push((b, symbol(&wt, w.id(), i as i64)));
+        }
+    }
+    for b in used {
+        if !b.is_const() && !drivers.contains_key(&b) {
+            drivers.insert(b.clone(), Driver::Var(inputs.len() as u32 + 1));
+            inputs.push((b, None));
+        }
+    }
+
+    let mut init = HashMap::new();
+    for w in module.wires() {
+        if let Some(c) = w.attrs().get("\\init") {
+            for (b, s) in wt.bits(&SigSpec::wire(w.id()))?.iter().zip(c.to_bits()) {
+                init.insert(sigmap.map_bit(b), s);
+            }
+        }
+    }
+    let mut latches = Vec::new();
+    for (i, c) in ffs.iter().enumerate() {
+        let q = match c.port("\\Q").map(|s| wt.bits(s)).transpose()? {
+            Some(q) if q.len() == 1 => sigmap.map_bit(&q[0]),
+            _ => bail!("port `Q' of cell `{}' is not a single bit", c.i2()),
+        };
+        let var = (inputs.len() + i) as u32 + 1;
+        drivers.insert(q.clone(), Driver::Var(var));
+        let name = match &q {
+            SigBit::Wire((w, n)) if w.starts_with('\\') => Some(symbol(&wt, w, *n)),
+            _ => None,
+        };
+        let reset = match init.get(&q) {
+            Some(State::S0) => 0,
+            Some(State::S1) => 1,
+            _ => var * 2,
+        };
+        latches.push((c, reset, name));
+    }
+
+    let mut aig = Aig {
+        wt,
+        sigmap,
+        drivers,
+        lits: HashMap::new(),
+        visiting: HashSet::new(),
+        strash: HashMap::new(),
+        ands: Vec::new(),
+        next: (inputs.len() + latches.len()) as u32 + 1,
+    };
+    let mut next = Vec::new();
+    for (c, _, _) in latches.iter() {
+        next.push(aig.port(c, "\\D")?);
+    }
+    let mut outs = Vec::new();
+    for (b, _) in outputs.iter() {
+        outs.push(aig.lit(b)?);
+    }
+
+    let (i, l) = (inputs.len(), latches.len());
+    let header = format!(
+        "{} {} {} {} {} {}",
+        if binary { "aig" } else { "aag" },
+        aig.next - 1,
+        i,
+        l,
+        outs.len(),
+        aig.ands.len()
+    );
+    writeln!(w, "{}", header)?;
+    if !binary {
+        for v in 1..=i {
+            writeln!(w, "{}", v * 2)?;
+        }
+    }
+    for (k, ((_, reset, _), n)) in latches.iter().zip(next.iter()).enumerate() {
+        let lit = (i + k + 1) as u32 * 2;
+        let mut line = if binary {
+            n.to_string()
+        } else {
+            format!("{} {}", lit, n)
+        };
+        if *reset != 0 {
+            line.push_str(&format!(" {}", reset));
+        }
+        writeln!(w, "{}", line)?;
+    }
+    for o in outs.iter() {
+        writeln!(w, "{}", o)?;
+    }
+    for (lhs, a, b) in aig.ands.iter() {
+        if binary {
+            encode(w, lhs - a)?;
+            encode(w, a - b)?;
+        } else {
+            writeln!(w, "{} {} {}", lhs, a, b)?;
+        }
+    }
+    for (k, (_, name)) in inputs.iter().enumerate() {
+        if let Some(n) = name {
+            writeln!(w, "i{} {}", k, n)?;
+        }
+    }
+    for (k, (_, _, name)) in latches.iter().enumerate() {
+        if let Some(n) = name {
+            writeln!(w, "l{} {}", k, n)?;
+        }
+    }
+    for (k, (_, name)) in outputs.iter().enumerate() {
+        writeln!(w, "o{} {}", k, name)?;
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn line(&mut self) -> Option<&'a str> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let end = rest.iter().position(|c| *c == b'\n').unwrap_or(rest.len());
+        self.pos += end + 1;
+        std::str::from_utf8(&rest[..end]).ok()
+    }
+
+    fn numbers(&mut self, what: &str) -> Result<Vec<u32>> {
+        let l = self
+            .line()
+            .ok_or_else(|| anyhow!("unexpected end of file reading {}", what))?;
+        l.split_whitespace()
+            .map(|n| n.parse().map_err(|_| anyhow!("invalid {} `{}'", what, l)))
+            .collect()
+    }
+
+    fn decode(&mut self) -> Result<u32> {
+        let mut n = 0;
+        for shift in (0..35).step_by(7) {
+            let c = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| anyhow!("unexpected end of file reading AND gates"))?;
+            self.pos += 1;
+            n |= ((c & 0x7f) as u32) << shift;
+            if c & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        bail!("invalid number in AND gates")
+    }
+}
+
+// Wires for named bits `name[i]`: one per name, wide enough for all
+// indices. Bits without a symbol are named `<prefix><k>`.
+fn group(syms: &HashMap<usize, String>, n: usize, prefix: &str) -> Vec<(String, Option<i64>)> {
+    (0..n)
+        .map(|k| {
+            let name = syms.get(&k).cloned().unwrap_or(format!("{}{}", prefix, k));
+            match name.strip_suffix(']').and_then(|s| s.rsplit_once('[')) {
+                Some((base, i)) if i.parse::<i64>().is_ok() => {
+                    (format!("\\{}", base), i.parse().ok())
+                }
+                _ => (format!("\\{}", name), None),
+            }
+        })
+        .collect()
+}
+
+// Declare the wires of `bits` from `group`, as ports from `port` on, and
+// return their bits. Symbols naming a bit twice, a wire both with and
+// without an index, or a wire already in `m` are an error.
+fn declare(
+    m: &mut Module,
+    bits: &[(String, Option<i64>)],
+    port: &mut i64,
+    input: bool,
+) -> Result<Vec<SigBit>> {
+    let mut wires: BTreeMap<&str, Option<(i64, i64)>> = BTreeMap::new();
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    for (w, i) in bits {
+        let sym = match i {
+            Some(i) => format!("{}[{}]", &w[1..], i),
+            None => w[1..].to_string(),
+        };
+        if !seen.insert((w, i)) {
+            bail!("duplicate symbol `{}'", sym);
+        }
+        if m.wires().iter().any(|x| x.id() == w) {
+            bail!("symbol `{}' names both an input and an output", sym);
+        }
+        match wires.get_mut(w.as_str()) {
+            None => {
+                order.push(w.as_str());
+                wires.insert(w, i.map(|i| (i, i)));
+            }
+            Some(r) => {
+                *r = match (*r, i) {
+                    (Some((lo, hi)), Some(i)) => Some((lo.min(*i), hi.max(*i))),
+                    _ => bail!(
+                        "symbol `{}' is used both with and without an index",
+                        &w[1..]
+                    ),
+                }
+            }
+        }
+    }
+    let mut wt = WireTable::default();
+    for w in order {
+        *port += 1;
+        let dir = if input {
+            WireOption::Input(*port)
+        } else {
+            WireOption::Output(*port)
+        };
+        let mut opts = vec![dir];
+        if let Some((lo, hi)) = wires[w] {
+            opts.push(WireOption::Width(hi - lo + 1));
+            opts.push(WireOption::Offset(lo));
+        }
+        let wire = Wire::new(w.to_string(), opts);
+        wt.insert(&wire);
+        m.wires_mut().push(wire);
+    }
+    bits.iter()
+        .map(|(w, i)| {
+            let s = match i {
+                None => SigSpec::wire(w),
+                Some(i) => SigSpec::Refer((w.clone(), Some((*i, *i)))),
+            };
+            Ok(wt.bits(&s)?.remove(0))
+        })
+        .collect()
+}
+
+/// Read an AIGER file, binary or ASCII, into a module named `ident` of
+/// `$_AND_`, `$_NOT_` and `$_FF_` cells. Inputs and outputs become ports,
+/// named after their symbols with `name[i]` symbols grouped into wide
+/// ports, and bad state properties become further outputs.
+pub fn read_aiger(data: &[u8], ident: &str) -> Result<Module> {
+    let mut r = Reader { data, pos: 0 };
+    let header = r
+        .line()
+        .ok_or_else(|| anyhow!("missing AIGER header"))?
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let binary = match header.first() {
+        Some(&"aig") => true,
+        Some(&"aag") => false,
+        _ => bail!("invalid AIGER header"),
+    };
+    let n: Vec<usize> = header[1..]
+        .iter()
+        .map(|n| n.parse().map_err(|_| anyhow!("invalid AIGER header")))
+        .collect::<Result<_>>()?;
+    if n.len() < 5 {
+        bail!("invalid AIGER header");
+    }
+    let (i, l, o, a) = (n[1], n[2], n[3], n[4]);
+    let b = n.get(5).cloned().unwrap_or(0);
+    if n[5..].iter().skip(1).any(|n| *n != 0) {
+        bail!("constraints and liveness properties are not supported");
+    }
+
+    if !binary {
+        for k in 0..i {
+            if r.numbers("input")? != [(k as u32 + 1) * 2] {
+                bail!("inputs must be numbered in order");
+            }
+        }
+    }
+    let mut latches = Vec::new();
+    for k in 0..l {
+        let lit = (i + k + 1) as u32 * 2;
+        let v = r.numbers("latch")?;
+        let v = if binary { v } else { v[1..].to_vec() };
+        match v.as_slice() {
+            [next] => latches.push((lit, *next, 0)),
+            [next, reset] => latches.push((lit, *next, *reset)),
+            _ => bail!("invalid latch {}", k),
+        }
+    }
+    let mut outputs = Vec::new();
+    for _ in 0..o + b {
+        match r.numbers("output")?.as_slice() {
+            [lit] => outputs.push(*lit),
+            _ => bail!("invalid output"),
+        }
+    }
+    let mut ands = Vec::new();
+    for k in 0..a {
+        if binary {
+            let lhs = (i + l + k + 1) as u32 * 2;
+            let d0 = r.decode()?;
+            let d1 = r.decode()?;
+            let rhs0 = lhs
+                .checked_sub(d0)
+                .ok_or_else(|| anyhow!("invalid AND gate {}", k))?;
+            let rhs1 = rhs0
+                .checked_sub(d1)
+                .ok_or_else(|| anyhow!("invalid AND gate {}", k))?;
+            ands.push((lhs, rhs0, rhs1));
+        } else {
+            match r.numbers("AND gate")?.as_slice() {
+                [lhs, rhs0, rhs1] => ands.push((*lhs, *rhs0, *rhs1)),
+                _ => bail!("invalid AND gate {}", k),
+            }
+        }
+    }
+    let mut syms: HashMap<char, HashMap<usize, String>> = HashMap::new();
+    while let Some(line) = r.line() {
+        let mut chars = line.chars();
+        match chars.next() {
+            Some('c') | None => break,
+            Some(t) => {
+                let (k, name) = chars
+                    .as_str()
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow!("invalid symbol `{}'", line))?;
+                let k = k
+                    .parse()
+                    .map_err(|_| anyhow!("invalid symbol `{}'", line))?;
+                syms.entry(t).or_default().insert(k, name.to_string());
+            }
+        }
+    }
+    let none = HashMap::new();
+    let syms = |t| syms.get(&t).unwrap_or(&none);
+
+    let mut m = Module::new(ident.to_string(), vec![]);
+    let mut port = 0;
+    let ins = declare(&mut m, &group(syms('i'), i, "i"), &mut port, true)?;
+    let mut outs = group(syms('o'), o, "o");
+    outs.extend(group(syms('b'), b, "b"));
+    let outs = declare(&mut m, &outs, &mut port, false)?;
+
+    let mut vars: HashMap<u32, SigBit> = HashMap::new();
+    for (k, b) in ins.into_iter().enumerate() {
+        vars.insert(k as u32 + 1, b);
+    }
+    for (k, (lit, _, reset)) in latches.iter().enumerate() {
+        let id = match syms('l').get(&k) {
+            Some(n) => format!("\\{}", n),
+            None => format!("$l{}", k),
+        };
+        if m.wires().iter().any(|x| *x.id() == id) {
+            bail!("latch symbol `{}' is already used", &id[1..]);
+        }
+        let mut w = Wire::new(id.clone(), vec![]);
+        let init = match *reset {
+            0 => Some(State::S0),
+            1 => Some(State::S1),
+            _ => None,
+        };
+        if let Some(s) = init {
+            w.attrs_mut()
+                .insert("\\init".to_string(), Const::from_bits(&[s]));
+        }
+        m.wires_mut().push(w);
+        vars.insert(lit / 2, SigBit::Wire((id, 0)));
+    }
+    for (lhs, _, _) in ands.iter() {
+        let id = format!("$n{}", lhs / 2);
+        m.wires_mut().push(Wire::new(id.clone(), vec![]));
+        vars.insert(lhs / 2, SigBit::Wire((id, 0)));
+    }
+
+    let mut wt = WireTable::new(&m);
+    let mut nots: HashMap<u32, SigBit> = HashMap::new();
+    let mut bit = |m: &mut Module, wt: &mut WireTable, lit: u32| -> Result<SigSpec> {
+        let b = match lit {
+            0 => SigBit::Const(State::S0),
+            1 => SigBit::Const(State::S1),
+            _ => {
+                let v = vars
+                    .get(&(lit / 2))
+                    .ok_or_else(|| anyhow!("undefined literal {}", lit))?
+                    .clone();
+                if lit & 1 == 0 {
+                    v
+                } else if let Some(n) = nots.get(&(lit / 2)) {
+                    n.clone()
+                } else {
+                    let id = format!("$n{}_not", lit / 2);
+                    let w = Wire::new(id.clone(), vec![]);
+                    wt.insert(&w);
+                    m.wires_mut().push(w);
+                    let y = SigBit::Wire((id.clone(), 0));
+                    let c = m.new_cell("$_NOT_", "not");
+                    c.set_port("\\A", wt.sigspec(&[v]));
+                    c.set_port("\\Y", SigSpec::wire(&id));
+                    nots.insert(lit / 2, y.clone());
+                    y
+                }
+            }
+        };
+        Ok(wt.sigspec(&[b]))
+    };
+    for (lhs, rhs0, rhs1) in ands.iter() {
+        let a = bit(&mut m, &mut wt, *rhs0)?;
+        let b = bit(&mut m, &mut wt, *rhs1)?;
+        let y = bit(&mut m, &mut wt, *lhs)?;
+        let c = m.new_cell("$_AND_", "and");
+        c.set_port("\\A", a);
+        c.set_port("\\B", b);
+        c.set_port("\\Y", y);
+    }
+    for (lit, next, _) in latches.iter() {
+        let d = bit(&mut m, &mut wt, *next)?;
+        let q = bit(&mut m, &mut wt, *lit)?;
+        let c = m.new_cell("$_FF_", "ff");
+        c.set_port("\\D", d);
+        c.set_port("\\Q", q);
+    }
+    for (lit, o) in outputs.iter().zip(outs) {
+        let s = bit(&mut m, &mut wt, *lit)?;
+        let c = Connect::new(wt.sigspec(&[o]), s);
+        m.connects_mut().push(c);
+    }
+    Ok(m)
+}
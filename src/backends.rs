@@ -0,0 +1,18 @@
+// Copyright (c) 2020 xhe
+
+//! Writers of designs in other formats.
+
+mod blif;
+pub use blif::*;
+
+mod dimacs;
+pub use dimacs::*;
+
+mod dot;
+pub use dot::*;
+
+mod smt2;
+pub use smt2::*;
+
+mod verilog;
+pub use verilog::*;
@@ -0,0 +1,304 @@
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+// Truth table rows of the single-output gates, inputs in port order.
+const GATES: &[(&str, &[&str], &[&str])] = &[
+    ("$_BUF_", &["A"], &["1 1"]),
+    ("$_NOT_", &["A"], &["0 1"]),
+    ("$_AND_", &["A", "B"], &["11 1"]),
+    ("$_NAND_", &["A", "B"], &["0- 1", "-0 1"]),
+    ("$_OR_", &["A", "B"], &["1- 1", "-1 1"]),
+    ("$_NOR_", &["A", "B"], &["00 1"]),
+    ("$_XOR_", &["A", "B"], &["10 1", "01 1"]),
+    ("$_XNOR_", &["A", "B"], &["11 1", "00 1"]),
+    ("$_ANDNOT_", &["A", "B"], &["10 1"]),
+    ("$_ORNOT_", &["A", "B"], &["1- 1", "-0 1"]),
+    ("$_MUX_", &["A", "B", "S"], &["1-0 1", "-11 1"]),
+    ("$_NMUX_", &["A", "B", "S"], &["0-0 1", "-01 1"]),
+];
+
+// Nets driven by the constant gates emitted at the end of a model.
+const FALSE: &str = "$false";
+const TRUE: &str = "$true";
+const UNDEF: &str = "$undef";
+
+// Widest `$lut` written, its table has a row per input value.
+const MAX_LUT_WIDTH: usize = 20;
+
+struct Writer<'a> {
+    module: &'a Module,
+    wt: WireTable,
+    sigmap: SigMap,
+    /// Initial values of flip-flop outputs, by net.
+    init: HashMap<SigBit, State>,
+    /// Constant nets used by the model.
+    consts: BTreeSet<&'static str>,
+    body: Vec<String>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(module: &'a Module) -> Result<Self> {
+        let wt = WireTable::new(module);
+        let mut sigmap = SigMap::new(module, &wt)?;
+        // nets connected to an input port are named after it
+        for w in module.wires().iter().filter(|w| *w.input()) {
+            for b in wt.bits(&SigSpec::wire(w.id()))? {
+                sigmap.promote(&b);
+            }
+        }
+        let mut init = HashMap::new();
+        for w in module.wires() {
+            if let Some(c) = w.attrs().get("\\init") {
+                let bits = wt.bits(&SigSpec::wire(w.id()))?;
+                for (b, s) in bits.iter().zip(c.to_bits()) {
+                    init.insert(sigmap.map_bit(b), s);
+                }
+            }
+        }
+        Ok(Self {
+            module,
+            wt,
+            sigmap,
+            init,
+            consts: BTreeSet::new(),
+            body: Vec::new(),
+        })
+    }
+
+    // Name of the net of `b`: the wire name, with the bit index if the wire
+    // is wider than one bit.
+    fn name(&mut self, b: &SigBit) -> String {
+        match self.sigmap.map_bit(b) {
+            SigBit::Const(s) => {
+                let n = match s {
+                    State::S0 => FALSE,
+                    State::S1 => TRUE,
+                    _ => UNDEF,
+                };
+                self.consts.insert(n);
+                n.to_string()
+            }
+            b => self.wire_bit(&b),
+        }
+    }
+
+    fn wire_bit(&self, b: &SigBit) -> String {
+        match self.wt.sigspec(std::slice::from_ref(b)) {
+            SigSpec::Refer((w, Some((i, _)))) if self.wt.width(&w) != Some(1) => {
+                format!("{}[{}]", blif_id(&w), i)
+            }
+            SigSpec::Refer((w, _)) => blif_id(&w),
+            s => unreachable!("`{}' is not a wire bit", s),
+        }
+    }
+
+    fn port(&mut self, cell: &Cell, k: &str) -> Result<Vec<String>> {
+        let bits = match cell.port(&format!("\\{}", k)) {
+            Some(s) => self.wt.bits(s)?,
+            None => bail!("cell `{}' has no port `{}'", cell.i2(), k),
+        };
+        Ok(bits.iter().map(|b| self.name(b)).collect())
+    }
+
+    fn names(&mut self, inputs: &[String], output: &str, rows: &[String]) {
+        let mut line = ".names".to_string();
+        for n in inputs.iter().chain(std::iter::once(&output.to_string())) {
+            line.push(' ');
+            line.push_str(n);
+        }
+        self.body.push(line);
+        self.body.extend(rows.iter().cloned());
+    }
+
+    // Initial value of a latch output as BLIF writes it: 0, 1, or 3 for
+    // unknown.
+    fn init_of(&self, cell: &Cell) -> Result<i32> {
+        let q = match cell.port("\\Q") {
+            Some(s) => self.wt.bits(s)?,
+            None => bail!("cell `{}' has no port `Q'", cell.i2()),
+        };
+        Ok(
+            match q
+                .first()
+                .and_then(|b| self.init.get(&self.sigmap.map_bit(b)))
+            {
+                Some(State::S0) => 0,
+                Some(State::S1) => 1,
+                _ => 3,
+            },
+        )
+    }
+
+    fn latch(&mut self, cell: &Cell, kind: Option<(&str, &str)>) -> Result<()> {
+        let d = self.port(cell, "D")?.remove(0);
+        let q = self.port(cell, "Q")?.remove(0);
+        let init = self.init_of(cell)?;
+        let line = match kind {
+            Some((tp, ctrl)) => {
+                let c = self.port(cell, ctrl)?.remove(0);
+                format!(".latch {} {} {} {} {}", d, q, tp, c, init)
+            }
+            None => format!(".latch {} {} {}", d, q, init),
+        };
+        self.body.push(line);
+        Ok(())
+    }
+
+    fn lut(&mut self, cell: &Cell) -> Result<()> {
+        let a = self.port(cell, "A")?;
+        let y = self.port(cell, "Y")?.remove(0);
+        let table = match cell.param("\\LUT") {
+            Some(c) => c.to_bits(),
+            None => bail!("cell `{}' has no parameter `LUT'", cell.i2()),
+        };
+        if a.len() > MAX_LUT_WIDTH {
+            bail!(
+                "cell `{}' has {} inputs, more than the {} supported",
+                cell.i2(),
+                a.len(),
+                MAX_LUT_WIDTH
+            );
+        }
+        let rows: Vec<String> = (0..1usize << a.len())
+            .filter(|i| table.get(*i) == Some(&State::S1))
+            .map(|i| {
+                let bits: String = (0..a.len())
+                    .map(|j| if (i >> j) & 1 == 1 { '1' } else { '0' })
+                    .collect();
+                format!("{} 1", bits)
+            })
+            .collect();
+        self.names(&a, &y, &rows);
+        Ok(())
+    }
+
+    fn subckt(&mut self, cell: &Cell) -> Result<()> {
+        let mut line = format!(".subckt {}", blif_id(cell.i1()));
+        let mut ports: Vec<(&String, &SigSpec)> = cell.connects().iter().collect();
+        ports.sort_by(|a, b| a.0.cmp(b.0));
+        for (k, s) in ports {
+            let bits = self.wt.bits(s)?;
+            for (i, b) in bits.iter().enumerate() {
+                line.push(' ');
+                line.push_str(&blif_id(k));
+                if bits.len() > 1 {
+                    line.push_str(&format!("[{}]", i));
+                }
+                line.push('=');
+                line.push_str(&self.name(b));
+            }
+        }
+        self.body.push(line);
+        Ok(())
+    }
+
+    fn cell(&mut self, cell: &Cell) -> Result<()> {
+        let tp = cell.i1().as_str();
+        if let Some((_, inputs, rows)) = GATES.iter().find(|g| g.0 == tp) {
+            let mut ins = Vec::new();
+            for p in inputs.iter() {
+                ins.extend(self.port(cell, p)?);
+            }
+            let y = self.port(cell, "Y")?.remove(0);
+            let rows: Vec<String> = rows.iter().map(|r| r.to_string()).collect();
+            self.names(&ins, &y, &rows);
+            return Ok(());
+        }
+        match tp {
+            "$lut" => self.lut(cell),
+            "$_FF_" => self.latch(cell, None),
+            "$_DFF_P_" => self.latch(cell, Some(("re", "C"))),
+            "$_DFF_N_" => self.latch(cell, Some(("fe", "C"))),
+            "$_DLATCH_P_" => self.latch(cell, Some(("ah", "E"))),
+            "$_DLATCH_N_" => self.latch(cell, Some(("al", "E"))),
+            _ => self.subckt(cell),
+        }
+    }
+
+    fn write<W: Write>(mut self, w: &mut W) -> Result<()> {
+        let m = self.module;
+        if !m.processes().is_empty() {
+            bail!("module `{}' has processes, run proc first", m.ident());
+        }
+        if !m.memories().is_empty() {
+            bail!("module `{}' has memories, map them first", m.ident());
+        }
+        let mut ports: Vec<&Wire> = m.wires().iter().filter(|w| *w.port() > 0).collect();
+        ports.sort_by_key(|w| *w.port());
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for p in ports {
+            for b in self.wt.bits(&SigSpec::wire(p.id()))? {
+                let name = self.name(&b);
+                if *p.input() {
+                    inputs.push(name);
+                } else {
+                    // outputs not naming their net are driven by a buffer
+                    let own = self.wire_bit(&b);
+                    if own != name {
+                        self.names(&[name], &own, &["1 1".to_string()]);
+                    }
+                    outputs.push(own);
+                }
+            }
+        }
+
+        writeln!(w, ".model {}", blif_id(m.ident()))?;
+        if !inputs.is_empty() {
+            writeln!(w, ".inputs {}", inputs.join(" "))?;
+        }
+        if !outputs.is_empty() {
+            writeln!(w, ".outputs {}", outputs.join(" "))?;
+        }
+        if matches!(m.attrs().get("\\blackbox"), Some(v) if v.as_bool()) {
+            writeln!(w, ".blackbox")?;
+            writeln!(w, ".end")?;
+            return Ok(());
+        }
+        for c in m.cells() {
+            self.cell(c)?;
+        }
+        for n in self.consts.iter() {
+            writeln!(w, ".names {}", n)?;
+            if *n == TRUE {
+                writeln!(w, "1")?;
+            }
+        }
+        for l in self.body.iter() {
+            writeln!(w, "{}", l)?;
+        }
+        writeln!(w, ".end")?;
+        Ok(())
+    }
+}
+
+/// BLIF name for the RTLIL identifier `id`: public names lose their `\`.
+pub fn blif_id(id: &str) -> String {
+    id.strip_prefix('\\').unwrap_or(id).to_string()
+}
+
+/// Write `design` as BLIF, a model per module with the one marked `top`
+/// first.
+///
+/// Gate cells and `$lut` of up to 20 inputs become `.names` tables, `$_FF_`, `$_DFF_[NP]_`
+/// and `$_DLATCH_[NP]_` become `.latch`es initialized from the `init`
+/// attribute of their output, and other cells become `.subckt`s with a
+/// connection per bit. Constants are driven by the nets `$false`, `$true`
+/// and `$undef`.
+pub fn write_blif<W: Write>(w: &mut W, design: &Design) -> Result<()> {
+    let is_top = |m: &&Module| matches!(m.attrs().get("\\top"), Some(v) if v.as_bool());
+    let modules = design.modules().iter();
+    let order = modules
+        .clone()
+        .filter(is_top)
+        .chain(modules.filter(|m| !is_top(m)));
+    for (i, m) in order.enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        Writer::new(m)?.write(w)?;
+    }
+    Ok(())
+}
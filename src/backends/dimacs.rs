@@ -0,0 +1,69 @@
+use crate::cnf::{Cnf, ModuleCnf};
+use crate::syntax::*;
+use anyhow::Result;
+use std::io::Write;
+
+fn join(lits: &[i32]) -> String {
+    lits.iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Write `cnf` in the DIMACS CNF format.
+pub fn write_cnf<W: Write>(w: &mut W, cnf: &Cnf) -> Result<()> {
+    writeln!(w, "p cnf {} {}", cnf.vars(), cnf.clauses().len())?;
+    for c in cnf.clauses() {
+        writeln!(w, "{} 0", join(c))?;
+    }
+    Ok(())
+}
+
+/// Write the transition relation of the gate-level `module` as DIMACS CNF,
+/// encoded by [`ModuleCnf`] with a next state variable per flip-flop bit
+/// equal to its `D` input.
+///
+/// Comment lines before the header give the variables of the module,
+/// LSB first: `c input <wire> <lits>` and `c output <wire> <lits>` for the
+/// ports, `c state <bit> <lit> <next lit>` for the flip-flops, and
+/// `c init <lits>` for the literals true in the initial state, from the
+/// `init` attributes.
+pub fn write_dimacs<W: Write>(w: &mut W, module: &Module) -> Result<()> {
+    let mut enc = ModuleCnf::new(module)?;
+    let mut ports: Vec<&Wire> = module.wires().iter().filter(|w| *w.port() > 0).collect();
+    ports.sort_by_key(|w| *w.port());
+    for p in ports {
+        let lits = enc.lits(&SigSpec::wire(p.id()))?;
+        let dir = if *p.input() { "input" } else { "output" };
+        writeln!(w, "c {} {} {}", dir, p.id(), join(&lits))?;
+    }
+    for (q, d) in enc.state().clone() {
+        let (q_lit, d_lit) = (enc.lit(&q), enc.lit(&d));
+        let next = enc.cnf_mut().new_var();
+        enc.cnf_mut().equal(next, d_lit);
+        let name = match &q {
+            SigBit::Wire((id, i)) => format!("{}[{}]", id, i),
+            b => b.to_string(),
+        };
+        writeln!(w, "c state {} {} {}", name, q_lit, next)?;
+    }
+    let wt = WireTable::new(module);
+    let mut init = Vec::new();
+    for wire in module.wires() {
+        if let Some(c) = wire.attrs().get("\\init") {
+            let bits = wt.bits(&SigSpec::wire(wire.id()))?;
+            for (b, s) in bits.iter().zip(c.to_bits()) {
+                let l = enc.lit(b);
+                match s {
+                    State::S0 => init.push(-l),
+                    State::S1 => init.push(l),
+                    _ => (),
+                }
+            }
+        }
+    }
+    if !init.is_empty() {
+        writeln!(w, "c init {}", join(&init))?;
+    }
+    write_cnf(w, enc.cnf())
+}
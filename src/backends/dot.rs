@@ -0,0 +1,265 @@
+use crate::celltypes::{CellTypes, PortDir};
+use crate::syntax::*;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Escape the characters with a meaning in record labels.
+fn escape(s: &str) -> String {
+    let mut r = String::new();
+    for c in s.chars() {
+        if matches!(c, '\\' | '"' | '|' | '{' | '}' | '<' | '>' | ' ') {
+            r.push('\\');
+        }
+        r.push(c);
+    }
+    r
+}
+
+fn display(id: &str) -> &str {
+    id.strip_prefix('\\').unwrap_or(id)
+}
+
+// Label of a part of a concatenation.
+fn chunk(s: &SigSpec) -> String {
+    match s {
+        SigSpec::Refer((w, Some((l, r)))) if l == r => format!("{}[{}]", display(w), l),
+        SigSpec::Refer((w, Some((l, r)))) => format!("{}[{}:{}]", display(w), l, r),
+        SigSpec::Refer((w, None)) => display(w).to_string(),
+        _ => s.to_string(),
+    }
+}
+
+// Attributes of an edge carrying `width` bits.
+fn style(width: usize) -> String {
+    if width > 1 {
+        format!("label=\"{}\", style=\"setlinewidth(3)\"", width)
+    } else {
+        "label=\"\"".to_string()
+    }
+}
+
+struct Graph<'a> {
+    module: &'a Module,
+    wt: WireTable,
+    wires: HashMap<&'a str, usize>,
+    nodes: Vec<String>,
+    edges: Vec<String>,
+    next: usize,
+}
+
+impl<'a> Graph<'a> {
+    // Node and port standing for `s`: the node of a whole wire, or a new
+    // node for constants and for slices and concatenations of wires. The
+    // edges between such a node and its wires follow the signal, from the
+    // node to the wires if `s` is driven.
+    fn net(&mut self, s: &SigSpec, driven: bool) -> Result<String> {
+        let bits = self.wt.bits(s)?;
+        let s = self.wt.sigspec(&bits);
+        let chunks = match &s {
+            SigSpec::Refer((w, None)) if self.wires.contains_key(w.as_str()) => {
+                return Ok(format!("n{}", self.wires[w.as_str()]));
+            }
+            SigSpec::Const(_) => {
+                self.next += 1;
+                self.nodes.push(format!(
+                    "x{} [ label=\"{}\" ];",
+                    self.next,
+                    s.to_string().replace('"', "\\\"")
+                ));
+                return Ok(format!("x{}", self.next));
+            }
+            SigSpec::List(v) => v.clone(),
+            _ => vec![s.clone()],
+        };
+        // a record with a field per chunk, most significant first
+        self.next += 1;
+        let id = self.next;
+        let mut fields = Vec::new();
+        for (i, c) in chunks.iter().enumerate() {
+            fields.push(format!("<s{}> {}", i, escape(&chunk(c))));
+            let width = self.wt.width_of(c)? as usize;
+            let wire = match c {
+                SigSpec::Refer((w, _)) => match self.wires.get(w.as_str()) {
+                    Some(n) => format!("n{}", n),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            self.edges.push(if driven {
+                format!("x{}:s{} -> {} [ {} ];", id, i, wire, style(width))
+            } else {
+                format!("{} -> x{}:s{} [ {} ];", wire, id, i, style(width))
+            });
+        }
+        self.nodes.push(format!(
+            "x{} [ shape=record, style=rounded, label=\"{}\" ];",
+            id,
+            fields.join("|")
+        ));
+        Ok(format!("x{}", id))
+    }
+
+    fn cell(&mut self, i: usize, cell: &Cell, ct: &CellTypes) -> Result<()> {
+        let mut ports: Vec<(&String, &SigSpec)> = cell.connects().iter().collect();
+        ports.sort_by(|a, b| a.0.cmp(b.0));
+        let (mut ins, mut outs) = (Vec::new(), Vec::new());
+        for (p, (k, s)) in ports.into_iter().enumerate() {
+            let field = format!("<p{}> {}", p, escape(display(k)));
+            let dir = ct.port_dir(cell.i1(), k);
+            let net = self.net(s, dir == Some(PortDir::Output))?;
+            let width = self.wt.width_of(s)? as usize;
+            let edge = match dir {
+                Some(PortDir::Output) => {
+                    outs.push(field);
+                    format!("c{}:p{}:e -> {}:w [ {} ];", i, p, net, style(width))
+                }
+                Some(PortDir::Input) => {
+                    ins.push(field);
+                    format!("{}:e -> c{}:p{}:w [ {} ];", net, i, p, style(width))
+                }
+                _ => {
+                    ins.push(field);
+                    format!(
+                        "{}:e -> c{}:p{}:w [ dir=none, {} ];",
+                        net,
+                        i,
+                        p,
+                        style(width)
+                    )
+                }
+            };
+            self.edges.push(edge);
+        }
+        self.nodes.push(format!(
+            "c{} [ shape=record, label=\"{{{{{}}}|{}\\n{}|{{{}}}}}\" ];",
+            i,
+            ins.join("|"),
+            escape(display(cell.i2())),
+            escape(display(cell.i1())),
+            outs.join("|")
+        ));
+        Ok(())
+    }
+
+    fn process(&mut self, i: usize, p: &Process) -> Result<()> {
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        fn case<'p>(
+            assign: &'p [(SigSpec, SigSpec)],
+            switch: &'p [ProcessSwitch],
+            reads: &mut Vec<&'p SigSpec>,
+            writes: &mut Vec<&'p SigSpec>,
+        ) {
+            for (l, r) in assign {
+                writes.push(l);
+                reads.push(r);
+            }
+            for s in switch {
+                reads.push(s.sig());
+                for c in s.cases() {
+                    case(c.assign(), c.switch(), reads, writes);
+                }
+            }
+        }
+        case(p.assign(), p.switch(), &mut reads, &mut writes);
+        for s in p.syncs() {
+            match s.tp() {
+                ProcessSyncType::Low(x)
+                | ProcessSyncType::High(x)
+                | ProcessSyncType::Posedge(x)
+                | ProcessSyncType::Negedge(x)
+                | ProcessSyncType::Edge(x) => reads.push(x),
+                _ => (),
+            }
+            for (l, r) in s.updates() {
+                writes.push(l);
+                reads.push(r);
+            }
+        }
+        self.nodes.push(format!(
+            "p{} [ shape=box, style=rounded, label=\"PROC {}\" ];",
+            i,
+            escape(display(p.id()))
+        ));
+        // only signals going through wires are worth an edge
+        let mut seen = Vec::new();
+        for (s, out) in reads
+            .into_iter()
+            .map(|s| (s, false))
+            .chain(writes.into_iter().map(|s| (s, true)))
+        {
+            if matches!(s, SigSpec::Const(_)) {
+                continue;
+            }
+            let net = self.net(s, out)?;
+            if seen.contains(&(net.clone(), out)) {
+                continue;
+            }
+            let width = self.wt.width_of(s)? as usize;
+            self.edges.push(if out {
+                format!("p{} -> {}:w [ {} ];", i, net, style(width))
+            } else {
+                format!("{}:e -> p{} [ {} ];", net, i, style(width))
+            });
+            seen.push((net, out));
+        }
+        Ok(())
+    }
+}
+
+/// Graph of `module` in the DOT language of Graphviz, like yosys' `show`:
+/// cells are records with their input ports on the left and their outputs
+/// on the right, ports are octagons and other public wires diamonds, and
+/// each connection is an edge, bold with its width when wider than one
+/// bit. Slices and concatenations get a node with a field per part.
+/// Processes are drawn as boxes linked to the signals they read and drive
+/// if `processes` is set.
+pub fn to_dot(module: &Module, processes: bool) -> Result<String> {
+    let ct = CellTypes::internals();
+    let mut g = Graph {
+        module,
+        wt: WireTable::new(module),
+        wires: HashMap::new(),
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        next: 0,
+    };
+    for (i, w) in module.wires().iter().enumerate() {
+        g.wires.insert(w.id(), i);
+        let name = escape(display(w.id()));
+        g.nodes.push(if *w.port() > 0 {
+            format!("n{} [ shape=octagon, label=\"{}\" ];", i, name)
+        } else if w.id().starts_with('\\') {
+            format!("n{} [ shape=diamond, label=\"{}\" ];", i, name)
+        } else {
+            format!("n{} [ shape=point ];", i)
+        });
+    }
+    for (i, c) in g.module.cells().iter().enumerate() {
+        g.cell(i, c, &ct)?;
+    }
+    for c in g.module.connects() {
+        let (dst, src) = (g.net(c.sig1(), true)?, g.net(c.sig2(), false)?);
+        let width = g.wt.width_of(c.sig1())? as usize;
+        g.edges
+            .push(format!("{}:e -> {}:w [ {} ];", src, dst, style(width)));
+    }
+    if processes {
+        for (i, p) in g.module.processes().iter().enumerate() {
+            g.process(i, p)?;
+        }
+    }
+
+    let mut r = String::new();
+    let name = display(module.ident()).replace('"', "\\\"");
+    writeln!(r, "digraph \"{}\" {{", name)?;
+    writeln!(r, "label=\"{}\";", name)?;
+    writeln!(r, "rankdir=\"LR\";")?;
+    writeln!(r, "remincross=true;")?;
+    for l in g.nodes.iter().chain(g.edges.iter()) {
+        writeln!(r, "{}", l)?;
+    }
+    writeln!(r, "}}")?;
+    Ok(r)
+}
@@ -0,0 +1,674 @@
+use crate::sim::ff::Ff;
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::io::Write;
+
+// A bit-vector term of width `w`.
+#[derive(Debug, Clone)]
+struct Bv {
+    t: String,
+    w: usize,
+}
+
+impl Bv {
+    fn new(t: String, w: usize) -> Self {
+        Self { t, w }
+    }
+
+    fn consts(bits: &[State]) -> Self {
+        let v: String = bits
+            .iter()
+            .rev()
+            .map(|s| if *s == State::S1 { '1' } else { '0' })
+            .collect();
+        Self::new(format!("#b{}", v), bits.len())
+    }
+
+    fn zeros(w: usize) -> Self {
+        Self::consts(&vec![State::S0; w])
+    }
+
+    fn from_bool(t: String) -> Self {
+        Self::new(format!("(ite {} #b1 #b0)", t), 1)
+    }
+
+    // Whether the term is not all zeros.
+    fn bool(&self) -> String {
+        format!("(not (= {} {}))", self.t, Self::zeros(self.w).t)
+    }
+
+    // Zero or sign extend, or truncate, to `w` bits.
+    fn ext(&self, w: usize, signed: bool) -> Self {
+        if w == self.w {
+            self.clone()
+        } else if self.w == 0 {
+            Self::zeros(w)
+        } else if w < self.w {
+            Self::new(format!("((_ extract {} 0) {})", w - 1, self.t), w)
+        } else {
+            let op = if signed { "sign_extend" } else { "zero_extend" };
+            Self::new(format!("((_ {} {}) {})", op, w - self.w, self.t), w)
+        }
+    }
+
+    fn bit(&self, i: usize) -> Self {
+        Self::new(format!("((_ extract {} {}) {})", i, i, self.t), 1)
+    }
+
+    fn op(&self, op: &str, other: &Self) -> Self {
+        Self::new(format!("({} {} {})", op, self.t, other.t), self.w)
+    }
+
+    fn not(&self) -> Self {
+        Self::new(format!("(bvnot {})", self.t), self.w)
+    }
+
+    fn ite(s: &str, b: &Self, a: &Self) -> Self {
+        Self::new(format!("(ite {} {} {})", s, b.t, a.t), a.w)
+    }
+}
+
+fn sort(w: usize) -> String {
+    format!("(_ BitVec {})", w)
+}
+
+fn conj(v: &[String]) -> String {
+    match v.len() {
+        0 => "true".to_string(),
+        1 => v[0].clone(),
+        _ => format!("(and {})", v.join(" ")),
+    }
+}
+
+// Whether the bit `t` is at its active level.
+fn active(t: &Bv, pol: bool) -> String {
+    format!("(= {} {})", t.t, if pol { "#b1" } else { "#b0" })
+}
+
+/// Text of the RTLIL identifier `id` in SMT-LIB2 quoted symbols.
+pub fn smt_id(id: &str) -> String {
+    id.strip_prefix('\\')
+        .unwrap_or(id)
+        .replace(['|', '\\'], "_")
+}
+
+fn int(c: &Cell, k: &str) -> Result<usize> {
+    c.param(k)
+        .and_then(|v| v.as_int())
+        .map(|v| v.max(0) as usize)
+        .ok_or_else(|| anyhow!("cell `{}' has no parameter `{}'", c.i2(), k))
+}
+
+fn flag(c: &Cell, k: &str) -> bool {
+    matches!(c.param(k), Some(v) if v.as_bool())
+}
+
+// A run of bits of a term: bits `lo` to `hi` of a node, or constant bits.
+enum Chunk {
+    Node(usize, usize, usize),
+    Const(Vec<State>),
+}
+
+struct Node {
+    w: usize,
+    comment: String,
+    /// Index of the combinational cell computing the node, if any; other
+    /// nodes are declared: inputs, registers, free and undriven nets.
+    cell: Option<usize>,
+}
+
+struct Writer<'a> {
+    module: &'a Module,
+    prefix: String,
+    wt: WireTable,
+    sigmap: SigMap,
+    nodes: Vec<Node>,
+    /// Node and bit driving each net.
+    drivers: HashMap<SigBit, (usize, usize)>,
+}
+
+impl<'a> Writer<'a> {
+    fn node(&mut self, comment: String, cell: Option<usize>, bits: &[SigBit]) -> usize {
+        let n = self.nodes.len();
+        self.nodes.push(Node {
+            w: bits.len(),
+            comment,
+            cell,
+        });
+        for (i, b) in bits.iter().enumerate() {
+            let b = self.sigmap.map_bit(b);
+            if !b.is_const() {
+                self.drivers.entry(b).or_insert((n, i));
+            }
+        }
+        n
+    }
+
+    fn call(&self, n: usize, state: &str) -> Bv {
+        let t = format!("(|{}#{}| {})", self.prefix, n, state);
+        Bv::new(t, self.nodes[n].w)
+    }
+
+    fn bits(&self, s: &SigSpec) -> Result<Vec<SigBit>> {
+        Ok(self.sigmap.map(&self.wt.bits(s)?))
+    }
+
+    fn driver(&mut self, b: &SigBit) -> (usize, usize) {
+        match self.drivers.get(b) {
+            Some(d) => *d,
+            None => (self.node(b.to_string(), None, std::slice::from_ref(b)), 0),
+        }
+    }
+
+    // Term of `bits` in `state`, concatenating slices of the nodes driving
+    // them. Undriven nets get a node of their own.
+    fn term(&mut self, bits: &[SigBit], state: &str) -> Bv {
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for b in bits {
+            if let SigBit::Const(s) = b {
+                match chunks.last_mut() {
+                    Some(Chunk::Const(v)) => v.push(*s),
+                    _ => chunks.push(Chunk::Const(vec![*s])),
+                }
+                continue;
+            }
+            let (n, i) = self.driver(b);
+            match chunks.last_mut() {
+                Some(Chunk::Node(m, _, hi)) if *m == n && *hi + 1 == i => *hi = i,
+                _ => chunks.push(Chunk::Node(n, i, i)),
+            }
+        }
+        let mut r: Option<Bv> = None;
+        for c in chunks {
+            let part = match c {
+                Chunk::Node(n, lo, hi) => {
+                    let t = self.call(n, state);
+                    if lo == 0 && hi + 1 == t.w {
+                        t
+                    } else {
+                        Bv::new(format!("((_ extract {} {}) {})", hi, lo, t.t), hi - lo + 1)
+                    }
+                }
+                Chunk::Const(v) => Bv::consts(&v),
+            };
+            r = Some(match r {
+                Some(low) => Bv::new(format!("(concat {} {})", part.t, low.t), part.w + low.w),
+                None => part,
+            });
+        }
+        r.unwrap_or_else(|| Bv::zeros(0))
+    }
+
+    fn port(&mut self, c: &Cell, k: &str) -> Result<Bv> {
+        let bits = match c.port(&format!("\\{}", k)) {
+            Some(s) => self.bits(s)?,
+            None => bail!("cell `{}' has no port `{}'", c.i2(), k),
+        };
+        Ok(self.term(&bits, "state"))
+    }
+
+    // Input port extended to the width given by its `<P>_WIDTH` parameter.
+    fn arg(&mut self, c: &Cell, k: &str) -> Result<Bv> {
+        let v = self.port(c, k)?;
+        let w = int(c, &format!("\\{}_WIDTH", k)).unwrap_or(v.w);
+        Ok(v.ext(w, flag(c, &format!("\\{}_SIGNED", k))))
+    }
+
+    // Term of the `Y` output of a combinational cell.
+    fn cell(&mut self, c: &Cell) -> Result<Bv> {
+        let tp = c.i1().as_str();
+        let (sa, sb) = (flag(c, "\\A_SIGNED"), flag(c, "\\B_SIGNED"));
+        // as in yosys, binary operators other than shifts are only signed
+        // when both operands are
+        let (sa, sb) = match tp {
+            "$not" | "$pos" | "$neg" | "$shl" | "$sshl" | "$shr" | "$sshr" => (sa, sb),
+            _ => (sa && sb, sa && sb),
+        };
+        let w = int(c, "\\Y_WIDTH").unwrap_or(1);
+        Ok(match tp {
+            "$not" | "$pos" | "$neg" => {
+                let a = self.arg(c, "A")?.ext(w, sa);
+                match tp {
+                    "$not" => a.not(),
+                    "$pos" => a,
+                    _ => Bv::new(format!("(bvneg {})", a.t), w),
+                }
+            }
+            "$and" | "$or" | "$xor" | "$xnor" => {
+                let a = self.arg(c, "A")?.ext(w, sa);
+                let b = self.arg(c, "B")?.ext(w, sb);
+                a.op(&format!("bv{}", &tp[1..]), &b)
+            }
+            "$reduce_and" | "$reduce_or" | "$reduce_xor" | "$reduce_xnor" | "$reduce_bool"
+            | "$logic_not" => {
+                let a = self.arg(c, "A")?;
+                let v = match tp {
+                    "$reduce_and" if a.w == 0 => Bv::consts(&[State::S1]),
+                    "$reduce_and" => {
+                        let ones = Bv::consts(&vec![State::S1; a.w]);
+                        Bv::from_bool(format!("(= {} {})", a.t, ones.t))
+                    }
+                    "$reduce_xor" | "$reduce_xnor" => {
+                        let v = (1..a.w).fold(a.bit(0), |r, i| r.op("bvxor", &a.bit(i)));
+                        if tp == "$reduce_xnor" {
+                            v.not()
+                        } else {
+                            v
+                        }
+                    }
+                    "$logic_not" => Bv::from_bool(format!("(not {})", a.bool())),
+                    _ => Bv::from_bool(a.bool()),
+                };
+                v.ext(w, false)
+            }
+            "$logic_and" | "$logic_or" => {
+                let (a, b) = (self.arg(c, "A")?, self.arg(c, "B")?);
+                let op = if tp == "$logic_and" { "and" } else { "or" };
+                Bv::from_bool(format!("({} {} {})", op, a.bool(), b.bool())).ext(w, false)
+            }
+            "$shl" | "$sshl" => {
+                let a = self.arg(c, "A")?.ext(w, sa);
+                let b = self.arg(c, "B")?;
+                let n = w.max(b.w);
+                a.ext(n, false).op("bvshl", &b.ext(n, false)).ext(w, false)
+            }
+            "$shr" | "$sshr" => {
+                let a = self.arg(c, "A")?;
+                let signed = tp == "$sshr" && sa;
+                let a = a.ext(w.max(a.w), sa);
+                let b = self.arg(c, "B")?;
+                let n = a.w.max(b.w);
+                let op = if signed { "bvashr" } else { "bvlshr" };
+                a.ext(n, signed).op(op, &b.ext(n, false)).ext(w, false)
+            }
+            "$eq" | "$ne" | "$eqx" | "$nex" => {
+                let (a, b) = (self.arg(c, "A")?, self.arg(c, "B")?);
+                let n = a.w.max(b.w);
+                let (a, b) = (a.ext(n, sa), b.ext(n, sb));
+                let op = if matches!(tp, "$eq" | "$eqx") {
+                    "="
+                } else {
+                    "distinct"
+                };
+                Bv::from_bool(format!("({} {} {})", op, a.t, b.t)).ext(w, false)
+            }
+            "$lt" | "$le" | "$gt" | "$ge" => {
+                let (a, b) = (self.arg(c, "A")?, self.arg(c, "B")?);
+                let n = a.w.max(b.w);
+                let (a, b) = (a.ext(n, sa), b.ext(n, sb));
+                let op = format!("bv{}{}", if sa { "s" } else { "u" }, &tp[1..]);
+                Bv::from_bool(format!("({} {} {})", op, a.t, b.t)).ext(w, false)
+            }
+            "$add" | "$sub" | "$mul" => {
+                let a = self.arg(c, "A")?.ext(w, sa);
+                let b = self.arg(c, "B")?.ext(w, sb);
+                a.op(&format!("bv{}", &tp[1..]), &b)
+            }
+            "$div" | "$mod" => {
+                let (a, b) = (self.arg(c, "A")?, self.arg(c, "B")?);
+                let n = a.w.max(b.w).max(w);
+                let op = match (tp, sa) {
+                    ("$div", true) => "bvsdiv",
+                    ("$div", false) => "bvudiv",
+                    (_, true) => "bvsrem",
+                    (_, false) => "bvurem",
+                };
+                a.ext(n, sa).op(op, &b.ext(n, sb)).ext(w, false)
+            }
+            "$mux" => {
+                let w = int(c, "\\WIDTH")?;
+                let (a, b) = (self.port(c, "A")?.ext(w, false), self.port(c, "B")?);
+                let s = self.port(c, "S")?;
+                Bv::ite(&active(&s, true), &b.ext(w, false), &a)
+            }
+            // the first selected case wins
+            "$pmux" => {
+                let w = int(c, "\\WIDTH")?;
+                let a = self.port(c, "A")?.ext(w, false);
+                let s = self.port(c, "S")?;
+                let b = self.port(c, "B")?.ext(w * s.w, false);
+                (0..s.w).rev().fold(a, |r, i| {
+                    let case = Bv::new(
+                        format!("((_ extract {} {}) {})", (i + 1) * w - 1, i * w, b.t),
+                        w,
+                    );
+                    Bv::ite(&active(&s.bit(i), true), &case, &r)
+                })
+            }
+            "$concat" => {
+                let (a, b) = (self.arg(c, "A")?, self.arg(c, "B")?);
+                match (a.w, b.w) {
+                    (0, _) => b,
+                    (_, 0) => a,
+                    _ => Bv::new(format!("(concat {} {})", b.t, a.t), a.w + b.w),
+                }
+            }
+            "$slice" => {
+                let a = self.arg(c, "A")?;
+                let off = int(c, "\\OFFSET")?;
+                Bv::new(format!("((_ extract {} {}) {})", off + w - 1, off, a.t), w)
+            }
+            "$_BUF_" => self.port(c, "A")?,
+            "$_NOT_" => self.port(c, "A")?.not(),
+            "$_AND_" | "$_NAND_" | "$_OR_" | "$_NOR_" | "$_XOR_" | "$_XNOR_" | "$_ANDNOT_"
+            | "$_ORNOT_" => {
+                let (a, b) = (self.port(c, "A")?, self.port(c, "B")?);
+                match tp {
+                    "$_AND_" => a.op("bvand", &b),
+                    "$_NAND_" => a.op("bvnand", &b),
+                    "$_OR_" => a.op("bvor", &b),
+                    "$_NOR_" => a.op("bvnor", &b),
+                    "$_XOR_" => a.op("bvxor", &b),
+                    "$_XNOR_" => a.op("bvxnor", &b),
+                    "$_ANDNOT_" => a.op("bvand", &b.not()),
+                    _ => a.op("bvor", &b.not()),
+                }
+            }
+            "$_MUX_" | "$_NMUX_" => {
+                let (a, b) = (self.port(c, "A")?, self.port(c, "B")?);
+                let s = self.port(c, "S")?;
+                let v = Bv::ite(&active(&s, true), &b, &a);
+                if tp == "$_NMUX_" {
+                    v.not()
+                } else {
+                    v
+                }
+            }
+            _ => bail!("can not write cell `{}' of type `{}'", c.i2(), tp),
+        })
+    }
+
+    // Nodes of the combinational cells, each after those driving its
+    // inputs.
+    fn order(&self) -> Result<Vec<usize>> {
+        let cells = self.module.cells();
+        let name = |n: usize| cells[self.nodes[n].cell.unwrap()].i2();
+        let mut deps: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (n, node) in self.nodes.iter().enumerate() {
+            if let Some(i) = node.cell {
+                let mut v = Vec::new();
+                for (k, s) in cells[i].connects() {
+                    if k == "\\Y" {
+                        continue;
+                    }
+                    for b in self.bits(s)? {
+                        if let Some((m, _)) = self.drivers.get(&b) {
+                            if self.nodes[*m].cell.is_some() {
+                                v.push(*m);
+                            }
+                        }
+                    }
+                }
+                deps.insert(n, v);
+            }
+        }
+        let mut roots: Vec<usize> = deps.keys().copied().collect();
+        roots.sort_unstable();
+        // depth first, with `false` marking the nodes being visited
+        let mut mark: HashMap<usize, bool> = HashMap::new();
+        let mut order = Vec::new();
+        for r in roots {
+            let mut stack = vec![(r, 0)];
+            while let Some((n, k)) = stack.pop() {
+                if k == 0 {
+                    match mark.get(&n) {
+                        Some(true) => continue,
+                        Some(false) => bail!("combinational loop through cell `{}'", name(n)),
+                        None => {
+                            mark.insert(n, false);
+                        }
+                    }
+                }
+                match deps[&n].get(k) {
+                    Some(&m) => {
+                        stack.push((n, k + 1));
+                        stack.push((m, 0));
+                    }
+                    None => {
+                        mark.insert(n, true);
+                        order.push(n);
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+}
+
+/// Write `module` as SMT-LIB2 bit-vector formulas.
+///
+/// The module is described over an uninterpreted sort `|<m>_s|` of states,
+/// `<m>` being its name: inputs, registers and free values are functions
+/// of the state, declared as `|<m>#<n>|`, and each combinational cell output
+/// is a function `|<m>#<n>|` defined over them. Public wires are defined as
+/// `|<m>_n <wire>|`. The predicates `|<m>_i|` (initial values from `init`
+/// attributes), `|<m>_t|` (transition between two states), `|<m>_a|`
+/// (`$assert`s hold) and `|<m>_u|` (`$assume`s hold) describe the
+/// behaviour, and `$cover` cells become `|<m>_c <n>|`. Each transition is
+/// one active edge of every clock; registers with asynchronous controls are
+/// not supported. Undefined bits are taken as 0.
+///
+/// The names follow yosys' `write_smt2`, but the output is not meant for
+/// `yosys-smtbmc`: every signal is a bit-vector, single bits included,
+/// there is no hierarchy and no `|<m>_is|` or `|<m>_h|` predicate, and of
+/// the `; yosys-smt2-*` comments only `module`, `input`, `output`,
+/// `register` and `wire` are written.
+pub fn write_smt2<W: Write>(w: &mut W, module: &Module) -> Result<()> {
+    if !module.processes().is_empty() {
+        bail!("module `{}' has processes, run proc first", module.ident());
+    }
+    if !module.memories().is_empty() {
+        bail!("module `{}' has memories, map them first", module.ident());
+    }
+    let wt = WireTable::new(module);
+    let sigmap = SigMap::new(module, &wt)?;
+    let mut g = Writer {
+        module,
+        prefix: smt_id(module.ident()),
+        wt,
+        sigmap,
+        nodes: Vec::new(),
+        drivers: HashMap::new(),
+    };
+    let p = g.prefix.clone();
+    let mut info = Vec::new();
+
+    let mut ports: Vec<&Wire> = module.wires().iter().filter(|w| *w.port() > 0).collect();
+    ports.sort_by_key(|w| *w.port());
+    for wire in ports.iter().filter(|w| *w.input()) {
+        let bits = g.bits(&SigSpec::wire(wire.id()))?;
+        g.node(wire.id().clone(), None, &bits);
+        info.push(format!(
+            "; yosys-smt2-input {} {}",
+            smt_id(wire.id()),
+            wire.width()
+        ));
+    }
+
+    let mut regs = Vec::new();
+    let mut anyconst = Vec::new();
+    let mut checks = Vec::new();
+    for (i, c) in module.cells().iter().enumerate() {
+        let ff = Ff::new(c, |k| match c.port(k) {
+            Some(s) => g.bits(s),
+            None => bail!("cell `{}' has no port `{}'", c.i2(), k),
+        })?;
+        if let Some(ff) = ff {
+            if ff.arst.is_some() || ff.aload.is_some() || ff.set.is_some() || ff.clr.is_some() {
+                bail!("register `{}' has asynchronous controls", c.i2());
+            }
+            if ff.clk.is_none() && !ff.global {
+                bail!("can not write latch `{}'", c.i2());
+            }
+            let n = g.node(c.i2().clone(), None, &ff.q);
+            info.push(format!(
+                "; yosys-smt2-register {} {}",
+                smt_id(c.i2()),
+                ff.q.len()
+            ));
+            regs.push((n, ff));
+            continue;
+        }
+        match c.i1().as_str() {
+            "$anyconst" | "$anyseq" => {
+                let bits = g.bits(c.port("\\Y").ok_or_else(|| anyhow!("no port `Y'"))?)?;
+                let n = g.node(c.i2().clone(), None, &bits);
+                if c.i1() == "$anyconst" {
+                    anyconst.push(n);
+                }
+            }
+            "$assert" | "$assume" | "$cover" => checks.push(c),
+            _ => {
+                let bits = match c.port("\\Y") {
+                    Some(s) => g.bits(s)?,
+                    None => bail!("can not write cell `{}' of type `{}'", c.i2(), c.i1()),
+                };
+                if !bits.is_empty() {
+                    g.node(c.i2().clone(), Some(i), &bits);
+                }
+            }
+        }
+    }
+
+    let mut defs = Vec::new();
+    for n in g.order()? {
+        let c = &module.cells()[g.nodes[n].cell.unwrap()];
+        let v = g.cell(c)?.ext(g.nodes[n].w, false);
+        defs.push(format!(
+            "(define-fun |{}#{}| ((state |{}_s|)) {} {}) ; {}",
+            p,
+            n,
+            p,
+            sort(v.w),
+            v.t,
+            c.i2()
+        ));
+    }
+    for wire in module.wires().iter().filter(|w| w.id().starts_with('\\')) {
+        let bits = g.bits(&SigSpec::wire(wire.id()))?;
+        if bits.is_empty() {
+            continue;
+        }
+        let v = g.term(&bits, "state");
+        if *wire.output() {
+            defs.push(format!("; yosys-smt2-output {} {}", smt_id(wire.id()), v.w));
+        }
+        defs.push(format!("; yosys-smt2-wire {} {}", smt_id(wire.id()), v.w));
+        defs.push(format!(
+            "(define-fun |{}_n {}| ((state |{}_s|)) {} {})",
+            p,
+            smt_id(wire.id()),
+            p,
+            sort(v.w),
+            v.t
+        ));
+    }
+
+    let (mut asserts, mut assumes) = (Vec::new(), Vec::new());
+    for (k, c) in checks.into_iter().enumerate() {
+        let (a, en) = (g.port(c, "A")?, g.port(c, "EN")?);
+        match c.i1().as_str() {
+            "$assert" => asserts.push(format!("(or {} {})", active(&en, false), active(&a, true))),
+            "$assume" => assumes.push(format!("(or {} {})", active(&en, false), active(&a, true))),
+            _ => defs.push(format!(
+                "(define-fun |{}_c {}| ((state |{}_s|)) Bool (and {} {})) ; {}",
+                p,
+                k,
+                p,
+                active(&en, true),
+                active(&a, true),
+                c.i2()
+            )),
+        }
+    }
+
+    let mut init = HashMap::new();
+    for wire in module.wires() {
+        if let Some(c) = wire.attrs().get("\\init") {
+            let bits = g.bits(&SigSpec::wire(wire.id()))?;
+            for (b, s) in bits.into_iter().zip(c.to_bits()) {
+                if s == State::S0 || s == State::S1 {
+                    init.insert(b, s);
+                }
+            }
+        }
+    }
+    let (mut inits, mut trans) = (Vec::new(), Vec::new());
+    for (n, ff) in regs.iter() {
+        let q = g.call(*n, "state");
+        for (i, b) in ff.q.iter().enumerate() {
+            if let Some(s) = init.get(b) {
+                inits.push(format!("(= {} {})", q.bit(i).t, Bv::consts(&[*s]).t));
+            }
+        }
+        let mut next = g.term(&ff.d, "state");
+        let en = ff
+            .en
+            .as_ref()
+            .map(|(e, pol)| active(&g.term(std::slice::from_ref(e), "state"), *pol));
+        if let (Some(e), false) = (&en, ff.ce_over_srst) {
+            next = Bv::ite(e, &next, &q);
+        }
+        if let Some((r, pol, v)) = &ff.srst {
+            let r = active(&g.term(std::slice::from_ref(r), "state"), *pol);
+            next = Bv::ite(&r, &Bv::consts(v), &next);
+        }
+        if let (Some(e), true) = (&en, ff.ce_over_srst) {
+            next = Bv::ite(e, &next, &q);
+        }
+        trans.push(format!("(= {} {})", next.t, g.call(*n, "next_state").t));
+    }
+    for n in anyconst {
+        trans.push(format!(
+            "(= {} {})",
+            g.call(n, "state").t,
+            g.call(n, "next_state").t
+        ));
+    }
+
+    writeln!(w, "; SMT-LIBv2 description generated by rtlil-rs")?;
+    writeln!(w, "; yosys-smt2-module {}", p)?;
+    writeln!(w, "(declare-sort |{}_s| 0)", p)?;
+    for l in info.iter() {
+        writeln!(w, "{}", l)?;
+    }
+    for (n, node) in g.nodes.iter().enumerate() {
+        if node.cell.is_none() {
+            writeln!(
+                w,
+                "(declare-fun |{}#{}| (|{}_s|) {}) ; {}",
+                p,
+                n,
+                p,
+                sort(node.w),
+                node.comment
+            )?;
+        }
+    }
+    for l in defs.iter() {
+        writeln!(w, "{}", l)?;
+    }
+    let pred = |name: &str, v: &[String]| {
+        format!(
+            "(define-fun |{}_{}| ((state |{}_s|)) Bool {})",
+            p,
+            name,
+            p,
+            conj(v)
+        )
+    };
+    writeln!(w, "{}", pred("a", &asserts))?;
+    writeln!(w, "{}", pred("u", &assumes))?;
+    writeln!(w, "{}", pred("i", &inits))?;
+    writeln!(
+        w,
+        "(define-fun |{}_t| ((state |{}_s|) (next_state |{}_s|)) Bool {})",
+        p,
+        p,
+        p,
+        conj(&trans)
+    )?;
+    writeln!(w, "; end of module {}", p)?;
+    Ok(())
+}
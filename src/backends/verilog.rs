@@ -0,0 +1,1358 @@
+use crate::sim::ff::Ff;
+use crate::sim::memory::{mem_cell, rd_cell, wr_cell, Mem, RdPort, WrPort};
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "automatic",
+    "begin",
+    "buf",
+    "bufif0",
+    "bufif1",
+    "case",
+    "casex",
+    "casez",
+    "cell",
+    "cmos",
+    "config",
+    "deassign",
+    "default",
+    "defparam",
+    "design",
+    "disable",
+    "edge",
+    "else",
+    "end",
+    "endcase",
+    "endconfig",
+    "endfunction",
+    "endgenerate",
+    "endmodule",
+    "endprimitive",
+    "endspecify",
+    "endtable",
+    "endtask",
+    "event",
+    "for",
+    "force",
+    "forever",
+    "fork",
+    "function",
+    "generate",
+    "genvar",
+    "highz0",
+    "highz1",
+    "if",
+    "ifnone",
+    "incdir",
+    "include",
+    "initial",
+    "inout",
+    "input",
+    "instance",
+    "integer",
+    "join",
+    "large",
+    "liblist",
+    "library",
+    "localparam",
+    "macromodule",
+    "medium",
+    "module",
+    "nand",
+    "negedge",
+    "nmos",
+    "nor",
+    "noshowcancelled",
+    "not",
+    "notif0",
+    "notif1",
+    "or",
+    "output",
+    "parameter",
+    "pmos",
+    "posedge",
+    "primitive",
+    "pull0",
+    "pull1",
+    "pulldown",
+    "pullup",
+    "pulsestyle_onevent",
+    "pulsestyle_ondetect",
+    "rcmos",
+    "real",
+    "realtime",
+    "reg",
+    "release",
+    "repeat",
+    "rnmos",
+    "rpmos",
+    "rtran",
+    "rtranif0",
+    "rtranif1",
+    "scalared",
+    "showcancelled",
+    "signed",
+    "small",
+    "specify",
+    "specparam",
+    "strong0",
+    "strong1",
+    "supply0",
+    "supply1",
+    "table",
+    "task",
+    "time",
+    "tran",
+    "tranif0",
+    "tranif1",
+    "tri",
+    "tri0",
+    "tri1",
+    "triand",
+    "trior",
+    "trireg",
+    "unsigned",
+    "use",
+    "uwire",
+    "vectored",
+    "wait",
+    "wand",
+    "weak0",
+    "weak1",
+    "while",
+    "wire",
+    "wor",
+    "xnor",
+    "xor",
+];
+
+/// Verilog identifier for the RTLIL identifier `id`: public names lose
+/// their `\`, anything that is not a plain identifier is escaped.
+pub fn verilog_id(id: &str) -> String {
+    let name = id.strip_prefix('\\').unwrap_or(id);
+    let mut chars = name.chars();
+    let plain = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && !KEYWORDS.contains(&name);
+    if plain {
+        name.to_string()
+    } else {
+        format!("\\{} ", name)
+    }
+}
+
+// Sized binary literal of `bits` (LSB first), `-` bits written as `?`.
+fn literal(bits: &[State]) -> String {
+    if bits.is_empty() {
+        // Verilog has no empty literals, see IEEE 1364-2005 5.1.14
+        return "{0{1'b0}}".to_string();
+    }
+    let digits: String = bits
+        .iter()
+        .rev()
+        .map(|s| match s {
+            State::S0 => '0',
+            State::S1 => '1',
+            State::Sz => 'z',
+            State::Sa => '?',
+            _ => 'x',
+        })
+        .collect();
+    format!("{}'b{}", bits.len(), digits)
+}
+
+// Value of a parameter or attribute.
+fn value(c: &Const, signed: bool) -> String {
+    match c {
+        Const::Empty => "\"\"".to_string(),
+        Const::Int(n) => n.to_string(),
+        Const::Str(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        Const::Sig(_) if signed => literal(&c.to_bits()).replacen('\'', "'s", 1),
+        Const::Sig(_) => literal(&c.to_bits()),
+    }
+}
+
+fn attrs(attrs: &std::collections::HashMap<String, Const>, indent: &str) -> Vec<String> {
+    let mut keys: Vec<&String> = attrs.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .map(|k| {
+            format!(
+                "{}(* {} = {} *)",
+                indent,
+                verilog_id(k),
+                value(&attrs[k], false)
+            )
+        })
+        .collect()
+}
+
+fn render(s: &SigSpec) -> String {
+    match s {
+        SigSpec::Const((c, _)) => literal(&c.to_bits()),
+        SigSpec::Refer((n, None)) => verilog_id(n),
+        SigSpec::Refer((n, Some((l, r)))) if l == r => format!("{}[{}]", verilog_id(n), l),
+        SigSpec::Refer((n, Some((l, r)))) => format!("{}[{}:{}]", verilog_id(n), l, r),
+        SigSpec::List(v) => format!(
+            "{{ {} }}",
+            v.iter().map(render).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+fn range(width: i64, offset: i64, upto: bool) -> String {
+    if width == 1 && offset == 0 {
+        String::new()
+    } else if upto {
+        format!(" [{}:{}]", offset, offset + width - 1)
+    } else {
+        format!(" [{}:{}]", offset + width - 1, offset)
+    }
+}
+
+struct Writer<'a> {
+    module: &'a Module,
+    wt: WireTable,
+    sm: SigMap,
+    /// Initial values of the bits of wires with an `init` attribute, by
+    /// representative of their connected bits.
+    init: HashMap<SigBit, State>,
+    /// Wires assigned from `always` blocks.
+    regs: HashSet<String>,
+    /// Declarations of registers and memories not in the module.
+    decls: Vec<String>,
+    body: Vec<String>,
+    memories: Vec<String>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(module: &'a Module) -> Result<Self> {
+        let wt = WireTable::new(module);
+        let sm = SigMap::new(module, &wt)?;
+        let mut init = HashMap::new();
+        for w in module.wires() {
+            if let Some(v) = w.attrs().get("\\init") {
+                for (b, v) in wt.bits(&SigSpec::wire(w.id()))?.iter().zip(v.to_bits()) {
+                    if v == State::S0 || v == State::S1 {
+                        init.insert(sm.map_bit(b), v);
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            module,
+            wt,
+            sm,
+            init,
+            regs: HashSet::new(),
+            decls: Vec::new(),
+            body: Vec::new(),
+            memories: Vec::new(),
+        })
+    }
+
+    fn expr(&self, bits: &[SigBit]) -> String {
+        if bits.is_empty() {
+            return literal(&[]);
+        }
+        render(&self.wt.sigspec(bits))
+    }
+
+    // Initializer of a register holding `bits`, from the `init` attributes
+    // of the wires connected to them.
+    fn init_value(&self, bits: &[SigBit]) -> Option<String> {
+        let v: Vec<State> = bits
+            .iter()
+            .map(|b| *self.init.get(&self.sm.map_bit(b)).unwrap_or(&State::Sx))
+            .collect();
+        if v.iter().all(|s| *s == State::Sx) {
+            None
+        } else {
+            Some(literal(&v))
+        }
+    }
+
+    fn bit(&self, b: &SigBit) -> String {
+        self.expr(std::slice::from_ref(b))
+    }
+
+    fn sig(&self, s: &SigSpec) -> Result<String> {
+        Ok(self.expr(&self.wt.bits(s)?))
+    }
+
+    fn port(&self, cell: &Cell, k: &str) -> Result<Vec<SigBit>> {
+        match cell.port(k) {
+            Some(s) => self.wt.bits(s),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn cond(&self, bit: &SigBit, pol: bool) -> String {
+        if pol {
+            self.bit(bit)
+        } else {
+            format!("!{}", self.bit(bit))
+        }
+    }
+
+    fn edge(&self, bit: &SigBit, pol: bool) -> String {
+        let e = if pol { "posedge" } else { "negedge" };
+        format!("{} {}", e, self.bit(bit))
+    }
+
+    // The wire `bits` make up entirely, if any.
+    fn whole_wire(&self, bits: &[SigBit]) -> Option<String> {
+        let w = bits.first().and_then(|b| b.wire())?;
+        let whole = self.wt.width(w) == Some(bits.len() as i64)
+            && bits
+                .iter()
+                .enumerate()
+                .all(|(i, b)| *b == SigBit::Wire((w.to_string(), i as i64)));
+        if whole {
+            Some(w.to_string())
+        } else {
+            None
+        }
+    }
+
+    // Bits of a register holding `bits`: the bits themselves when they make
+    // up a whole wire, else a new register named after `name` that drives
+    // them.
+    fn reg(&mut self, bits: &[SigBit], name: &str) -> Vec<SigBit> {
+        if let Some(w) = self.whole_wire(bits) {
+            self.regs.insert(w);
+            return bits.to_vec();
+        }
+        let init = self.init_value(bits);
+        let r = self.new_net("reg", &format!("{}$reg", name), bits.len(), init);
+        self.assign(bits, self.expr(&r));
+        r
+    }
+
+    fn new_reg(&mut self, id: &str, width: usize) -> Vec<SigBit> {
+        self.new_net("reg", id, width, None)
+    }
+
+    fn new_wire(&mut self, id: &str, width: usize) -> Vec<SigBit> {
+        self.new_net("wire", id, width, None)
+    }
+
+    fn new_net(&mut self, kind: &str, id: &str, width: usize, init: Option<String>) -> Vec<SigBit> {
+        let w = Wire::new(id.to_string(), vec![WireOption::Width(width as i64)]);
+        self.wt.insert(&w);
+        self.decls.push(format!(
+            "  {}{} {}{};",
+            kind,
+            range(width as i64, 0, false),
+            verilog_id(id),
+            init.map(|v| format!(" = {}", v)).unwrap_or_default()
+        ));
+        (0..width as i64)
+            .map(|i| SigBit::Wire((id.to_string(), i)))
+            .collect()
+    }
+
+    // Name of a vector holding `bits` that can be indexed from 0: a whole
+    // wire declared as `[n-1:0]`, or else a new wire named `id`.
+    fn vector(&mut self, bits: &[SigBit], id: &str) -> String {
+        if let Some(w) = self.whole_wire(bits) {
+            let plain = self
+                .module
+                .wires()
+                .iter()
+                .any(|x| *x.id() == w && *x.offset() == 0 && !*x.upto());
+            if plain {
+                return verilog_id(&w);
+            }
+        }
+        let v = self.new_wire(id, bits.len());
+        self.assign(&v, self.expr(bits));
+        verilog_id(id)
+    }
+
+    fn assign(&mut self, y: &[SigBit], e: String) {
+        if !y.is_empty() {
+            self.body
+                .push(format!("  assign {} = {};", self.expr(y), e));
+        }
+    }
+
+    // Expression of a combinational internal cell, or None if it has none.
+    fn cell_expr(&self, cell: &Cell) -> Result<Option<String>> {
+        let flag = |k: &str| matches!(cell.param(k), Some(v) if v.as_bool());
+        let int = |k: &str| cell.param(k).and_then(|v| v.as_int()).unwrap_or(0);
+        let operand = |k: &str| -> Result<String> {
+            let e = self.expr(&self.port(cell, k)?);
+            Ok(if flag(&format!("{}_SIGNED", k)) {
+                format!("$signed({})", e)
+            } else {
+                e
+            })
+        };
+        let plain = |k: &str| -> Result<String> { Ok(self.expr(&self.port(cell, k)?)) };
+        let tp = cell.i1().as_str();
+        let unary = match tp {
+            "$not" | "$_NOT_" => Some("~"),
+            "$pos" | "$_BUF_" => Some(""),
+            "$neg" => Some("-"),
+            "$logic_not" => Some("!"),
+            "$reduce_and" => Some("&"),
+            "$reduce_or" | "$reduce_bool" => Some("|"),
+            "$reduce_xor" => Some("^"),
+            "$reduce_xnor" => Some("~^"),
+            _ => None,
+        };
+        if let Some(op) = unary {
+            return Ok(Some(format!("{}{}", op, operand("\\A")?)));
+        }
+        let signed = flag("\\A_SIGNED") || flag("\\B_SIGNED");
+        let binary = match tp {
+            "$and" | "$_AND_" => Some("&"),
+            "$or" | "$_OR_" => Some("|"),
+            "$xor" | "$_XOR_" => Some("^"),
+            "$xnor" => Some("~^"),
+            "$shl" => Some("<<"),
+            "$shr" => Some(">>"),
+            "$sshl" => Some("<<<"),
+            "$sshr" => Some(">>>"),
+            "$logic_and" => Some("&&"),
+            "$logic_or" => Some("||"),
+            "$eq" => Some("=="),
+            "$ne" => Some("!="),
+            "$eqx" => Some("==="),
+            "$nex" => Some("!=="),
+            "$lt" => Some("<"),
+            "$le" => Some("<="),
+            "$gt" => Some(">"),
+            "$ge" => Some(">="),
+            "$add" => Some("+"),
+            "$sub" => Some("-"),
+            "$mul" => Some("*"),
+            "$div" => Some("/"),
+            "$mod" => Some("%"),
+            // rounding only differs for negative operands
+            "$divfloor" if !signed => Some("/"),
+            "$modfloor" if !signed => Some("%"),
+            "$pow" => Some("**"),
+            _ => None,
+        };
+        if let Some(op) = binary {
+            // shift amounts are unsigned in Verilog
+            let b = if matches!(tp, "$shl" | "$shr" | "$sshl" | "$sshr") {
+                plain("\\B")?
+            } else {
+                operand("\\B")?
+            };
+            return Ok(Some(format!("{} {} {}", operand("\\A")?, op, b)));
+        }
+        let (a, b) = (|| plain("\\A"), || plain("\\B"));
+        let e = match tp {
+            "$_NAND_" => format!("~({} & {})", a()?, b()?),
+            "$_NOR_" => format!("~({} | {})", a()?, b()?),
+            "$_XNOR_" => format!("~({} ^ {})", a()?, b()?),
+            "$_ANDNOT_" => format!("{} & ~{}", a()?, b()?),
+            "$_ORNOT_" => format!("{} | ~{}", a()?, b()?),
+            "$_AOI3_" => format!("~(({} & {}) | {})", a()?, b()?, plain("\\C")?),
+            "$_OAI3_" => format!("~(({} | {}) & {})", a()?, b()?, plain("\\C")?),
+            "$_AOI4_" => format!(
+                "~(({} & {}) | ({} & {}))",
+                a()?,
+                b()?,
+                plain("\\C")?,
+                plain("\\D")?
+            ),
+            "$_OAI4_" => format!(
+                "~(({} | {}) & ({} | {}))",
+                a()?,
+                b()?,
+                plain("\\C")?,
+                plain("\\D")?
+            ),
+            "$mux" | "$_MUX_" => format!("{} ? {} : {}", plain("\\S")?, b()?, a()?),
+            "$bwmux" => {
+                let s = plain("\\S")?;
+                format!("({} & ~{}) | ({} & {})", a()?, s, b()?, s)
+            }
+            "$bweqx" => {
+                let (a, b) = (self.port(cell, "\\A")?, self.port(cell, "\\B")?);
+                let bits: Vec<String> = a
+                    .iter()
+                    .zip(b.iter())
+                    .rev()
+                    .map(|(x, y)| format!("{} === {}", self.bit(x), self.bit(y)))
+                    .collect();
+                format!("{{ {} }}", bits.join(", "))
+            }
+            "$_MUX4_" | "$_MUX8_" | "$_MUX16_" => {
+                let (inputs, selects): (&[&str], &[&str]) = match tp {
+                    "$_MUX4_" => (&["A", "B", "C", "D"], &["S", "T"]),
+                    "$_MUX8_" => (&["A", "B", "C", "D", "E", "F", "G", "H"], &["S", "T", "U"]),
+                    _ => (
+                        &[
+                            "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N",
+                            "O", "P",
+                        ],
+                        &["S", "T", "U", "V"],
+                    ),
+                };
+                // one level of selection per select input, `S` closest to
+                // the data
+                let mut level = inputs
+                    .iter()
+                    .map(|k| plain(&format!("\\{}", k)))
+                    .collect::<Result<Vec<_>>>()?;
+                for k in selects {
+                    let s = plain(&format!("\\{}", k))?;
+                    level = level
+                        .chunks(2)
+                        .map(|p| format!("({} ? {} : {})", s, p[1], p[0]))
+                        .collect();
+                }
+                let e = level.pop().unwrap_or_default();
+                e[1..e.len() - 1].to_string()
+            }
+            "$_NMUX_" => format!("~({} ? {} : {})", plain("\\S")?, b()?, a()?),
+            "$pmux" => {
+                let (s, bb) = (self.port(cell, "\\S")?, self.port(cell, "\\B")?);
+                let width = self.port(cell, "\\Y")?.len();
+                let mut e = String::new();
+                for (i, sb) in s.iter().enumerate() {
+                    let chunk = &bb[i * width..(i + 1) * width];
+                    e += &format!("{} ? {} : ", self.bit(sb), self.expr(chunk));
+                }
+                e + &a()?
+            }
+            "$tribuf" | "$_TBUF_" => {
+                let width = self.port(cell, "\\Y")?.len();
+                let en = if tp == "$tribuf" { "\\EN" } else { "\\E" };
+                format!(
+                    "{} ? {} : {}",
+                    plain(en)?,
+                    a()?,
+                    literal(&vec![State::Sz; width])
+                )
+            }
+            "$shift" if flag("\\B_SIGNED") => {
+                let b = operand("\\B")?;
+                format!(
+                    "{} < 0 ? {} << -{} : {} >> {}",
+                    b,
+                    operand("\\A")?,
+                    b,
+                    operand("\\A")?,
+                    b
+                )
+            }
+            "$shift" => format!("{} >> {}", operand("\\A")?, b()?),
+            "$bmux" => format!("{} >> ({} * {})", a()?, plain("\\S")?, int("\\WIDTH")),
+            "$demux" => format!("{} << ({} * {})", a()?, plain("\\S")?, int("\\WIDTH")),
+            "$lut" => {
+                let lut = cell.param("\\LUT").map(|v| v.to_bits()).unwrap_or_default();
+                format!("{} >> {}", literal(&lut), a()?)
+            }
+            "$sop" => {
+                let a = self.port(cell, "\\A")?;
+                let table = cell
+                    .param("\\TABLE")
+                    .map(|v| v.to_bits())
+                    .unwrap_or_default();
+                let mut terms = Vec::new();
+                for t in table
+                    .chunks(2 * a.len().max(1))
+                    .take(int("\\DEPTH") as usize)
+                {
+                    let lits: Vec<String> = a
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, b)| {
+                            let e = self.bit(b);
+                            match (t.get(2 * i), t.get(2 * i + 1)) {
+                                (Some(State::S1), _) => Some(format!("!{}", e)),
+                                (_, Some(State::S1)) => Some(e),
+                                _ => None,
+                            }
+                        })
+                        .collect();
+                    terms.push(match lits.len() {
+                        0 => "1'b1".to_string(),
+                        _ => format!("({})", lits.join(" & ")),
+                    });
+                }
+                match terms.len() {
+                    0 => "1'b0".to_string(),
+                    _ => terms.join(" | "),
+                }
+            }
+            "$slice" => {
+                let a = self.port(cell, "\\A")?;
+                let (off, w) = (int("\\OFFSET") as usize, int("\\Y_WIDTH") as usize);
+                self.expr(&a[off.min(a.len())..(off + w).min(a.len())])
+            }
+            "$concat" => {
+                let mut bits = self.port(cell, "\\A")?;
+                bits.extend(self.port(cell, "\\B")?);
+                self.expr(&bits)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(e))
+    }
+
+    // Input port `k` extended to `width` bits like yosys' cells do, by its
+    // `<k>_SIGNED` parameter.
+    fn extended(&self, cell: &Cell, k: &str, width: usize) -> Result<Vec<SigBit>> {
+        let mut v = self.port(cell, &format!("\\{}", k))?;
+        let signed = matches!(cell.param(&format!("\\{}_SIGNED", k)), Some(p) if p.as_bool());
+        let pad = match v.last() {
+            Some(b) if signed => b.clone(),
+            _ => SigBit::Const(State::S0),
+        };
+        v.resize(width, pad);
+        Ok(v)
+    }
+
+    // Statements of internal cells that take more than one expression:
+    // cells with several outputs, intermediate wires or procedural code.
+    // Returns false for other cells.
+    fn cell_stmts(&mut self, cell: &Cell) -> Result<bool> {
+        let tp = cell.i1().as_str();
+        let name = cell.i2();
+        let signed = |k: &str| matches!(cell.param(k), Some(v) if v.as_bool());
+        let (a, b) = (self.port(cell, "\\A")?, self.port(cell, "\\B")?);
+        match tp {
+            // out of range bits read as `x`, as in yosys' `write_verilog`
+            "$shiftx" => {
+                let y = self.port(cell, "\\Y")?;
+                let v = self.vector(&a, &format!("{}$a", name));
+                let b = match signed("\\B_SIGNED") {
+                    true => format!("$signed({})", self.expr(&b)),
+                    false => self.expr(&b),
+                };
+                self.assign(&y, format!("{}[{} +: {}]", v, b, y.len()));
+            }
+            "$alu" => {
+                let y = self.port(cell, "\\Y")?;
+                let a = self.expr(&self.extended(cell, "A", y.len())?);
+                let b = self.expr(&self.extended(cell, "B", y.len())?);
+                let bi = self.expr(&self.port(cell, "\\BI")?);
+                let ci = self.expr(&self.port(cell, "\\CI")?);
+                let b = format!("({} ^ {{{}{{{}}}}})", b, y.len(), bi);
+                let sum = format!("{} + {} + {}", a, b, ci);
+                // the carry into each bit is `a ^ b ^ y`
+                let co = format!(
+                    "({} & {}) | (({} ^ {}) & ({} ^ {} ^ ({})))",
+                    a, b, a, b, a, b, sum
+                );
+                self.assign(&self.port(cell, "\\X")?, format!("{} ^ {}", a, b));
+                self.assign(&y, sum);
+                self.assign(&self.port(cell, "\\CO")?, co);
+            }
+            "$lcu" => {
+                let (p, g) = (self.port(cell, "\\P")?, self.port(cell, "\\G")?);
+                let co = self.new_wire(&format!("{}$co", name), p.len());
+                let mut carry = self.expr(&self.port(cell, "\\CI")?);
+                for i in 0..p.len() {
+                    let e = format!("{} | ({} & {})", self.bit(&g[i]), self.bit(&p[i]), carry);
+                    self.assign(&co[i..=i], e);
+                    carry = self.bit(&co[i]);
+                }
+                self.assign(&self.port(cell, "\\CO")?, self.expr(&co));
+            }
+            "$fa" => {
+                let (a, b) = (self.expr(&a), self.expr(&b));
+                let c = self.expr(&self.port(cell, "\\C")?);
+                self.assign(
+                    &self.port(cell, "\\X")?,
+                    format!("({} & {}) | ({} & {}) | ({} & {})", a, b, a, c, b, c),
+                );
+                self.assign(&self.port(cell, "\\Y")?, format!("{} ^ {} ^ {}", a, b, c));
+            }
+            // signed division rounding towards negative infinity, in terms
+            // of Verilog's truncating `/` and `%`
+            "$divfloor" | "$modfloor" => {
+                let y = self.port(cell, "\\Y")?;
+                let width = a.len().max(b.len()).max(y.len());
+                let (am, bm) = match (a.last(), b.last()) {
+                    (Some(x), Some(y)) => (self.bit(x), self.bit(y)),
+                    _ => bail!("cell `{}' of type `{}' has an empty operand", name, tp),
+                };
+                let (sa, sb) = (
+                    format!("$signed({})", self.expr(&a)),
+                    format!("$signed({})", self.expr(&b)),
+                );
+                if tp == "$divfloor" {
+                    let t = self.new_wire(&format!("{}$div", name), width + 1);
+                    self.assign(
+                        &t,
+                        format!(
+                            "{} == {} || {} == 0 ? {} : {} - ({} ? {} + 1 : {} - 1)",
+                            am,
+                            bm,
+                            self.expr(&a),
+                            sa,
+                            sa,
+                            bm,
+                            sb,
+                            sb
+                        ),
+                    );
+                    self.assign(&y, format!("$signed({}) / {}", self.expr(&t), sb));
+                } else {
+                    let t = self.new_wire(&format!("{}$mod", name), width);
+                    self.assign(&t, format!("{} % {}", sa, sb));
+                    let t = self.expr(&t);
+                    self.assign(
+                        &y,
+                        format!(
+                            "{} == {} || {} == 0 ? $signed({}) : {} + $signed({})",
+                            am, bm, t, t, sb, t
+                        ),
+                    );
+                }
+            }
+            "$assert" | "$assume" | "$cover" => {
+                let en = self.expr(&self.port(cell, "\\EN")?);
+                self.body.push(format!(
+                    "  always @* if ({}) {}({});",
+                    en,
+                    &tp[1..],
+                    self.expr(&a)
+                ));
+            }
+            // system functions of yosys' formal Verilog dialect
+            "$anyconst" | "$anyseq" | "$allconst" | "$allseq" | "$initstate" => {
+                self.assign(&self.port(cell, "\\Y")?, tp.to_string());
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn ff(&mut self, cell: &Cell, mut ff: Ff<SigBit>) -> Result<()> {
+        if ff.global {
+            let q = self.reg(&ff.q, cell.i2());
+            let (q, d) = (self.expr(&q), self.expr(&ff.d));
+            self.body.push("  always @($global_clock)".to_string());
+            self.body.push(format!("    {} <= {};", q, d));
+            return Ok(());
+        }
+        // controls tied to a constant level
+        ff.en = ff.en.filter(|(e, p)| !is_level(e, *p, true));
+        ff.arst = ff.arst.filter(|(r, p, _)| !is_level(r, *p, false));
+        ff.srst = ff.srst.filter(|(r, p, _)| !is_level(r, *p, false));
+        ff.aload = ff.aload.filter(|(l, p, _)| !is_level(l, *p, false));
+        let q = self.reg(&ff.q, cell.i2());
+        let d = self.expr(&ff.d);
+        let mut lines = Vec::new();
+        if let (Some((set, sp)), Some((clr, cp))) = (&ff.set, &ff.clr) {
+            // every bit has its own asynchronous set and reset
+            for i in 0..q.len() {
+                let qi = self.bit(&q[i]);
+                let mut sens = Vec::new();
+                let mut body = format!(
+                    "if ({}) {} <= 1'b0; else if ({}) {} <= 1'b1;",
+                    self.cond(&clr[i], *cp),
+                    qi,
+                    self.cond(&set[i], *sp),
+                    qi
+                );
+                if let Some((c, p)) = &ff.clk {
+                    sens.push(self.edge(c, *p));
+                    sens.push(self.edge(&clr[i], *cp));
+                    sens.push(self.edge(&set[i], *sp));
+                }
+                if !ff.d.is_empty() {
+                    let load = format!("{} <= {};", qi, self.bit(&ff.d[i]));
+                    body += &match &ff.en {
+                        Some((e, p)) => format!(" else if ({}) {}", self.cond(e, *p), load),
+                        None => format!(" else {}", load),
+                    };
+                }
+                let sens = if sens.is_empty() {
+                    "*".to_string()
+                } else {
+                    format!("({})", sens.join(", "))
+                };
+                lines.push(format!("  always @{}", sens));
+                lines.push(format!("    {}", body));
+            }
+            self.body.extend(lines);
+            return Ok(());
+        }
+
+        let q = self.expr(&q);
+        let mut sens = Vec::new();
+        let mut chain = Vec::new();
+        if let Some((c, p)) = &ff.clk {
+            sens.push(self.edge(c, *p));
+        }
+        if let Some((r, p, v)) = &ff.arst {
+            if ff.clk.is_some() {
+                sens.push(self.edge(r, *p));
+            }
+            chain.push((Some(self.cond(r, *p)), literal(v)));
+        }
+        if let Some((l, p, ad)) = &ff.aload {
+            sens.push(self.edge(l, *p));
+            chain.push((Some(self.cond(l, *p)), self.expr(ad)));
+        }
+        let en = ff.en.as_ref().map(|(e, p)| self.cond(e, *p));
+        match &ff.srst {
+            Some((r, p, v)) if ff.ce_over_srst => {
+                chain.push((en, format!("{} ? {} : {}", self.cond(r, *p), literal(v), d)))
+            }
+            Some((r, p, v)) => {
+                chain.push((Some(self.cond(r, *p)), literal(v)));
+                chain.push((en, d));
+            }
+            None => chain.push((en, d)),
+        }
+        let mut body = String::new();
+        for (i, (c, v)) in chain.iter().enumerate() {
+            if i > 0 {
+                body += " else ";
+            }
+            match c {
+                Some(c) => body += &format!("if ({}) {} <= {};", c, q, v),
+                None => body += &format!("{} <= {};", q, v),
+            }
+        }
+        let sens = if sens.is_empty() {
+            "*".to_string()
+        } else {
+            format!("({})", sens.join(", "))
+        };
+        self.body.push(format!("  always @{}", sens));
+        self.body.push(format!("    {}", body));
+        Ok(())
+    }
+
+    fn memory(&mut self, id: &str, width: usize, offset: i64, size: usize) {
+        if self.memories.iter().any(|m| m == id) {
+            return;
+        }
+        self.memories.push(id.to_string());
+        self.decls.push(format!(
+            "  reg{} {} [{}:{}];",
+            range(width as i64, 0, false),
+            verilog_id(id),
+            offset,
+            offset + size as i64 - 1
+        ));
+    }
+
+    fn mem_init(&mut self, mem: &Mem) {
+        let id = verilog_id(&mem.id);
+        let mut lines = Vec::new();
+        for (i, w) in mem.data.iter().enumerate() {
+            if w.iter().any(|s| *s != State::Sx) {
+                lines.push(format!(
+                    "    {}[{}] = {};",
+                    id,
+                    mem.offset + i as i64,
+                    literal(w)
+                ));
+            }
+        }
+        if !lines.is_empty() {
+            self.body.push("  initial begin".to_string());
+            self.body.extend(lines);
+            self.body.push("  end".to_string());
+        }
+    }
+
+    fn read_port(&mut self, mem: &str, p: &RdPort<SigBit>, name: &str) {
+        let mut p = p.clone();
+        p.en = p.en.filter(|e| !is_level(e, true, true));
+        p.arst = p.arst.filter(|(r, _)| !is_level(r, true, false));
+        p.srst = p.srst.filter(|(r, _)| !is_level(r, true, false));
+        let word = format!("{}[{}]", verilog_id(mem), self.expr(&p.addr));
+        let (c, pol) = match &p.clk {
+            Some(c) => c.clone(),
+            None => {
+                self.assign(&p.data, word);
+                return;
+            }
+        };
+        let enabled = matches!(&p.en, None | Some(SigBit::Const(State::S1)));
+        if p.transparent && enabled && p.arst.is_none() && p.srst.is_none() {
+            // new data shows up through the registered address
+            let addr = self.new_reg(&format!("{}$addr", name), p.addr.len());
+            let a = self.expr(&addr);
+            self.body
+                .push(format!("  always @({})", self.edge(&c, pol)));
+            self.body
+                .push(format!("    {} <= {};", a, self.expr(&p.addr)));
+            self.assign(&p.data, format!("{}[{}]", verilog_id(mem), a));
+            return;
+        }
+        let q = self.reg(&p.data, name);
+        let q = self.expr(&q);
+        let mut sens = vec![self.edge(&c, pol)];
+        let mut chain = Vec::new();
+        if let Some((r, v)) = &p.arst {
+            sens.push(self.edge(r, true));
+            chain.push((Some(self.bit(r)), literal(v)));
+        }
+        let en = p.en.as_ref().map(|e| self.bit(e));
+        match &p.srst {
+            Some((r, v)) if p.ce_over_srst => {
+                chain.push((en, format!("{} ? {} : {}", self.bit(r), literal(v), word)))
+            }
+            Some((r, v)) => {
+                chain.push((Some(self.bit(r)), literal(v)));
+                chain.push((en, word));
+            }
+            None => chain.push((en, word)),
+        }
+        let mut body = String::new();
+        for (i, (c, v)) in chain.iter().enumerate() {
+            if i > 0 {
+                body += " else ";
+            }
+            match c {
+                Some(c) => body += &format!("if ({}) {} <= {};", c, q, v),
+                None => body += &format!("{} <= {};", q, v),
+            }
+        }
+        self.body.push(format!("  always @({})", sens.join(", ")));
+        self.body.push(format!("    {}", body));
+    }
+
+    fn write_port(&mut self, mem: &str, width: usize, p: &WrPort<SigBit>) {
+        let addr = self.expr(&p.addr);
+        let mut lines = Vec::new();
+        for (w, data) in p.data.chunks(width.max(1)).enumerate() {
+            let word = match w {
+                0 => format!("{}[{}]", verilog_id(mem), addr),
+                _ => format!("{}[{} + {}]", verilog_id(mem), addr, w),
+            };
+            // runs of bits sharing an enable
+            let en = &p.en[w * width..(w * width + data.len()).min(p.en.len())];
+            let mut i = 0;
+            while i < en.len() {
+                let mut j = i + 1;
+                while j < en.len() && en[j] == en[i] {
+                    j += 1;
+                }
+                let target = if i == 0 && j == data.len() {
+                    word.clone()
+                } else if j - i == 1 {
+                    format!("{}[{}]", word, i)
+                } else {
+                    format!("{}[{}:{}]", word, j - 1, i)
+                };
+                let stmt = format!("{} <= {};", target, self.expr(&data[i..j]));
+                match &en[i] {
+                    SigBit::Const(State::S1) => lines.push(format!("    {}", stmt)),
+                    SigBit::Const(_) => (),
+                    e => lines.push(format!("    if ({}) {}", self.bit(e), stmt)),
+                }
+                i = j;
+            }
+        }
+        let sens = match &p.clk {
+            Some((c, pol)) => format!("({})", self.edge(c, *pol)),
+            None => "*".to_string(),
+        };
+        self.body.push(format!("  always @{} begin", sens));
+        self.body.extend(lines);
+        self.body.push("  end".to_string());
+    }
+
+    fn mem_cell(&mut self, cell: &Cell) -> Result<bool> {
+        let port = |k: &str| self.port(cell, k);
+        let memid = match cell.param("\\MEMID") {
+            Some(Const::Str(s)) => s.clone(),
+            _ => cell.i2().clone(),
+        };
+        let find = |id: &str| self.module.memories().iter().find(|m| m.id() == id);
+        let width = |id: &str| find(id).map_or(0, |m| *m.width() as usize);
+        match cell.i1().as_str() {
+            "$mem" | "$mem_v2" => {
+                let (m, rd, wr) = mem_cell(cell, 0, port)?;
+                self.memory(&m.id, m.width, m.offset, m.data.len());
+                self.mem_init(&m);
+                for (i, p) in rd.iter().enumerate() {
+                    self.read_port(&m.id, p, &format!("{}$rd{}", cell.i2(), i));
+                }
+                for p in wr.iter() {
+                    self.write_port(&m.id, m.width, p);
+                }
+            }
+            "$memrd" | "$memrd_v2" => {
+                let p = rd_cell(cell, 0, port)?;
+                self.read_port(&memid, &p, cell.i2());
+            }
+            "$memwr" | "$memwr_v2" => {
+                let p = wr_cell(cell, 0, port)?;
+                self.write_port(&memid, width(&memid), &p);
+            }
+            "$meminit" | "$meminit_v2" => {
+                let id = verilog_id(&memid);
+                let addr = self.port(cell, "\\ADDR")?;
+                let data = self.port(cell, "\\DATA")?;
+                let en = self.port(cell, "\\EN")?;
+                let width = width(&memid).max(1);
+                let base = self.expr(&addr);
+                let mut lines = Vec::new();
+                for (w, word) in data.chunks(width).enumerate() {
+                    let target = match w {
+                        0 => format!("{}[{}]", id, base),
+                        _ => format!("{}[{} + {}]", id, base, w),
+                    };
+                    let masked = en.iter().any(|b| *b != SigBit::Const(State::S1));
+                    if !masked {
+                        lines.push(format!("    {} = {};", target, self.expr(word)));
+                        continue;
+                    }
+                    for (i, b) in word.iter().enumerate() {
+                        if en.get(i) == Some(&SigBit::Const(State::S1)) {
+                            lines.push(format!("    {}[{}] = {};", target, i, self.bit(b)));
+                        }
+                    }
+                }
+                self.body.push("  initial begin".to_string());
+                self.body.extend(lines);
+                self.body.push("  end".to_string());
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Instance of a module or of an internal cell with no Verilog
+    // equivalent.
+    fn instance(&mut self, cell: &Cell) -> Result<()> {
+        let mut params: Vec<(&String, &CellParam)> = cell.params().iter().collect();
+        params.sort_by_key(|(k, _)| *k);
+        let mut ports: Vec<(&String, &SigSpec)> = cell.connects().iter().collect();
+        ports.sort_by_key(|(k, _)| *k);
+        let mut s = verilog_id(cell.i1());
+        if !params.is_empty() {
+            let p: Vec<String> = params
+                .iter()
+                .map(|(k, v)| {
+                    let signed = v.flags().contains(CellFlag::SIGNED);
+                    format!(".{}({})", verilog_id(k), value(v.val(), signed))
+                })
+                .collect();
+            s += &format!(" #({})", p.join(", "));
+        }
+        let mut conns = Vec::new();
+        for (k, v) in ports {
+            let bits = self.wt.bits(v)?;
+            let e = if bits.is_empty() {
+                String::new()
+            } else {
+                self.expr(&bits)
+            };
+            conns.push(format!("    .{}({})", verilog_id(k), e));
+        }
+        self.body.extend(attrs(cell.attrs(), "  "));
+        self.body
+            .push(format!("  {} {} (", s, verilog_id(cell.i2())));
+        self.body.push(conns.join(",\n"));
+        self.body.push("  );".to_string());
+        Ok(())
+    }
+
+    fn cell(&mut self, cell: &Cell) -> Result<()> {
+        let tp = cell.i1();
+        if tp.starts_with('$') {
+            if let Some(e) = self.cell_expr(cell)? {
+                let y = self.port(cell, "\\Y")?;
+                self.assign(&y, e);
+                return Ok(());
+            }
+            if self.cell_stmts(cell)? {
+                return Ok(());
+            }
+            if let Some(ff) = Ff::new(cell, |k| self.port(cell, k))? {
+                return self.ff(cell, ff);
+            }
+            if self.mem_cell(cell)? {
+                return Ok(());
+            }
+            // derived modules are written like any other module
+            if !tp.starts_with("$paramod") && !tp.starts_with("$abstract") {
+                bail!("can not write cell `{}' of type `{}'", cell.i2(), tp);
+            }
+        }
+        self.instance(cell)
+    }
+
+    fn case(&self, c: &ProcessSwitchCase, indent: usize, out: &mut Vec<String>) -> Result<()> {
+        let ind = " ".repeat(indent);
+        for (l, r) in c.assign() {
+            out.push(format!("{}{} = {};", ind, self.sig(l)?, self.sig(r)?));
+        }
+        for s in c.switch() {
+            out.push(format!("{}casez ({})", ind, self.sig(s.sig())?));
+            for case in s.cases() {
+                let label = if case.sigs().is_empty() {
+                    "default".to_string()
+                } else {
+                    case.sigs()
+                        .iter()
+                        .map(|p| self.sig(p))
+                        .collect::<Result<Vec<_>>>()?
+                        .join(", ")
+                };
+                out.push(format!("{}  {}: begin", ind, label));
+                self.case(case, indent + 4, out)?;
+                out.push(format!("{}  end", ind));
+            }
+            out.push(format!("{}endcase", ind));
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, p: &Process) -> Result<()> {
+        let mut root = ProcessSwitchCase::default();
+        *root.assign_mut() = p.assign().clone();
+        *root.switch_mut() = p.switch().clone();
+        let mut lhs = Vec::new();
+        collect_lhs(&root, &mut lhs);
+        for s in p.syncs() {
+            lhs.extend(s.updates().iter().map(|(l, _)| l.clone()));
+        }
+        for l in lhs {
+            for b in self.wt.bits(&l)? {
+                if let Some(w) = b.wire() {
+                    self.regs.insert(w.to_string());
+                }
+            }
+        }
+
+        self.body.extend(attrs(p.attrs(), "  "));
+        if !root.assign().is_empty() || !root.switch().is_empty() {
+            self.body.push("  always @* begin".to_string());
+            let mut out = Vec::new();
+            self.case(&root, 4, &mut out)?;
+            self.body.extend(out);
+            self.body.push("  end".to_string());
+        }
+
+        let updates = |s: &ProcessSync, op: &str, ind: &str| -> Result<Vec<String>> {
+            s.updates()
+                .iter()
+                .map(|(l, r)| Ok(format!("{}{} {} {};", ind, self.sig(l)?, op, self.sig(r)?)))
+                .collect()
+        };
+        // edge rules with the same updates share one block
+        let mut edges: Vec<(Vec<String>, Vec<String>, &ProcessSync)> = Vec::new();
+        let mut levels: Vec<(String, String, &ProcessSync)> = Vec::new();
+        let mut always = None;
+        let mut lines = Vec::new();
+        let mut out = Vec::new();
+        for s in p.syncs() {
+            let edge = match s.tp() {
+                ProcessSyncType::Posedge(c) => Some(format!("posedge {}", self.sig(c)?)),
+                ProcessSyncType::Negedge(c) => Some(format!("negedge {}", self.sig(c)?)),
+                ProcessSyncType::Edge(c) => Some(self.sig(c)?),
+                _ => None,
+            };
+            if let Some(e) = edge {
+                let u = updates(s, "<=", "")?;
+                match edges.iter_mut().find(|(_, v, _)| *v == u) {
+                    Some((sens, _, _)) => sens.push(e),
+                    None => edges.push((vec![e], u, s)),
+                }
+                continue;
+            }
+            match s.tp() {
+                ProcessSyncType::Always => always = Some(s),
+                ProcessSyncType::Init => {
+                    lines.push("  initial begin".to_string());
+                    lines.extend(updates(s, "=", "    ")?);
+                    lines.push("  end".to_string());
+                }
+                ProcessSyncType::Low(c) => {
+                    let c = self.sig(c)?;
+                    levels.push((format!("!{}", c), format!("negedge {}", c), s));
+                }
+                ProcessSyncType::High(c) => {
+                    let c = self.sig(c)?;
+                    levels.push((c.clone(), format!("posedge {}", c), s));
+                }
+                _ => bail!("can not write `sync global' of process `{}'", p.id()),
+            }
+        }
+
+        if let Some(s) = always {
+            if !edges.is_empty() || !levels.is_empty() {
+                bail!(
+                    "can not write process `{}' with `sync always' and other sync rules",
+                    p.id()
+                );
+            }
+            out.push("  always @* begin".to_string());
+            out.extend(updates(s, "=", "    ")?);
+            out.push("  end".to_string());
+        }
+        if levels.is_empty() {
+            for (sens, _, s) in edges {
+                out.push(format!("  always @({}) begin", sens.join(", ")));
+                out.extend(updates(s, "<=", "    ")?);
+                out.push("  end".to_string());
+            }
+        } else {
+            // levels take priority over edges, all in one block so that
+            // each register has a single driver; with edges the levels are
+            // only sampled when they become active, like asynchronous resets
+            if edges.len() > 1 {
+                bail!(
+                    "can not write process `{}' with level rules and edge rules updating different signals",
+                    p.id()
+                );
+            }
+            let sens = match edges.first() {
+                Some((sens, _, _)) => {
+                    let mut v = sens.clone();
+                    v.extend(levels.iter().map(|(_, e, _)| e.clone()));
+                    format!("({})", v.join(", "))
+                }
+                None => "*".to_string(),
+            };
+            out.push(format!("  always @{} begin", sens));
+            for (i, (c, _, s)) in levels.iter().enumerate() {
+                let kw = if i == 0 { "    if" } else { "    end else if" };
+                out.push(format!("{} ({}) begin", kw, c));
+                out.extend(updates(s, "<=", "      ")?);
+            }
+            if let Some((_, _, s)) = edges.first() {
+                out.push("    end else begin".to_string());
+                out.extend(updates(s, "<=", "      ")?);
+            }
+            out.push("    end".to_string());
+            out.push("  end".to_string());
+        }
+        self.body.extend(out);
+        self.body.extend(lines);
+        Ok(())
+    }
+
+    fn write<W: Write>(mut self, w: &mut W) -> Result<()> {
+        let m = self.module;
+        let blackbox = matches!(m.attrs().get("\\blackbox"), Some(v) if v.as_bool());
+        if !blackbox {
+            for c in m.connects() {
+                let y = self.wt.bits(c.sig1())?;
+                let e = self.sig(c.sig2())?;
+                self.assign(&y, e);
+            }
+            for mem in m.memories() {
+                self.memory(
+                    mem.id(),
+                    *mem.width() as usize,
+                    *mem.offset(),
+                    *mem.size() as usize,
+                );
+            }
+            for c in m.cells() {
+                self.cell(c)?;
+            }
+            for p in m.processes() {
+                self.process(p)?;
+            }
+        }
+
+        for l in attrs(m.attrs(), "") {
+            writeln!(w, "{}", l)?;
+        }
+        let mut ports: Vec<&Wire> = m.wires().iter().filter(|w| *w.port() > 0).collect();
+        ports.sort_by_key(|w| *w.port());
+        let names: Vec<String> = ports.iter().map(|w| verilog_id(w.id())).collect();
+        writeln!(w, "module {}({});", verilog_id(m.ident()), names.join(", "))?;
+        for (k, v) in m.params() {
+            writeln!(w, "  parameter {} = {};", verilog_id(k), value(v, false))?;
+        }
+        for wire in m.wires() {
+            if blackbox && *wire.port() == 0 {
+                continue;
+            }
+            let reg = self.regs.contains(wire.id());
+            let init = match reg {
+                true => self.init_value(&self.wt.bits(&SigSpec::wire(wire.id()))?),
+                false => None,
+            };
+            // the initial value of a register goes into its declaration
+            let mut wattrs = wire.attrs().clone();
+            if init.is_some() {
+                wattrs.remove("\\init");
+            }
+            for l in attrs(&wattrs, "  ") {
+                writeln!(w, "{}", l)?;
+            }
+            let kind = match (wire.input(), wire.output()) {
+                (true, true) => "inout",
+                (true, false) => "input",
+                (false, true) if reg => "output reg",
+                (false, true) => "output",
+                _ if reg => "reg",
+                _ => "wire",
+            };
+            let init = init.map(|v| format!(" = {}", v)).unwrap_or_default();
+            writeln!(
+                w,
+                "  {}{}{} {}{};",
+                kind,
+                if *wire.signed() { " signed" } else { "" },
+                range(*wire.width(), *wire.offset(), *wire.upto()),
+                verilog_id(wire.id()),
+                init
+            )?;
+        }
+        for l in self.decls.iter().chain(self.body.iter()) {
+            writeln!(w, "{}", l)?;
+        }
+        writeln!(w, "endmodule")?;
+        Ok(())
+    }
+}
+
+// Whether `bit` is tied to the level making a control of polarity `pol`
+// `active`.
+fn is_level(bit: &SigBit, pol: bool, active: bool) -> bool {
+    let one = pol == active;
+    *bit == SigBit::Const(if one { State::S1 } else { State::S0 })
+}
+
+fn collect_lhs(c: &ProcessSwitchCase, out: &mut Vec<SigSpec>) {
+    out.extend(c.assign().iter().map(|(l, _)| l.clone()));
+    for s in c.switch() {
+        for c in s.cases() {
+            collect_lhs(c, out);
+        }
+    }
+}
+
+/// Write `design` as Verilog-2005.
+///
+/// Internal cells become continuous assignments or `always` blocks,
+/// memories become arrays, and processes become `always` blocks with a
+/// `casez` per switch. Level and edge sync rules of a process share one
+/// block, with the levels sampled on their active edges like asynchronous
+/// resets. As in yosys' `write_verilog`, `$ff` cells are clocked by
+/// `$global_clock` and formal cells use the `assert`/`assume`/`cover`
+/// statements and `$anyconst`-style functions of its formal dialect. Other
+/// internal cells without a Verilog equivalent are an error, cells of other
+/// types are written as instances, and blackbox modules only with their
+/// ports. Registers get their initial value from the `init` attributes of
+/// the wires connected to them.
+pub fn write_verilog<W: Write>(w: &mut W, design: &Design) -> Result<()> {
+    for (i, m) in design.modules().iter().enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        Writer::new(m)?.write(w)?;
+    }
+    Ok(())
+}
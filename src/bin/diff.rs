@@ -0,0 +1,31 @@
+// Copyright (c) 2020 xhe
+
+use rtlil::{diff::diff, lexer::Lexer, parser::Parser};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::process;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = env::args().collect();
+    let structural = args.iter().any(|a| a == "-s");
+    args.retain(|a| a != "-s");
+    match args.len() {
+        3 => {
+            let mut designs = Vec::new();
+            for f in &args[1..] {
+                let input = fs::read_to_string(f)?;
+                designs.push(Parser::new().parse(Lexer::new(input.chars()))?);
+            }
+            let diffs = diff(&designs[0], &designs[1], structural);
+            for d in diffs.iter() {
+                println!("{}", d);
+            }
+            if !diffs.is_empty() {
+                process::exit(1);
+            }
+        }
+        _ => println!("diff [-s] [a] [b]"),
+    }
+    Ok(())
+}
@@ -0,0 +1,486 @@
+// Copyright (c) 2020 xhe
+
+//! Bounded model checking of formal cells, built on the SAT solver.
+
+use crate::cnf::ModuleCnf;
+use crate::passes::techmap;
+use crate::sat::Solver;
+use crate::sim::{ff::Ff, Simulator, Vcd};
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use getset::*;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+// Literal `l` of the step whose variables start after `off`.
+fn shift(l: i32, off: i32) -> i32 {
+    if l > 0 {
+        l + off
+    } else {
+        l - off
+    }
+}
+
+// Builder of the gates added while preparing a module.
+struct Gates<'a> {
+    module: &'a mut Module,
+    wt: WireTable,
+}
+
+impl Gates<'_> {
+    fn bit(&mut self) -> SigBit {
+        let s = self.module.new_wire("bmc", 1);
+        let id = match &s {
+            SigSpec::Refer((id, _)) => id.clone(),
+            _ => unreachable!(),
+        };
+        self.wt.insert(self.module.wire(&id).unwrap());
+        SigBit::Wire((id, 0))
+    }
+
+    fn sig(&self, b: &SigBit) -> SigSpec {
+        self.wt.sigspec(std::slice::from_ref(b))
+    }
+
+    // `s ? b : a`, for an active-`pol` select.
+    fn mux(&mut self, a: &SigBit, b: &SigBit, s: &SigBit, pol: bool) -> SigBit {
+        let (a, b) = if pol { (a, b) } else { (b, a) };
+        let y = self.bit();
+        let (a, b, s, ys) = (self.sig(a), self.sig(b), self.sig(s), self.sig(&y));
+        let c = self.module.new_cell("$_MUX_", "bmc");
+        c.set_port("\\A", a);
+        c.set_port("\\B", b);
+        c.set_port("\\S", s);
+        c.set_port("\\Y", ys);
+        y
+    }
+
+    fn ff(&mut self, d: &SigBit, q: &SigBit) {
+        let (d, q) = (self.sig(d), self.sig(q));
+        let c = self.module.new_cell("$_FF_", "bmc");
+        c.set_port("\\D", d);
+        c.set_port("\\Q", q);
+    }
+
+    // Wire connected to `bits`, or None if there are none.
+    fn wire(&mut self, bits: &[SigBit]) -> Option<String> {
+        if bits.is_empty() {
+            return None;
+        }
+        let s = self.module.new_wire("bmc", bits.len() as i64);
+        let rhs = self.wt.sigspec(bits);
+        self.module
+            .connects_mut()
+            .push(Connect::new(s.clone(), rhs));
+        match s {
+            SigSpec::Refer((id, _)) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Check {
+    tp: String,
+    name: String,
+    a: SigBit,
+    en: SigBit,
+}
+
+// A module made ready for unrolling: every register is a `$_FF_` updated
+// once per step, the formal cells are taken out and the rest is lowered to
+// gates.
+#[derive(Debug, Clone)]
+struct Model {
+    design: Design,
+    checks: Vec<Check>,
+    init: Vec<(SigBit, State)>,
+    /// Wire over all register outputs.
+    state: Option<String>,
+    /// Wire over all `$anyseq` outputs.
+    free: Option<String>,
+}
+
+impl Model {
+    fn new(module: &Module) -> Result<Self> {
+        if !module.processes().is_empty() {
+            bail!("module `{}' has processes, run proc first", module.ident());
+        }
+        if !module.memories().is_empty() {
+            bail!("module `{}' has memories, map them first", module.ident());
+        }
+        let mut m = module.clone();
+        let cells = std::mem::take(m.cells_mut());
+        let wt = WireTable::new(&m);
+        let mut g = Gates { module: &mut m, wt };
+        let (mut state, mut free) = (Vec::new(), Vec::new());
+        let (mut checks, mut init) = (Vec::new(), Vec::new());
+        for c in cells {
+            let port = |wt: &WireTable, k: &str| match c.port(k) {
+                Some(s) => wt.bits(s),
+                None => bail!("cell `{}' has no port `{}'", c.i2(), k),
+            };
+            if let Some(ff) = Ff::new(&c, |k| port(&g.wt, k))? {
+                if ff.arst.is_some() || ff.aload.is_some() || ff.set.is_some() || ff.clr.is_some() {
+                    bail!("register `{}' has asynchronous controls", c.i2());
+                }
+                if ff.clk.is_none() && !ff.global {
+                    bail!("can not check latch `{}'", c.i2());
+                }
+                for (i, q) in ff.q.iter().enumerate() {
+                    let mut next = ff.d[i].clone();
+                    if let (Some((e, pol)), false) = (&ff.en, ff.ce_over_srst) {
+                        next = g.mux(q, &next, e, *pol);
+                    }
+                    if let Some((r, pol, v)) = &ff.srst {
+                        next = g.mux(&next, &SigBit::Const(v[i]), r, *pol);
+                    }
+                    if let (Some((e, pol)), true) = (&ff.en, ff.ce_over_srst) {
+                        next = g.mux(q, &next, e, *pol);
+                    }
+                    g.ff(&next, q);
+                }
+                state.extend(ff.q);
+                continue;
+            }
+            match c.i1().as_str() {
+                "$anyconst" | "$anyseq" | "$initstate" => {
+                    let y = port(&g.wt, "\\Y")?;
+                    match c.i1().as_str() {
+                        "$anyseq" => free.extend(y),
+                        "$anyconst" => {
+                            y.iter().for_each(|b| g.ff(b, b));
+                            state.extend(y);
+                        }
+                        _ => {
+                            g.ff(&SigBit::Const(State::S0), &y[0]);
+                            init.push((y[0].clone(), State::S1));
+                            state.push(y[0].clone());
+                        }
+                    }
+                }
+                "$assert" | "$assume" | "$cover" => {
+                    let (a, en) = (port(&g.wt, "\\A")?, port(&g.wt, "\\EN")?);
+                    if a.len() != 1 || en.len() != 1 {
+                        bail!("ports of cell `{}' are not bits", c.i2());
+                    }
+                    checks.push(Check {
+                        tp: c.i1().clone(),
+                        name: c.i2().clone(),
+                        a: a[0].clone(),
+                        en: en[0].clone(),
+                    });
+                }
+                "$live" | "$fair" | "$allconst" | "$allseq" => {
+                    bail!("can not check cell `{}' of type `{}'", c.i2(), c.i1())
+                }
+                _ => g.module.cells_mut().push(c),
+            }
+        }
+        let (state, free) = (g.wire(&state), g.wire(&free));
+        let wt = g.wt;
+        for w in m.wires() {
+            if let Some(c) = w.attrs().get("\\init") {
+                let bits = wt.bits(&SigSpec::wire(w.id()))?;
+                for (b, s) in bits.into_iter().zip(c.to_bits()) {
+                    if matches!(s, State::S0 | State::S1) {
+                        init.push((b, s));
+                    }
+                }
+            }
+        }
+        techmap(&mut m)?;
+        let mut design = Design::new();
+        design.modules_mut().push(m);
+        Ok(Self {
+            design,
+            checks,
+            init,
+            state,
+            free,
+        })
+    }
+}
+
+/// A trace of the inputs of a module, step by step, leading to a failing
+/// `$assert` or a reached `$cover`.
+#[derive(Debug, Clone, Getters)]
+pub struct Witness {
+    /// Names of the cells failing or reached at the last step.
+    #[get = "pub"]
+    cells: Vec<String>,
+    /// Values of the input ports at each step.
+    #[get = "pub"]
+    inputs: Vec<BTreeMap<String, Const>>,
+    design: Design,
+    /// Initial register values.
+    state: Option<(String, Vec<State>)>,
+    /// `$anyseq` values at each step.
+    free: Option<(String, Vec<Vec<State>>)>,
+}
+
+impl Witness {
+    /// Number of steps of the trace.
+    pub fn steps(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Replay the trace in the simulator and write it as a Value Change
+    /// Dump, one time unit per step.
+    pub fn write_vcd<W: Write>(&self, w: W) -> Result<W> {
+        let ident = self.design.modules()[0].ident();
+        let mut sim = Simulator::new(&self.design, ident)?;
+        if let Some((id, v)) = &self.state {
+            sim.set(id, v)?;
+        }
+        let mut vcd = Vcd::new(w, &sim, "1ns")?;
+        for (k, inputs) in self.inputs.iter().enumerate() {
+            for (id, c) in inputs {
+                sim.set(id, &c.to_bits())?;
+            }
+            if let Some((id, v)) = &self.free {
+                sim.set(id, &v[k])?;
+            }
+            sim.update()?;
+            vcd.sample(&sim, k as u64)?;
+            sim.tick()?;
+        }
+        vcd.finish()
+    }
+}
+
+// The model encoded once and copied into the solver step after step, each
+// copy over its own variables, the registers of a step taking the next
+// state of the step before.
+struct Unroller {
+    model: Model,
+    solver: Solver,
+    clauses: Vec<Vec<i32>>,
+    vars: i32,
+    inputs: Vec<(String, Vec<i32>)>,
+    next: Vec<(i32, i32)>,
+    init: Vec<i32>,
+    checks: Vec<(i32, i32)>,
+    state: Vec<i32>,
+    free: Vec<i32>,
+    /// Offset of the variables of each step so far.
+    steps: Vec<i32>,
+    top: i32,
+}
+
+impl Unroller {
+    fn new(module: &Module) -> Result<Self> {
+        let model = Model::new(module)?;
+        let m = &model.design.modules()[0];
+        let mut enc = ModuleCnf::new(m)?;
+        let mut inputs = Vec::new();
+        for w in m.wires().iter().filter(|w| *w.input()) {
+            inputs.push((w.id().clone(), enc.lits(&SigSpec::wire(w.id()))?));
+        }
+        let next = enc
+            .state()
+            .clone()
+            .iter()
+            .map(|(q, d)| (enc.lit(q), enc.lit(d)))
+            .collect();
+        let init = model
+            .init
+            .iter()
+            .map(|(b, s)| match s {
+                State::S1 => enc.lit(b),
+                _ => -enc.lit(b),
+            })
+            .collect();
+        let checks = model
+            .checks
+            .iter()
+            .map(|c| (enc.lit(&c.a), enc.lit(&c.en)))
+            .collect();
+        let mut lits = |w: &Option<String>| match w {
+            Some(id) => enc.lits(&SigSpec::wire(id)),
+            None => Ok(Vec::new()),
+        };
+        let (state, free) = (lits(&model.state)?, lits(&model.free)?);
+        Ok(Self {
+            solver: Solver::new(),
+            clauses: enc.cnf().clauses().clone(),
+            vars: *enc.cnf().vars() as i32,
+            inputs,
+            next,
+            init,
+            checks,
+            state,
+            free,
+            steps: Vec::new(),
+            top: 0,
+            model,
+        })
+    }
+
+    fn fresh(&mut self) -> i32 {
+        self.top += 1;
+        self.top
+    }
+
+    // Add the next step and return its index. `$assume`s hold in it.
+    fn step(&mut self) -> usize {
+        let off = self.top;
+        self.top += self.vars;
+        for c in self.clauses.iter() {
+            let c: Vec<i32> = c.iter().map(|l| shift(*l, off)).collect();
+            self.solver.add_clause(&c);
+        }
+        match self.steps.last() {
+            None => {
+                for l in self.init.iter() {
+                    self.solver.add_clause(&[shift(*l, off)]);
+                }
+            }
+            Some(&prev) => {
+                for (q, d) in self.next.iter() {
+                    let (q, d) = (shift(*q, off), shift(*d, prev));
+                    self.solver.add_clause(&[-q, d]);
+                    self.solver.add_clause(&[q, -d]);
+                }
+            }
+        }
+        self.steps.push(off);
+        let k = self.steps.len() - 1;
+        for i in 0..self.checks.len() {
+            if self.model.checks[i].tp == "$assume" {
+                let (a, en) = self.check(k, i);
+                self.solver.add_clause(&[-en, a]);
+            }
+        }
+        k
+    }
+
+    // Literals of `A` and `EN` of check `i` at step `k`.
+    fn check(&self, k: usize, i: usize) -> (i32, i32) {
+        let (a, en) = self.checks[i];
+        (shift(a, self.steps[k]), shift(en, self.steps[k]))
+    }
+
+    fn value(&self, l: i32) -> State {
+        match self.solver.value(l) {
+            Some(true) => State::S1,
+            _ => State::S0,
+        }
+    }
+
+    fn values(&self, lits: &[i32], k: usize) -> Vec<State> {
+        lits.iter()
+            .map(|l| self.value(shift(*l, self.steps[k])))
+            .collect()
+    }
+
+    // Trace of the current model.
+    fn witness(&self, cells: Vec<String>) -> Witness {
+        let steps = 0..self.steps.len();
+        let inputs = steps
+            .clone()
+            .map(|k| {
+                self.inputs
+                    .iter()
+                    .map(|(id, lits)| (id.clone(), Const::from_bits(&self.values(lits, k))))
+                    .collect()
+            })
+            .collect();
+        let state = (self.model.state.clone()).map(|id| (id, self.values(&self.state, 0)));
+        let free = (self.model.free.clone())
+            .map(|id| (id, steps.map(|k| self.values(&self.free, k)).collect()));
+        Witness {
+            cells,
+            inputs,
+            design: self.model.design.clone(),
+            state,
+            free,
+        }
+    }
+
+    fn checks_of(&self, tp: &str) -> Vec<usize> {
+        (0..self.checks.len())
+            .filter(|i| self.model.checks[*i].tp == tp)
+            .collect()
+    }
+}
+
+/// Check the `$assert` cells of `module` over its first `depth` steps,
+/// starting from the values of the `init` attributes and under its
+/// `$assume` cells. Returns a trace to the first step where an assert
+/// fails, if there is one.
+///
+/// Each step is one active edge of every clock, `$anyseq` cells take any
+/// value at each step, and `$anyconst` cells any value kept for the whole
+/// trace. Registers without initial values start with any value. The module
+/// must be flat, without processes, memories or registers with asynchronous
+/// controls; word-level cells are lowered with
+/// [`techmap`](crate::passes::techmap). Undefined bits are taken as 0.
+pub fn bmc(module: &Module, depth: usize) -> Result<Option<Witness>> {
+    let mut u = Unroller::new(module)?;
+    let asserts = u.checks_of("$assert");
+    if asserts.is_empty() {
+        return Ok(None);
+    }
+    for _ in 0..depth {
+        let k = u.step();
+        // some assert fails at step k
+        let bad = u.fresh();
+        let mut any = vec![-bad];
+        for i in asserts.iter() {
+            let (a, en) = u.check(k, *i);
+            let t = u.fresh();
+            u.solver.add_clause(&[-t, en]);
+            u.solver.add_clause(&[-t, -a]);
+            any.push(t);
+        }
+        u.solver.add_clause(&any);
+        if u.solver.solve_with(&[bad]) {
+            let failed = asserts
+                .iter()
+                .filter(|i| {
+                    let (a, en) = u.check(k, **i);
+                    u.value(en) == State::S1 && u.value(a) == State::S0
+                })
+                .map(|i| u.model.checks[*i].name.clone())
+                .collect();
+            return Ok(Some(u.witness(failed)));
+        }
+        for i in asserts.iter() {
+            let (a, en) = u.check(k, *i);
+            u.solver.add_clause(&[-en, a]);
+        }
+    }
+    Ok(None)
+}
+
+/// Search traces of `module` up to `depth` steps long reaching each of its
+/// `$cover` cells, under the same semantics as [`bmc`]. Returns the
+/// shortest trace found for each cover, by name.
+pub fn cover(module: &Module, depth: usize) -> Result<Vec<(String, Option<Witness>)>> {
+    let mut u = Unroller::new(module)?;
+    let covers = u.checks_of("$cover");
+    let mut r: Vec<(String, Option<Witness>)> = covers
+        .iter()
+        .map(|i| (u.model.checks[*i].name.clone(), None))
+        .collect();
+    for _ in 0..depth {
+        if r.iter().all(|(_, w)| w.is_some()) {
+            break;
+        }
+        let k = u.step();
+        for (j, i) in covers.iter().enumerate() {
+            if r[j].1.is_some() {
+                continue;
+            }
+            let (a, en) = u.check(k, *i);
+            let hit = u.fresh();
+            u.solver.add_clause(&[-hit, en]);
+            u.solver.add_clause(&[-hit, a]);
+            if u.solver.solve_with(&[hit]) {
+                r[j].1 = Some(u.witness(vec![r[j].0.clone()]));
+            }
+        }
+    }
+    Ok(r)
+}
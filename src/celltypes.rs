@@ -0,0 +1,380 @@
+// Copyright (c) 2020 xhe
+
+//! Port directions of yosys' internal cells and of user modules.
+
+use super::syntax::*;
+use getset::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDir {
+    Input,
+    Output,
+    Inout,
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct CellType {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    /// Outputs only depend on the current inputs.
+    comb: bool,
+}
+
+impl CellType {
+    fn new(inputs: &[&str], outputs: &[&str], comb: bool) -> Self {
+        Self {
+            inputs: inputs.iter().map(|p| format!("\\{}", p)).collect(),
+            outputs: outputs.iter().map(|p| format!("\\{}", p)).collect(),
+            comb,
+        }
+    }
+
+    pub fn port_dir(&self, port: &str) -> Option<PortDir> {
+        match (
+            self.inputs.iter().any(|p| p == port),
+            self.outputs.iter().any(|p| p == port),
+        ) {
+            (true, true) => Some(PortDir::Inout),
+            (true, false) => Some(PortDir::Input),
+            (false, true) => Some(PortDir::Output),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CellTypes {
+    types: HashMap<String, CellType>,
+}
+
+impl CellTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Word-level and gate-level internal cells.
+    pub fn internals() -> Self {
+        let mut r = Self::new();
+        let mut add = |names: &[&str], i: &[&str], o: &[&str], comb: bool| {
+            for n in names {
+                r.types.insert(n.to_string(), CellType::new(i, o, comb));
+            }
+        };
+
+        add(
+            &[
+                "$not",
+                "$pos",
+                "$neg",
+                "$reduce_and",
+                "$reduce_or",
+                "$reduce_xor",
+                "$reduce_xnor",
+                "$reduce_bool",
+                "$logic_not",
+                "$slice",
+                "$lut",
+                "$sop",
+            ],
+            &["A"],
+            &["Y"],
+            true,
+        );
+        add(
+            &[
+                "$and",
+                "$or",
+                "$xor",
+                "$xnor",
+                "$shl",
+                "$shr",
+                "$sshl",
+                "$sshr",
+                "$shift",
+                "$shiftx",
+                "$lt",
+                "$le",
+                "$eq",
+                "$ne",
+                "$eqx",
+                "$nex",
+                "$ge",
+                "$gt",
+                "$add",
+                "$sub",
+                "$mul",
+                "$div",
+                "$mod",
+                "$divfloor",
+                "$modfloor",
+                "$pow",
+                "$logic_and",
+                "$logic_or",
+                "$concat",
+                "$bweqx",
+                "$equiv",
+            ],
+            &["A", "B"],
+            &["Y"],
+            true,
+        );
+        add(&["$mux", "$pmux", "$bwmux"], &["A", "B", "S"], &["Y"], true);
+        add(&["$bmux", "$demux"], &["A", "S"], &["Y"], true);
+        add(&["$tribuf"], &["A", "EN"], &["Y"], true);
+        add(&["$lcu"], &["P", "G", "CI"], &["CO"], true);
+        add(&["$alu"], &["A", "B", "CI", "BI"], &["X", "Y", "CO"], true);
+        add(&["$fa"], &["A", "B", "C"], &["X", "Y"], true);
+
+        add(
+            &["$assert", "$assume", "$live", "$fair", "$cover"],
+            &["A", "EN"],
+            &[],
+            false,
+        );
+        add(
+            &["$initstate", "$anyconst", "$anyseq", "$allconst", "$allseq"],
+            &[],
+            &["Y"],
+            false,
+        );
+
+        add(&["$sr"], &["SET", "CLR"], &["Q"], false);
+        add(&["$ff"], &["D"], &["Q"], false);
+        add(&["$dff"], &["CLK", "D"], &["Q"], false);
+        add(&["$dffe"], &["CLK", "EN", "D"], &["Q"], false);
+        add(&["$dffsr"], &["CLK", "SET", "CLR", "D"], &["Q"], false);
+        add(
+            &["$dffsre"],
+            &["CLK", "SET", "CLR", "D", "EN"],
+            &["Q"],
+            false,
+        );
+        add(&["$adff"], &["CLK", "ARST", "D"], &["Q"], false);
+        add(&["$adffe"], &["CLK", "ARST", "D", "EN"], &["Q"], false);
+        add(&["$aldff"], &["CLK", "ALOAD", "AD", "D"], &["Q"], false);
+        add(
+            &["$aldffe"],
+            &["CLK", "ALOAD", "AD", "D", "EN"],
+            &["Q"],
+            false,
+        );
+        add(&["$sdff"], &["CLK", "SRST", "D"], &["Q"], false);
+        add(
+            &["$sdffe", "$sdffce"],
+            &["CLK", "SRST", "D", "EN"],
+            &["Q"],
+            false,
+        );
+        add(&["$dlatch"], &["EN", "D"], &["Q"], false);
+        add(&["$adlatch"], &["EN", "ARST", "D"], &["Q"], false);
+        add(&["$dlatchsr"], &["EN", "SET", "CLR", "D"], &["Q"], false);
+
+        add(&["$memrd"], &["CLK", "EN", "ADDR"], &["DATA"], false);
+        add(
+            &["$memrd_v2"],
+            &["CLK", "EN", "ARST", "SRST", "ADDR"],
+            &["DATA"],
+            false,
+        );
+        add(
+            &["$memwr", "$memwr_v2"],
+            &["CLK", "EN", "ADDR", "DATA"],
+            &[],
+            false,
+        );
+        add(&["$meminit"], &["ADDR", "DATA"], &[], false);
+        add(&["$meminit_v2"], &["ADDR", "DATA", "EN"], &[], false);
+        add(
+            &["$mem"],
+            &[
+                "RD_CLK", "RD_EN", "RD_ADDR", "WR_CLK", "WR_EN", "WR_ADDR", "WR_DATA",
+            ],
+            &["RD_DATA"],
+            false,
+        );
+        add(
+            &["$mem_v2"],
+            &[
+                "RD_CLK", "RD_EN", "RD_ARST", "RD_SRST", "RD_ADDR", "WR_CLK", "WR_EN", "WR_ADDR",
+                "WR_DATA",
+            ],
+            &["RD_DATA"],
+            false,
+        );
+        add(&["$fsm"], &["CLK", "ARST", "CTRL_IN"], &["CTRL_OUT"], false);
+
+        add(&["$_BUF_", "$_NOT_"], &["A"], &["Y"], true);
+        add(
+            &[
+                "$_AND_",
+                "$_NAND_",
+                "$_OR_",
+                "$_NOR_",
+                "$_XOR_",
+                "$_XNOR_",
+                "$_ANDNOT_",
+                "$_ORNOT_",
+            ],
+            &["A", "B"],
+            &["Y"],
+            true,
+        );
+        add(&["$_MUX_", "$_NMUX_"], &["A", "B", "S"], &["Y"], true);
+        add(&["$_MUX4_"], &["A", "B", "C", "D", "S", "T"], &["Y"], true);
+        add(
+            &["$_MUX8_"],
+            &["A", "B", "C", "D", "E", "F", "G", "H", "S", "T", "U"],
+            &["Y"],
+            true,
+        );
+        add(
+            &["$_MUX16_"],
+            &[
+                "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P",
+                "S", "T", "U", "V",
+            ],
+            &["Y"],
+            true,
+        );
+        add(&["$_AOI3_", "$_OAI3_"], &["A", "B", "C"], &["Y"], true);
+        add(&["$_AOI4_", "$_OAI4_"], &["A", "B", "C", "D"], &["Y"], true);
+        add(&["$_TBUF_"], &["A", "E"], &["Y"], true);
+
+        add(&["$_FF_"], &["D"], &["Q"], false);
+        for a in ["N", "P"].iter() {
+            add(&[&format!("$_DFF_{}_", a)], &["C", "D"], &["Q"], false);
+            add(&[&format!("$_DLATCH_{}_", a)], &["E", "D"], &["Q"], false);
+            for b in ["N", "P"].iter() {
+                add(&[&format!("$_SR_{}{}_", a, b)], &["S", "R"], &["Q"], false);
+                add(
+                    &[&format!("$_DFFE_{}{}_", a, b)],
+                    &["C", "D", "E"],
+                    &["Q"],
+                    false,
+                );
+                add(
+                    &[&format!("$_ALDFF_{}{}_", a, b)],
+                    &["C", "L", "AD", "D"],
+                    &["Q"],
+                    false,
+                );
+                for e in ["N", "P"].iter() {
+                    add(
+                        &[&format!("$_ALDFFE_{}{}{}_", a, b, e)],
+                        &["C", "L", "AD", "D", "E"],
+                        &["Q"],
+                        false,
+                    );
+                }
+                for v in ["0", "1"].iter() {
+                    let n = format!("{}{}{}", a, b, v);
+                    add(&[&format!("$_DFF_{}_", n)], &["C", "R", "D"], &["Q"], false);
+                    add(
+                        &[&format!("$_SDFF_{}_", n)],
+                        &["C", "R", "D"],
+                        &["Q"],
+                        false,
+                    );
+                    add(
+                        &[&format!("$_DLATCH_{}_", n)],
+                        &["E", "R", "D"],
+                        &["Q"],
+                        false,
+                    );
+                    for e in ["N", "P"].iter() {
+                        let n = format!("{}{}", n, e);
+                        add(
+                            &[&format!("$_DFFE_{}_", n)],
+                            &["C", "R", "D", "E"],
+                            &["Q"],
+                            false,
+                        );
+                        add(
+                            &[&format!("$_SDFFE_{}_", n)],
+                            &["C", "R", "D", "E"],
+                            &["Q"],
+                            false,
+                        );
+                        add(
+                            &[&format!("$_SDFFCE_{}_", n)],
+                            &["C", "R", "D", "E"],
+                            &["Q"],
+                            false,
+                        );
+                    }
+                }
+                for c in ["N", "P"].iter() {
+                    let n = format!("{}{}{}", a, b, c);
+                    add(
+                        &[&format!("$_DFFSR_{}_", n)],
+                        &["C", "S", "R", "D"],
+                        &["Q"],
+                        false,
+                    );
+                    add(
+                        &[&format!("$_DLATCHSR_{}_", n)],
+                        &["E", "S", "R", "D"],
+                        &["Q"],
+                        false,
+                    );
+                    for e in ["N", "P"].iter() {
+                        add(
+                            &[&format!("$_DFFSRE_{}{}_", n, e)],
+                            &["C", "S", "R", "D", "E"],
+                            &["Q"],
+                            false,
+                        );
+                    }
+                }
+            }
+        }
+        r
+    }
+
+    /// Internal cells plus the modules of `design`, with directions taken
+    /// from their port wires.
+    pub fn with_design(design: &Design) -> Self {
+        let mut r = Self::internals();
+        r.add_design(design);
+        r
+    }
+
+    pub fn add_design(&mut self, design: &Design) {
+        for m in design.modules() {
+            let mut t = CellType::default();
+            for w in m.wires() {
+                if *w.input() {
+                    t.inputs.push(w.id().clone());
+                }
+                if *w.output() {
+                    t.outputs.push(w.id().clone());
+                }
+            }
+            self.types.insert(m.ident().clone(), t);
+        }
+    }
+
+    pub fn get(&self, tp: &str) -> Option<&CellType> {
+        self.types.get(tp)
+    }
+
+    pub fn port_dir(&self, tp: &str, port: &str) -> Option<PortDir> {
+        self.get(tp).and_then(|t| t.port_dir(port))
+    }
+
+    pub fn is_input(&self, tp: &str, port: &str) -> bool {
+        matches!(
+            self.port_dir(tp, port),
+            Some(PortDir::Input) | Some(PortDir::Inout)
+        )
+    }
+
+    pub fn is_output(&self, tp: &str, port: &str) -> bool {
+        matches!(
+            self.port_dir(tp, port),
+            Some(PortDir::Output) | Some(PortDir::Inout)
+        )
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (c) 2020 xhe
+
+//! Conjunctive normal form encoding of gate-level modules.
+
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use getset::*;
+use std::collections::HashMap;
+
+/// A formula in conjunctive normal form. Variables are numbered from 1 and
+/// literals are variables, negated when below zero, as in DIMACS.
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct Cnf {
+    vars: usize,
+    clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_var(&mut self) -> i32 {
+        self.vars += 1;
+        self.vars as i32
+    }
+
+    pub fn add(&mut self, clause: &[i32]) {
+        self.clauses.push(clause.to_vec());
+    }
+
+    /// Constrain `a` and `b` to be equal.
+    pub fn equal(&mut self, a: i32, b: i32) {
+        self.add(&[-a, b]);
+        self.add(&[a, -b]);
+    }
+
+    // Constrain `y` to `a & b`.
+    fn and_to(&mut self, y: i32, a: i32, b: i32) {
+        self.add(&[-y, a]);
+        self.add(&[-y, b]);
+        self.add(&[y, -a, -b]);
+    }
+
+    // Constrain `y` to `a ^ b`.
+    fn xor_to(&mut self, y: i32, a: i32, b: i32) {
+        self.add(&[-y, a, b]);
+        self.add(&[-y, -a, -b]);
+        self.add(&[y, -a, b]);
+        self.add(&[y, a, -b]);
+    }
+
+    // Constrain `y` to `s ? b : a`.
+    fn mux_to(&mut self, y: i32, a: i32, b: i32, s: i32) {
+        self.add(&[s, -a, y]);
+        self.add(&[s, a, -y]);
+        self.add(&[-s, -b, y]);
+        self.add(&[-s, b, -y]);
+    }
+
+    pub fn and(&mut self, a: i32, b: i32) -> i32 {
+        let y = self.new_var();
+        self.and_to(y, a, b);
+        y
+    }
+
+    pub fn or(&mut self, a: i32, b: i32) -> i32 {
+        -self.and(-a, -b)
+    }
+
+    pub fn xor(&mut self, a: i32, b: i32) -> i32 {
+        let y = self.new_var();
+        self.xor_to(y, a, b);
+        y
+    }
+
+    /// `s ? b : a`, like `$_MUX_`.
+    pub fn mux(&mut self, a: i32, b: i32, s: i32) -> i32 {
+        let y = self.new_var();
+        self.mux_to(y, a, b, s);
+        y
+    }
+}
+
+/// Tseitin encoding of a gate-level module: a variable per net and the
+/// clauses tying the output of each gate to its inputs.
+///
+/// The outputs of `$_FF_` and `$_DFF_[NP]_` cells are left free, so that
+/// the formula relates the current state and inputs to the outputs and the
+/// next state, found on the `D` input of the flip-flops. Undefined constant
+/// bits are taken as 0.
+#[derive(Debug, Clone, Getters)]
+pub struct ModuleCnf {
+    #[get = "pub"]
+    cnf: Cnf,
+    wt: WireTable,
+    sigmap: SigMap,
+    lits: HashMap<SigBit, i32>,
+    /// Variable fixed to true.
+    truth: i32,
+    /// `Q` and `D` of each flip-flop, one entry per bit.
+    #[get = "pub"]
+    state: Vec<(SigBit, SigBit)>,
+}
+
+impl ModuleCnf {
+    pub fn new(module: &Module) -> Result<Self> {
+        if !module.processes().is_empty() {
+            bail!("module `{}' has processes, run proc first", module.ident());
+        }
+        if !module.memories().is_empty() {
+            bail!("module `{}' has memories, map them first", module.ident());
+        }
+        let wt = WireTable::new(module);
+        let sigmap = SigMap::new(module, &wt)?;
+        let mut cnf = Cnf::new();
+        let truth = cnf.new_var();
+        cnf.add(&[truth]);
+        let mut r = Self {
+            cnf,
+            wt,
+            sigmap,
+            lits: HashMap::new(),
+            truth,
+            state: Vec::new(),
+        };
+        for c in module.cells() {
+            r.cell(c)?;
+        }
+        Ok(r)
+    }
+
+    /// Literal of the net of `b`.
+    pub fn lit(&mut self, b: &SigBit) -> i32 {
+        match self.sigmap.map_bit(b) {
+            SigBit::Const(State::S1) => self.truth,
+            SigBit::Const(_) => -self.truth,
+            b => {
+                if let Some(l) = self.lits.get(&b) {
+                    return *l;
+                }
+                let l = self.cnf.new_var();
+                self.lits.insert(b, l);
+                l
+            }
+        }
+    }
+
+    /// Literals of the bits of `s`, LSB first.
+    pub fn lits(&mut self, s: &SigSpec) -> Result<Vec<i32>> {
+        Ok(self.wt.bits(s)?.iter().map(|b| self.lit(b)).collect())
+    }
+
+    /// Add the clause `lits`, as for [`Cnf::add`].
+    pub fn add(&mut self, lits: &[i32]) {
+        self.cnf.add(lits);
+    }
+
+    /// The encoder, to build more logic over the module.
+    pub fn cnf_mut(&mut self) -> &mut Cnf {
+        &mut self.cnf
+    }
+
+    fn port(&mut self, cell: &Cell, k: &str) -> Result<i32> {
+        let bits = match cell.port(&format!("\\{}", k)) {
+            Some(s) => self.wt.bits(s)?,
+            None => bail!("cell `{}' has no port `{}'", cell.i2(), k),
+        };
+        if bits.len() != 1 {
+            bail!("port `{}' of cell `{}' is not a bit", k, cell.i2());
+        }
+        Ok(self.lit(&bits[0]))
+    }
+
+    fn cell(&mut self, cell: &Cell) -> Result<()> {
+        let tp = cell.i1().as_str();
+        if matches!(tp, "$_FF_" | "$_DFF_P_" | "$_DFF_N_") {
+            let bit = |k: &str| -> Result<SigBit> {
+                match cell.port(k).map(|s| self.wt.bits(s)) {
+                    Some(Ok(b)) if b.len() == 1 => Ok(b[0].clone()),
+                    _ => bail!("port `{}' of cell `{}' is not a bit", k, cell.i2()),
+                }
+            };
+            let q = self.sigmap.map_bit(&bit("\\Q")?);
+            let d = self.sigmap.map_bit(&bit("\\D")?);
+            self.state.push((q, d));
+            return Ok(());
+        }
+        let a = |r: &mut Self| r.port(cell, "A");
+        let b = |r: &mut Self| r.port(cell, "B");
+        let (y, a) = (self.port(cell, "Y")?, a(self)?);
+        match tp {
+            "$_BUF_" => self.cnf.equal(y, a),
+            "$_NOT_" => self.cnf.equal(y, -a),
+            "$_AND_" => {
+                let b = b(self)?;
+                self.cnf.and_to(y, a, b)
+            }
+            "$_NAND_" => {
+                let b = b(self)?;
+                self.cnf.and_to(-y, a, b)
+            }
+            "$_OR_" => {
+                let b = b(self)?;
+                self.cnf.and_to(-y, -a, -b)
+            }
+            "$_NOR_" => {
+                let b = b(self)?;
+                self.cnf.and_to(y, -a, -b)
+            }
+            "$_XOR_" => {
+                let b = b(self)?;
+                self.cnf.xor_to(y, a, b)
+            }
+            "$_XNOR_" => {
+                let b = b(self)?;
+                self.cnf.xor_to(-y, a, b)
+            }
+            "$_ANDNOT_" => {
+                let b = b(self)?;
+                self.cnf.and_to(y, a, -b)
+            }
+            "$_ORNOT_" => {
+                let b = b(self)?;
+                self.cnf.and_to(-y, -a, b)
+            }
+            "$_MUX_" | "$_NMUX_" => {
+                let (b, s) = (b(self)?, self.port(cell, "S")?);
+                let y = if tp == "$_MUX_" { y } else { -y };
+                self.cnf.mux_to(y, a, b, s)
+            }
+            _ => bail!(
+                "can not encode cell `{}' of type `{}', run techmap first",
+                cell.i2(),
+                tp
+            ),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright (c) 2020 xhe
+
+//! Structural comparison of designs.
+
+use crate::syntax::*;
+use getset::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Module,
+    Wire,
+    Memory,
+    Cell,
+    Process,
+    Connect,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Kind::Module => "module",
+            Kind::Wire => "wire",
+            Kind::Memory => "memory",
+            Kind::Cell => "cell",
+            Kind::Process => "process",
+            Kind::Connect => "connect",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    /// The property `what` went from `old` to `new`, `None` standing for an
+    /// attribute, parameter or port that is not there.
+    Changed {
+        what: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct Difference {
+    /// The module of the item, or the module itself.
+    module: String,
+    kind: Kind,
+    /// Name of the item in the first design, or in the second one if it was
+    /// added. Connections are named by their text.
+    name: String,
+    change: Change,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let item = match self.kind {
+            Kind::Module => format!("module {}", self.module),
+            k => format!("{} {} {}", k, self.module, self.name),
+        };
+        let opt = |v: &Option<String>| v.clone().unwrap_or_else(|| "(none)".to_string());
+        match &self.change {
+            Change::Added => write!(f, "+ {}", item),
+            Change::Removed => write!(f, "- {}", item),
+            Change::Changed { what, old, new } => {
+                write!(f, "~ {}: {}: {} -> {}", item, what, opt(old), opt(new))
+            }
+        }
+    }
+}
+
+fn is_auto(id: &str) -> bool {
+    id.starts_with('$')
+}
+
+// Copy of `s` with the wires renamed by `f`.
+fn rename<F: Fn(&str) -> String>(s: &SigSpec, f: &F) -> SigSpec {
+    match s {
+        SigSpec::Refer((w, r)) => SigSpec::Refer((f(w), *r)),
+        SigSpec::List(v) => SigSpec::List(v.iter().map(|s| rename(s, f)).collect()),
+        s => s.clone(),
+    }
+}
+
+struct Matcher<'a> {
+    a: &'a Module,
+    b: &'a Module,
+    /// Wires and cells of `a` paired with those of `b`.
+    wires: HashMap<&'a str, &'a str>,
+    cells: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(a: &'a Module, b: &'a Module) -> Self {
+        let wires = a
+            .wires()
+            .iter()
+            .filter(|w| b.wire(w.id()).is_some())
+            .map(|w| (w.id().as_str(), w.id().as_str()))
+            .collect();
+        let cells = a
+            .cells()
+            .iter()
+            .filter(|c| b.cell(c.i2()).is_some())
+            .map(|c| (c.i2().as_str(), c.i2().as_str()))
+            .collect();
+        Self { a, b, wires, cells }
+    }
+
+    // `s` of the first module with the names of the second one.
+    fn map_sig(&self, s: &SigSpec) -> SigSpec {
+        rename(s, &|w: &str| self.wires.get(w).unwrap_or(&w).to_string())
+    }
+
+    // Signature of a cell with auto names of unpaired wires erased: cells
+    // with the same one are taken for the same.
+    fn key(&self, c: &Cell, first: bool, exact: bool) -> String {
+        let paired: BTreeSet<&str> = self.wires.values().copied().collect();
+        let erase = |w: &str| {
+            let known = if first {
+                self.wires.contains_key(w)
+            } else {
+                paired.contains(w)
+            };
+            if is_auto(w) && !known {
+                "?".to_string()
+            } else if first {
+                self.wires.get(w).unwrap_or(&w).to_string()
+            } else {
+                w.to_string()
+            }
+        };
+        let params: BTreeMap<&String, String> = c
+            .params()
+            .iter()
+            .filter(|_| exact)
+            .map(|(k, p)| (k, p.val().to_string()))
+            .collect();
+        let ports: BTreeMap<&String, String> = c
+            .connects()
+            .iter()
+            .map(|(k, s)| (k, rename(s, &erase).to_string()))
+            .collect();
+        format!("{} {:?} {:?}", c.i1(), params, ports)
+    }
+
+    // Pair the remaining cells with auto names by signature, and the auto
+    // wires they connect to in the same way, until nothing changes. Cells
+    // are paired ignoring their parameters when nothing else is left, so
+    // that changed parameters are reported as such.
+    fn by_structure(&mut self) {
+        let mut exact = true;
+        loop {
+            let mut progress = false;
+            let mut keys: HashMap<String, (Vec<&'a Cell>, Vec<&'a Cell>)> = HashMap::new();
+            let paired: BTreeSet<&str> = self.cells.values().copied().collect();
+            for c in self.a.cells() {
+                if is_auto(c.i2()) && !self.cells.contains_key(c.i2().as_str()) {
+                    keys.entry(self.key(c, true, exact)).or_default().0.push(c);
+                }
+            }
+            for c in self.b.cells() {
+                if is_auto(c.i2()) && !paired.contains(c.i2().as_str()) {
+                    keys.entry(self.key(c, false, exact)).or_default().1.push(c);
+                }
+            }
+            let mut pairs: Vec<(&Cell, &Cell)> = keys
+                .values()
+                .filter(|(x, y)| x.len() == 1 && y.len() == 1)
+                .map(|(x, y)| (x[0], y[0]))
+                .collect();
+            pairs.sort_by(|x, y| x.0.i2().cmp(y.0.i2()));
+            for (x, y) in pairs {
+                self.cells.insert(x.i2(), y.i2());
+                progress = true;
+                for (k, s) in x.connects() {
+                    if let Some(t) = y.port(k) {
+                        self.pair_wires(s, t);
+                    }
+                }
+            }
+            if progress {
+                exact = true;
+            } else if exact {
+                exact = false;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn pair_wires(&mut self, s: &'a SigSpec, t: &'a SigSpec) {
+        match (s, t) {
+            (SigSpec::Refer((v, r)), SigSpec::Refer((w, q))) if r == q => {
+                let taken = self.wires.values().any(|x| *x == w);
+                let same = matches!(
+                    (self.a.wire(v), self.b.wire(w)),
+                    (Some(x), Some(y)) if x.width() == y.width()
+                );
+                if is_auto(v) && is_auto(w) && same && !taken {
+                    self.wires.entry(v).or_insert(w);
+                }
+            }
+            (SigSpec::List(x), SigSpec::List(y)) if x.len() == y.len() => {
+                for (s, t) in x.iter().zip(y.iter()) {
+                    self.pair_wires(s, t);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct Report<'a> {
+    module: &'a str,
+    out: Vec<Difference>,
+}
+
+impl<'a> Report<'a> {
+    fn push(&mut self, kind: Kind, name: &str, change: Change) {
+        self.out.push(Difference {
+            module: self.module.to_string(),
+            kind,
+            name: name.to_string(),
+            change,
+        });
+    }
+
+    fn value<T: PartialEq + ToString>(&mut self, kind: Kind, name: &str, what: &str, a: T, b: T) {
+        if a != b {
+            let change = Change::Changed {
+                what: what.to_string(),
+                old: Some(a.to_string()),
+                new: Some(b.to_string()),
+            };
+            self.push(kind, name, change);
+        }
+    }
+
+    // Compare two maps of named values, reporting each key as `what key`.
+    fn map<V: ToString>(
+        &mut self,
+        kind: Kind,
+        name: &str,
+        what: &str,
+        a: &HashMap<String, V>,
+        b: &HashMap<String, V>,
+    ) {
+        let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+        for k in keys {
+            let old = a.get(k).map(|v| v.to_string());
+            let new = b.get(k).map(|v| v.to_string());
+            if old != new {
+                let what = format!("{} {}", what, k);
+                self.push(kind, name, Change::Changed { what, old, new });
+            }
+        }
+    }
+}
+
+fn module_diff(a: &Module, b: &Module, structural: bool, out: &mut Vec<Difference>) {
+    let mut m = Matcher::new(a, b);
+    if structural {
+        m.by_structure();
+    }
+    let mut r = Report {
+        module: a.ident(),
+        out: Vec::new(),
+    };
+    r.map(Kind::Module, "", "attribute", a.attrs(), b.attrs());
+    let params = |m: &Module| m.params().iter().cloned().collect::<HashMap<_, _>>();
+    r.map(Kind::Module, "", "parameter", &params(a), &params(b));
+
+    let paired: BTreeSet<&str> = m.wires.values().copied().collect();
+    for x in a.wires() {
+        let y = match m.wires.get(x.id().as_str()).and_then(|w| b.wire(w)) {
+            Some(y) => y,
+            None => {
+                r.push(Kind::Wire, x.id(), Change::Removed);
+                continue;
+            }
+        };
+        let n = x.id();
+        r.value(Kind::Wire, n, "width", x.width(), y.width());
+        r.value(Kind::Wire, n, "offset", x.offset(), y.offset());
+        r.value(Kind::Wire, n, "port", x.port(), y.port());
+        r.value(Kind::Wire, n, "input", x.input(), y.input());
+        r.value(Kind::Wire, n, "output", x.output(), y.output());
+        r.value(Kind::Wire, n, "upto", x.upto(), y.upto());
+        r.value(Kind::Wire, n, "signed", x.signed(), y.signed());
+        r.map(Kind::Wire, n, "attribute", x.attrs(), y.attrs());
+    }
+    for y in b
+        .wires()
+        .iter()
+        .filter(|w| !paired.contains(w.id().as_str()))
+    {
+        r.push(Kind::Wire, y.id(), Change::Added);
+    }
+
+    for x in a.memories() {
+        let y = match b.memories().iter().find(|y| y.id() == x.id()) {
+            Some(y) => y,
+            None => {
+                r.push(Kind::Memory, x.id(), Change::Removed);
+                continue;
+            }
+        };
+        let n = x.id();
+        r.value(Kind::Memory, n, "width", x.width(), y.width());
+        r.value(Kind::Memory, n, "offset", x.offset(), y.offset());
+        r.value(Kind::Memory, n, "size", x.size(), y.size());
+        r.map(Kind::Memory, n, "attribute", x.attrs(), y.attrs());
+    }
+    for y in b.memories() {
+        if !a.memories().iter().any(|x| x.id() == y.id()) {
+            r.push(Kind::Memory, y.id(), Change::Added);
+        }
+    }
+
+    let paired: BTreeSet<&str> = m.cells.values().copied().collect();
+    for x in a.cells() {
+        let y = match m.cells.get(x.i2().as_str()).and_then(|c| b.cell(c)) {
+            Some(y) => y,
+            None => {
+                r.push(Kind::Cell, x.i2(), Change::Removed);
+                continue;
+            }
+        };
+        let n = x.i2();
+        r.value(Kind::Cell, n, "type", x.i1(), y.i1());
+        let params = |c: &Cell| -> HashMap<String, String> {
+            c.params()
+                .iter()
+                .map(|(k, p)| (k.clone(), p.val().to_string()))
+                .collect()
+        };
+        r.map(Kind::Cell, n, "parameter", &params(x), &params(y));
+        r.map(Kind::Cell, n, "attribute", x.attrs(), y.attrs());
+        let ports: HashMap<String, SigSpec> = x
+            .connects()
+            .iter()
+            .map(|(k, s)| (k.clone(), m.map_sig(s)))
+            .collect();
+        r.map(Kind::Cell, n, "port", &ports, y.connects());
+    }
+    for y in b
+        .cells()
+        .iter()
+        .filter(|c| !paired.contains(c.i2().as_str()))
+    {
+        r.push(Kind::Cell, y.i2(), Change::Added);
+    }
+
+    for x in a.processes() {
+        if !b.processes().iter().any(|y| y.id() == x.id()) {
+            r.push(Kind::Process, x.id(), Change::Removed);
+        }
+    }
+    for y in b.processes() {
+        if !a.processes().iter().any(|x| x.id() == y.id()) {
+            r.push(Kind::Process, y.id(), Change::Added);
+        }
+    }
+
+    // connections are compared as multisets of their text
+    let text = |c: &Connect| format!("{} {}", c.sig1(), c.sig2());
+    let mut left: BTreeMap<String, usize> = BTreeMap::new();
+    for c in a.connects() {
+        let c = Connect::new(m.map_sig(c.sig1()), m.map_sig(c.sig2()));
+        *left.entry(text(&c)).or_insert(0) += 1;
+    }
+    let mut right: BTreeMap<String, usize> = BTreeMap::new();
+    for c in b.connects() {
+        *right.entry(text(c)).or_insert(0) += 1;
+    }
+    for (k, n) in left.iter() {
+        for _ in *right.get(k).unwrap_or(&0)..*n {
+            r.push(Kind::Connect, k, Change::Removed);
+        }
+    }
+    for (k, n) in right.iter() {
+        for _ in *left.get(k).unwrap_or(&0)..*n {
+            r.push(Kind::Connect, k, Change::Added);
+        }
+    }
+    out.extend(r.out);
+}
+
+/// Differences between the designs `a` and `b`, in the order of the modules
+/// of `a`, then of those only in `b`.
+///
+/// Modules, wires, memories, cells and processes are paired by name, and
+/// paired items are compared property by property: sizes, port flags,
+/// attributes, parameters, cell types and port connections. Processes are
+/// only compared by name. If `structural` is set, cells with auto names
+/// (starting with `$`) left unpaired are also paired when they have the
+/// same type, parameters and connections (or failing that, the same type
+/// and connections), along with the auto wires they connect, so that
+/// renaming by a tool does not show as a difference.
+pub fn diff(a: &Design, b: &Design, structural: bool) -> Vec<Difference> {
+    let mut out = Vec::new();
+    let module = |m: &Module, change| Difference {
+        module: m.ident().clone(),
+        kind: Kind::Module,
+        name: String::new(),
+        change,
+    };
+    for x in a.modules() {
+        match b.module(x.ident()) {
+            Some(y) => module_diff(x, y, structural, &mut out),
+            None => out.push(module(x, Change::Removed)),
+        }
+    }
+    for y in b.modules() {
+        if a.module(y.ident()).is_none() {
+            out.push(module(y, Change::Added));
+        }
+    }
+    out
+}
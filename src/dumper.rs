@@ -57,7 +57,7 @@ impl Visitor for Dumper {
                     info!("attribute {} {}", k, v);
                 }
                 let mut s = "wire".to_string();
-                if *n.width() != 0 {
+                if *n.width() != 1 {
                     s = format!("{} width {}", s, n.width());
                 }
                 if *n.upto() {
@@ -99,7 +99,7 @@ impl Visitor for Dumper {
                     info!("attribute {} {}", k, v);
                 }
                 let mut s = "memory".to_string();
-                if *n.width() != 0 {
+                if *n.width() != 1 {
                     s = format!("{} width {}", s, n.width())
                 }
                 if *n.offset() != 0 {
@@ -165,6 +165,9 @@ impl Visitor for Dumper {
                 }
                 self.indent(-2);
             }
+            Node::Connect(n) => {
+                info!("connect {} {}", n.sig1(), n.sig2());
+            }
             _ => (),
         }
         Ok(())
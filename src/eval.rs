@@ -0,0 +1,835 @@
+// Copyright (c) 2020 xhe
+
+//! Constant evaluation of combinational internal cells, following the
+//! semantics of yosys' `calc.cc` for undefined bits.
+
+use super::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::cmp::Ordering;
+
+use State::{Sx, S0, S1};
+
+fn norm(s: State) -> State {
+    match s {
+        S0 | S1 => s,
+        _ => Sx,
+    }
+}
+
+fn from_bool(b: bool) -> State {
+    if b {
+        S1
+    } else {
+        S0
+    }
+}
+
+fn not(a: State) -> State {
+    match a {
+        S0 => S1,
+        S1 => S0,
+        _ => Sx,
+    }
+}
+
+fn and(a: State, b: State) -> State {
+    match (a, b) {
+        (S0, _) | (_, S0) => S0,
+        (S1, S1) => S1,
+        _ => Sx,
+    }
+}
+
+fn or(a: State, b: State) -> State {
+    match (a, b) {
+        (S1, _) | (_, S1) => S1,
+        (S0, S0) => S0,
+        _ => Sx,
+    }
+}
+
+fn xor(a: State, b: State) -> State {
+    match (norm(a), norm(b)) {
+        (Sx, _) | (_, Sx) => Sx,
+        (a, b) => from_bool(a != b),
+    }
+}
+
+fn mux(a: State, b: State, s: State) -> State {
+    match s {
+        S0 => a,
+        S1 => b,
+        _ if norm(a) == norm(b) => norm(a),
+        _ => Sx,
+    }
+}
+
+/// Sign or zero extend (or truncate) `v` to `width` bits.
+pub fn extend(v: &[State], width: usize, signed: bool) -> Vec<State> {
+    let mut r = v.to_vec();
+    let pad = match r.last() {
+        Some(s) if signed => *s,
+        _ => S0,
+    };
+    r.resize(width, pad);
+    r
+}
+
+fn defined(v: &[State]) -> bool {
+    v.iter().all(|s| *s == S0 || *s == S1)
+}
+
+fn first_undef(v: &[State]) -> Option<usize> {
+    v.iter().position(|s| *s != S0 && *s != S1)
+}
+
+fn reduce_bool(a: &[State]) -> State {
+    a.iter().fold(S0, |r, s| or(r, *s))
+}
+
+fn result(bit: State, width: usize) -> Vec<State> {
+    let mut r = vec![S0; width];
+    if width > 0 {
+        r[0] = bit;
+    }
+    r
+}
+
+// Magnitude arithmetic on defined bit vectors, LSB first.
+
+fn to_bools(v: &[State]) -> Vec<bool> {
+    v.iter().map(|s| *s == S1).collect()
+}
+
+fn from_bools(v: &[bool], width: usize) -> Vec<State> {
+    (0..width)
+        .map(|i| from_bool(v.get(i).cloned().unwrap_or(false)))
+        .collect()
+}
+
+fn trim(v: &mut Vec<bool>) {
+    while v.last() == Some(&false) {
+        v.pop();
+    }
+}
+
+fn mag_cmp(a: &[bool], b: &[bool]) -> Ordering {
+    let n = a.len().max(b.len());
+    for i in (0..n).rev() {
+        let (x, y) = (
+            a.get(i).cloned().unwrap_or(false),
+            b.get(i).cloned().unwrap_or(false),
+        );
+        if x != y {
+            return x.cmp(&y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn mag_add(a: &[bool], b: &[bool], width: usize) -> Vec<bool> {
+    let mut c = false;
+    (0..width)
+        .map(|i| {
+            let (x, y) = (
+                a.get(i).cloned().unwrap_or(false),
+                b.get(i).cloned().unwrap_or(false),
+            );
+            let s = x ^ y ^ c;
+            c = (x & y) | (x & c) | (y & c);
+            s
+        })
+        .collect()
+}
+
+fn negate(a: &[bool], width: usize) -> Vec<bool> {
+    let inv: Vec<bool> = (0..width)
+        .map(|i| !a.get(i).cloned().unwrap_or(false))
+        .collect();
+    mag_add(&inv, &[true], width)
+}
+
+fn mag_sub(a: &[bool], b: &[bool]) -> Vec<bool> {
+    let width = a.len().max(b.len());
+    mag_add(a, &negate(b, width), width)
+}
+
+fn mag_mul(a: &[bool], b: &[bool], width: usize) -> Vec<bool> {
+    let mut r = vec![false; width];
+    for (i, y) in b.iter().enumerate().take(width) {
+        if *y {
+            let mut shifted = vec![false; i];
+            shifted.extend_from_slice(a);
+            r = mag_add(&r, &shifted, width);
+        }
+    }
+    r
+}
+
+fn mag_divmod(a: &[bool], b: &[bool]) -> (Vec<bool>, Vec<bool>) {
+    let mut q = vec![false; a.len()];
+    let mut r: Vec<bool> = Vec::new();
+    for i in (0..a.len()).rev() {
+        r.insert(0, a[i]);
+        trim(&mut r);
+        if mag_cmp(&r, b) != Ordering::Less {
+            r = mag_sub(&r, b);
+            trim(&mut r);
+            q[i] = true;
+        }
+    }
+    (q, r)
+}
+
+// Sign and magnitude of a defined value.
+fn to_signed(v: &[State], signed: bool) -> (bool, Vec<bool>) {
+    let b = to_bools(v);
+    if signed && b.last() == Some(&true) {
+        (true, negate(&b, b.len()))
+    } else {
+        (false, b)
+    }
+}
+
+fn from_signed(neg: bool, mag: &[bool], width: usize) -> Vec<State> {
+    if neg {
+        from_bools(&negate(mag, width), width)
+    } else {
+        from_bools(mag, width)
+    }
+}
+
+fn is_zero(v: &[bool]) -> bool {
+    v.iter().all(|b| !b)
+}
+
+fn compare(a: &[State], b: &[State], signed: bool) -> Option<Ordering> {
+    if !defined(a) || !defined(b) {
+        return None;
+    }
+    let (na, ma) = to_signed(a, signed);
+    let (nb, mb) = to_signed(b, signed);
+    let (za, zb) = (is_zero(&ma), is_zero(&mb));
+    Some(match (na && !za, nb && !zb) {
+        (false, false) => mag_cmp(&ma, &mb),
+        (true, true) => mag_cmp(&mb, &ma),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    })
+}
+
+fn shift(
+    a: &[State],
+    offset: Option<i64>,
+    sign_ext: bool,
+    vacant: State,
+    width: usize,
+) -> Vec<State> {
+    let offset = match offset {
+        Some(v) => v,
+        None => return vec![Sx; width],
+    };
+    (0..width)
+        .map(|i| {
+            let pos = i as i64 + offset;
+            if pos < 0 {
+                vacant
+            } else if pos >= a.len() as i64 {
+                if sign_ext {
+                    a.last().cloned().unwrap_or(vacant)
+                } else {
+                    vacant
+                }
+            } else {
+                a[pos as usize]
+            }
+        })
+        .collect()
+}
+
+// Shift amount, saturated to avoid overflow on huge amounts.
+fn shift_amount(b: &[State], signed: bool) -> Option<i64> {
+    if !defined(b) {
+        return None;
+    }
+    let (neg, mag) = to_signed(b, signed);
+    let mut v: i64 = 0;
+    for (i, x) in mag.iter().enumerate() {
+        if *x {
+            if i >= 40 {
+                v = 1 << 40;
+                break;
+            }
+            v |= 1 << i;
+        }
+    }
+    Some(if neg { -v } else { v })
+}
+
+fn arith(tp: &str, a: &[State], b: &[State], signed: bool, width: usize) -> Vec<State> {
+    match tp {
+        "$add" | "$sub" => {
+            let undef = match (first_undef(a), first_undef(b)) {
+                (Some(x), _) => Some(x),
+                (None, y) => y,
+            };
+            let (x, y) = (
+                to_bools(&extend(a, width, signed)),
+                to_bools(&extend(b, width, signed)),
+            );
+            let y = if tp == "$sub" { negate(&y, width) } else { y };
+            let mut r = from_bools(&mag_add(&x, &y, width), width);
+            if let Some(u) = undef {
+                for s in r.iter_mut().skip(u) {
+                    *s = Sx;
+                }
+            }
+            r
+        }
+        _ if !defined(a) || !defined(b) => vec![Sx; width],
+        "$mul" => {
+            let (x, y) = (
+                to_bools(&extend(a, width, signed)),
+                to_bools(&extend(b, width, signed)),
+            );
+            from_bools(&mag_mul(&x, &y, width), width)
+        }
+        "$div" | "$mod" | "$divfloor" | "$modfloor" => {
+            let (na, ma) = to_signed(a, signed);
+            let (nb, mb) = to_signed(b, signed);
+            if is_zero(&mb) {
+                return vec![Sx; width];
+            }
+            let (q, r) = mag_divmod(&ma, &mb);
+            let (qneg, rzero) = (na != nb, is_zero(&r));
+            match tp {
+                "$div" => from_signed(qneg, &q, width),
+                "$mod" => from_signed(na, &r, width),
+                "$divfloor" if qneg && !rzero => {
+                    let q1 = mag_add(&q, &[true], q.len() + 1);
+                    from_signed(true, &q1, width)
+                }
+                "$divfloor" => from_signed(qneg, &q, width),
+                _ if nb != na && !rzero => {
+                    // the result takes the sign of the divisor
+                    let m = mag_sub(&mb, &r);
+                    from_signed(nb, &m, width)
+                }
+                _ => from_signed(nb, &r, width),
+            }
+        }
+        "$pow" => {
+            let (nb, mb) = to_signed(b, signed);
+            let base = to_bools(&extend(a, width.max(a.len()), signed));
+            if nb && !is_zero(&mb) {
+                let (na, ma) = to_signed(a, signed);
+                let one = from_bools(&[true], width);
+                return match (na, ma.iter().filter(|b| **b).count(), ma.first()) {
+                    (_, 0, _) => vec![Sx; width],
+                    (false, 1, Some(true)) => one,
+                    (true, 1, Some(true)) if mb.first() == Some(&true) => {
+                        from_signed(true, &[true], width)
+                    }
+                    (true, 1, Some(true)) => one,
+                    _ => vec![S0; width],
+                };
+            }
+            let mut r = vec![false; width];
+            if width > 0 {
+                r[0] = true;
+            }
+            let mut p = base;
+            p.truncate(width);
+            for x in mb.iter() {
+                if *x {
+                    r = mag_mul(&r, &p, width);
+                }
+                p = mag_mul(&p, &p, width);
+            }
+            from_bools(&r, width)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Handle to the parameters and input values of a cell being evaluated.
+struct Ctx<'a, F: Fn(&str) -> Option<Vec<State>>> {
+    cell: &'a Cell,
+    input: F,
+}
+
+impl<'a, F: Fn(&str) -> Option<Vec<State>>> Ctx<'a, F> {
+    fn int(&self, k: &str) -> Result<usize> {
+        self.cell
+            .param(k)
+            .and_then(|v| v.as_int())
+            .map(|v| v.max(0) as usize)
+            .ok_or_else(|| anyhow!("cell `{}' has no parameter `{}'", self.cell.i2(), k))
+    }
+
+    fn flag(&self, k: &str) -> bool {
+        matches!(self.cell.param(k), Some(v) if v.as_bool())
+    }
+
+    fn port(&self, k: &str) -> Result<Vec<State>> {
+        (self.input)(k).ok_or_else(|| anyhow!("cell `{}' has no port `{}'", self.cell.i2(), k))
+    }
+
+    // Input port extended to the width given by the `<P>_WIDTH` parameter.
+    fn arg(&self, k: &str) -> Result<Vec<State>> {
+        let v = self.port(&format!("\\{}", k))?;
+        let signed = self.flag(&format!("\\{}_SIGNED", k));
+        match self.int(&format!("\\{}_WIDTH", k)) {
+            Ok(w) => Ok(extend(&v, w, signed)),
+            Err(_) => Ok(v),
+        }
+    }
+
+    // Number of values of a select input of `n` bits.
+    fn values(&self, n: usize) -> Result<usize> {
+        if n >= usize::BITS as usize {
+            bail!("cell `{}' has a {} bit select, too wide", self.cell.i2(), n);
+        }
+        Ok(1 << n)
+    }
+
+    fn bit(&self, k: &str) -> Result<State> {
+        Ok(norm(
+            self.port(&format!("\\{}", k))?
+                .first()
+                .cloned()
+                .unwrap_or(Sx),
+        ))
+    }
+}
+
+/// Whether [`eval`] supports cells of type `tp`.
+pub fn is_evaluable(tp: &str) -> bool {
+    matches!(
+        tp,
+        "$not"
+            | "$pos"
+            | "$neg"
+            | "$reduce_and"
+            | "$reduce_or"
+            | "$reduce_xor"
+            | "$reduce_xnor"
+            | "$reduce_bool"
+            | "$logic_not"
+            | "$slice"
+            | "$lut"
+            | "$sop"
+            | "$and"
+            | "$or"
+            | "$xor"
+            | "$xnor"
+            | "$shl"
+            | "$shr"
+            | "$sshl"
+            | "$sshr"
+            | "$shift"
+            | "$shiftx"
+            | "$lt"
+            | "$le"
+            | "$eq"
+            | "$ne"
+            | "$eqx"
+            | "$nex"
+            | "$ge"
+            | "$gt"
+            | "$add"
+            | "$sub"
+            | "$mul"
+            | "$div"
+            | "$mod"
+            | "$divfloor"
+            | "$modfloor"
+            | "$pow"
+            | "$logic_and"
+            | "$logic_or"
+            | "$concat"
+            | "$bweqx"
+            | "$equiv"
+            | "$mux"
+            | "$pmux"
+            | "$bwmux"
+            | "$bmux"
+            | "$demux"
+            | "$tribuf"
+            | "$lcu"
+            | "$alu"
+            | "$fa"
+            | "$_BUF_"
+            | "$_NOT_"
+            | "$_AND_"
+            | "$_NAND_"
+            | "$_OR_"
+            | "$_NOR_"
+            | "$_XOR_"
+            | "$_XNOR_"
+            | "$_ANDNOT_"
+            | "$_ORNOT_"
+            | "$_MUX_"
+            | "$_NMUX_"
+            | "$_MUX4_"
+            | "$_MUX8_"
+            | "$_MUX16_"
+            | "$_AOI3_"
+            | "$_OAI3_"
+            | "$_AOI4_"
+            | "$_OAI4_"
+            | "$_TBUF_"
+    )
+}
+
+/// Evaluate the outputs of a combinational internal cell. `input` returns
+/// the value of an input port (e.g. `\A`), LSB first.
+pub fn eval<F>(cell: &Cell, input: F) -> Result<Vec<(String, Vec<State>)>>
+where
+    F: Fn(&str) -> Option<Vec<State>>,
+{
+    let c = Ctx { cell, input };
+    let tp = cell.i1().as_str();
+    let y = |v: Vec<State>| Ok(vec![("\\Y".to_string(), v)]);
+
+    if tp.starts_with("$_") {
+        let p = |k: &str| c.bit(k);
+        let v = match tp {
+            "$_BUF_" => p("A")?,
+            "$_NOT_" => not(p("A")?),
+            "$_AND_" => and(p("A")?, p("B")?),
+            "$_NAND_" => not(and(p("A")?, p("B")?)),
+            "$_OR_" => or(p("A")?, p("B")?),
+            "$_NOR_" => not(or(p("A")?, p("B")?)),
+            "$_XOR_" => xor(p("A")?, p("B")?),
+            "$_XNOR_" => not(xor(p("A")?, p("B")?)),
+            "$_ANDNOT_" => and(p("A")?, not(p("B")?)),
+            "$_ORNOT_" => or(p("A")?, not(p("B")?)),
+            "$_MUX_" => mux(p("A")?, p("B")?, p("S")?),
+            "$_NMUX_" => not(mux(p("A")?, p("B")?, p("S")?)),
+            "$_MUX4_" | "$_MUX8_" | "$_MUX16_" => {
+                let (data, sel): (&[&str], &[&str]) = match tp {
+                    "$_MUX4_" => (&["A", "B", "C", "D"], &["S", "T"]),
+                    "$_MUX8_" => (&["A", "B", "C", "D", "E", "F", "G", "H"], &["S", "T", "U"]),
+                    _ => (
+                        &[
+                            "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N",
+                            "O", "P",
+                        ],
+                        &["S", "T", "U", "V"],
+                    ),
+                };
+                let mut v = data.iter().map(|k| p(k)).collect::<Result<Vec<_>>>()?;
+                for s in sel {
+                    let s = p(s)?;
+                    v = v.chunks(2).map(|x| mux(x[0], x[1], s)).collect();
+                }
+                v[0]
+            }
+            "$_AOI3_" => not(or(and(p("A")?, p("B")?), p("C")?)),
+            "$_OAI3_" => not(and(or(p("A")?, p("B")?), p("C")?)),
+            "$_AOI4_" => not(or(and(p("A")?, p("B")?), and(p("C")?, p("D")?))),
+            "$_OAI4_" => not(and(or(p("A")?, p("B")?), or(p("C")?, p("D")?))),
+            "$_TBUF_" => match p("E")? {
+                S1 => p("A")?,
+                S0 => State::Sz,
+                _ => Sx,
+            },
+            _ => bail!("can not evaluate cell type `{}'", tp),
+        };
+        return y(vec![v]);
+    }
+
+    let width = c.int("\\Y_WIDTH");
+    let (sa, sb) = (c.flag("\\A_SIGNED"), c.flag("\\B_SIGNED"));
+    // as in yosys, binary operators other than shifts are only signed when
+    // both operands are
+    let (sa, sb) = match tp {
+        "$not" | "$pos" | "$neg" | "$shl" | "$sshl" | "$shr" | "$sshr" | "$shift" | "$shiftx"
+        | "$alu" => (sa, sb),
+        _ => (sa && sb, sa && sb),
+    };
+    match tp {
+        "$not" | "$pos" | "$neg" => {
+            let w = width?;
+            let a = extend(&c.arg("A")?, w, sa);
+            y(match tp {
+                "$not" => a.into_iter().map(not).collect(),
+                "$pos" => a.into_iter().map(norm).collect(),
+                _ => arith("$sub", &vec![S0; w], &a, false, w),
+            })
+        }
+        "$reduce_and" | "$reduce_or" | "$reduce_xor" | "$reduce_xnor" | "$reduce_bool"
+        | "$logic_not" => {
+            let a = c.arg("A")?;
+            let v = match tp {
+                "$reduce_and" => a.iter().fold(S1, |r, s| and(r, *s)),
+                "$reduce_or" | "$reduce_bool" => reduce_bool(&a),
+                "$reduce_xor" => a.iter().fold(S0, |r, s| xor(r, *s)),
+                "$reduce_xnor" => not(a.iter().fold(S0, |r, s| xor(r, *s))),
+                _ => not(reduce_bool(&a)),
+            };
+            y(result(v, width?))
+        }
+        "$logic_and" | "$logic_or" => {
+            let (a, b) = (reduce_bool(&c.arg("A")?), reduce_bool(&c.arg("B")?));
+            let v = if tp == "$logic_and" {
+                and(a, b)
+            } else {
+                or(a, b)
+            };
+            y(result(v, width?))
+        }
+        "$and" | "$or" | "$xor" | "$xnor" | "$bweqx" => {
+            let w = width.or_else(|_| c.int("\\WIDTH"))?;
+            let a = extend(&c.arg("A")?, w, sa);
+            let b = extend(&c.arg("B")?, w, sb);
+            y(a.into_iter()
+                .zip(b)
+                .map(|(a, b)| match tp {
+                    "$and" => and(a, b),
+                    "$or" => or(a, b),
+                    "$xor" => xor(a, b),
+                    "$xnor" => not(xor(a, b)),
+                    _ => from_bool(a == b),
+                })
+                .collect())
+        }
+        "$shl" | "$sshl" | "$shr" | "$sshr" => {
+            let w = width?;
+            let a = c.arg("A")?;
+            let n = shift_amount(&c.arg("B")?, false);
+            y(match tp {
+                "$shl" | "$sshl" => shift(&extend(&a, w, sa), n.map(|v| -v), false, S0, w),
+                "$sshr" if sa => shift(&extend(&a, w.max(a.len()), true), n, true, S0, w),
+                _ => shift(&extend(&a, w.max(a.len()), sa), n, false, S0, w),
+            })
+        }
+        "$shift" | "$shiftx" => {
+            let w = width?;
+            let n = shift_amount(&c.arg("B")?, sb);
+            let vacant = if tp == "$shift" { S0 } else { Sx };
+            y(shift(&c.arg("A")?, n, false, vacant, w))
+        }
+        "$lt" | "$le" | "$ge" | "$gt" => {
+            let v = match compare(&c.arg("A")?, &c.arg("B")?, sa) {
+                None => Sx,
+                Some(o) => from_bool(match tp {
+                    "$lt" => o == Ordering::Less,
+                    "$le" => o != Ordering::Greater,
+                    "$ge" => o != Ordering::Less,
+                    _ => o == Ordering::Greater,
+                }),
+            };
+            y(result(v, width?))
+        }
+        "$eq" | "$ne" | "$eqx" | "$nex" => {
+            let (a, b) = (c.arg("A")?, c.arg("B")?);
+            let n = a.len().max(b.len());
+            let (a, b) = (extend(&a, n, sa), extend(&b, n, sb));
+            let v = if tp == "$eqx" || tp == "$nex" {
+                from_bool(a == b)
+            } else {
+                let mut v = S1;
+                for (x, y) in a.iter().zip(b.iter()) {
+                    match (norm(*x), norm(*y)) {
+                        (Sx, _) | (_, Sx) => v = Sx,
+                        (x, y) if x != y => {
+                            v = S0;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                v
+            };
+            let v = if tp == "$ne" || tp == "$nex" {
+                not(v)
+            } else {
+                v
+            };
+            y(result(v, width?))
+        }
+        "$add" | "$sub" | "$mul" | "$div" | "$mod" | "$divfloor" | "$modfloor" | "$pow" => {
+            let w = width?;
+            y(arith(tp, &c.arg("A")?, &c.arg("B")?, sa, w))
+        }
+        "$concat" => {
+            let mut v = c.arg("A")?;
+            v.extend(c.arg("B")?);
+            y(v)
+        }
+        "$slice" => {
+            let off = c.int("\\OFFSET")?;
+            let a = c.arg("A")?;
+            y((0..width?)
+                .map(|i| a.get(off + i).cloned().unwrap_or(Sx))
+                .collect())
+        }
+        "$equiv" => y(c.port("\\A")?),
+        "$mux" | "$bwmux" => {
+            let w = c.int("\\WIDTH")?;
+            let a = extend(&c.port("\\A")?, w, false);
+            let b = extend(&c.port("\\B")?, w, false);
+            let s = c.port("\\S")?;
+            y((0..w)
+                .map(|i| {
+                    let s = if tp == "$mux" { s[0] } else { s[i] };
+                    mux(a[i], b[i], norm(s))
+                })
+                .collect())
+        }
+        "$pmux" => {
+            let w = c.int("\\WIDTH")?;
+            let a = extend(&c.port("\\A")?, w, false);
+            let b = c.port("\\B")?;
+            let s = c.port("\\S")?;
+            if s.iter().all(|x| *x == S0) {
+                return y(a);
+            }
+            if !defined(&s) || s.iter().filter(|x| **x == S1).count() != 1 {
+                return y(vec![Sx; w]);
+            }
+            let i = s.iter().position(|x| *x == S1).unwrap();
+            y(extend(
+                &b[(i * w).min(b.len())..((i + 1) * w).min(b.len())],
+                w,
+                false,
+            ))
+        }
+        "$bmux" | "$demux" => {
+            let w = c.int("\\WIDTH")?;
+            let a = c.port("\\A")?;
+            let s = c.port("\\S")?;
+            let n = c.values(s.len())?;
+            if tp == "$demux" && w.checked_mul(n).is_none() {
+                bail!(
+                    "cell `{}' has a {} bit select, too wide",
+                    cell.i2(),
+                    s.len()
+                );
+            }
+            if !defined(&s) {
+                return y(vec![Sx; if tp == "$bmux" { w } else { w * n }]);
+            }
+            let sel = s
+                .iter()
+                .enumerate()
+                .fold(0, |r, (i, x)| r | ((*x == S1) as usize) << i);
+            if tp == "$bmux" {
+                y(extend(
+                    &a[(sel * w).min(a.len())..((sel + 1) * w).min(a.len())],
+                    w,
+                    false,
+                ))
+            } else {
+                let mut v = vec![S0; w * n];
+                v[sel * w..(sel + 1) * w].copy_from_slice(&extend(&a, w, false));
+                y(v)
+            }
+        }
+        "$tribuf" => {
+            let w = c.int("\\WIDTH")?;
+            let a = extend(&c.port("\\A")?, w, false);
+            y(match c.bit("EN")? {
+                S1 => a,
+                S0 => vec![State::Sz; w],
+                _ => vec![Sx; w],
+            })
+        }
+        "$lut" => {
+            let lut = c
+                .cell
+                .param("\\LUT")
+                .map(|v| v.to_bits())
+                .ok_or_else(|| anyhow!("cell `{}' has no parameter `\\LUT'", cell.i2()))?;
+            let a = c.port("\\A")?;
+            let mut v: Option<State> = None;
+            for i in 0..c.values(a.len())? {
+                let hit = a.iter().enumerate().all(|(j, s)| match norm(*s) {
+                    Sx => true,
+                    s => s == from_bool((i >> j) & 1 == 1),
+                });
+                if hit {
+                    let o = norm(lut.get(i).cloned().unwrap_or(Sx));
+                    v = Some(match v {
+                        None => o,
+                        Some(p) if p == o => p,
+                        _ => Sx,
+                    });
+                }
+            }
+            y(vec![v.unwrap_or(Sx)])
+        }
+        "$sop" => {
+            let w = c.int("\\WIDTH")?;
+            let depth = c.int("\\DEPTH")?;
+            let table = c
+                .cell
+                .param("\\TABLE")
+                .map(|v| v.to_bits())
+                .unwrap_or_default();
+            let a = extend(&c.port("\\A")?, w, false);
+            let mut v = S0;
+            for t in 0..depth {
+                let mut term = S1;
+                for (i, x) in a.iter().enumerate() {
+                    let k = 2 * (t * w + i);
+                    if table.get(k) == Some(&S1) {
+                        term = and(term, not(*x));
+                    }
+                    if table.get(k + 1) == Some(&S1) {
+                        term = and(term, *x);
+                    }
+                }
+                v = or(v, term);
+            }
+            y(vec![v])
+        }
+        "$lcu" => {
+            let w = c.int("\\WIDTH")?;
+            let p = extend(&c.port("\\P")?, w, false);
+            let g = extend(&c.port("\\G")?, w, false);
+            let mut carry = c.bit("CI")?;
+            let co = (0..w)
+                .map(|i| {
+                    carry = or(g[i], and(p[i], carry));
+                    carry
+                })
+                .collect();
+            Ok(vec![("\\CO".to_string(), co)])
+        }
+        "$alu" => {
+            let w = width?;
+            let a = extend(&c.arg("A")?, w, sa);
+            let b = extend(&c.arg("B")?, w, sb);
+            let bi = c.bit("BI")?;
+            let b: Vec<State> = b.into_iter().map(|x| xor(x, bi)).collect();
+            let mut carry = c.bit("CI")?;
+            let (mut x, mut s, mut co) = (Vec::new(), Vec::new(), Vec::new());
+            for i in 0..w {
+                x.push(xor(a[i], b[i]));
+                s.push(xor(x[i], carry));
+                carry = or(and(a[i], b[i]), and(x[i], carry));
+                co.push(carry);
+            }
+            Ok(vec![
+                ("\\X".to_string(), x),
+                ("\\Y".to_string(), s),
+                ("\\CO".to_string(), co),
+            ])
+        }
+        "$fa" => {
+            let w = c.int("\\WIDTH")?;
+            let a = extend(&c.port("\\A")?, w, false);
+            let b = extend(&c.port("\\B")?, w, false);
+            let d = extend(&c.port("\\C")?, w, false);
+            let s = (0..w).map(|i| xor(xor(a[i], b[i]), d[i])).collect();
+            let x = (0..w)
+                .map(|i| or(or(and(a[i], b[i]), and(a[i], d[i])), and(b[i], d[i])))
+                .collect();
+            Ok(vec![("\\X".to_string(), x), ("\\Y".to_string(), s)])
+        }
+        _ => bail!("can not evaluate cell type `{}'", tp),
+    }
+}
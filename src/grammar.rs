@@ -27198,7 +27198,7 @@ fn __action15<
     (_, __0, _): (Location, Const, Location),
 ) -> SigSpec
 {
-    SigSpec::Const((__0, 0, 0))
+    SigSpec::Const((__0, None))
 }
 
 #[allow(unused_variables)]
@@ -27208,7 +27208,7 @@ fn __action16<
     (_, __0, _): (Location, String, Location),
 ) -> SigSpec
 {
-    SigSpec::Refer((__0, 0, 0))
+    SigSpec::Refer((__0, None))
 }
 
 #[allow(unused_variables)]
@@ -27222,8 +27222,8 @@ fn __action17<
 ) -> Result<SigSpec,__lalrpop_util::ParseError<Location,Token,Error>>
 {
     match i {
-		SigSpec:: Const((c, _, _)) => Ok(SigSpec::Const((c, l, 0))),
-		SigSpec:: Refer((c, _, _)) => Ok(SigSpec::Refer((c, l, 0))),
+		SigSpec:: Const((c, _)) => Ok(SigSpec::Const((c, Some((l, l))))),
+		SigSpec:: Refer((c, _)) => Ok(SigSpec::Refer((c, Some((l, l))))),
 		_ => Err(ParseError::User{ error:anyhow!("except a sigspec") }),
 	}
 }
@@ -27241,8 +27241,8 @@ fn __action18<
 ) -> Result<SigSpec,__lalrpop_util::ParseError<Location,Token,Error>>
 {
     match i {
-		SigSpec:: Const((c, _, _)) => Ok(SigSpec::Const((c, l, r))),
-		SigSpec:: Refer((c, _, _)) => Ok(SigSpec::Refer((c, l, r))),
+		SigSpec:: Const((c, _)) => Ok(SigSpec::Const((c, Some((l, r))))),
+		SigSpec:: Refer((c, _)) => Ok(SigSpec::Refer((c, Some((l, r))))),
 		_ => Err(ParseError::User{ error:anyhow!("except a sigspec") }),
 	}
 }
@@ -0,0 +1,602 @@
+// Copyright (c) 2020 xhe
+
+//! Conversion between designs and the netlist format of yosys' `write_json`.
+//!
+//! Nets are numbered from 2, wires connected together share their numbers
+//! and the bits of constants are written as `"0"`, `"1"`, `"x"` and `"z"`.
+//! Parameters and attributes are encoded like yosys does: numbers as binary
+//! strings, strings as themselves with a space appended when they would
+//! read as a number.
+
+use crate::celltypes::{CellTypes, PortDir};
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+
+/// A JSON value keeping the order of object members, which carries the
+/// order of module ports.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, k: &str) -> Option<&Json> {
+        match self {
+            Json::Object(v) => v.iter().find(|(n, _)| n == k).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn members(&self) -> &[(String, Json)] {
+        match self {
+            Json::Object(v) => v,
+            _ => &[],
+        }
+    }
+
+    fn int(&self, k: &str) -> i64 {
+        match self.get(k) {
+            Some(Json::Int(n)) => *n,
+            _ => 0,
+        }
+    }
+
+    // Laid out like yosys does: a line per object member, arrays inline.
+    fn write<W: Write>(&self, w: &mut W, indent: usize) -> Result<()> {
+        match self {
+            Json::Null => write!(w, "null")?,
+            Json::Bool(b) => write!(w, "{}", b)?,
+            Json::Int(n) => write!(w, "{}", n)?,
+            Json::Float(n) => write!(w, "{}", n)?,
+            Json::Str(s) => write!(w, "{}", serde_json::to_string(s)?)?,
+            Json::Array(v) => {
+                write!(w, "[")?;
+                for (i, e) in v.iter().enumerate() {
+                    write!(w, "{} ", if i == 0 { "" } else { "," })?;
+                    e.write(w, indent)?;
+                }
+                write!(w, " ]")?;
+            }
+            Json::Object(v) if v.is_empty() => write!(w, "{{ }}")?,
+            Json::Object(v) => {
+                write!(w, "{{")?;
+                for (i, (k, e)) in v.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { "," };
+                    let k = serde_json::to_string(k)?;
+                    write!(w, "{}\n{:3$}{}: ", sep, "", k, indent + 2)?;
+                    e.write(w, indent + 2)?;
+                }
+                write!(w, "\n{:1$}}}", "", indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Json, E> {
+        Ok(Json::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Json, E> {
+        Ok(Json::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Json, E> {
+        Ok(Json::Int(v as i64))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Json, E> {
+        Ok(Json::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Json, E> {
+        Ok(Json::Str(v.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut a: A) -> std::result::Result<Json, A::Error> {
+        let mut v = Vec::new();
+        while let Some(e) = a.next_element()? {
+            v.push(e);
+        }
+        Ok(Json::Array(v))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut a: A) -> std::result::Result<Json, A::Error> {
+        let mut v = Vec::new();
+        while let Some(e) = a.next_entry()? {
+            v.push(e);
+        }
+        Ok(Json::Object(v))
+    }
+}
+
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> std::result::Result<Json, D::Error> {
+        d.deserialize_any(JsonVisitor)
+    }
+}
+
+fn obj(v: Vec<(&str, Json)>) -> Json {
+    Json::Object(v.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+// Name of `id` in JSON: public names lose their `\`.
+fn unescape(id: &str) -> String {
+    match id.strip_prefix('\\') {
+        Some(n)
+            if !n.is_empty()
+                && !n.starts_with(|c: char| c == '$' || c == '\\' || c.is_ascii_digit()) =>
+        {
+            n.to_string()
+        }
+        _ => id.to_string(),
+    }
+}
+
+fn escape(name: &str) -> String {
+    if name.starts_with('\\') || name.starts_with('$') {
+        name.to_string()
+    } else {
+        format!("\\{}", name)
+    }
+}
+
+fn hidden(id: &str) -> Json {
+    Json::Int(id.starts_with('$') as i64)
+}
+
+fn is_bit(c: char) -> bool {
+    matches!(c, '0' | '1' | 'x' | 'z')
+}
+
+fn state(c: char) -> State {
+    match c {
+        '0' => State::S0,
+        '1' => State::S1,
+        'z' => State::Sz,
+        _ => State::Sx,
+    }
+}
+
+fn const_json(c: &Const) -> Json {
+    match c {
+        Const::Empty => Json::Str(String::new()),
+        Const::Int(_) | Const::Sig(_) => Json::Str(
+            c.to_bits()
+                .iter()
+                .rev()
+                .map(|s| match s {
+                    State::S0 | State::S1 | State::Sz => s.to_string(),
+                    _ => "x".to_string(),
+                })
+                .collect(),
+        ),
+        Const::Str(s) => {
+            // strings that would read as a number get a trailing space
+            let rest = s.trim_start_matches(is_bit);
+            if rest.trim_start_matches(' ').is_empty() {
+                Json::Str(format!("{} ", s))
+            } else {
+                Json::Str(s.clone())
+            }
+        }
+    }
+}
+
+fn json_const(j: &Json) -> Result<Const> {
+    let s = match j {
+        Json::Int(n) => return Ok(Const::Int(*n)),
+        Json::Str(s) => s,
+        _ => bail!("invalid constant {:?}", j),
+    };
+    let rest = s.trim_start_matches(is_bit);
+    Ok(if s.is_empty() {
+        Const::Empty
+    } else if rest.is_empty() {
+        let bits: Vec<State> = s.chars().rev().map(state).collect();
+        let c = Const::from_bits(&bits);
+        match c.as_int() {
+            Some(n) if bits.len() == 32 => Const::Int(n as i32 as i64),
+            _ => c,
+        }
+    } else if rest.trim_start_matches(' ').is_empty() {
+        Const::Str(s[..s.len() - 1].to_string())
+    } else {
+        Const::Str(s.clone())
+    })
+}
+
+fn attrs_json(attrs: &HashMap<String, Const>) -> Json {
+    let mut v: Vec<_> = attrs.iter().collect();
+    v.sort_by(|a, b| a.0.cmp(b.0));
+    Json::Object(
+        v.into_iter()
+            .map(|(k, c)| (unescape(k), const_json(c)))
+            .collect(),
+    )
+}
+
+// Parameters in declaration order.
+fn params_json(params: &[(String, Const)]) -> Json {
+    Json::Object(
+        params
+            .iter()
+            .map(|(k, c)| (unescape(k), const_json(c)))
+            .collect(),
+    )
+}
+
+fn json_attrs(j: Option<&Json>) -> Result<HashMap<String, Const>> {
+    let mut r = HashMap::new();
+    for (k, v) in j.map_or(&[][..], Json::members) {
+        r.insert(escape(k), json_const(v)?);
+    }
+    Ok(r)
+}
+
+// Shape of a wire as written for ports and netnames.
+fn shape(w: &Wire, v: &mut Vec<(&str, Json)>) {
+    if *w.offset() != 0 {
+        v.push(("offset", Json::Int(*w.offset())));
+    }
+    if *w.upto() {
+        v.push(("upto", Json::Int(1)));
+    }
+    if *w.signed() {
+        v.push(("signed", Json::Int(1)));
+    }
+}
+
+fn dir_name(d: PortDir) -> &'static str {
+    match d {
+        PortDir::Input => "input",
+        PortDir::Output => "output",
+        PortDir::Inout => "inout",
+    }
+}
+
+// Numbering of the nets of a module, shared by connected bits.
+struct Nets {
+    wt: WireTable,
+    sigmap: SigMap,
+    ids: HashMap<SigBit, i64>,
+}
+
+impl Nets {
+    fn bits(&mut self, s: &SigSpec) -> Result<Json> {
+        let mut r = Vec::new();
+        for b in self.sigmap.map(&self.wt.bits(s)?) {
+            r.push(match b {
+                SigBit::Const(State::S0) => Json::Str("0".to_string()),
+                SigBit::Const(State::S1) => Json::Str("1".to_string()),
+                SigBit::Const(State::Sz) => Json::Str("z".to_string()),
+                SigBit::Const(_) => Json::Str("x".to_string()),
+                b => {
+                    let next = self.ids.len() as i64 + 2;
+                    Json::Int(*self.ids.entry(b).or_insert(next))
+                }
+            });
+        }
+        Ok(Json::Array(r))
+    }
+}
+
+fn module_json(m: &Module, ct: &CellTypes) -> Result<Json> {
+    if !m.processes().is_empty() {
+        bail!(
+            "module `{}' has processes, which have no JSON form",
+            m.ident()
+        );
+    }
+    let wt = WireTable::new(m);
+    let sigmap = SigMap::new(m, &wt)?;
+    let mut nets = Nets {
+        wt,
+        sigmap,
+        ids: HashMap::new(),
+    };
+
+    let mut ports: Vec<&Wire> = m.wires().iter().filter(|w| *w.port() > 0).collect();
+    ports.sort_by_key(|w| *w.port());
+    let mut jports = Vec::new();
+    for w in ports {
+        let dir = match (*w.input(), *w.output()) {
+            (true, true) => PortDir::Inout,
+            (true, false) => PortDir::Input,
+            _ => PortDir::Output,
+        };
+        let mut v = vec![
+            ("direction", Json::Str(dir_name(dir).to_string())),
+            ("bits", nets.bits(&SigSpec::wire(w.id()))?),
+        ];
+        shape(w, &mut v);
+        jports.push((unescape(w.id()), obj(v)));
+    }
+
+    let mut cells = Vec::new();
+    for c in m.cells() {
+        let mut params: Vec<_> = c.params().iter().collect();
+        params.sort_by(|a, b| a.0.cmp(b.0));
+        let mut conns: Vec<_> = c.connects().iter().collect();
+        conns.sort_by(|a, b| a.0.cmp(b.0));
+        let dirs = conns
+            .iter()
+            .filter_map(|(p, _)| {
+                ct.port_dir(c.i1(), p)
+                    .map(|d| (unescape(p), Json::Str(dir_name(d).to_string())))
+            })
+            .collect();
+        let mut jconns = Vec::new();
+        for (p, s) in conns {
+            jconns.push((unescape(p), nets.bits(s)?));
+        }
+        let v = vec![
+            ("hide_name", hidden(c.i2())),
+            ("type", Json::Str(unescape(c.i1()))),
+            (
+                "parameters",
+                Json::Object(
+                    params
+                        .into_iter()
+                        .map(|(k, p)| (unescape(k), const_json(p.val())))
+                        .collect(),
+                ),
+            ),
+            ("attributes", attrs_json(c.attrs())),
+            ("port_directions", Json::Object(dirs)),
+            ("connections", Json::Object(jconns)),
+        ];
+        cells.push((unescape(c.i2()), obj(v)));
+    }
+
+    let memories = m
+        .memories()
+        .iter()
+        .map(|mem| {
+            let v = vec![
+                ("hide_name", hidden(mem.id())),
+                ("attributes", attrs_json(mem.attrs())),
+                ("width", Json::Int(*mem.width())),
+                ("start_offset", Json::Int(*mem.offset())),
+                ("size", Json::Int(*mem.size())),
+            ];
+            (unescape(mem.id()), obj(v))
+        })
+        .collect();
+
+    let mut netnames = Vec::new();
+    for w in m.wires() {
+        let mut v = vec![
+            ("hide_name", hidden(w.id())),
+            ("bits", nets.bits(&SigSpec::wire(w.id()))?),
+        ];
+        shape(w, &mut v);
+        v.push(("attributes", attrs_json(w.attrs())));
+        netnames.push((unescape(w.id()), obj(v)));
+    }
+
+    let mut v = vec![
+        ("attributes", attrs_json(m.attrs())),
+        ("parameter_default_values", params_json(m.params())),
+        ("ports", Json::Object(jports)),
+        ("cells", Json::Object(cells)),
+    ];
+    if !m.memories().is_empty() {
+        v.push(("memories", Json::Object(memories)));
+    }
+    v.push(("netnames", Json::Object(netnames)));
+    Ok(obj(v))
+}
+
+/// Write `design` as yosys' `write_json` does. Modules must not have
+/// processes left.
+pub fn write_json<W: Write>(w: &mut W, design: &Design) -> Result<()> {
+    let ct = CellTypes::with_design(design);
+    let mut modules = Vec::new();
+    for m in design.modules() {
+        modules.push((unescape(m.ident()), module_json(m, &ct)?));
+    }
+    let j = obj(vec![
+        ("creator", Json::Str("rtlil-rs".to_string())),
+        ("modules", Json::Object(modules)),
+    ]);
+    j.write(w, 0)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+fn json_module(name: &str, j: &Json) -> Result<Module> {
+    let mut m = Module::new(escape(name), vec![]);
+    *m.attrs_mut() = json_attrs(j.get("attributes"))?;
+    for (k, v) in j
+        .get("parameter_default_values")
+        .map_or(&[][..], Json::members)
+    {
+        m.set_param(&escape(k), json_const(v)?);
+    }
+
+    let bits_of = |j: &Json, what: &str| -> Result<Vec<Json>> {
+        match j.get("bits") {
+            Some(Json::Array(v)) => Ok(v.clone()),
+            _ => Err(anyhow!("missing bits of `{}'", what)),
+        }
+    };
+    let wire = |id: String, j: &Json, bits: &[Json], dir: Option<WireOption>| {
+        let mut opts = vec![
+            WireOption::Width(bits.len() as i64),
+            WireOption::Offset(j.int("offset")),
+        ];
+        if j.int("upto") != 0 {
+            opts.push(WireOption::Upto);
+        }
+        if j.int("signed") != 0 {
+            opts.push(WireOption::Signed);
+        }
+        opts.extend(dir);
+        Wire::new(id, opts)
+    };
+
+    let mut ports = Vec::new();
+    for (i, (name, p)) in j
+        .get("ports")
+        .map_or(&[][..], Json::members)
+        .iter()
+        .enumerate()
+    {
+        let n = i as i64 + 1;
+        let (rank, dir) = match p.get("direction") {
+            Some(Json::Str(d)) if d == "input" => (0, WireOption::Input(n)),
+            Some(Json::Str(d)) if d == "output" => (1, WireOption::Output(n)),
+            Some(Json::Str(d)) if d == "inout" => (1, WireOption::Inout(n)),
+            _ => bail!("invalid direction of port `{}'", name),
+        };
+        ports.push((name.as_str(), rank, Some(dir), p));
+    }
+
+    // wires and their bits, in the order picking the wire that names a net
+    let mut wires: Vec<(u8, String, Vec<Json>)> = Vec::new();
+    let mut add = |m: &mut Module, name: &str, rank, dir, j: &Json, attrs| -> Result<()> {
+        let id = escape(name);
+        let bits = bits_of(j, name)?;
+        let mut w = wire(id.clone(), j, &bits, dir);
+        *w.attrs_mut() = attrs;
+        m.wires_mut().push(w);
+        wires.push((rank, id, bits));
+        Ok(())
+    };
+    for (name, n) in j.get("netnames").map_or(&[][..], Json::members) {
+        let attrs = json_attrs(n.get("attributes"))?;
+        match ports.iter().position(|p| p.0 == name) {
+            Some(i) => {
+                let (_, rank, dir, p) = ports.remove(i);
+                add(&mut m, name, rank, dir, p, attrs)?;
+            }
+            None => {
+                let rank = if n.int("hide_name") != 0 { 3 } else { 2 };
+                add(&mut m, name, rank, None, n, attrs)?;
+            }
+        }
+    }
+    for (name, rank, dir, p) in ports {
+        add(&mut m, name, rank, dir, p, HashMap::new())?;
+    }
+    wires.sort_by_key(|w| w.0);
+
+    let mut wt = WireTable::new(&m);
+    let mut nets: HashMap<i64, SigBit> = HashMap::new();
+    for (_, id, bits) in wires.iter() {
+        for (i, b) in bits.iter().enumerate() {
+            if let Json::Int(n) = b {
+                nets.entry(*n)
+                    .or_insert_with(|| SigBit::Wire((id.clone(), i as i64)));
+            }
+        }
+    }
+    let mut bit = |m: &mut Module, wt: &mut WireTable, b: &Json| -> Result<SigBit> {
+        Ok(match b {
+            Json::Int(n) => nets
+                .entry(*n)
+                .or_insert_with(|| {
+                    let w = Wire::new(new_id("json"), vec![]);
+                    wt.insert(&w);
+                    let b = SigBit::Wire((w.id().clone(), 0));
+                    m.wires_mut().push(w);
+                    b
+                })
+                .clone(),
+            Json::Str(s) if s.len() == 1 && s.chars().all(is_bit) => {
+                SigBit::Const(state(s.chars().next().unwrap()))
+            }
+            _ => bail!("invalid bit {:?}", b),
+        })
+    };
+
+    // bits of wires other than the one naming their net are connected to it
+    for (_, id, bits) in wires.iter() {
+        let (mut lhs, mut rhs) = (Vec::new(), Vec::new());
+        for (i, b) in bits.iter().enumerate() {
+            let own = SigBit::Wire((id.clone(), i as i64));
+            let net = bit(&mut m, &mut wt, b)?;
+            if net != own {
+                lhs.push(own);
+                rhs.push(net);
+            }
+        }
+        if !lhs.is_empty() {
+            let c = Connect::new(wt.sigspec(&lhs), wt.sigspec(&rhs));
+            m.connects_mut().push(c);
+        }
+    }
+
+    for (name, c) in j.get("cells").map_or(&[][..], Json::members) {
+        let tp = match c.get("type") {
+            Some(Json::Str(t)) => escape(t),
+            _ => bail!("missing type of cell `{}'", name),
+        };
+        let mut cell = Cell::new(tp, escape(name), vec![]);
+        for (k, v) in c.get("parameters").map_or(&[][..], Json::members) {
+            cell.set_param(&escape(k), json_const(v)?);
+        }
+        *cell.attrs_mut() = json_attrs(c.get("attributes"))?;
+        for (p, v) in c.get("connections").map_or(&[][..], Json::members) {
+            let bits = match v {
+                Json::Array(v) => v
+                    .iter()
+                    .map(|b| bit(&mut m, &mut wt, b))
+                    .collect::<Result<Vec<_>>>()?,
+                _ => bail!("invalid connection of port `{}' of cell `{}'", p, name),
+            };
+            cell.set_port(&escape(p), wt.sigspec(&bits));
+        }
+        m.cells_mut().push(cell);
+    }
+
+    for (name, mem) in j.get("memories").map_or(&[][..], Json::members) {
+        let mut r = Memory::new(
+            escape(name),
+            vec![
+                MemoryOption::Width(mem.int("width")),
+                MemoryOption::Offset(mem.int("start_offset")),
+                MemoryOption::Size(mem.int("size")),
+            ],
+        );
+        *r.attrs_mut() = json_attrs(mem.get("attributes"))?;
+        m.memories_mut().push(r);
+    }
+    Ok(m)
+}
+
+/// Read a design written by yosys' `write_json`. Bits of a net are driven
+/// through the first input port, other port or public wire holding it, and
+/// nets without a name get a fresh wire.
+pub fn read_json(text: &str) -> Result<Design> {
+    let j: Json = serde_json::from_str(text)?;
+    let mut d = Design::new();
+    for (name, m) in j.get("modules").map_or(&[][..], Json::members) {
+        d.modules_mut().push(json_module(name, m)?);
+    }
+    Ok(d)
+}
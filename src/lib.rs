@@ -1,8 +1,23 @@
 extern crate getset;
 
+pub mod aiger;
+pub mod backends;
+#[cfg(feature = "sat")]
+pub mod bmc;
+pub mod celltypes;
+pub mod cnf;
+pub mod diff;
 pub mod dumper;
+pub mod eval;
 #[allow(dead_code)]
 mod grammar;
+pub mod json;
 pub mod lexer;
+pub mod memory;
 pub mod parser;
+pub mod passes;
+#[cfg(feature = "sat")]
+pub mod sat;
+pub mod sim;
+pub mod stats;
 pub mod syntax;
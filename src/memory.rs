@@ -0,0 +1,570 @@
+// Copyright (c) 2020 xhe
+
+//! Typed view of memories and the ports accessing them.
+
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use getset::*;
+use std::collections::HashMap;
+
+use State::{Sx, S0, S1};
+
+fn int(cell: &Cell, k: &str) -> Result<i64> {
+    cell.param(k)
+        .and_then(|v| v.as_int())
+        .ok_or_else(|| anyhow!("cell `{}' has no parameter `{}'", cell.i2(), k))
+}
+
+fn flag(cell: &Cell, k: &str) -> bool {
+    matches!(cell.param(k), Some(v) if v.as_bool())
+}
+
+fn bits(cell: &Cell, k: &str) -> Vec<State> {
+    cell.param(k).map(|v| v.to_bits()).unwrap_or_default()
+}
+
+fn bit(v: &[State], i: usize) -> bool {
+    v.get(i) == Some(&S1)
+}
+
+fn bools(v: &[bool]) -> Const {
+    let bits: Vec<State> = v.iter().map(|b| if *b { S1 } else { S0 }).collect();
+    Const::from_bits(&bits)
+}
+
+// Bits of `v` resized to `n`, padded with `fill`.
+fn sized(mut v: Vec<State>, n: usize, fill: State) -> Vec<State> {
+    v.resize(n, fill);
+    v
+}
+
+/// Memory id referenced by the `MEMID` parameter of `cell`.
+pub fn memid(cell: &Cell) -> Option<&str> {
+    match cell.param("\\MEMID") {
+        Some(Const::Str(s)) => Some(s),
+        _ => None,
+    }
+}
+
+// Concatenation of `parts`, the first one in the least significant bits.
+fn concat(parts: Vec<SigSpec>) -> SigSpec {
+    match parts.len() {
+        1 => parts.into_iter().next().unwrap(),
+        _ => SigSpec::List(parts.into_iter().rev().collect()),
+    }
+}
+
+/// A read port of a memory. Ports without reset have constant 0 reset
+/// signals.
+#[derive(Debug, Clone, Getters, MutGetters)]
+#[get = "pub"]
+#[get_mut = "pub"]
+pub struct RdPort {
+    /// Clock and active edge of a synchronous port, None if asynchronous.
+    clk: Option<(SigSpec, bool)>,
+    en: SigSpec,
+    arst: SigSpec,
+    srst: SigSpec,
+    arst_value: Vec<State>,
+    srst_value: Vec<State>,
+    init_value: Vec<State>,
+    ce_over_srst: bool,
+    /// Write ports, by index, whose data is read in the cycle it is
+    /// written.
+    transparency: Vec<bool>,
+    /// Write ports, by index, whose writes to the address read make the
+    /// data undefined.
+    collision_x: Vec<bool>,
+    addr: SigSpec,
+    data: SigSpec,
+}
+
+/// A write port of a memory, with an enable bit per data bit.
+#[derive(Debug, Clone, Getters, MutGetters)]
+#[get = "pub"]
+#[get_mut = "pub"]
+pub struct WrPort {
+    /// Clock and active edge of a synchronous port, None if asynchronous.
+    clk: Option<(SigSpec, bool)>,
+    en: SigSpec,
+    addr: SigSpec,
+    data: SigSpec,
+    /// Write ports, by index, this one wins over when writing the same
+    /// word.
+    priority: Vec<bool>,
+}
+
+/// A memory with all its ports, in the terms of a `$mem_v2` cell.
+#[derive(Debug, Clone, Getters, MutGetters)]
+#[get = "pub"]
+#[get_mut = "pub"]
+pub struct MemoryPorts {
+    memid: String,
+    width: usize,
+    offset: i64,
+    size: usize,
+    abits: usize,
+    /// Initial contents, `size` words LSB first, undefined where not set.
+    init: Vec<State>,
+    attrs: HashMap<String, Const>,
+    rd: Vec<RdPort>,
+    wr: Vec<WrPort>,
+}
+
+// Whether two ports are clocked by the same edge of the same clock, or both
+// asynchronous.
+fn same_clock(wt: &WireTable, a: &Option<(SigSpec, bool)>, b: &Option<(SigSpec, bool)>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some((x, p)), Some((y, q))) => p == q && wt.bits(x).ok() == wt.bits(y).ok(),
+        _ => false,
+    }
+}
+
+// Masks of version 1 cells: transparency with the write ports on the same
+// clock, priority over the earlier write ports on the same clock.
+fn v1_masks(wt: &WireTable, rd: &mut [RdPort], transparent: &[bool], wr: &mut [WrPort]) {
+    for (r, t) in rd.iter_mut().zip(transparent) {
+        r.transparency = wr
+            .iter()
+            .map(|w| *t && r.clk.is_some() && same_clock(wt, &r.clk, &w.clk))
+            .collect();
+        r.collision_x = vec![false; wr.len()];
+    }
+    for i in 0..wr.len() {
+        wr[i].priority = (0..wr.len())
+            .map(|j| j < i && same_clock(wt, &wr[i].clk, &wr[j].clk))
+            .collect();
+    }
+}
+
+impl MemoryPorts {
+    /// View of memory `id` of `module` and its `$memrd`, `$memwr` and
+    /// `$meminit` cells, version 1 or 2. Write ports are ordered by their
+    /// `PORTID`, or `PRIORITY` for version 1 cells.
+    pub fn from_cells(module: &Module, id: &str) -> Result<Self> {
+        let mem = module
+            .memories()
+            .iter()
+            .find(|m| m.id() == id)
+            .ok_or_else(|| anyhow!("memory `{}' not found", id))?;
+        let wt = WireTable::new(module);
+        let (width, size) = (*mem.width() as usize, *mem.size() as usize);
+        let mut abits = 0;
+        while (1u64 << abits) < size as u64 {
+            abits += 1;
+        }
+        let cells: Vec<&Cell> = module
+            .cells()
+            .iter()
+            .filter(|c| memid(c) == Some(id))
+            .collect();
+        let clock = |c: &Cell| match c.port("\\CLK") {
+            Some(s) if flag(c, "\\CLK_ENABLE") => Some((s.clone(), flag(c, "\\CLK_POLARITY"))),
+            _ => None,
+        };
+        let port = |c: &Cell, k: &str, n: usize| -> Result<SigSpec> {
+            match c.port(k) {
+                Some(s) if wt.width_of(s)? as usize == n => Ok(s.clone()),
+                Some(_) => bail!(
+                    "port `{}' of cell `{}' does not match memory `{}'",
+                    k,
+                    c.i2(),
+                    id
+                ),
+                None => bail!("cell `{}' has no port `{}'", c.i2(), k),
+            }
+        };
+        let addr = |c: &Cell| -> Result<SigSpec> {
+            match c.port("\\ADDR") {
+                Some(s) => Ok(s.clone()),
+                None => bail!("cell `{}' has no port `ADDR'", c.i2()),
+            }
+        };
+
+        // write ports, in the order their masks refer to them
+        let mut wcells: Vec<(i64, &Cell)> = Vec::new();
+        for c in cells.iter().filter(|c| c.i1().starts_with("$memwr")) {
+            let key = match c.i1().as_str() {
+                "$memwr_v2" => int(c, "\\PORTID")?,
+                _ => c.param("\\PRIORITY").and_then(|v| v.as_int()).unwrap_or(0),
+            };
+            wcells.push((key, c));
+        }
+        wcells.sort_by_key(|(k, _)| *k);
+        let ids: Vec<i64> = wcells.iter().map(|(k, _)| *k).collect();
+        let remap = |mask: Vec<State>| -> Vec<bool> {
+            ids.iter().map(|p| bit(&mask, *p as usize)).collect()
+        };
+        let mut wr = Vec::new();
+        for (_, c) in wcells.iter() {
+            let a = addr(c)?;
+            abits = abits.max(wt.width_of(&a)? as usize);
+            wr.push(WrPort {
+                clk: clock(c),
+                en: port(c, "\\EN", width)?,
+                addr: a,
+                data: port(c, "\\DATA", width)?,
+                priority: remap(bits(c, "\\PRIORITY_MASK")),
+            });
+        }
+
+        let mut rd = Vec::new();
+        let mut transparent = Vec::new();
+        let v1 = cells
+            .iter()
+            .any(|c| c.i1() == "$memrd" || c.i1() == "$memwr");
+        for c in cells.iter().filter(|c| c.i1().starts_with("$memrd")) {
+            let a = addr(c)?;
+            abits = abits.max(wt.width_of(&a)? as usize);
+            let reset = |k: &str| match c.port(k) {
+                Some(s) => s.clone(),
+                None => SigSpec::constant(&[S0]),
+            };
+            transparent.push(flag(c, "\\TRANSPARENT"));
+            rd.push(RdPort {
+                clk: clock(c),
+                en: match c.port("\\EN") {
+                    Some(s) => s.clone(),
+                    None => SigSpec::constant(&[S1]),
+                },
+                arst: reset("\\ARST"),
+                srst: reset("\\SRST"),
+                arst_value: sized(bits(c, "\\ARST_VALUE"), width, Sx),
+                srst_value: sized(bits(c, "\\SRST_VALUE"), width, Sx),
+                init_value: sized(bits(c, "\\INIT_VALUE"), width, Sx),
+                ce_over_srst: flag(c, "\\CE_OVER_SRST"),
+                transparency: remap(bits(c, "\\TRANSPARENCY_MASK")),
+                collision_x: remap(bits(c, "\\COLLISION_X_MASK")),
+                addr: a,
+                data: port(c, "\\DATA", width)?,
+            });
+        }
+        if v1 {
+            v1_masks(&wt, &mut rd, &transparent, &mut wr);
+        }
+
+        // initial contents, later priorities written last
+        let mut init = vec![Sx; size * width];
+        let mut inits: Vec<&&Cell> = cells
+            .iter()
+            .filter(|c| c.i1().starts_with("$meminit"))
+            .collect();
+        inits.sort_by_key(|c| c.param("\\PRIORITY").and_then(|v| v.as_int()));
+        for c in inits {
+            let value = |k: &str| -> Result<Vec<State>> {
+                let b = wt.bits(c.port(k).ok_or_else(|| anyhow!("no port `{}'", k))?)?;
+                b.iter()
+                    .map(|b| b.state())
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| anyhow!("port `{}' of cell `{}' is not constant", k, c.i2()))
+            };
+            let start = match Const::from_bits(&value("\\ADDR")?).as_int() {
+                Some(a) => a - mem.offset(),
+                None => bail!("cell `{}' has an undefined address", c.i2()),
+            };
+            let en = match c.i1().as_str() {
+                "$meminit_v2" => value("\\EN")?,
+                _ => vec![S1; width],
+            };
+            for (k, b) in value("\\DATA")?.into_iter().enumerate() {
+                let i = start * width as i64 + k as i64;
+                if i >= 0 && (i as usize) < init.len() && bit(&en, k % width.max(1)) {
+                    init[i as usize] = b;
+                }
+            }
+        }
+
+        Ok(Self {
+            memid: id.to_string(),
+            width,
+            offset: *mem.offset(),
+            size,
+            abits,
+            init,
+            attrs: mem.attrs().clone(),
+            rd,
+            wr,
+        })
+    }
+
+    /// View of a `$mem` or `$mem_v2` cell of `module`.
+    pub fn from_cell(module: &Module, cell: &Cell) -> Result<Self> {
+        let wt = WireTable::new(module);
+        let v2 = match cell.i1().as_str() {
+            "$mem_v2" => true,
+            "$mem" => false,
+            tp => bail!("cell `{}' of type `{}' is not a memory", cell.i2(), tp),
+        };
+        let size = |k: &str| -> Result<usize> { Ok(int(cell, k)?.max(0) as usize) };
+        let (width, abits, size) = (size("\\WIDTH")?, size("\\ABITS")?, size("\\SIZE")?);
+        let (nrd, nwr) = (
+            int(cell, "\\RD_PORTS")? as usize,
+            int(cell, "\\WR_PORTS")? as usize,
+        );
+        // bits of port `k`, split for each of `n` ports
+        let split = |k: &str, n: usize| -> Result<Vec<SigSpec>> {
+            let b = match cell.port(k) {
+                Some(s) => wt.bits(s)?,
+                None if !v2 => vec![SigBit::Const(S0); n],
+                None => bail!("cell `{}' has no port `{}'", cell.i2(), k),
+            };
+            if n == 0 {
+                return Ok(vec![]);
+            }
+            if b.len() % n != 0 {
+                bail!("port `{}' of cell `{}' has a bad width", k, cell.i2());
+            }
+            Ok(b.chunks(b.len() / n).map(|c| wt.sigspec(c)).collect())
+        };
+        let slice = |k: &str, i: usize, n: usize| -> Vec<State> {
+            sized(bits(cell, k).into_iter().skip(i * n).collect(), n, Sx)
+        };
+
+        let (clk, en, addr, data) = (
+            split("\\WR_CLK", nwr)?,
+            split("\\WR_EN", nwr)?,
+            split("\\WR_ADDR", nwr)?,
+            split("\\WR_DATA", nwr)?,
+        );
+        let (ce, pol) = (
+            bits(cell, "\\WR_CLK_ENABLE"),
+            bits(cell, "\\WR_CLK_POLARITY"),
+        );
+        let prio = bits(cell, "\\WR_PRIORITY_MASK");
+        let mut wr = Vec::new();
+        for i in 0..nwr {
+            wr.push(WrPort {
+                clk: Some((clk[i].clone(), bit(&pol, i))).filter(|_| bit(&ce, i)),
+                en: en[i].clone(),
+                addr: addr[i].clone(),
+                data: data[i].clone(),
+                priority: (0..nwr).map(|j| bit(&prio, i * nwr + j)).collect(),
+            });
+        }
+
+        let (clk, en, addr, data) = (
+            split("\\RD_CLK", nrd)?,
+            split("\\RD_EN", nrd)?,
+            split("\\RD_ADDR", nrd)?,
+            split("\\RD_DATA", nrd)?,
+        );
+        let (arst, srst) = (split("\\RD_ARST", nrd)?, split("\\RD_SRST", nrd)?);
+        let (ce, pol) = (
+            bits(cell, "\\RD_CLK_ENABLE"),
+            bits(cell, "\\RD_CLK_POLARITY"),
+        );
+        let (transp, collx) = (
+            bits(cell, "\\RD_TRANSPARENCY_MASK"),
+            bits(cell, "\\RD_COLLISION_X_MASK"),
+        );
+        let ce_over = bits(cell, "\\RD_CE_OVER_SRST");
+        let mut rd = Vec::new();
+        for i in 0..nrd {
+            rd.push(RdPort {
+                clk: Some((clk[i].clone(), bit(&pol, i))).filter(|_| bit(&ce, i)),
+                en: en[i].clone(),
+                arst: arst[i].clone(),
+                srst: srst[i].clone(),
+                arst_value: slice("\\RD_ARST_VALUE", i, width),
+                srst_value: slice("\\RD_SRST_VALUE", i, width),
+                init_value: slice("\\RD_INIT_VALUE", i, width),
+                ce_over_srst: bit(&ce_over, i),
+                transparency: (0..nwr).map(|j| bit(&transp, i * nwr + j)).collect(),
+                collision_x: (0..nwr).map(|j| bit(&collx, i * nwr + j)).collect(),
+                addr: addr[i].clone(),
+                data: data[i].clone(),
+            });
+        }
+        if !v2 {
+            let transparent: Vec<bool> = (0..nrd)
+                .map(|i| bit(&bits(cell, "\\RD_TRANSPARENT"), i))
+                .collect();
+            v1_masks(&wt, &mut rd, &transparent, &mut wr);
+        }
+
+        Ok(Self {
+            memid: memid(cell).unwrap_or(cell.i2()).to_string(),
+            width,
+            offset: cell.param("\\OFFSET").and_then(|v| v.as_int()).unwrap_or(0),
+            size,
+            abits,
+            init: sized(bits(cell, "\\INIT"), size * width, Sx),
+            attrs: cell.attrs().clone(),
+            rd,
+            wr,
+        })
+    }
+
+    /// The `$mem_v2` cell of the memory, named after it.
+    pub fn to_cell(&self) -> Cell {
+        let mut c = Cell::new("$mem_v2".to_string(), self.memid.clone(), vec![]);
+        *c.attrs_mut() = self.attrs.clone();
+        let (nrd, nwr) = (self.rd.len(), self.wr.len());
+        c.set_param("\\MEMID", Const::Str(self.memid.clone()));
+        c.set_param("\\SIZE", Const::Int(self.size as i64));
+        c.set_param("\\OFFSET", Const::Int(self.offset));
+        c.set_param("\\ABITS", Const::Int(self.abits as i64));
+        c.set_param("\\WIDTH", Const::Int(self.width as i64));
+        c.set_param("\\INIT", Const::from_bits(&self.init));
+
+        let clock = |p: &Option<(SigSpec, bool)>| match p {
+            Some((s, _)) => s.clone(),
+            None => SigSpec::constant(&[Sx]),
+        };
+        let rd = &self.rd;
+        let each = |f: &dyn Fn(&RdPort) -> bool| bools(&rd.iter().map(f).collect::<Vec<_>>());
+        let flat = |f: &dyn Fn(&RdPort) -> &Vec<bool>| {
+            bools(&rd.iter().flat_map(|p| f(p).clone()).collect::<Vec<_>>())
+        };
+        let states = |f: &dyn Fn(&RdPort) -> &Vec<State>| {
+            let v: Vec<State> = rd.iter().flat_map(|p| f(p).clone()).collect();
+            Const::from_bits(&v)
+        };
+        let sigs = |f: &dyn Fn(&RdPort) -> SigSpec| concat(rd.iter().map(f).collect());
+        c.set_param("\\RD_PORTS", Const::Int(nrd as i64));
+        c.set_param("\\RD_CLK_ENABLE", each(&|p| p.clk.is_some()));
+        c.set_param(
+            "\\RD_CLK_POLARITY",
+            each(&|p| p.clk.as_ref().is_none_or(|(_, pol)| *pol)),
+        );
+        c.set_param("\\RD_TRANSPARENCY_MASK", flat(&|p| &p.transparency));
+        c.set_param("\\RD_COLLISION_X_MASK", flat(&|p| &p.collision_x));
+        c.set_param("\\RD_WIDE_CONTINUATION", bools(&vec![false; nrd]));
+        c.set_param("\\RD_CE_OVER_SRST", each(&|p| p.ce_over_srst));
+        c.set_param("\\RD_ARST_VALUE", states(&|p| &p.arst_value));
+        c.set_param("\\RD_SRST_VALUE", states(&|p| &p.srst_value));
+        c.set_param("\\RD_INIT_VALUE", states(&|p| &p.init_value));
+        c.set_port("\\RD_CLK", sigs(&|p| clock(&p.clk)));
+        c.set_port("\\RD_EN", sigs(&|p| p.en.clone()));
+        c.set_port("\\RD_ARST", sigs(&|p| p.arst.clone()));
+        c.set_port("\\RD_SRST", sigs(&|p| p.srst.clone()));
+        c.set_port("\\RD_ADDR", sigs(&|p| p.addr.clone()));
+        c.set_port("\\RD_DATA", sigs(&|p| p.data.clone()));
+
+        let wr = &self.wr;
+        let each = |f: &dyn Fn(&WrPort) -> bool| bools(&wr.iter().map(f).collect::<Vec<_>>());
+        let sigs = |f: &dyn Fn(&WrPort) -> SigSpec| concat(wr.iter().map(f).collect());
+        c.set_param("\\WR_PORTS", Const::Int(nwr as i64));
+        c.set_param("\\WR_CLK_ENABLE", each(&|p| p.clk.is_some()));
+        c.set_param(
+            "\\WR_CLK_POLARITY",
+            each(&|p| p.clk.as_ref().is_none_or(|(_, pol)| *pol)),
+        );
+        let prio: Vec<bool> = wr.iter().flat_map(|p| p.priority.clone()).collect();
+        c.set_param("\\WR_PRIORITY_MASK", bools(&prio));
+        c.set_param("\\WR_WIDE_CONTINUATION", bools(&vec![false; nwr]));
+        c.set_port("\\WR_CLK", sigs(&|p| clock(&p.clk)));
+        c.set_port("\\WR_EN", sigs(&|p| p.en.clone()));
+        c.set_port("\\WR_ADDR", sigs(&|p| p.addr.clone()));
+        c.set_port("\\WR_DATA", sigs(&|p| p.data.clone()));
+        c
+    }
+
+    /// The memory declaration and its `$memrd_v2`, `$memwr_v2` and
+    /// `$meminit_v2` cells, one for each run of initialised words.
+    pub fn to_cells(&self) -> (Memory, Vec<Cell>) {
+        let mut mem = Memory::new(
+            self.memid.clone(),
+            vec![
+                MemoryOption::Width(self.width as i64),
+                MemoryOption::Offset(self.offset),
+                MemoryOption::Size(self.size as i64),
+            ],
+        );
+        *mem.attrs_mut() = self.attrs.clone();
+        let new = |tp: &str| {
+            let mut c = Cell::new(tp.to_string(), new_id("memory"), vec![]);
+            c.set_param("\\MEMID", Const::Str(self.memid.clone()));
+            c.set_param("\\ABITS", Const::Int(self.abits as i64));
+            c.set_param("\\WIDTH", Const::Int(self.width as i64));
+            c
+        };
+        let clock = |c: &mut Cell, p: &Option<(SigSpec, bool)>| {
+            let (s, pol) = match p {
+                Some((s, pol)) => (s.clone(), *pol),
+                None => (SigSpec::constant(&[Sx]), true),
+            };
+            c.set_param("\\CLK_ENABLE", bools(&[p.is_some()]));
+            c.set_param("\\CLK_POLARITY", bools(&[pol]));
+            c.set_port("\\CLK", s);
+        };
+        let mut cells = Vec::new();
+
+        for p in self.rd.iter() {
+            let mut c = new("$memrd_v2");
+            clock(&mut c, &p.clk);
+            c.set_param("\\TRANSPARENCY_MASK", bools(&p.transparency));
+            c.set_param("\\COLLISION_X_MASK", bools(&p.collision_x));
+            c.set_param("\\CE_OVER_SRST", bools(&[p.ce_over_srst]));
+            c.set_param("\\ARST_VALUE", Const::from_bits(&p.arst_value));
+            c.set_param("\\SRST_VALUE", Const::from_bits(&p.srst_value));
+            c.set_param("\\INIT_VALUE", Const::from_bits(&p.init_value));
+            c.set_port("\\EN", p.en.clone());
+            c.set_port("\\ARST", p.arst.clone());
+            c.set_port("\\SRST", p.srst.clone());
+            c.set_port("\\ADDR", p.addr.clone());
+            c.set_port("\\DATA", p.data.clone());
+            cells.push(c);
+        }
+        for (i, p) in self.wr.iter().enumerate() {
+            let mut c = new("$memwr_v2");
+            clock(&mut c, &p.clk);
+            c.set_param("\\PORTID", Const::Int(i as i64));
+            c.set_param("\\PRIORITY_MASK", bools(&p.priority));
+            c.set_port("\\EN", p.en.clone());
+            c.set_port("\\ADDR", p.addr.clone());
+            c.set_port("\\DATA", p.data.clone());
+            cells.push(c);
+        }
+
+        let defined = |w: &[State]| w.iter().any(|b| matches!(b, S0 | S1));
+        let words: Vec<&[State]> = self.init.chunks(self.width.max(1)).collect();
+        let (mut i, mut prio) = (0, 0);
+        while i < words.len() {
+            if !defined(words[i]) {
+                i += 1;
+                continue;
+            }
+            let mut j = i + 1;
+            while j < words.len() && defined(words[j]) {
+                j += 1;
+            }
+            let mut c = new("$meminit_v2");
+            let addr = self.offset + i as i64;
+            let addr: Vec<State> = (0..self.abits)
+                .map(|k| {
+                    if k < 63 && (addr >> k) & 1 == 1 {
+                        S1
+                    } else {
+                        S0
+                    }
+                })
+                .collect();
+            c.set_param("\\WORDS", Const::Int((j - i) as i64));
+            c.set_param("\\PRIORITY", Const::Int(prio));
+            prio += 1;
+            c.set_port("\\ADDR", SigSpec::constant(&addr));
+            c.set_port("\\DATA", SigSpec::constant(&words[i..j].concat()));
+            c.set_port("\\EN", SigSpec::constant(&vec![S1; self.width]));
+            cells.push(c);
+            i = j;
+        }
+        (mem, cells)
+    }
+}
+
+/// Views of all memories of `module`: the declared ones with their cells,
+/// then the `$mem` and `$mem_v2` cells.
+pub fn memory_ports(module: &Module) -> Result<Vec<MemoryPorts>> {
+    let mut r = Vec::new();
+    for m in module.memories() {
+        r.push(MemoryPorts::from_cells(module, m.id())?);
+    }
+    for c in module.cells() {
+        if c.i1() == "$mem" || c.i1() == "$mem_v2" {
+            r.push(MemoryPorts::from_cell(module, c)?);
+        }
+    }
+    Ok(r)
+}
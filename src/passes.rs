@@ -0,0 +1,38 @@
+// Copyright (c) 2020 xhe
+
+//! Transformations over parsed designs.
+
+mod paramod;
+pub use paramod::*;
+
+mod uniquify;
+pub use uniquify::*;
+
+mod proc;
+pub use proc::*;
+
+mod rmdead;
+pub use rmdead::*;
+
+mod drivers;
+pub use drivers::*;
+
+mod opt_const;
+pub use opt_const::*;
+
+mod opt_clean;
+pub use opt_clean::*;
+
+mod opt_merge;
+pub use opt_merge::*;
+
+mod techmap;
+pub use techmap::*;
+
+mod miter;
+pub use miter::*;
+
+mod memory;
+pub use memory::*;
+mod memory_map;
+pub use memory_map::*;
@@ -0,0 +1,201 @@
+use super::rmdead::analyze_switch;
+use crate::syntax::*;
+use anyhow::Result;
+use getset::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[derive(Debug, Clone, Getters)]
+#[get = "pub"]
+pub struct CaseDrivers {
+    /// Indices leading to the case from the process root: alternately a
+    /// switch and a case index. Empty for the root.
+    path: Vec<usize>,
+    /// Bits assigned directly by the case, other than to their own current
+    /// value.
+    assigned: Vec<SigBit>,
+}
+
+#[derive(Debug, Clone, Getters)]
+#[get = "pub"]
+pub struct ProcessDrivers {
+    process: String,
+    /// Bits assigned anywhere in the process body, other than to their own
+    /// current value.
+    assigned: Vec<SigBit>,
+    cases: Vec<CaseDrivers>,
+    /// Bits updated by edge, level or global sync rules.
+    registered: Vec<SigBit>,
+    /// Bits driven combinationally: updated by `sync always` or assigned by
+    /// the body without being stored by a sync rule.
+    comb: Vec<SigBit>,
+    /// Combinationally driven bits not assigned on every path through the
+    /// body, or keeping their own value on some path, for which a latch
+    /// would be inferred.
+    partial: Vec<SigBit>,
+}
+
+// Whether assigning `r` to `l` keeps the current value of `l`, like the
+// `assign $0\q \q` yosys' frontend puts at the root of processes. `stored`
+// maps sync rule inputs to the bits they update.
+fn holds(l: &SigBit, r: &SigBit, stored: &HashMap<SigBit, Vec<SigBit>>) -> bool {
+    l == r || stored.get(l).is_some_and(|t| t.contains(r))
+}
+
+fn collect_cases(
+    c: &ProcessSwitchCase,
+    path: &mut Vec<usize>,
+    stored: &HashMap<SigBit, Vec<SigBit>>,
+    wt: &WireTable,
+    out: &mut Vec<CaseDrivers>,
+) -> Result<()> {
+    let mut assigned = BTreeSet::new();
+    for (l, r) in c.assign() {
+        for (lb, rb) in wt.bits(l)?.into_iter().zip(wt.bits(r)?) {
+            if !lb.is_const() && !holds(&lb, &rb, stored) {
+                assigned.insert(lb);
+            }
+        }
+    }
+    out.push(CaseDrivers {
+        path: path.clone(),
+        assigned: assigned.into_iter().collect(),
+    });
+    for (i, s) in c.switch().iter().enumerate() {
+        path.push(i);
+        for (j, k) in s.cases().iter().enumerate() {
+            path.push(j);
+            collect_cases(k, path, stored, wt, out)?;
+            path.pop();
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+// Bits given a new value on every path through `c`, starting with those in
+// `r`.
+fn full_bits(
+    c: &ProcessSwitchCase,
+    mut r: HashSet<SigBit>,
+    known: &HashMap<SigBit, bool>,
+    stored: &HashMap<SigBit, Vec<SigBit>>,
+    wt: &WireTable,
+) -> Result<HashSet<SigBit>> {
+    for (l, v) in c.assign() {
+        for (lb, vb) in wt.bits(l)?.into_iter().zip(wt.bits(v)?) {
+            if holds(&lb, &vb, stored) {
+                r.remove(&lb);
+            } else {
+                r.insert(lb);
+            }
+        }
+    }
+    for s in c.switch() {
+        let (info, inner) = analyze_switch(s, known, false, wt)?;
+        // without a case taken, the bits are left as they are
+        let mut all = if *info.full() { None } else { Some(r.clone()) };
+        for ((k, i), known) in s.cases().iter().zip(info.cases()).zip(inner.iter()) {
+            if *i.unreachable() {
+                continue;
+            }
+            let bits = full_bits(k, r.clone(), known, stored, wt)?;
+            all = Some(match all {
+                None => bits,
+                Some(a) => a.intersection(&bits).cloned().collect(),
+            });
+        }
+        r = all.unwrap_or(r);
+    }
+    Ok(r)
+}
+
+fn collect_targets(
+    c: &ProcessSwitchCase,
+    wt: &WireTable,
+    out: &mut BTreeSet<SigBit>,
+) -> Result<()> {
+    for (l, _) in c.assign() {
+        out.extend(wt.bits(l)?.into_iter().filter(|b| !b.is_const()));
+    }
+    for s in c.switch() {
+        for k in s.cases() {
+            collect_targets(k, wt, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Summarise the signals driven by every process of the module.
+pub fn process_drivers(module: &Module) -> Result<Vec<ProcessDrivers>> {
+    let wt = WireTable::new(module);
+    let mut out = Vec::new();
+    for p in module.processes() {
+        let mut root = ProcessSwitchCase::default();
+        *root.assign_mut() = p.assign().clone();
+        *root.switch_mut() = p.switch().clone();
+
+        // sync rule inputs, mapped to the bits they update
+        let mut stored: HashMap<SigBit, Vec<SigBit>> = HashMap::new();
+        let mut registered = BTreeSet::new();
+        let mut comb = BTreeSet::new();
+        for s in p.syncs() {
+            let always = match s.tp() {
+                ProcessSyncType::Init => continue,
+                ProcessSyncType::Always => true,
+                _ => false,
+            };
+            for (l, r) in s.updates() {
+                for (lb, rb) in wt.bits(l)?.into_iter().zip(wt.bits(r)?) {
+                    if lb.is_const() {
+                        continue;
+                    }
+                    if always {
+                        comb.insert(lb.clone());
+                    } else {
+                        registered.insert(lb.clone());
+                    }
+                    stored.entry(rb).or_default().push(lb);
+                }
+            }
+        }
+
+        let mut cases = Vec::new();
+        collect_cases(&root, &mut Vec::new(), &stored, &wt, &mut cases)?;
+        let assigned: BTreeSet<SigBit> = cases
+            .iter()
+            .flat_map(|c| c.assigned.iter().cloned())
+            .collect();
+        // bits only assigned their own value still need a latch
+        let mut targets = BTreeSet::new();
+        collect_targets(&root, &wt, &mut targets)?;
+
+        let full = full_bits(&root, HashSet::new(), &HashMap::new(), &stored, &wt)?;
+        let mut partial = BTreeSet::new();
+        for b in targets.iter() {
+            let targets = stored.get(b).cloned().unwrap_or_default();
+            if targets.iter().any(|t| registered.contains(t)) {
+                continue;
+            }
+            if targets.is_empty() {
+                comb.insert(b.clone());
+            }
+            if !full.contains(b) {
+                if targets.is_empty() {
+                    partial.insert(b.clone());
+                } else {
+                    partial.extend(targets);
+                }
+            }
+        }
+
+        out.push(ProcessDrivers {
+            process: p.id().clone(),
+            assigned: assigned.into_iter().collect(),
+            cases,
+            registered: registered.into_iter().collect(),
+            comb: comb.into_iter().collect(),
+            partial: partial.into_iter().collect(),
+        });
+    }
+    Ok(out)
+}
@@ -0,0 +1,51 @@
+use crate::memory::{memid, MemoryPorts};
+use crate::syntax::*;
+use anyhow::Result;
+
+/// Gather the `$memrd`, `$memwr` and `$meminit` cells of each memory of
+/// `module`, version 1 or 2, into a `$mem_v2` cell named after the memory,
+/// like yosys' `memory_collect`, and turn `$mem` cells into `$mem_v2`.
+/// Returns the number of memories collected.
+pub fn memory_collect(module: &mut Module) -> Result<usize> {
+    let mut mems = Vec::new();
+    for m in module.memories() {
+        mems.push(MemoryPorts::from_cells(module, m.id())?);
+    }
+    for c in module.cells().iter().filter(|c| c.i1() == "$mem") {
+        mems.push(MemoryPorts::from_cell(module, c)?);
+    }
+    let ids: Vec<String> = module.memories().iter().map(|m| m.id().clone()).collect();
+    module.memories_mut().clear();
+    module.cells_mut().retain(|c| {
+        let port = matches!(
+            c.i1().as_str(),
+            "$memrd" | "$memrd_v2" | "$memwr" | "$memwr_v2" | "$meminit" | "$meminit_v2"
+        );
+        !(c.i1() == "$mem" || port && memid(c).is_some_and(|m| ids.iter().any(|i| i == m)))
+    });
+    for m in mems.iter() {
+        module.cells_mut().push(m.to_cell());
+    }
+    Ok(mems.len())
+}
+
+/// Split each `$mem` and `$mem_v2` cell of `module` into a memory and its
+/// `$memrd_v2`, `$memwr_v2` and `$meminit_v2` cells, like yosys'
+/// `memory_unpack`. Returns the number of cells split.
+pub fn memory_unpack(module: &mut Module) -> Result<usize> {
+    let mut mems = Vec::new();
+    for c in module.cells() {
+        if c.i1() == "$mem" || c.i1() == "$mem_v2" {
+            mems.push(MemoryPorts::from_cell(module, c)?);
+        }
+    }
+    module
+        .cells_mut()
+        .retain(|c| c.i1() != "$mem" && c.i1() != "$mem_v2");
+    for m in mems.iter() {
+        let (mem, cells) = m.to_cells();
+        module.memories_mut().push(mem);
+        module.cells_mut().extend(cells);
+    }
+    Ok(mems.len())
+}
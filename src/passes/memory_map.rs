@@ -0,0 +1,350 @@
+use crate::memory::{memid, memory_ports, MemoryPorts};
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+use State::{Sx, S0, S1};
+
+// Builder of the logic replacing the memories of a module.
+struct Mapper<'a> {
+    module: &'a mut Module,
+    wt: WireTable,
+    /// Address decoders built so far, by address and word address.
+    decoders: HashMap<(String, i64), SigSpec>,
+}
+
+impl Mapper<'_> {
+    fn add_wire(&mut self, w: Wire) -> SigSpec {
+        self.wt.insert(&w);
+        let s = SigSpec::wire(w.id());
+        self.module.wires_mut().push(w);
+        s
+    }
+
+    fn wire(&mut self, width: usize) -> SigSpec {
+        let s = self.module.new_wire("memory_map", width as i64);
+        self.wt.insert(self.module.wires().last().unwrap());
+        s
+    }
+
+    fn bits(&self, s: &SigSpec) -> Result<Vec<SigBit>> {
+        self.wt.bits(s)
+    }
+
+    // Output `Y` of a new cell of type `tp` and `width` bits.
+    fn cell(
+        &mut self,
+        tp: &str,
+        width: usize,
+        params: &[(&str, usize)],
+        ports: Vec<(&str, SigSpec)>,
+    ) -> SigSpec {
+        let y = self.wire(width);
+        let c = self.module.new_cell(tp, "memory_map");
+        for (k, v) in params {
+            c.set_param(k, Const::Int(*v as i64));
+        }
+        for (k, v) in ports {
+            c.set_port(k, v);
+        }
+        c.set_port("\\Y", y.clone());
+        y
+    }
+
+    // Bit set when `addr` is `value`.
+    fn decode(&mut self, addr: &SigSpec, value: i64) -> Result<SigSpec> {
+        let key = (addr.to_string(), value);
+        if let Some(s) = self.decoders.get(&key) {
+            return Ok(s.clone());
+        }
+        let n = self.bits(addr)?.len();
+        if n < 64 && value >> n != 0 {
+            return Ok(SigSpec::constant(&[S0]));
+        }
+        let v: Vec<State> = (0..n)
+            .map(|i| {
+                if i < 64 && (value >> i) & 1 == 1 {
+                    S1
+                } else {
+                    S0
+                }
+            })
+            .collect();
+        let s = self.cell(
+            "$eq",
+            1,
+            &[
+                ("\\A_SIGNED", 0),
+                ("\\B_SIGNED", 0),
+                ("\\A_WIDTH", n),
+                ("\\B_WIDTH", n),
+                ("\\Y_WIDTH", 1),
+            ],
+            vec![("\\A", addr.clone()), ("\\B", SigSpec::constant(&v))],
+        );
+        self.decoders.insert(key, s.clone());
+        Ok(s)
+    }
+
+    fn and(&mut self, a: &SigBit, b: &SigBit) -> Result<SigBit> {
+        Ok(match (a, b) {
+            (SigBit::Const(S0), _) | (_, SigBit::Const(S0)) => SigBit::Const(S0),
+            (SigBit::Const(S1), x) | (x, SigBit::Const(S1)) => x.clone(),
+            _ => {
+                let (a, b) = (
+                    self.wt.sigspec(std::slice::from_ref(a)),
+                    self.wt.sigspec(std::slice::from_ref(b)),
+                );
+                let y = self.cell(
+                    "$and",
+                    1,
+                    &[
+                        ("\\A_SIGNED", 0),
+                        ("\\B_SIGNED", 0),
+                        ("\\A_WIDTH", 1),
+                        ("\\B_WIDTH", 1),
+                        ("\\Y_WIDTH", 1),
+                    ],
+                    vec![("\\A", a), ("\\B", b)],
+                );
+                self.bits(&y)?.remove(0)
+            }
+        })
+    }
+
+    // Words of `m` after the writes of the ports selected by `ports`, the
+    // later ports winning, as [`check`] made sure they may.
+    fn write(
+        &mut self,
+        m: &MemoryPorts,
+        words: &[Vec<SigBit>],
+        ports: &[bool],
+    ) -> Result<Vec<Vec<SigBit>>> {
+        let mut r = words.to_vec();
+        for (p, _) in m.wr().iter().zip(ports).filter(|(_, on)| **on) {
+            let (en, data) = (self.bits(p.en())?, self.bits(p.data())?);
+            for (w, word) in r.iter_mut().enumerate() {
+                let sel = self.decode(p.addr(), m.offset() + w as i64)?;
+                let sel = self.bits(&sel)?.remove(0);
+                // a multiplexer for each run of bits sharing an enable
+                let mut i = 0;
+                while i < en.len() {
+                    let mut j = i + 1;
+                    while j < en.len() && en[j] == en[i] {
+                        j += 1;
+                    }
+                    let e = self.and(&sel, &en[i])?;
+                    if e != SigBit::Const(S0) {
+                        let (a, b) = (self.wt.sigspec(&word[i..j]), self.wt.sigspec(&data[i..j]));
+                        let s = self.wt.sigspec(&[e]);
+                        let y = self.cell(
+                            "$mux",
+                            j - i,
+                            &[("\\WIDTH", j - i)],
+                            vec![("\\A", a), ("\\B", b), ("\\S", s)],
+                        );
+                        word.splice(i..j, self.bits(&y)?);
+                    }
+                    i = j;
+                }
+            }
+        }
+        Ok(r)
+    }
+
+    // Word of `words` at `addr`, undefined outside of the memory.
+    fn read(&mut self, m: &MemoryPorts, words: &[Vec<SigBit>], addr: &SigSpec) -> Result<SigSpec> {
+        let mut sel = Vec::new();
+        for w in 0..words.len() {
+            sel.push(self.decode(addr, m.offset() + w as i64)?);
+        }
+        let b: Vec<SigBit> = words.concat();
+        let (width, n) = (*m.width(), words.len());
+        Ok(self.cell(
+            "$pmux",
+            width,
+            &[("\\WIDTH", width), ("\\S_WIDTH", n)],
+            vec![
+                ("\\A", SigSpec::constant(&vec![Sx; width])),
+                ("\\B", self.wt.sigspec(&b)),
+                ("\\S", SigSpec::List(sel.into_iter().rev().collect())),
+            ],
+        ))
+    }
+
+    // Map `m`, whose write ports share `clk`.
+    fn map(&mut self, m: &MemoryPorts, clk: Option<(SigSpec, bool)>) -> Result<()> {
+        let (width, id) = (*m.width(), m.memid());
+
+        // a register per word, a constant if never written
+        let mut words = Vec::new();
+        for (w, init) in m.init().chunks(width.max(1)).enumerate() {
+            let mut wire = Wire::new(
+                format!("$memory{}[{}]", id, w),
+                vec![WireOption::Width(width as i64)],
+            );
+            if m.wr().is_empty() {
+                let q = self.add_wire(wire);
+                let c = Connect::new(q.clone(), SigSpec::constant(init));
+                self.module.connects_mut().push(c);
+                words.push(self.bits(&q)?);
+                continue;
+            }
+            if init.iter().any(|b| matches!(b, S0 | S1)) {
+                wire.attrs_mut()
+                    .insert("\\init".to_string(), Const::from_bits(init));
+            }
+            let q = self.add_wire(wire);
+            words.push(self.bits(&q)?);
+        }
+        let next = self.write(m, &words, &vec![true; m.wr().len()])?;
+        if let Some((c, pol)) = &clk {
+            for (q, d) in words.iter().zip(next.iter()) {
+                let cell = self.module.new_cell("$dff", "memory_map");
+                cell.set_param("\\WIDTH", Const::Int(width as i64));
+                cell.set_param("\\CLK_POLARITY", Const::Int(*pol as i64));
+                cell.set_port("\\CLK", c.clone());
+                cell.set_port("\\D", self.wt.sigspec(d));
+                cell.set_port("\\Q", self.wt.sigspec(q));
+            }
+        }
+
+        for p in m.rd() {
+            let (c, pol) = match p.clk() {
+                Some(c) => c.clone(),
+                None => {
+                    let y = self.read(m, &words, p.addr())?;
+                    let c = Connect::new(p.data().clone(), y);
+                    self.module.connects_mut().push(c);
+                    continue;
+                }
+            };
+            // transparent ports read the words being written
+            let seen = if p.transparency().iter().all(|t| *t) {
+                next.clone()
+            } else {
+                self.write(m, &words, p.transparency())?
+            };
+            let y = self.read(m, &seen, p.addr())?;
+            let active =
+                |s: &SigSpec, v: State| -> Result<bool> { Ok(self.bits(s)? != [SigBit::Const(v)]) };
+            let (en, arst, srst) = (
+                active(p.en(), S1)?,
+                active(p.arst(), S0)?,
+                active(p.srst(), S0)?,
+            );
+            let tp = match (en, arst, srst) {
+                (false, false, false) => "$dff",
+                (true, false, false) => "$dffe",
+                (false, true, false) => "$adff",
+                (true, true, false) => "$adffe",
+                (false, false, true) => "$sdff",
+                (true, false, true) if *p.ce_over_srst() => "$sdffce",
+                (true, false, true) => "$sdffe",
+                _ => unreachable!("read port with both resets"),
+            };
+            let mut wire = Wire::new(new_id("memory_map"), vec![WireOption::Width(width as i64)]);
+            if p.init_value().iter().any(|b| matches!(b, S0 | S1)) {
+                let v = Const::from_bits(p.init_value());
+                wire.attrs_mut().insert("\\init".to_string(), v);
+            }
+            let q = self.add_wire(wire);
+            let cell = self.module.new_cell(tp, "memory_map");
+            cell.set_param("\\WIDTH", Const::Int(width as i64));
+            cell.set_param("\\CLK_POLARITY", Const::Int(pol as i64));
+            cell.set_port("\\CLK", c);
+            cell.set_port("\\D", y);
+            cell.set_port("\\Q", q.clone());
+            if en {
+                cell.set_param("\\EN_POLARITY", Const::Int(1));
+                cell.set_port("\\EN", p.en().clone());
+            }
+            if arst {
+                cell.set_param("\\ARST_POLARITY", Const::Int(1));
+                cell.set_param("\\ARST_VALUE", Const::from_bits(p.arst_value()));
+                cell.set_port("\\ARST", p.arst().clone());
+            }
+            if srst {
+                cell.set_param("\\SRST_POLARITY", Const::Int(1));
+                cell.set_param("\\SRST_VALUE", Const::from_bits(p.srst_value()));
+                cell.set_port("\\SRST", p.srst().clone());
+            }
+            let c = Connect::new(p.data().clone(), q);
+            self.module.connects_mut().push(c);
+        }
+        Ok(())
+    }
+}
+
+// The clock shared by the write ports of `m`, after checking that `m` can be
+// mapped.
+fn check(m: &MemoryPorts, wt: &WireTable) -> Result<Option<(SigSpec, bool)>> {
+    let id = m.memid();
+    let clk = match m.wr().first() {
+        Some(p) => p.clk().clone(),
+        None => None,
+    };
+    for (i, p) in m.wr().iter().enumerate() {
+        let same = match (p.clk(), &clk) {
+            (Some((a, x)), Some((b, y))) => x == y && wt.bits(a)? == wt.bits(b)?,
+            _ => bail!("memory `{}' has asynchronous write ports", id),
+        };
+        if !same {
+            bail!("write ports of memory `{}' have different clocks", id);
+        }
+        // ports are applied in order, so a port can only win over earlier
+        // ones, which is all `$mem_v2` allows
+        if p.priority().iter().skip(i).any(|b| *b) {
+            bail!(
+                "write port {} of memory `{}' has priority over a later port",
+                i,
+                id
+            );
+        }
+    }
+    let active = |s: &SigSpec| -> Result<bool> { Ok(wt.bits(s)? != [SigBit::Const(S0)]) };
+    for p in m.rd() {
+        if active(p.arst())? && active(p.srst())? {
+            bail!("read port of memory `{}' has both resets", id);
+        }
+    }
+    Ok(clk)
+}
+
+/// Replace the memories of `module`, declared or in `$mem` and `$mem_v2`
+/// cells, with a `$dff` per word, starting from the initial contents, and
+/// the logic around them, like yosys' `memory_map`: `$eq` address decoders,
+/// a `$mux` for each run of data bits sharing a write enable, later write
+/// ports winning, and a `$pmux` for each read port, registered for
+/// synchronous ports. Memories never written become constants. The logic
+/// grows with the number of words, so this is meant for small memories.
+/// Write ports must share a single clock, and each may only have priority
+/// over earlier ones. The module is left unchanged if any memory can not be
+/// mapped. Returns the number of memories mapped.
+pub fn memory_map(module: &mut Module) -> Result<usize> {
+    let mems = memory_ports(module)?;
+    let wt = WireTable::new(module);
+    let clocks = mems
+        .iter()
+        .map(|m| check(m, &wt))
+        .collect::<Result<Vec<_>>>()?;
+    let ids: Vec<&str> = mems.iter().map(|m| m.memid().as_str()).collect();
+    module.memories_mut().clear();
+    module.cells_mut().retain(|c| {
+        let port = matches!(
+            c.i1().as_str(),
+            "$memrd" | "$memrd_v2" | "$memwr" | "$memwr_v2" | "$meminit" | "$meminit_v2"
+        );
+        let mem = c.i1() == "$mem" || c.i1() == "$mem_v2";
+        !(mem || port && memid(c).is_some_and(|m| ids.contains(&m)))
+    });
+    let mut g = Mapper {
+        module,
+        wt,
+        decoders: HashMap::new(),
+    };
+    for (m, clk) in mems.iter().zip(clocks) {
+        g.map(m, clk)?;
+    }
+    Ok(mems.len())
+}
@@ -0,0 +1,140 @@
+use crate::syntax::*;
+use anyhow::{bail, Result};
+
+// Name of `id` of the copy `prefix` in the miter.
+fn prefixed(prefix: &str, id: &str) -> String {
+    match id.strip_prefix('\\') {
+        Some(n) => format!("\\{}.{}", prefix, n),
+        None => format!("${}{}", prefix, id),
+    }
+}
+
+fn rename(s: &mut SigSpec, prefix: &str) {
+    match s {
+        SigSpec::Refer((w, _)) => *w = prefixed(prefix, w),
+        SigSpec::List(v) => v.iter_mut().for_each(|s| rename(s, prefix)),
+        _ => (),
+    }
+}
+
+// Add a copy of `m` to `miter` with all names prefixed.
+fn copy(miter: &mut Module, m: &Module, prefix: &str) -> Result<()> {
+    if !m.processes().is_empty() {
+        bail!("module `{}' has processes, run proc first", m.ident());
+    }
+    let mut m = m.clone();
+    m.rewrite_sigs(|s| rename(s, prefix));
+    for mut w in m.wires().iter().cloned() {
+        *w.id_mut() = prefixed(prefix, w.id());
+        *w.port_mut() = 0;
+        *w.input_mut() = false;
+        *w.output_mut() = false;
+        miter.wires_mut().push(w);
+    }
+    for mut mem in m.memories().iter().cloned() {
+        *mem.id_mut() = prefixed(prefix, mem.id());
+        miter.memories_mut().push(mem);
+    }
+    for mut c in m.cells().iter().cloned() {
+        *c.i2_mut() = prefixed(prefix, c.i2());
+        if let Some(Const::Str(id)) = c.param("\\MEMID") {
+            let id = prefixed(prefix, id);
+            c.set_param("\\MEMID", Const::Str(id));
+        }
+        miter.cells_mut().push(c);
+    }
+    miter.connects_mut().extend(m.connects().iter().cloned());
+    Ok(())
+}
+
+/// Miter of the modules `gold` and `gate`, which must have the same ports:
+/// a module `ident` with the inputs of both, feeding copies of both with
+/// names prefixed by `gold.` and `gate.`, and a single output `trigger`
+/// set when any of their outputs differ.
+///
+/// The comparison is built of gate cells, so the miter of gate-level
+/// modules is gate-level as well.
+pub fn miter(gold: &Module, gate: &Module, ident: &str) -> Result<Module> {
+    let ports = |m: &Module| {
+        let mut v: Vec<Wire> = m
+            .wires()
+            .iter()
+            .filter(|w| *w.port() > 0)
+            .cloned()
+            .collect();
+        v.sort_by(|a, b| a.id().cmp(b.id()));
+        v
+    };
+    let (x, y) = (ports(gold), ports(gate));
+    let same = |a: &Wire, b: &Wire| {
+        a.id() == b.id()
+            && a.width() == b.width()
+            && a.input() == b.input()
+            && a.output() == b.output()
+    };
+    if x.len() != y.len() || x.iter().zip(y.iter()).any(|(a, b)| !same(a, b)) {
+        bail!(
+            "modules `{}' and `{}' have different ports",
+            gold.ident(),
+            gate.ident()
+        );
+    }
+
+    let mut m = Module::new(ident.to_string(), vec![]);
+    copy(&mut m, gold, "gold")?;
+    copy(&mut m, gate, "gate")?;
+    let mut port = 0;
+    let mut diffs = Vec::new();
+    for w in x.iter() {
+        let width = *w.width();
+        let (a, b) = (
+            SigSpec::wire(&prefixed("gold", w.id())),
+            SigSpec::wire(&prefixed("gate", w.id())),
+        );
+        if *w.input() {
+            port += 1;
+            let mut i = w.clone();
+            *i.port_mut() = port;
+            m.wires_mut().push(i);
+            m.connects_mut()
+                .push(Connect::new(a, SigSpec::wire(w.id())));
+            m.connects_mut()
+                .push(Connect::new(b, SigSpec::wire(w.id())));
+            continue;
+        }
+        // compare the outputs through plain wires, to index them from 0
+        let (p, q) = (m.new_wire("miter", width), m.new_wire("miter", width));
+        m.connects_mut().push(Connect::new(p.clone(), a));
+        m.connects_mut().push(Connect::new(q.clone(), b));
+        for i in 0..width {
+            let bit = |s: &SigSpec| match s {
+                SigSpec::Refer((w, _)) => SigSpec::Refer((w.clone(), Some((i, i)))),
+                s => s.clone(),
+            };
+            let d = m.new_wire("miter", 1);
+            let c = m.new_cell("$_XOR_", "miter");
+            c.set_port("\\A", bit(&p));
+            c.set_port("\\B", bit(&q));
+            c.set_port("\\Y", d.clone());
+            diffs.push(d);
+        }
+    }
+
+    let trigger = Wire::new("\\trigger".to_string(), vec![WireOption::Output(port + 1)]);
+    m.wires_mut().push(trigger);
+    let mut any = match diffs.pop() {
+        Some(d) => d,
+        None => SigSpec::constant(&[State::S0]),
+    };
+    while let Some(d) = diffs.pop() {
+        let y = m.new_wire("miter", 1);
+        let c = m.new_cell("$_OR_", "miter");
+        c.set_port("\\A", d);
+        c.set_port("\\B", any);
+        c.set_port("\\Y", y.clone());
+        any = y;
+    }
+    m.connects_mut()
+        .push(Connect::new(SigSpec::wire("\\trigger"), any));
+    Ok(m)
+}
@@ -0,0 +1,205 @@
+use crate::celltypes::CellTypes;
+use crate::syntax::*;
+use anyhow::Result;
+use getset::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct CleanStats {
+    cells: usize,
+    wires: usize,
+    connects: usize,
+}
+
+fn refs(s: &SigSpec, out: &mut HashSet<String>) {
+    match s {
+        SigSpec::Refer((n, _)) => {
+            out.insert(n.clone());
+        }
+        SigSpec::List(v) => v.iter().for_each(|m| refs(m, out)),
+        SigSpec::Const(_) => (),
+    }
+}
+
+fn is_kept(attrs: &HashMap<String, Const>) -> bool {
+    matches!(attrs.get("\\keep"), Some(v) if v.as_bool())
+}
+
+// Cells that may be removed when nothing reads their outputs: internal cells
+// with outputs, private names and no `keep` attribute.
+fn removable(c: &Cell, ct: &CellTypes) -> bool {
+    c.i2().starts_with('$')
+        && !is_kept(c.attrs())
+        && matches!(ct.get(c.i1()), Some(t) if !t.outputs().is_empty())
+}
+
+fn process_sigs(module: &mut Module) -> Vec<SigSpec> {
+    let mut sigs = Vec::new();
+    for p in module.processes_mut().iter_mut() {
+        p.rewrite_sigs(&mut |s: &mut SigSpec| sigs.push(s.clone()));
+    }
+    sigs
+}
+
+// Remove cells none of whose outputs reach a port, a public or `keep` wire,
+// a process or a cell that is kept.
+fn clean_cells(module: &mut Module, ct: &CellTypes, sm: &SigMap, wt: &WireTable) -> Result<usize> {
+    let mut used: Vec<SigBit> = Vec::new();
+    for w in module.wires() {
+        if *w.output() || w.id().starts_with('\\') || is_kept(w.attrs()) {
+            used.extend(sm.map(&wt.bits(&SigSpec::wire(w.id()))?));
+        }
+    }
+    for s in process_sigs(module) {
+        used.extend(sm.map(&wt.bits(&s)?));
+    }
+
+    let mut drivers: HashMap<SigBit, Vec<usize>> = HashMap::new();
+    let mut live = vec![false; module.cells().len()];
+    for (i, c) in module.cells().iter().enumerate() {
+        if !removable(c, ct) {
+            live[i] = true;
+            for s in c.connects().values() {
+                used.extend(sm.map(&wt.bits(s)?));
+            }
+            continue;
+        }
+        for (k, s) in c.connects() {
+            if ct.is_output(c.i1(), k) {
+                for b in sm.map(&wt.bits(s)?) {
+                    drivers.entry(b).or_default().push(i);
+                }
+            }
+        }
+    }
+
+    let mut seen: HashSet<SigBit> = HashSet::new();
+    while let Some(b) = used.pop() {
+        if b.is_const() || !seen.insert(b.clone()) {
+            continue;
+        }
+        for i in drivers.get(&b).cloned().unwrap_or_default() {
+            if live[i] {
+                continue;
+            }
+            live[i] = true;
+            let c = &module.cells()[i];
+            for (k, s) in c.connects() {
+                if ct.is_input(c.i1(), k) {
+                    used.extend(sm.map(&wt.bits(s)?));
+                }
+            }
+        }
+    }
+
+    let before = module.cells().len();
+    let mut i = 0;
+    module.cells_mut().retain(|_| {
+        i += 1;
+        live[i - 1]
+    });
+    Ok(before - module.cells().len())
+}
+
+// Point cell connections at the representatives of their bits. Outputs are
+// never tied to constants.
+fn rewrite_cells(module: &mut Module, ct: &CellTypes, sm: &SigMap, wt: &WireTable) -> Result<()> {
+    for c in module.cells_mut().iter_mut() {
+        let tp = c.i1().clone();
+        for (k, s) in c.connects_mut().iter_mut() {
+            let input = ct.is_input(&tp, k) && !ct.is_output(&tp, k);
+            let bits: Vec<SigBit> = wt
+                .bits(s)?
+                .into_iter()
+                .map(|b| match sm.map_bit(&b) {
+                    r if r.is_const() && !input => b,
+                    r => r,
+                })
+                .collect();
+            *s = wt.sigspec(&bits);
+        }
+    }
+    Ok(())
+}
+
+/// Remove internal cells whose outputs are never observed, drop private
+/// wires nothing refers to and merge wires aliased by `connect` statements
+/// (`opt_clean`). Cell connections are rewritten to one representative per
+/// group of connected bits, preferring constants, ports and public wires,
+/// and only the `connect`s needed by the remaining wires are kept. Anything
+/// with a public name or a `keep` attribute is preserved.
+pub fn opt_clean(module: &mut Module) -> Result<CleanStats> {
+    let ct = CellTypes::internals();
+    let wt = WireTable::new(module);
+    let mut sm = SigMap::new(module, &wt)?;
+    for out in [true, false].iter() {
+        for w in module.wires() {
+            if (*w.output() && *out) || (*w.input() && !*out) {
+                for b in wt.bits(&SigSpec::wire(w.id()))? {
+                    sm.promote(&b);
+                }
+            }
+        }
+    }
+
+    let cells = clean_cells(module, &ct, &sm, &wt)?;
+    rewrite_cells(module, &ct, &sm, &wt)?;
+
+    let mut used: HashSet<String> = HashSet::new();
+    for c in module.cells() {
+        for s in c.connects().values() {
+            refs(s, &mut used);
+        }
+    }
+    for s in process_sigs(module) {
+        refs(&s, &mut used);
+    }
+    for w in module.wires() {
+        if *w.input() || *w.output() || w.id().starts_with('\\') || is_kept(w.attrs()) {
+            used.insert(w.id().clone());
+        }
+    }
+
+    // re-create the connections of the remaining wires to their
+    // representatives, one per run of aliased bits
+    let mut connects = Vec::new();
+    for w in module.wires() {
+        if !used.contains(w.id()) {
+            continue;
+        }
+        let bits = wt.bits(&SigSpec::wire(w.id()))?;
+        let end = SigBit::Const(State::S0);
+        let mut run: (Vec<SigBit>, Vec<SigBit>) = (Vec::new(), Vec::new());
+        for b in bits.iter().chain(std::iter::once(&end)) {
+            let r = sm.map_bit(b);
+            if !b.is_const() && r != *b {
+                run.0.push(b.clone());
+                run.1.push(r);
+            } else if !run.0.is_empty() {
+                let (l, r) = std::mem::take(&mut run);
+                connects.push((l, r));
+            }
+        }
+    }
+    for (_, r) in connects.iter() {
+        for b in r {
+            if let Some(w) = b.wire() {
+                used.insert(w.to_string());
+            }
+        }
+    }
+    let before = module.connects().len();
+    *module.connects_mut() = connects
+        .into_iter()
+        .map(|(l, r)| Connect::new(wt.sigspec(&l), wt.sigspec(&r)))
+        .collect();
+
+    let wires = module.wires().len();
+    module.wires_mut().retain(|w| used.contains(w.id()));
+    Ok(CleanStats {
+        cells,
+        wires: wires - module.wires().len(),
+        connects: before.saturating_sub(module.connects().len()),
+    })
+}
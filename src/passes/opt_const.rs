@@ -0,0 +1,274 @@
+use crate::celltypes::CellTypes;
+use crate::eval::{eval, is_evaluable};
+use crate::syntax::*;
+use anyhow::Result;
+
+use State::{S0, S1};
+
+fn ext(v: &[SigBit], width: usize, signed: bool) -> Vec<SigBit> {
+    let mut r = v.to_vec();
+    let pad = match r.last() {
+        Some(b) if signed => b.clone(),
+        _ => SigBit::Const(S0),
+    };
+    r.resize(width, pad);
+    r
+}
+
+fn is_all(v: &[SigBit], s: State) -> bool {
+    v.iter().all(|b| *b == SigBit::Const(s))
+}
+
+fn consts(v: &[SigBit]) -> Option<Vec<State>> {
+    v.iter().map(|b| b.state()).collect()
+}
+
+// New values of a cell's output ports.
+type Values = Vec<(String, Vec<SigBit>)>;
+
+struct Folder<'a> {
+    cell: &'a Cell,
+    sm: &'a SigMap,
+    wt: &'a WireTable,
+}
+
+impl<'a> Folder<'a> {
+    fn port(&self, k: &str) -> Result<Option<Vec<SigBit>>> {
+        Ok(match self.cell.port(k) {
+            Some(s) => Some(self.sm.map(&self.wt.bits(s)?)),
+            None => None,
+        })
+    }
+
+    fn int(&self, k: &str) -> Option<usize> {
+        self.cell
+            .param(k)
+            .and_then(|v| v.as_int())
+            .map(|v| v.max(0) as usize)
+    }
+
+    fn flag(&self, k: &str) -> bool {
+        matches!(self.cell.param(k), Some(v) if v.as_bool())
+    }
+
+    // Output values of a cell whose inputs are all constant.
+    fn constant(&self, ct: &CellTypes) -> Result<Option<Values>> {
+        let t = match ct.get(self.cell.i1()) {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        for p in t.inputs() {
+            if let Some(v) = self.port(p)? {
+                if consts(&v).is_none() {
+                    return Ok(None);
+                }
+            }
+        }
+        let input = |k: &str| self.port(k).ok().flatten().and_then(|v| consts(&v));
+        let outputs = match eval(self.cell, input) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+        Ok(Some(
+            outputs
+                .into_iter()
+                .map(|(k, v)| (k, v.into_iter().map(SigBit::Const).collect()))
+                .collect(),
+        ))
+    }
+
+    // Value of `\Y` for cells with an operand that makes the result trivial.
+    fn identity(&self) -> Result<Option<Vec<SigBit>>> {
+        let tp = self.cell.i1().as_str();
+        let c = |s: State| vec![SigBit::Const(s)];
+        if tp.starts_with("$_") {
+            let (a, b, s) = (self.port("\\A")?, self.port("\\B")?, self.port("\\S")?);
+            let one = |v: &Option<Vec<SigBit>>, s: State| v.as_deref() == Some(&c(s)[..]);
+            let (a0, a1, b0, b1) = (one(&a, S0), one(&a, S1), one(&b, S0), one(&b, S1));
+            return Ok(match tp {
+                "$_AND_" if a0 || b0 => Some(c(S0)),
+                "$_AND_" if a1 => b,
+                "$_AND_" if b1 => a,
+                "$_NAND_" if a0 || b0 => Some(c(S1)),
+                "$_OR_" if a1 || b1 => Some(c(S1)),
+                "$_OR_" if a0 => b,
+                "$_OR_" if b0 => a,
+                "$_NOR_" if a1 || b1 => Some(c(S0)),
+                "$_XOR_" if a0 => b,
+                "$_XOR_" if b0 => a,
+                "$_XNOR_" if a1 => b,
+                "$_XNOR_" if b1 => a,
+                "$_ANDNOT_" if a0 || b1 => Some(c(S0)),
+                "$_ANDNOT_" if b0 => a,
+                "$_ORNOT_" if a1 || b0 => Some(c(S1)),
+                "$_ORNOT_" if b1 => a,
+                "$_MUX_" if a.is_some() && a == b => a,
+                "$_MUX_" if one(&s, S0) => a,
+                "$_MUX_" if one(&s, S1) => b,
+                _ => None,
+            });
+        }
+
+        let w = match self.int("\\Y_WIDTH").or_else(|| self.int("\\WIDTH")) {
+            Some(w) => w,
+            None => return Ok(None),
+        };
+        let (a, b) = match (self.port("\\A")?, self.port("\\B")?) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(None),
+        };
+        let (sa, sb) = (self.flag("\\A_SIGNED"), self.flag("\\B_SIGNED"));
+        let zero = || vec![SigBit::Const(S0); w];
+        let one = || vec![SigBit::Const(S1); w];
+        if tp == "$mux" {
+            let s = self.port("\\S")?;
+            return Ok(match s.as_deref() {
+                _ if a == b => Some(a),
+                Some([SigBit::Const(S0)]) => Some(a),
+                Some([SigBit::Const(S1)]) => Some(b),
+                _ => None,
+            });
+        }
+
+        // like eval, operands other than a shifted value are only sign
+        // extended when both are signed
+        let s = sa && sb;
+        let (ea, eb) = (ext(&a, w, s), ext(&b, w, s));
+        Ok(match tp {
+            "$and" if is_all(&ea, S0) || is_all(&eb, S0) => Some(zero()),
+            "$and" if is_all(&ea, S1) => Some(eb),
+            "$and" if is_all(&eb, S1) => Some(ea),
+            "$or" if is_all(&ea, S1) || is_all(&eb, S1) => Some(one()),
+            "$or" | "$xor" if is_all(&ea, S0) => Some(eb),
+            "$or" | "$xor" if is_all(&eb, S0) => Some(ea),
+            "$xnor" if is_all(&ea, S1) => Some(eb),
+            "$xnor" if is_all(&eb, S1) => Some(ea),
+            "$mul" if is_all(&a, S0) || is_all(&b, S0) => Some(zero()),
+            "$add" if is_all(&a, S0) => Some(eb),
+            "$add" | "$sub" if is_all(&b, S0) => Some(ea),
+            "$shl" | "$shr" | "$sshl" | "$sshr" if is_all(&b, S0) => Some(ext(&a, w, sa)),
+            _ => None,
+        })
+    }
+}
+
+// Drop `$pmux` cases whose select bit is constant zero, and reduce it to its
+// default or single remaining case when possible. Returns the new value of
+// `\Y` if the cell is no longer needed.
+fn shrink_pmux(
+    cell: &mut Cell,
+    sm: &SigMap,
+    wt: &WireTable,
+) -> Result<(bool, Option<Vec<SigBit>>)> {
+    let w = match cell.param("\\WIDTH").and_then(|v| v.as_int()) {
+        Some(w) => w as usize,
+        None => return Ok((false, None)),
+    };
+    let (a, b, s) = match (cell.port("\\A"), cell.port("\\B"), cell.port("\\S")) {
+        (Some(a), Some(b), Some(s)) => (wt.bits(a)?, wt.bits(b)?, wt.bits(s)?),
+        _ => return Ok((false, None)),
+    };
+    let ms = sm.map(&s);
+    if b.len() != w * s.len() {
+        return Ok((false, None));
+    }
+    // with a constant one-hot select the result is the selected case
+    if ms.iter().filter(|x| **x == SigBit::Const(S1)).count() == 1
+        && ms.iter().all(|x| x.is_const())
+    {
+        let i = ms.iter().position(|x| *x == SigBit::Const(S1)).unwrap();
+        return Ok((false, Some(sm.map(&b[i * w..(i + 1) * w]))));
+    }
+    let keep: Vec<usize> = (0..s.len())
+        .filter(|i| ms[*i] != SigBit::Const(S0))
+        .collect();
+    if keep.is_empty() {
+        return Ok((false, Some(sm.map(&a))));
+    }
+    if keep.len() == s.len() {
+        return Ok((false, None));
+    }
+    let nb: Vec<SigBit> = keep
+        .iter()
+        .flat_map(|i| b[i * w..(i + 1) * w].iter().cloned())
+        .collect();
+    let ns: Vec<SigBit> = keep.iter().map(|i| s[*i].clone()).collect();
+    cell.set_port("\\B", wt.sigspec(&nb));
+    cell.set_port("\\S", wt.sigspec(&ns));
+    cell.set_param("\\S_WIDTH", Const::Int(keep.len() as i64));
+    Ok((true, None))
+}
+
+fn has_keep(c: &Cell) -> bool {
+    matches!(c.attrs().get("\\keep"), Some(v) if v.as_bool())
+}
+
+/// Fold combinational internal cells whose inputs are all constant into
+/// `connect` statements, and bypass cells with operands that make the result
+/// trivial (`x & 0`, `x | 0`, a mux with a constant select, ...), like yosys'
+/// `opt_expr`. Constant results follow the `x`/`z` rules of [`eval`]. The
+/// identities assume operands never carry `z`, and like `opt_expr` they may
+/// give defined bits where [`eval`] gives `x` (`x * 0` becomes 0, `x + 0`
+/// keeps the defined bits of `x`). Cells with a `keep` attribute are left
+/// alone. Returns the number of cells removed or simplified.
+pub fn opt_const(module: &mut Module) -> Result<usize> {
+    let ct = CellTypes::internals();
+    let wt = WireTable::new(module);
+    let mut sm = SigMap::new(module, &wt)?;
+    let mut count = 0;
+    loop {
+        let mut changed = false;
+        let mut i = 0;
+        while i < module.cells().len() {
+            let cell = &module.cells()[i];
+            let tp = cell.i1().clone();
+            if !is_evaluable(&tp) || tp == "$equiv" || has_keep(cell) {
+                i += 1;
+                continue;
+            }
+            let f = Folder {
+                cell,
+                sm: &sm,
+                wt: &wt,
+            };
+            let mut values = match f.constant(&ct)? {
+                Some(v) => v,
+                None => match f.identity()? {
+                    Some(v) => vec![("\\Y".to_string(), v)],
+                    None => vec![],
+                },
+            };
+            if values.is_empty() && tp == "$pmux" {
+                let (shrunk, y) = shrink_pmux(&mut module.cells_mut()[i], &sm, &wt)?;
+                if shrunk {
+                    count += 1;
+                    changed = true;
+                }
+                if let Some(y) = y {
+                    values.push(("\\Y".to_string(), y));
+                }
+            }
+            if values.is_empty() {
+                i += 1;
+                continue;
+            }
+            let cell = module.cells_mut().remove(i);
+            for (k, v) in values {
+                if let Some(y) = cell.port(&k) {
+                    let y_bits = wt.bits(y)?;
+                    let v = ext(&v, y_bits.len(), false);
+                    sm.add(&y_bits, &v);
+                    module
+                        .connects_mut()
+                        .push(Connect::new(y.clone(), wt.sigspec(&v)));
+                }
+            }
+            count += 1;
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+    Ok(count)
+}
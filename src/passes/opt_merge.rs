@@ -0,0 +1,199 @@
+use crate::celltypes::CellTypes;
+use crate::syntax::*;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Cells with the same key compute the same outputs.
+#[derive(PartialEq, Eq, Hash)]
+struct CellKey {
+    tp: String,
+    params: Vec<(String, Vec<State>)>,
+    inputs: Vec<(String, Vec<SigBit>)>,
+    outputs: Vec<(String, usize)>,
+}
+
+fn commutative(tp: &str) -> bool {
+    matches!(
+        tp,
+        "$and"
+            | "$or"
+            | "$xor"
+            | "$xnor"
+            | "$add"
+            | "$mul"
+            | "$eq"
+            | "$ne"
+            | "$eqx"
+            | "$nex"
+            | "$logic_and"
+            | "$logic_or"
+            | "$_AND_"
+            | "$_NAND_"
+            | "$_OR_"
+            | "$_NOR_"
+            | "$_XOR_"
+            | "$_XNOR_"
+    )
+}
+
+fn mergeable(c: &Cell, ct: &CellTypes) -> bool {
+    let tp = c.i1();
+    !tp.starts_with("$mem")
+        && !tp.starts_with("$any")
+        && !tp.starts_with("$all")
+        && !matches!(c.attrs().get("\\keep"), Some(v) if v.as_bool())
+        && matches!(ct.get(tp), Some(t) if !t.outputs().is_empty())
+}
+
+fn cell_key(c: &Cell, ct: &CellTypes, sm: &SigMap, wt: &WireTable) -> Result<CellKey> {
+    let tp = c.i1().clone();
+    let mut params: Vec<(String, Vec<State>)> = c
+        .params()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.val().to_bits()))
+        .collect();
+    params.sort();
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    for (k, s) in c.connects() {
+        if ct.is_output(&tp, k) {
+            outputs.push((k.clone(), wt.width_of(s)? as usize));
+        } else {
+            inputs.push((k.clone(), sm.map(&wt.bits(s)?)));
+        }
+    }
+    inputs.sort();
+    outputs.sort();
+
+    if tp.starts_with("$reduce_") || tp == "$logic_not" {
+        for (_, v) in inputs.iter_mut() {
+            if tp != "$reduce_xor" && tp != "$reduce_xnor" {
+                v.sort();
+                v.dedup();
+            }
+        }
+    }
+    let flag = |k: &str| matches!(c.param(k), Some(v) if v.as_bool());
+    let width = |k: &str| c.param(k).and_then(|v| v.as_int());
+    if commutative(&tp)
+        && flag("\\A_SIGNED") == flag("\\B_SIGNED")
+        && width("\\A_WIDTH") == width("\\B_WIDTH")
+    {
+        let a = inputs.iter().position(|(k, _)| k == "\\A");
+        let b = inputs.iter().position(|(k, _)| k == "\\B");
+        if let (Some(a), Some(b)) = (a, b) {
+            if inputs[b].1 < inputs[a].1 {
+                let t = inputs[a].1.clone();
+                inputs[a].1 = inputs[b].1.clone();
+                inputs[b].1 = t;
+            }
+        }
+    }
+    Ok(CellKey {
+        tp,
+        params,
+        inputs,
+        outputs,
+    })
+}
+
+// Whether `a` should survive rather than `b`: public names first, then the
+// smallest name.
+fn preferred(a: &Cell, b: &Cell) -> bool {
+    (!a.i2().starts_with('\\'), a.i2()) < (!b.i2().starts_with('\\'), b.i2())
+}
+
+/// Merge internal cells of the same type with equal parameters and inputs
+/// (`opt_merge`). Inputs are compared after resolving `connect` aliases, and
+/// the operands of commutative cells in either order. Of each group of
+/// identical cells, the one with a public name, or else the smallest name,
+/// is kept and the outputs of the others are connected to its outputs.
+/// Flip-flops whose outputs carry an `init` attribute are never merged.
+/// Repeats until no more cells can be merged and returns how many were
+/// removed.
+pub fn opt_merge(module: &mut Module) -> Result<usize> {
+    let ct = CellTypes::internals();
+    let wt = WireTable::new(module);
+    let mut sm = SigMap::new(module, &wt)?;
+    let mut init: HashSet<SigBit> = HashSet::new();
+    for w in module.wires() {
+        if w.attrs().contains_key("\\init") {
+            init.extend(sm.map(&wt.bits(&SigSpec::wire(w.id()))?));
+        }
+    }
+
+    let mut count = 0;
+    loop {
+        let mut seen: HashMap<CellKey, usize> = HashMap::new();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (i, c) in module.cells().iter().enumerate() {
+            if !mergeable(c, &ct) {
+                continue;
+            }
+            if matches!(ct.get(c.i1()), Some(t) if !*t.comb()) {
+                let mut has_init = false;
+                for (k, s) in c.connects() {
+                    if ct.is_output(c.i1(), k) {
+                        has_init |= sm.map(&wt.bits(s)?).iter().any(|b| init.contains(b));
+                    }
+                }
+                if has_init {
+                    continue;
+                }
+            }
+            let key = cell_key(c, &ct, &sm, &wt)?;
+            match seen.get_mut(&key) {
+                None => {
+                    seen.insert(key, i);
+                }
+                Some(j) => {
+                    if preferred(c, &module.cells()[*j]) {
+                        merged.push((*j, i));
+                        *j = i;
+                    } else {
+                        merged.push((i, *j));
+                    }
+                }
+            }
+        }
+        if merged.is_empty() {
+            break;
+        }
+
+        // a group may have changed its survivor, follow to the final one
+        let mut target: BTreeMap<usize, usize> = merged.into_iter().collect();
+        let resolve = |mut i: usize, t: &BTreeMap<usize, usize>| {
+            while let Some(j) = t.get(&i) {
+                i = *j;
+            }
+            i
+        };
+        let snapshot = target.clone();
+        for v in target.values_mut() {
+            *v = resolve(*v, &snapshot);
+        }
+
+        for (dup, keep) in target.iter() {
+            let (d, k) = (&module.cells()[*dup], &module.cells()[*keep]);
+            let mut ports: Vec<&String> = d.connects().keys().collect();
+            ports.sort();
+            let mut conns = Vec::new();
+            for p in ports {
+                if let (true, Some(t)) = (ct.is_output(d.i1(), p), k.port(p)) {
+                    conns.push((d.connects()[p].clone(), t.clone()));
+                }
+            }
+            for (s, t) in conns {
+                sm.add(&wt.bits(&s)?, &wt.bits(&t)?);
+                module.connects_mut().push(Connect::new(s, t));
+            }
+        }
+        let mut i = 0;
+        module.cells_mut().retain(|_| {
+            i += 1;
+            !target.contains_key(&(i - 1))
+        });
+        count += target.len();
+    }
+    Ok(count)
+}
@@ -0,0 +1,269 @@
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Rebuilds the body of a specialised module from its parameter values.
+///
+/// `module` is a copy of the base module, still holding the default values,
+/// and `params` the resolved values it is specialised with.
+pub trait Elaborate {
+    fn elaborate(&mut self, module: &mut Module, params: &HashMap<String, Const>) -> Result<()>;
+}
+
+impl<F> Elaborate for F
+where
+    F: FnMut(&mut Module, &HashMap<String, Const>) -> Result<()>,
+{
+    fn elaborate(&mut self, module: &mut Module, params: &HashMap<String, Const>) -> Result<()> {
+        self(module, params)
+    }
+}
+
+/// Default elaborator: replaces every SigSpec that refers to a parameter
+/// (instead of a wire) by the value of that parameter.
+///
+/// RTLIL only keeps the body elaborated with the default values, and yosys
+/// writes parameter values rather than references into it, so nothing is
+/// re-elaborated: a value that differs from the default is an error unless
+/// it only replaces references of the same width. Anything else needs a
+/// custom [`Elaborate`].
+#[derive(Debug, Default)]
+pub struct Substitute;
+
+impl Elaborate for Substitute {
+    fn elaborate(&mut self, module: &mut Module, params: &HashMap<String, Const>) -> Result<()> {
+        let wires: HashSet<String> = module.wires().iter().map(|w| w.id().clone()).collect();
+        let mut used = HashSet::new();
+        module.rewrite_sigs(|s| substitute(s, &wires, params, &mut used));
+        for (n, old) in module.params() {
+            let new = match params.get(n) {
+                Some(v) if v.to_bits() != old.to_bits() => v,
+                _ => continue,
+            };
+            if !used.contains(n) {
+                bail!(
+                    "parameter `{}' of module `{}' is not referenced, so its value needs re-elaboration",
+                    n,
+                    module.ident()
+                );
+            }
+            if !matches!(old, Const::Empty) && old.to_bits().len() != new.to_bits().len() {
+                bail!(
+                    "parameter `{}' of module `{}' changes width, which needs re-elaboration",
+                    n,
+                    module.ident()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn substitute(
+    s: &mut SigSpec,
+    wires: &HashSet<String>,
+    params: &HashMap<String, Const>,
+    used: &mut HashSet<String>,
+) {
+    match s {
+        SigSpec::Refer((n, range)) if !wires.contains(n) => {
+            if let Some(v) = params.get(n) {
+                used.insert(n.clone());
+                *s = SigSpec::Const((v.clone(), *range));
+            }
+        }
+        SigSpec::List(v) => {
+            for m in v.iter_mut() {
+                substitute(m, wires, params, used);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn param_value(c: &Const) -> String {
+    match c {
+        Const::Sig(s)
+            if *s.width() == 32 && s.bits().iter().all(|b| *b == State::S0 || *b == State::S1) =>
+        {
+            // yosys prints 32-bit values as signed integers
+            let mut v: i32 = 0;
+            for b in s.bits() {
+                v = (v << 1) | (*b == State::S1) as i32;
+            }
+            format!("{}", v)
+        }
+        _ => format!("{}", c),
+    }
+}
+
+/// Name of `module` specialised with `params`, as yosys' `derive` spells it.
+/// `params` are the overridden parameters in declaration order.
+pub fn paramod_name(module: &str, params: &[(String, Const)]) -> String {
+    let stripped = module.strip_prefix("$abstract").unwrap_or(module);
+    let mut info = String::new();
+    for (k, v) in params {
+        info.push_str(&format!("{}={}", k, param_value(v)));
+    }
+    if info.len() > 60 {
+        format!("$paramod${}{}", sha1_hex(info.as_bytes()), stripped)
+    } else {
+        format!("$paramod{}{}", stripped, info)
+    }
+}
+
+/// Specialise `module` with `params` using the default [`Substitute`] elaborator.
+pub fn derive(
+    design: &mut Design,
+    module: &str,
+    params: &HashMap<String, Const>,
+) -> Result<String> {
+    derive_with(design, module, params, &mut Substitute)
+}
+
+/// Specialise `module` with `params`, returning the name of the derived
+/// module. The derived module is only created once per set of values.
+pub fn derive_with<E: Elaborate>(
+    design: &mut Design,
+    module: &str,
+    params: &HashMap<String, Const>,
+    e: &mut E,
+) -> Result<String> {
+    let base = design
+        .module(module)
+        .ok_or_else(|| anyhow!("module `{}' not found", module))?;
+    if params.is_empty() {
+        return Ok(module.to_string());
+    }
+    if base.params().is_empty() {
+        bail!(
+            "module `{}' is used with parameters but is not parametric",
+            module
+        );
+    }
+    for k in params.keys() {
+        if base.param(k).is_none() {
+            bail!("module `{}' has no parameter `{}'", module, k);
+        }
+    }
+
+    let ordered: Vec<(String, Const)> = base
+        .params()
+        .iter()
+        .filter_map(|(k, _)| params.get(k).map(|v| (k.clone(), v.clone())))
+        .collect();
+    let name = paramod_name(module, &ordered);
+    if design.module(&name).is_some() {
+        return Ok(name);
+    }
+
+    let mut m = base.clone();
+    *m.ident_mut() = name.clone();
+    let mut resolved: HashMap<String, Const> = m.params().iter().cloned().collect();
+    for (k, v) in params {
+        resolved.insert(k.clone(), v.clone());
+    }
+    e.elaborate(&mut m, &resolved)?;
+    for (k, v) in ordered {
+        m.set_param(&k, v);
+    }
+    design.modules_mut().push(m);
+    Ok(name)
+}
+
+/// Specialise every module instantiated with parameter overrides and retarget
+/// the instantiating cells, like `hierarchy` does. Returns the number of cells
+/// retargeted.
+pub fn derive_all(design: &mut Design) -> Result<usize> {
+    derive_all_with(design, &mut Substitute)
+}
+
+/// [`derive_all`] with a custom elaborator. Each round specialises one more
+/// level of the hierarchy, so a module instantiating itself with ever new
+/// parameters is an error once 64 levels deep.
+pub fn derive_all_with<E: Elaborate>(design: &mut Design, e: &mut E) -> Result<usize> {
+    let mut count = 0;
+    for depth in 0.. {
+        let mut todo = Vec::new();
+        for (mi, m) in design.modules().iter().enumerate() {
+            for (ci, c) in m.cells().iter().enumerate() {
+                if c.params().is_empty() || design.module(c.i1()).is_none() {
+                    continue;
+                }
+                let params: HashMap<String, Const> = c
+                    .params()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.val().clone()))
+                    .collect();
+                todo.push((mi, ci, c.i1().clone(), params));
+            }
+        }
+        if todo.is_empty() {
+            break;
+        }
+        if depth == 64 {
+            bail!(
+                "parametric instances of module `{}' are nested 64 levels deep, it instantiates itself",
+                todo[0].2
+            );
+        }
+        for (mi, ci, tp, params) in todo {
+            let name = derive_with(design, &tp, &params, e)?;
+            let c = &mut design.modules_mut()[mi].cells_mut()[ci];
+            *c.i1_mut() = name;
+            c.params_mut().clear();
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in msg.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                chunk[4 * i],
+                chunk[4 * i + 1],
+                chunk[4 * i + 2],
+                chunk[4 * i + 3],
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e].iter()) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+
+    h.iter().map(|x| format!("{:08x}", x)).collect()
+}
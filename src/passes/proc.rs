@@ -0,0 +1,942 @@
+use super::rmdead::proc_rmdead;
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::mem;
+
+pub(crate) fn take_root(p: &mut Process) -> ProcessSwitchCase {
+    let mut c = ProcessSwitchCase::default();
+    *c.assign_mut() = mem::take(p.assign_mut());
+    *c.switch_mut() = mem::take(p.switch_mut());
+    c
+}
+
+pub(crate) fn put_root(p: &mut Process, mut c: ProcessSwitchCase) {
+    *p.assign_mut() = mem::take(c.assign_mut());
+    *p.switch_mut() = mem::take(c.switch_mut());
+}
+
+fn bool_const(b: bool) -> Const {
+    Const::from_bits(&[if b { State::S1 } else { State::S0 }])
+}
+
+// Whether a case pattern bit matches any value: `-`, and `x` or `z` like
+// yosys does.
+pub(crate) fn dont_care(p: &SigBit) -> bool {
+    matches!(p, SigBit::Const(s) if *s != State::S0 && *s != State::S1)
+}
+
+// Whether `pat` matches `sig`: Some(true) if it always does, Some(false) if
+// it never does and None if it depends on signal values.
+pub(crate) fn pattern_match(sig: &[SigBit], pat: &[SigBit]) -> Option<bool> {
+    let mut known = true;
+    for (i, s) in sig.iter().enumerate() {
+        let p = pat.get(i).cloned().unwrap_or(SigBit::Const(State::S0));
+        match (s, &p) {
+            (_, p) if dont_care(p) => (),
+            (SigBit::Const(a), SigBit::Const(b))
+                if (*a == State::S0 || *a == State::S1) && (*b == State::S0 || *b == State::S1) =>
+            {
+                if a != b {
+                    return Some(false);
+                }
+            }
+            (a, b) if a == b => (),
+            _ => known = false,
+        }
+    }
+    if known {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn case_is_empty(c: &ProcessSwitchCase) -> bool {
+    c.assign().is_empty() && c.switch().is_empty()
+}
+
+fn clean_case(c: &mut ProcessSwitchCase) -> usize {
+    let mut count = 0;
+    for s in c.switch_mut().iter_mut() {
+        count += clean_switch(s);
+    }
+    let before = c.switch().len();
+    c.switch_mut()
+        .retain(|s| !s.cases().iter().all(case_is_empty));
+    count + before - c.switch().len()
+}
+
+fn clean_switch(s: &mut ProcessSwitch) -> usize {
+    let mut count = 0;
+    for c in s.cases_mut().iter_mut() {
+        count += clean_case(c);
+    }
+    // taking an empty case at the end is the same as taking none
+    while matches!(s.cases().last(), Some(c) if case_is_empty(c)) {
+        s.cases_mut().pop();
+        count += 1;
+    }
+    count
+}
+
+/// Remove empty switches, cases and sync rules, and processes left without
+/// any statement (`proc_clean`). Returns the number of items removed.
+pub fn proc_clean(module: &mut Module) -> usize {
+    let mut count = 0;
+    for p in module.processes_mut().iter_mut() {
+        let mut root = take_root(p);
+        count += clean_case(&mut root);
+        put_root(p, root);
+        let before = p.syncs().len();
+        p.syncs_mut().retain(|s| !s.updates().is_empty());
+        count += before - p.syncs().len();
+    }
+    let before = module.processes().len();
+    module
+        .processes_mut()
+        .retain(|p| !(p.assign().is_empty() && p.switch().is_empty() && p.syncs().is_empty()));
+    count + before - module.processes().len()
+}
+
+/// Turn `sync init` rules into `init` attributes on the updated wires
+/// (`proc_init`).
+pub fn proc_init(module: &mut Module) -> Result<()> {
+    let wt = WireTable::new(module);
+    let mut inits: HashMap<String, Vec<State>> = HashMap::new();
+    for p in module.processes_mut().iter_mut() {
+        for s in p.syncs().iter() {
+            if !matches!(s.tp(), ProcessSyncType::Init) {
+                continue;
+            }
+            for (l, r) in s.updates() {
+                let lhs = wt.bits(l)?;
+                let rhs = wt.bits(r)?;
+                for (lb, rb) in lhs.iter().zip(rhs.iter()) {
+                    let v = match rb.state() {
+                        Some(v) => v,
+                        None => bail!("failed to get constant init value for `{}'", l),
+                    };
+                    if let SigBit::Wire((w, i)) = lb {
+                        let width = wt.width(w).unwrap() as usize;
+                        let bits = inits
+                            .entry(w.clone())
+                            .or_insert_with(|| vec![State::Sx; width]);
+                        bits[*i as usize] = v;
+                    }
+                }
+            }
+        }
+        p.syncs_mut()
+            .retain(|s| !matches!(s.tp(), ProcessSyncType::Init));
+    }
+    for w in module.wires_mut().iter_mut() {
+        if let Some(bits) = inits.remove(w.id()) {
+            let mut old = match w.attrs().get("\\init") {
+                Some(c) => c.to_bits(),
+                None => vec![],
+            };
+            old.resize(bits.len(), State::Sx);
+            let merged: Vec<State> = bits
+                .iter()
+                .zip(old.iter())
+                .map(|(n, o)| if *n == State::Sx { *o } else { *n })
+                .collect();
+            w.attrs_mut()
+                .insert("\\init".to_string(), Const::from_bits(&merged));
+        }
+    }
+    Ok(())
+}
+
+// Simplify the tree under the assumption that `bit` has the value `val`.
+fn assume_case(c: &mut ProcessSwitchCase, bit: &SigBit, val: State, wt: &WireTable) -> Result<()> {
+    for s in c.switch_mut().iter_mut() {
+        let sig: Vec<SigBit> = wt
+            .bits(s.sig())?
+            .into_iter()
+            .map(|b| if b == *bit { SigBit::Const(val) } else { b })
+            .collect();
+        let mut cases = Vec::new();
+        for mut k in mem::take(s.cases_mut()) {
+            let mut taken = k.sigs().is_empty();
+            let mut sigs = Vec::new();
+            for p in k.sigs().iter() {
+                match pattern_match(&sig, &wt.bits(p)?) {
+                    Some(false) => (),
+                    Some(true) => taken = true,
+                    None => sigs.push(p.clone()),
+                }
+            }
+            if !taken && sigs.is_empty() {
+                continue;
+            }
+            if taken {
+                sigs.clear();
+            }
+            *k.sigs_mut() = sigs;
+            assume_case(&mut k, bit, val, wt)?;
+            cases.push(k);
+            if taken {
+                break;
+            }
+        }
+        *s.cases_mut() = cases;
+    }
+    Ok(())
+}
+
+// Value of `bits` after the tree, when it does not depend on any switch.
+fn static_value(
+    c: &ProcessSwitchCase,
+    bits: &[SigBit],
+    wt: &WireTable,
+) -> Result<Option<Vec<SigBit>>> {
+    let mut v = bits.to_vec();
+    if !static_apply(c, &mut v, bits, wt)? {
+        return Ok(None);
+    }
+    Ok(Some(v))
+}
+
+fn static_apply(
+    c: &ProcessSwitchCase,
+    v: &mut [SigBit],
+    bits: &[SigBit],
+    wt: &WireTable,
+) -> Result<bool> {
+    for (l, r) in c.assign() {
+        for (lb, rb) in wt.bits(l)?.iter().zip(wt.bits(r)?) {
+            if let Some(i) = bits.iter().position(|b| b == lb) {
+                v[i] = rb;
+            }
+        }
+    }
+    for s in c.switch() {
+        match s.cases().as_slice() {
+            [k] if k.sigs().is_empty() => {
+                if !static_apply(k, v, bits, wt)? {
+                    return Ok(false);
+                }
+            }
+            cases => {
+                let targets: HashSet<SigBit> = bits.iter().cloned().collect();
+                for k in cases {
+                    if case_assigns(k, &targets, wt)? {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn case_assigns(c: &ProcessSwitchCase, targets: &HashSet<SigBit>, wt: &WireTable) -> Result<bool> {
+    for (l, _) in c.assign() {
+        if wt.bits(l)?.iter().any(|b| targets.contains(b)) {
+            return Ok(true);
+        }
+    }
+    for s in c.switch() {
+        for k in s.cases() {
+            if case_assigns(k, targets, wt)? {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn sync_edge(tp: &ProcessSyncType) -> Option<(&SigSpec, bool)> {
+    match tp {
+        ProcessSyncType::Posedge(s) => Some((s, true)),
+        ProcessSyncType::Negedge(s) => Some((s, false)),
+        _ => None,
+    }
+}
+
+/// Detect asynchronous resets (`proc_arst`): an edge rule whose signal
+/// selects a root switch and which assigns values independent of any other
+/// switch becomes a level sensitive rule updating those values. The reset
+/// branch is then removed from the remaining clock path.
+pub fn proc_arst(module: &mut Module) -> Result<usize> {
+    let wt = WireTable::new(module);
+    let mut count = 0;
+    for p in module.processes_mut().iter_mut() {
+        let edges: Vec<usize> = (0..p.syncs().len())
+            .filter(|i| sync_edge(p.syncs()[*i].tp()).is_some())
+            .collect();
+        if edges.len() < 2 {
+            continue;
+        }
+        let mut root = take_root(p);
+        let mut roots = HashSet::new();
+        for s in root.switch() {
+            for b in wt.bits(s.sig())? {
+                roots.insert(b);
+            }
+        }
+
+        let mut resets = Vec::new();
+        for i in edges.iter().cloned() {
+            let (sig, pol) = sync_edge(p.syncs()[i].tp()).unwrap();
+            let bits = wt.bits(sig)?;
+            if bits.len() != 1 || !roots.contains(&bits[0]) {
+                continue;
+            }
+            let active = if pol { State::S1 } else { State::S0 };
+            let mut tree = root.clone();
+            assume_case(&mut tree, &bits[0], active, &wt)?;
+            let mut updates = Vec::new();
+            for (l, r) in p.syncs()[i].updates() {
+                match static_value(&tree, &wt.bits(r)?, &wt)? {
+                    Some(v) => updates.push((l.clone(), wt.sigspec(&v))),
+                    None => break,
+                }
+            }
+            if updates.len() == p.syncs()[i].updates().len() {
+                resets.push((i, sig.clone(), bits[0].clone(), pol, updates));
+            }
+        }
+        // keep at least one clock
+        if resets.len() == edges.len() {
+            resets.remove(0);
+        }
+
+        for (i, sig, bit, pol, updates) in resets {
+            let inactive = if pol { State::S0 } else { State::S1 };
+            assume_case(&mut root, &bit, inactive, &wt)?;
+            let s = &mut p.syncs_mut()[i];
+            *s.tp_mut() = if pol {
+                ProcessSyncType::High(sig)
+            } else {
+                ProcessSyncType::Low(sig)
+            };
+            *s.updates_mut() = updates;
+            count += 1;
+        }
+        put_root(p, root);
+    }
+    Ok(count)
+}
+
+// Bits assigned by the tree, grouped so that every assignment covers either
+// all or none of the bits of a group.
+fn assign_groups(root: &ProcessSwitchCase, wt: &WireTable) -> Result<Vec<Vec<SigBit>>> {
+    fn walk(
+        c: &ProcessSwitchCase,
+        wt: &WireTable,
+        next: &mut usize,
+        sets: &mut HashMap<SigBit, BTreeSet<usize>>,
+        order: &mut Vec<SigBit>,
+    ) -> Result<()> {
+        for (l, _) in c.assign() {
+            for b in wt.bits(l)? {
+                if b.is_const() {
+                    continue;
+                }
+                if !sets.contains_key(&b) {
+                    order.push(b.clone());
+                }
+                sets.entry(b).or_default().insert(*next);
+            }
+            *next += 1;
+        }
+        for s in c.switch() {
+            for k in s.cases() {
+                walk(k, wt, next, sets, order)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut sets = HashMap::new();
+    let mut order = Vec::new();
+    walk(root, wt, &mut 0, &mut sets, &mut order)?;
+    let mut index: HashMap<&BTreeSet<usize>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<SigBit>> = Vec::new();
+    for b in order.iter() {
+        let set = &sets[b];
+        let i = *index.entry(set).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(b.clone());
+    }
+    Ok(groups)
+}
+
+// Builds the multiplexer trees implementing a switch tree.
+struct MuxBuilder<'a> {
+    module: &'a mut Module,
+    wt: WireTable,
+    /// Control signals of the cases seen so far, by their path of switch
+    /// and case indices from the root.
+    ctrls: HashMap<Vec<usize>, SigBit>,
+}
+
+impl<'a> MuxBuilder<'a> {
+    fn new(module: &'a mut Module) -> Self {
+        let wt = WireTable::new(module);
+        Self {
+            module,
+            wt,
+            ctrls: HashMap::new(),
+        }
+    }
+
+    fn wire(&mut self, tag: &str, width: usize) -> Vec<SigBit> {
+        let s = self.module.new_wire(tag, width as i64);
+        self.wt.insert(self.module.wires().last().unwrap());
+        self.wt.bits(&s).unwrap()
+    }
+
+    fn eq(&mut self, a: &[SigBit], b: &[SigBit]) -> SigBit {
+        if a.len() == 1 && b[0] == SigBit::Const(State::S1) {
+            return a[0].clone();
+        }
+        let y = self.wire("procmux", 1);
+        let (sa, sb, sy) = (self.wt.sigspec(a), self.wt.sigspec(b), self.wt.sigspec(&y));
+        let c = self.module.new_cell("$eq", "procmux");
+        c.set_param("\\A_SIGNED", Const::Int(0));
+        c.set_param("\\B_SIGNED", Const::Int(0));
+        c.set_param("\\A_WIDTH", Const::Int(a.len() as i64));
+        c.set_param("\\B_WIDTH", Const::Int(b.len() as i64));
+        c.set_param("\\Y_WIDTH", Const::Int(1));
+        c.set_port("\\A", sa);
+        c.set_port("\\B", sb);
+        c.set_port("\\Y", sy);
+        y[0].clone()
+    }
+
+    fn or(&mut self, a: &[SigBit]) -> SigBit {
+        if a.len() == 1 {
+            return a[0].clone();
+        }
+        let y = self.wire("procmux", 1);
+        let (sa, sy) = (self.wt.sigspec(a), self.wt.sigspec(&y));
+        let c = self.module.new_cell("$reduce_or", "procmux");
+        c.set_param("\\A_SIGNED", Const::Int(0));
+        c.set_param("\\A_WIDTH", Const::Int(a.len() as i64));
+        c.set_param("\\Y_WIDTH", Const::Int(1));
+        c.set_port("\\A", sa);
+        c.set_port("\\Y", sy);
+        y[0].clone()
+    }
+
+    // Control signal selecting case `c`, found at `path`, of a switch on
+    // `sig`.
+    fn ctrl(&mut self, sig: &[SigBit], c: &ProcessSwitchCase, path: &[usize]) -> Result<SigBit> {
+        if let Some(s) = self.ctrls.get(path) {
+            return Ok(s.clone());
+        }
+        let mut terms = Vec::new();
+        let mut always = false;
+        for p in c.sigs() {
+            let mut pat = self.wt.bits(p)?;
+            pat.resize(sig.len(), SigBit::Const(State::S0));
+            match pattern_match(sig, &pat) {
+                Some(false) => continue,
+                Some(true) => {
+                    always = true;
+                    break;
+                }
+                None => (),
+            }
+            let (a, b): (Vec<SigBit>, Vec<SigBit>) = sig
+                .iter()
+                .cloned()
+                .zip(pat)
+                .filter(|(_, b)| !dont_care(b))
+                .unzip();
+            terms.push(self.eq(&a, &b));
+        }
+        let s = if always {
+            SigBit::Const(State::S1)
+        } else if terms.is_empty() {
+            SigBit::Const(State::S0)
+        } else {
+            self.or(&terms)
+        };
+        self.ctrls.insert(path.to_vec(), s.clone());
+        Ok(s)
+    }
+
+    fn mux(&mut self, s: &SigBit, a: Vec<SigBit>, b: Vec<SigBit>) -> Vec<SigBit> {
+        match s {
+            _ if a == b => a,
+            SigBit::Const(State::S0) => a,
+            SigBit::Const(State::S1) => b,
+            _ => {
+                let y = self.wire("procmux", a.len());
+                let (sa, sb, ss, sy) = (
+                    self.wt.sigspec(&a),
+                    self.wt.sigspec(&b),
+                    self.wt.sigspec(std::slice::from_ref(s)),
+                    self.wt.sigspec(&y),
+                );
+                let c = self.module.new_cell("$mux", "procmux");
+                c.set_param("\\WIDTH", Const::Int(a.len() as i64));
+                c.set_port("\\A", sa);
+                c.set_port("\\B", sb);
+                c.set_port("\\S", ss);
+                c.set_port("\\Y", sy);
+                y
+            }
+        }
+    }
+
+    // Value of `group` after case `c`, found at `path`, given its `incoming`
+    // value.
+    fn value(
+        &mut self,
+        c: &ProcessSwitchCase,
+        path: &mut Vec<usize>,
+        group: &[SigBit],
+        incoming: Vec<SigBit>,
+    ) -> Result<Vec<SigBit>> {
+        let mut v = incoming;
+        for (l, r) in c.assign() {
+            let (lb, rb) = (self.wt.bits(l)?, self.wt.bits(r)?);
+            if lb.len() != rb.len() {
+                bail!("width mismatch in assignment of `{}' to `{}'", r, l);
+            }
+            for (x, y) in lb.iter().zip(rb) {
+                if let Some(i) = group.iter().position(|b| b == x) {
+                    v[i] = y;
+                }
+            }
+        }
+        let targets: HashSet<SigBit> = group.iter().cloned().collect();
+        for (si, s) in c.switch().iter().enumerate() {
+            let mut touched = false;
+            for k in s.cases() {
+                touched |= case_assigns(k, &targets, &self.wt)?;
+            }
+            if !touched {
+                continue;
+            }
+            let sig = self.wt.bits(s.sig())?;
+            let mut other = v.clone();
+            let mut branches = Vec::new();
+            for (ki, k) in s.cases().iter().enumerate() {
+                path.extend([si, ki].iter());
+                if k.sigs().is_empty() {
+                    other = self.value(k, path, group, v.clone())?;
+                    path.truncate(path.len() - 2);
+                    break;
+                }
+                let ctrl = self.ctrl(&sig, k, path)?;
+                let val = self.value(k, path, group, v.clone())?;
+                path.truncate(path.len() - 2);
+                branches.push((ctrl, val));
+            }
+            for (ctrl, val) in branches.into_iter().rev() {
+                other = self.mux(&ctrl, other, val);
+            }
+            v = other;
+        }
+        Ok(v)
+    }
+}
+
+// Bits read by edge or level sensitive sync rules, with the register they
+// update.
+fn registered(p: &Process, wt: &WireTable) -> Result<HashMap<SigBit, SigBit>> {
+    let mut r = HashMap::new();
+    for s in p.syncs() {
+        if matches!(s.tp(), ProcessSyncType::Always | ProcessSyncType::Init) {
+            continue;
+        }
+        for (l, v) in s.updates() {
+            for (lb, vb) in wt.bits(l)?.into_iter().zip(wt.bits(v)?) {
+                r.entry(vb).or_insert(lb);
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// Replace the switch trees of processes by `$mux` trees (`proc_mux`). Each
+/// process is left with plain root assignments of the multiplexer outputs.
+/// Registered signals keep their value on paths not assigning them; other
+/// signals feed back their own value, turned into latches by
+/// [`proc_dlatch`].
+pub fn proc_mux(module: &mut Module) -> Result<()> {
+    let mut processes = mem::take(module.processes_mut());
+    let mut b = MuxBuilder::new(module);
+    for p in processes.iter_mut() {
+        b.ctrls.clear();
+        let regs = registered(p, &b.wt)?;
+        let root = take_root(p);
+        let mut assign = Vec::new();
+        for group in assign_groups(&root, &b.wt)? {
+            let incoming: Vec<SigBit> = group
+                .iter()
+                .map(|x| regs.get(x).unwrap_or(x).clone())
+                .collect();
+            let v = b.value(&root, &mut Vec::new(), &group, incoming)?;
+            assign.push((b.wt.sigspec(&group), b.wt.sigspec(&v)));
+        }
+        *p.assign_mut() = assign;
+    }
+    *module.processes_mut() = processes;
+    Ok(())
+}
+
+/// Turn sync rules into flip-flop and latch cells, and the remaining process
+/// statements into connections (`proc_dff`). Processes are removed.
+pub fn proc_dff(module: &mut Module) -> Result<()> {
+    proc_init(module)?;
+    let wt = WireTable::new(module);
+    for p in mem::take(module.processes_mut()) {
+        if !p.switch().is_empty() {
+            bail!(
+                "process `{}' still has switches, run proc_mux first",
+                p.id()
+            );
+        }
+        for (l, r) in p.assign() {
+            module
+                .connects_mut()
+                .push(Connect::new(l.clone(), r.clone()));
+        }
+
+        let mut edge = None;
+        let mut levels = Vec::new();
+        for s in p.syncs() {
+            match s.tp() {
+                ProcessSyncType::Always => {
+                    for (l, r) in s.updates() {
+                        module
+                            .connects_mut()
+                            .push(Connect::new(l.clone(), r.clone()));
+                    }
+                }
+                ProcessSyncType::Global => {
+                    for (l, r) in s.updates() {
+                        let width = wt.width_of(l)?;
+                        let c = module.new_cell("$ff", "procdff");
+                        c.set_param("\\WIDTH", Const::Int(width));
+                        c.set_port("\\D", r.clone());
+                        c.set_port("\\Q", l.clone());
+                    }
+                }
+                ProcessSyncType::Posedge(sig) | ProcessSyncType::Negedge(sig) => {
+                    if edge.is_some() {
+                        bail!("multiple edge sensitive events in process `{}'", p.id());
+                    }
+                    let pol = matches!(s.tp(), ProcessSyncType::Posedge(_));
+                    edge = Some((sig.clone(), pol, s));
+                }
+                ProcessSyncType::Edge(_) => {
+                    bail!("both-edge sensitive event in process `{}'", p.id());
+                }
+                ProcessSyncType::Low(sig) => levels.push((sig.clone(), false, s)),
+                ProcessSyncType::High(sig) => levels.push((sig.clone(), true, s)),
+                ProcessSyncType::Init => (),
+            }
+        }
+        if levels.len() > 1 {
+            bail!("multiple level sensitive events in process `{}'", p.id());
+        }
+
+        let (clk, clk_pol, rule) = match edge {
+            Some(v) => v,
+            None => {
+                for (en, pol, s) in levels {
+                    for (l, r) in s.updates() {
+                        let width = wt.width_of(l)?;
+                        let c = module.new_cell("$dlatch", "procdff");
+                        c.set_param("\\WIDTH", Const::Int(width));
+                        c.set_param("\\EN_POLARITY", bool_const(pol));
+                        c.set_port("\\EN", en.clone());
+                        c.set_port("\\D", r.clone());
+                        c.set_port("\\Q", l.clone());
+                    }
+                }
+                continue;
+            }
+        };
+
+        let mut resets = HashMap::new();
+        if let Some((_, _, s)) = levels.first() {
+            for (l, r) in s.updates() {
+                for (lb, rb) in wt.bits(l)?.into_iter().zip(wt.bits(r)?) {
+                    resets.insert(lb, rb);
+                }
+            }
+        }
+        for (l, r) in rule.updates() {
+            let (q, d) = (wt.bits(l)?, wt.bits(r)?);
+            let (plain, reset): (Vec<usize>, Vec<usize>) =
+                (0..q.len()).partition(|i| !resets.contains_key(&q[*i]));
+            for idx in [plain, reset].iter() {
+                if idx.is_empty() {
+                    continue;
+                }
+                let sq: Vec<SigBit> = idx.iter().map(|i| q[*i].clone()).collect();
+                let sd: Vec<SigBit> = idx.iter().map(|i| d[*i].clone()).collect();
+                let sv: Vec<SigBit> = sq.iter().filter_map(|b| resets.get(b).cloned()).collect();
+                let tp = if sv.is_empty() {
+                    "$dff"
+                } else if sv.iter().all(|b| b.is_const()) {
+                    "$adff"
+                } else {
+                    "$aldff"
+                };
+                let c = module.new_cell(tp, "procdff");
+                c.set_param("\\WIDTH", Const::Int(idx.len() as i64));
+                c.set_param("\\CLK_POLARITY", bool_const(clk_pol));
+                c.set_port("\\CLK", clk.clone());
+                c.set_port("\\D", wt.sigspec(&sd));
+                c.set_port("\\Q", wt.sigspec(&sq));
+                if let Some((arst, pol, _)) = levels.first() {
+                    if tp == "$adff" {
+                        let v: Vec<State> = sv.iter().map(|b| b.state().unwrap()).collect();
+                        c.set_param("\\ARST_POLARITY", bool_const(*pol));
+                        c.set_param("\\ARST_VALUE", Const::from_bits(&v));
+                        c.set_port("\\ARST", arst.clone());
+                    } else if tp == "$aldff" {
+                        c.set_param("\\ALOAD_POLARITY", bool_const(*pol));
+                        c.set_port("\\ALOAD", arst.clone());
+                        c.set_port("\\AD", wt.sigspec(&sv));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Finds the paths through the multiplexers built by `proc_mux` on which
+// signals keep their own value.
+struct Feedback<'a> {
+    module: &'a mut Module,
+    wt: WireTable,
+    /// Cell index and bit of the `$procmux` output driving a bit.
+    muxes: HashMap<SigBit, (usize, usize)>,
+    /// Hold conditions built so far, by their inputs.
+    holds: HashMap<(SigBit, SigBit, SigBit), SigBit>,
+}
+
+impl Feedback<'_> {
+    fn input(&self, ci: usize, port: &str, j: usize) -> Result<SigBit> {
+        let c = &self.module.cells()[ci];
+        Ok(self.wt.bits(c.port(port).unwrap())?[j].clone())
+    }
+
+    // Bit set when `bit` carries one of `leaves`, the current value of a
+    // signal.
+    fn hold(&mut self, bit: &SigBit, leaves: &[SigBit]) -> Result<SigBit> {
+        if leaves.contains(bit) {
+            return Ok(SigBit::Const(State::S1));
+        }
+        let (ci, j) = match self.muxes.get(bit) {
+            Some(v) => *v,
+            None => return Ok(SigBit::Const(State::S0)),
+        };
+        let a = self.input(ci, "\\A", j)?;
+        let b = self.input(ci, "\\B", j)?;
+        let s = self.input(ci, "\\S", 0)?;
+        let (ha, hb) = (self.hold(&a, leaves)?, self.hold(&b, leaves)?);
+        if ha == hb {
+            return Ok(ha);
+        }
+        if ha == SigBit::Const(State::S0) && hb == SigBit::Const(State::S1) {
+            return Ok(s);
+        }
+        let key = (ha.clone(), hb.clone(), s.clone());
+        if let Some(h) = self.holds.get(&key) {
+            return Ok(h.clone());
+        }
+        let y = self.module.new_wire("proclatch", 1);
+        self.wt.insert(self.module.wires().last().unwrap());
+        let (sa, sb, ss) = (
+            self.wt.sigspec(&[ha]),
+            self.wt.sigspec(&[hb]),
+            self.wt.sigspec(&[s]),
+        );
+        let c = self.module.new_cell("$mux", "proclatch");
+        c.set_param("\\WIDTH", Const::Int(1));
+        c.set_port("\\A", sa);
+        c.set_port("\\B", sb);
+        c.set_port("\\S", ss);
+        c.set_port("\\Y", y.clone());
+        let h = self.wt.bits(&y)?.remove(0);
+        self.holds.insert(key, h.clone());
+        Ok(h)
+    }
+
+    // Replace `leaves` reaching `bit` by undefined bits, as the latch holds
+    // the value on those paths.
+    fn cut(&mut self, bit: &SigBit, leaves: &[SigBit]) -> Result<()> {
+        let (ci, j) = match self.muxes.get(bit) {
+            Some(v) => *v,
+            None => return Ok(()),
+        };
+        for port in ["\\A", "\\B"].iter() {
+            let x = self.input(ci, port, j)?;
+            if leaves.contains(&x) {
+                let c = &self.module.cells()[ci];
+                let mut bits = self.wt.bits(c.port(port).unwrap())?;
+                bits[j] = SigBit::Const(State::Sx);
+                let s = self.wt.sigspec(&bits);
+                self.module.cells_mut()[ci].set_port(port, s);
+            } else {
+                self.cut(&x, leaves)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Remove the assignments to `bits` from `v`.
+fn drop_bits(
+    wt: &WireTable,
+    v: Vec<(SigSpec, SigSpec)>,
+    bits: &HashSet<SigBit>,
+) -> Result<Vec<(SigSpec, SigSpec)>> {
+    let mut r = Vec::new();
+    for (l, x) in v {
+        let (lb, xb): (Vec<SigBit>, Vec<SigBit>) = wt
+            .bits(&l)?
+            .into_iter()
+            .zip(wt.bits(&x)?)
+            .filter(|(a, _)| !bits.contains(a))
+            .unzip();
+        if !lb.is_empty() {
+            r.push((wt.sigspec(&lb), wt.sigspec(&xb)));
+        }
+    }
+    Ok(r)
+}
+
+/// Create `$dlatch` cells for combinational signals keeping their value on
+/// some path through their process (`proc_dlatch`), to run after
+/// [`proc_mux`]. A signal keeps its value where its own current value, as
+/// in the `assign $0\q \q` yosys' frontend puts at the root of processes,
+/// reaches its update through the multiplexers. The latch is enabled on the
+/// other paths, and the update is removed from the process. Returns the
+/// number of latches created.
+pub fn proc_dlatch(module: &mut Module) -> Result<usize> {
+    let wt = WireTable::new(module);
+    let mut muxes = HashMap::new();
+    for (ci, c) in module.cells().iter().enumerate() {
+        if c.i1() != "$mux" || !c.i2().starts_with("$procmux$") {
+            continue;
+        }
+        if let Some(y) = c.port("\\Y") {
+            for (j, b) in wt.bits(y)?.into_iter().enumerate() {
+                muxes.insert(b, (ci, j));
+            }
+        }
+    }
+    let mut processes = mem::take(module.processes_mut());
+    let mut f = Feedback {
+        module,
+        wt,
+        muxes,
+        holds: HashMap::new(),
+    };
+    let mut count = 0;
+    for p in processes.iter_mut() {
+        if !p.switch().is_empty() {
+            bail!(
+                "process `{}' still has switches, run proc_mux first",
+                p.id()
+            );
+        }
+        let regs = registered(p, &f.wt)?;
+        let mut always = HashMap::new();
+        for s in p.syncs() {
+            if matches!(s.tp(), ProcessSyncType::Always) {
+                for (l, r) in s.updates() {
+                    for (lb, rb) in f.wt.bits(l)?.into_iter().zip(f.wt.bits(r)?) {
+                        always.insert(rb, lb);
+                    }
+                }
+            }
+        }
+
+        // latched bits with their data, by hold condition
+        let mut latches: Vec<(SigBit, Vec<SigBit>, Vec<SigBit>)> = Vec::new();
+        // bits no longer assigned by the process or its `sync always` rules
+        let (mut assigned, mut updated) = (HashSet::new(), HashSet::new());
+        for (l, r) in p.assign().clone() {
+            for (a, v) in f.wt.bits(&l)?.into_iter().zip(f.wt.bits(&r)?) {
+                if a.is_const() || regs.contains_key(&a) {
+                    continue;
+                }
+                // signals updated by `sync always` are driven through `a`
+                let (q, d, leaves) = match always.get(&a) {
+                    Some(q) => (q.clone(), a.clone(), vec![q.clone(), a.clone()]),
+                    None => (a.clone(), v.clone(), vec![a.clone()]),
+                };
+                let hold = f.hold(&v, &leaves)?;
+                if hold.is_const() {
+                    continue;
+                }
+                f.cut(&v, &leaves)?;
+                if q == a {
+                    assigned.insert(a.clone());
+                } else {
+                    updated.insert(q.clone());
+                }
+                match latches.iter_mut().find(|(h, _, _)| *h == hold) {
+                    Some((_, qs, ds)) => {
+                        qs.push(q);
+                        ds.push(d);
+                    }
+                    None => latches.push((hold, vec![q], vec![d])),
+                }
+            }
+        }
+        if latches.is_empty() {
+            continue;
+        }
+
+        for (hold, q, d) in latches {
+            let (se, sd, sq) = (f.wt.sigspec(&[hold]), f.wt.sigspec(&d), f.wt.sigspec(&q));
+            let c = f.module.new_cell("$dlatch", "proclatch");
+            c.set_param("\\WIDTH", Const::Int(q.len() as i64));
+            c.set_param("\\EN_POLARITY", bool_const(false));
+            c.set_port("\\EN", se);
+            c.set_port("\\D", sd);
+            c.set_port("\\Q", sq);
+            count += 1;
+        }
+        let assign = mem::take(p.assign_mut());
+        *p.assign_mut() = drop_bits(&f.wt, assign, &assigned)?;
+        for s in p.syncs_mut().iter_mut() {
+            if matches!(s.tp(), ProcessSyncType::Always) {
+                let updates = mem::take(s.updates_mut());
+                *s.updates_mut() = drop_bits(&f.wt, updates, &updated)?;
+            }
+        }
+    }
+    *module.processes_mut() = processes;
+    Ok(count)
+}
+
+/// Convert all processes of `module` into netlist cells, running the `proc`
+/// passes in yosys' order.
+pub fn proc(module: &mut Module) -> Result<()> {
+    proc_clean(module);
+    proc_rmdead(module)?;
+    proc_init(module)?;
+    proc_arst(module)?;
+    proc_mux(module)?;
+    proc_dlatch(module)?;
+    proc_dff(module)?;
+    Ok(())
+}
+
+/// Run [`proc`] on every module of the design.
+pub fn proc_design(design: &mut Design) -> Result<()> {
+    for m in design.modules_mut().iter_mut() {
+        proc(m)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use super::proc::{dont_care, put_root, take_root};
+use crate::syntax::*;
+use anyhow::Result;
+use getset::*;
+use std::collections::HashMap;
+
+// A set of switch signal values: `Some(v)` fixes a variable, `None` leaves it
+// free.
+type Cube = Vec<Option<bool>>;
+
+fn intersects(a: &Cube, b: &Cube) -> bool {
+    a.iter().zip(b).all(|(x, y)| match (x, y) {
+        (Some(x), Some(y)) => x == y,
+        _ => true,
+    })
+}
+
+fn contains(a: &Cube, b: &Cube) -> bool {
+    a.iter().zip(b).all(|(x, y)| x.is_none() || x == y)
+}
+
+// Whether the union of `set` contains `c`.
+fn covered(c: &Cube, set: &[&Cube]) -> bool {
+    if set.iter().any(|s| contains(s, c)) {
+        return true;
+    }
+    let hits: Vec<&Cube> = set.iter().cloned().filter(|s| intersects(s, c)).collect();
+    let split = hits
+        .iter()
+        .find_map(|s| (0..c.len()).find(|i| c[*i].is_none() && s[*i].is_some()));
+    match split {
+        None => false,
+        Some(i) => [false, true].iter().all(|v| {
+            let mut h = c.clone();
+            h[i] = Some(*v);
+            covered(&h, &hits)
+        }),
+    }
+}
+
+// Switch signal with the wire bits numbered as variables.
+struct SwitchSpace {
+    bits: Vec<Result<usize, State>>,
+    vars: usize,
+}
+
+impl SwitchSpace {
+    fn new(sig: &[SigBit], known: &HashMap<SigBit, bool>) -> Self {
+        let mut vars: HashMap<&SigBit, usize> = HashMap::new();
+        let mut bits = Vec::new();
+        for b in sig {
+            bits.push(match (b, known.get(b)) {
+                (_, Some(v)) => Err(if *v { State::S1 } else { State::S0 }),
+                (SigBit::Const(s), _) => Err(*s),
+                _ => {
+                    let n = vars.len();
+                    Ok(*vars.entry(b).or_insert(n))
+                }
+            });
+        }
+        Self {
+            bits,
+            vars: vars.len(),
+        }
+    }
+
+    fn universe(&self) -> Cube {
+        vec![None; self.vars]
+    }
+
+    // Values matched by a pattern: Ok(None) if it never matches, Err(()) if
+    // the pattern is not constant. Bits `-`, `x` and `z` match anything.
+    fn cube(&self, pat: &[SigBit]) -> std::result::Result<Option<Cube>, ()> {
+        let mut c = self.universe();
+        for (i, s) in self.bits.iter().enumerate() {
+            let p = match pat.get(i).cloned().unwrap_or(SigBit::Const(State::S0)) {
+                p if dont_care(&p) => continue,
+                SigBit::Const(p) => p == State::S1,
+                SigBit::Wire(_) => return Err(()),
+            };
+            match s {
+                Ok(v) => match c[*v] {
+                    Some(x) if x != p => return Ok(None),
+                    _ => c[*v] = Some(p),
+                },
+                Err(State::S1) if p => (),
+                Err(State::S0) if !p => (),
+                Err(_) => return Ok(None),
+            }
+        }
+        Ok(Some(c))
+    }
+
+    // Wire bits fixed by `c`.
+    fn known(&self, sig: &[SigBit], c: &Cube) -> Vec<(SigBit, bool)> {
+        let mut r = Vec::new();
+        for (b, s) in sig.iter().zip(self.bits.iter()) {
+            if let Ok(v) = s {
+                if let Some(x) = c[*v] {
+                    r.push((b.clone(), x));
+                }
+            }
+        }
+        r
+    }
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct CaseReport {
+    index: usize,
+    /// The case can never be taken.
+    unreachable: bool,
+    /// The case matches every value not matched by the cases before it.
+    always: bool,
+    /// Indices into the case's patterns that only match values already
+    /// matched before.
+    dead_patterns: Vec<usize>,
+    /// Earlier cases matching some of the values this case matches.
+    overlaps: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Getters)]
+#[get = "pub"]
+pub struct SwitchReport {
+    process: String,
+    /// Indices leading to the switch from the process root: alternately a
+    /// switch and a case index, ending with the switch's own index.
+    path: Vec<usize>,
+    sig: SigSpec,
+    /// Some case is taken for every value of the switch signal.
+    full: bool,
+    /// No value of the switch signal matches more than one case.
+    parallel: bool,
+    /// The switch is inside an unreachable case.
+    unreachable: bool,
+    cases: Vec<CaseReport>,
+}
+
+pub(crate) fn analyze_switch(
+    s: &ProcessSwitch,
+    known: &HashMap<SigBit, bool>,
+    dead: bool,
+    wt: &WireTable,
+) -> Result<(SwitchReport, Vec<HashMap<SigBit, bool>>)> {
+    let sig = wt.bits(s.sig())?;
+    let space = SwitchSpace::new(&sig, known);
+    let universe = space.universe();
+    let mut before: Vec<Cube> = Vec::new();
+    let mut parallel = true;
+    let mut full = false;
+    let mut cases = Vec::new();
+    let mut inner = Vec::new();
+
+    for (index, c) in s.cases().iter().enumerate() {
+        let mut r = CaseReport {
+            index,
+            ..CaseReport::default()
+        };
+        let prev: Vec<&Cube> = before.iter().collect();
+        let mut own: Vec<Cube> = Vec::new();
+        let mut live: Vec<usize> = Vec::new();
+        let mut opaque = false;
+        if c.sigs().is_empty() {
+            own.push(universe.clone());
+        }
+        for (i, p) in c.sigs().iter().enumerate() {
+            match space.cube(&wt.bits(p)?) {
+                Err(()) => opaque = true,
+                Ok(None) => r.dead_patterns.push(i),
+                Ok(Some(cube)) => {
+                    let mut seen = prev.clone();
+                    seen.extend(own.iter());
+                    if covered(&cube, &seen) {
+                        r.dead_patterns.push(i);
+                    } else {
+                        live.push(own.len());
+                    }
+                    own.push(cube);
+                }
+            }
+        }
+
+        r.unreachable = !opaque && r.dead_patterns.len() == c.sigs().len() && !c.sigs().is_empty()
+            || covered(&universe, &prev);
+        for (j, o) in cases.iter().enumerate() {
+            let o: &CaseReport = o;
+            if o.unreachable || c.sigs().is_empty() {
+                continue;
+            }
+            let theirs = &s.cases()[j];
+            let mut hit = theirs.sigs().is_empty() && !own.is_empty();
+            for p in theirs.sigs() {
+                if let Ok(Some(t)) = space.cube(&wt.bits(p)?) {
+                    hit |= own.iter().any(|x| intersects(x, &t));
+                }
+            }
+            if hit && !r.unreachable {
+                r.overlaps.push(j);
+            }
+        }
+        if opaque || !r.overlaps.is_empty() {
+            parallel = false;
+        }
+        before.extend(own.iter().cloned());
+        if !r.unreachable && covered(&universe, &before.iter().collect::<Vec<_>>()) {
+            r.always = !full;
+            full = true;
+        }
+
+        let mut k = known.clone();
+        if let ([i], false) = (live.as_slice(), opaque) {
+            k.extend(space.known(&sig, &own[*i]));
+        }
+        inner.push(k);
+        cases.push(r);
+    }
+
+    Ok((
+        SwitchReport {
+            process: String::new(),
+            path: Vec::new(),
+            sig: s.sig().clone(),
+            full,
+            parallel,
+            unreachable: dead,
+            cases,
+        },
+        inner,
+    ))
+}
+
+fn analyze_case(
+    c: &ProcessSwitchCase,
+    process: &str,
+    path: &mut Vec<usize>,
+    known: &HashMap<SigBit, bool>,
+    dead: bool,
+    wt: &WireTable,
+    out: &mut Vec<SwitchReport>,
+) -> Result<()> {
+    for (i, s) in c.switch().iter().enumerate() {
+        path.push(i);
+        let (mut r, inner) = analyze_switch(s, known, dead, wt)?;
+        r.process = process.to_string();
+        r.path = path.clone();
+        let reach: Vec<bool> = r.cases.iter().map(|k| !k.unreachable).collect();
+        out.push(r);
+        for (j, k) in s.cases().iter().enumerate() {
+            path.push(j);
+            analyze_case(k, process, path, &inner[j], dead || !reach[j], wt, out)?;
+            path.pop();
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Report reachability, full/parallel case properties and overlapping
+/// patterns for every switch of the module's processes, in pre-order.
+///
+/// Only constant `0`/`1` pattern bits are compared, `-`, `x` and `z` match
+/// anything, as in yosys. Values implied by an enclosing single-pattern case
+/// are taken into account.
+pub fn analyze_switches(module: &Module) -> Result<Vec<SwitchReport>> {
+    let wt = WireTable::new(module);
+    let mut out = Vec::new();
+    for p in module.processes() {
+        let mut root = ProcessSwitchCase::default();
+        *root.switch_mut() = p.switch().clone();
+        analyze_case(
+            &root,
+            p.id(),
+            &mut Vec::new(),
+            &HashMap::new(),
+            false,
+            &wt,
+            &mut out,
+        )?;
+    }
+    Ok(out)
+}
+
+fn rmdead_case(
+    c: &mut ProcessSwitchCase,
+    known: &HashMap<SigBit, bool>,
+    wt: &WireTable,
+) -> Result<usize> {
+    let mut count = 0;
+    for s in c.switch_mut().iter_mut() {
+        let (r, inner) = analyze_switch(s, known, false, wt)?;
+        let mut cases = Vec::new();
+        for ((mut k, info), known) in std::mem::take(s.cases_mut())
+            .into_iter()
+            .zip(r.cases)
+            .zip(inner)
+        {
+            if info.unreachable {
+                count += 1;
+                continue;
+            }
+            if info.always {
+                k.sigs_mut().clear();
+            } else {
+                let sigs = k
+                    .sigs()
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !info.dead_patterns.contains(i))
+                    .map(|(_, p)| p.clone())
+                    .collect();
+                *k.sigs_mut() = sigs;
+            }
+            count += rmdead_case(&mut k, &known, wt)?;
+            cases.push(k);
+        }
+        *s.cases_mut() = cases;
+    }
+    Ok(count)
+}
+
+/// Remove switch cases that can never be taken and patterns shadowed by
+/// earlier ones (`proc_rmdead`), as found by [`analyze_switches`]. A case
+/// catching every remaining value becomes the default case. Returns the
+/// number of cases removed.
+pub fn proc_rmdead(module: &mut Module) -> Result<usize> {
+    let wt = WireTable::new(module);
+    let mut count = 0;
+    for p in module.processes_mut().iter_mut() {
+        let mut root = take_root(p);
+        let r = rmdead_case(&mut root, &HashMap::new(), &wt);
+        put_root(p, root);
+        count += r?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,394 @@
+use crate::syntax::*;
+use anyhow::{anyhow, Result};
+
+use State::{S0, S1};
+
+const ZERO: SigBit = SigBit::Const(S0);
+const ONE: SigBit = SigBit::Const(S1);
+
+fn ext(v: &[SigBit], width: usize, signed: bool) -> Vec<SigBit> {
+    let mut r = v.to_vec();
+    let pad = match r.last() {
+        Some(b) if signed => b.clone(),
+        _ => ZERO,
+    };
+    r.resize(width, pad);
+    r
+}
+
+/// Builder of single-bit gates in a module, folding constant operands.
+struct Gates<'a> {
+    module: &'a mut Module,
+    wt: &'a mut WireTable,
+}
+
+impl<'a> Gates<'a> {
+    fn gate(&mut self, tp: &str, ports: &[(&str, &SigBit)]) -> SigBit {
+        let y = self.module.new_wire("techmap", 1);
+        self.wt.insert(self.module.wires().last().unwrap());
+        let cell = self.module.new_cell(tp, "techmap");
+        for (k, b) in ports {
+            cell.set_port(k, self.wt.sigspec(std::slice::from_ref(b)));
+        }
+        cell.set_port("\\Y", y.clone());
+        self.wt.bits(&y).unwrap().remove(0)
+    }
+
+    fn not(&mut self, a: &SigBit) -> SigBit {
+        match a {
+            SigBit::Const(S0) => ONE,
+            SigBit::Const(S1) => ZERO,
+            _ => self.gate("$_NOT_", &[("\\A", a)]),
+        }
+    }
+
+    fn and(&mut self, a: &SigBit, b: &SigBit) -> SigBit {
+        match (a, b) {
+            (SigBit::Const(S0), _) | (_, SigBit::Const(S0)) => ZERO,
+            (SigBit::Const(S1), x) | (x, SigBit::Const(S1)) => x.clone(),
+            _ if a == b => a.clone(),
+            _ => self.gate("$_AND_", &[("\\A", a), ("\\B", b)]),
+        }
+    }
+
+    fn or(&mut self, a: &SigBit, b: &SigBit) -> SigBit {
+        match (a, b) {
+            (SigBit::Const(S1), _) | (_, SigBit::Const(S1)) => ONE,
+            (SigBit::Const(S0), x) | (x, SigBit::Const(S0)) => x.clone(),
+            _ if a == b => a.clone(),
+            _ => self.gate("$_OR_", &[("\\A", a), ("\\B", b)]),
+        }
+    }
+
+    fn xor(&mut self, a: &SigBit, b: &SigBit) -> SigBit {
+        match (a, b) {
+            (SigBit::Const(S0), x) | (x, SigBit::Const(S0)) => x.clone(),
+            (SigBit::Const(S1), x) | (x, SigBit::Const(S1)) => self.not(&x.clone()),
+            _ if a == b => ZERO,
+            _ => self.gate("$_XOR_", &[("\\A", a), ("\\B", b)]),
+        }
+    }
+
+    // `s ? b : a`
+    fn mux(&mut self, a: &SigBit, b: &SigBit, s: &SigBit) -> SigBit {
+        match s {
+            SigBit::Const(S0) => a.clone(),
+            SigBit::Const(S1) => b.clone(),
+            _ if a == b => a.clone(),
+            _ => self.gate("$_MUX_", &[("\\A", a), ("\\B", b), ("\\S", s)]),
+        }
+    }
+
+    // Balanced tree of `f` over `v`, `empty` if `v` is.
+    fn reduce<F>(&mut self, v: &[SigBit], empty: SigBit, f: F) -> SigBit
+    where
+        F: Fn(&mut Self, &SigBit, &SigBit) -> SigBit,
+    {
+        let mut v = v.to_vec();
+        if v.is_empty() {
+            return empty;
+        }
+        while v.len() > 1 {
+            v = v
+                .chunks(2)
+                .map(|c| match c {
+                    [a, b] => f(self, a, b),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        v.remove(0)
+    }
+
+    fn reduce_or(&mut self, v: &[SigBit]) -> SigBit {
+        self.reduce(v, ZERO, Self::or)
+    }
+
+    // `a + b + ci` over the width of `a` and `b`.
+    fn add(&mut self, a: &[SigBit], b: &[SigBit], ci: SigBit) -> Vec<SigBit> {
+        let mut carry = ci;
+        let mut r = Vec::new();
+        for (x, y) in a.iter().zip(b) {
+            let p = self.xor(x, y);
+            r.push(self.xor(&p, &carry));
+            let g = self.and(x, y);
+            let c = self.and(&p, &carry);
+            carry = self.or(&g, &c);
+        }
+        r
+    }
+
+    fn sub(&mut self, a: &[SigBit], b: &[SigBit]) -> Vec<SigBit> {
+        let nb: Vec<SigBit> = b.iter().map(|x| self.not(x)).collect();
+        self.add(a, &nb, ONE)
+    }
+
+    fn mul(&mut self, a: &[SigBit], b: &[SigBit]) -> Vec<SigBit> {
+        let w = a.len();
+        let mut acc = vec![ZERO; w];
+        for (i, y) in b.iter().enumerate().take(w) {
+            let mut p = vec![ZERO; i];
+            for x in &a[..w - i] {
+                p.push(self.and(x, y));
+            }
+            acc = self.add(&acc, &p, ZERO);
+        }
+        acc
+    }
+
+    fn eq(&mut self, a: &[SigBit], b: &[SigBit]) -> SigBit {
+        let ne: Vec<SigBit> = a.iter().zip(b).map(|(x, y)| self.xor(x, y)).collect();
+        let any = self.reduce_or(&ne);
+        self.not(&any)
+    }
+
+    // `a < b`, both extended to a common width.
+    fn lt(&mut self, a: &[SigBit], b: &[SigBit], signed: bool) -> SigBit {
+        let n = a.len().max(b.len()) + 1;
+        let d = self.sub(&ext(a, n, signed), &ext(b, n, signed));
+        d[n - 1].clone()
+    }
+
+    // Shift `a` by `b` bits, left or right, filling vacated bits with
+    // `fill`.
+    fn shift(&mut self, a: &[SigBit], b: &[SigBit], left: bool, fill: &SigBit) -> Vec<SigBit> {
+        let w = a.len();
+        let mut v = a.to_vec();
+        for (j, s) in b.iter().enumerate() {
+            let k = if j < 32 { 1usize << j } else { usize::MAX };
+            let shifted: Vec<SigBit> = (0..w)
+                .map(|i| {
+                    let src = if left {
+                        i.checked_sub(k)
+                    } else {
+                        i.checked_add(k).filter(|p| *p < w)
+                    };
+                    src.map_or(fill.clone(), |p| v[p].clone())
+                })
+                .collect();
+            v = v
+                .iter()
+                .zip(&shifted)
+                .map(|(x, y)| self.mux(x, y, s))
+                .collect();
+        }
+        v
+    }
+}
+
+struct Mapper<'a> {
+    cell: &'a Cell,
+}
+
+impl<'a> Mapper<'a> {
+    fn int(&self, k: &str) -> Result<usize> {
+        self.cell
+            .param(k)
+            .and_then(|v| v.as_int())
+            .map(|v| v.max(0) as usize)
+            .ok_or_else(|| anyhow!("cell `{}' has no parameter `{}'", self.cell.i2(), k))
+    }
+
+    fn flag(&self, k: &str) -> bool {
+        matches!(self.cell.param(k), Some(v) if v.as_bool())
+    }
+
+    fn port(&self, g: &Gates, k: &str) -> Result<Vec<SigBit>> {
+        match self.cell.port(k) {
+            Some(s) => g.wt.bits(s),
+            None => Err(anyhow!("cell `{}' has no port `{}'", self.cell.i2(), k)),
+        }
+    }
+
+    // Input port extended to the width given by its `<P>_WIDTH` parameter.
+    fn arg(&self, g: &Gates, k: &str) -> Result<Vec<SigBit>> {
+        let v = self.port(g, &format!("\\{}", k))?;
+        Ok(ext(
+            &v,
+            self.int(&format!("\\{}_WIDTH", k)).unwrap_or(v.len()),
+            self.flag(&format!("\\{}_SIGNED", k)),
+        ))
+    }
+
+    // Gates computing `\Y`, or `None` for cells that are not mapped.
+    fn map(&self, g: &mut Gates) -> Result<Option<Vec<SigBit>>> {
+        let tp = self.cell.i1().as_str();
+        let (sa, sb) = (self.flag("\\A_SIGNED"), self.flag("\\B_SIGNED"));
+        // as in yosys, binary operators other than shifts are only signed
+        // when both operands are
+        let (sa, sb) = match tp {
+            "$not" | "$pos" | "$neg" | "$shl" | "$sshl" | "$shr" | "$sshr" => (sa, sb),
+            _ => (sa && sb, sa && sb),
+        };
+        let bit = |b: SigBit, w: usize| ext(&[b], w, false);
+        Ok(Some(match tp {
+            "$not" | "$pos" | "$neg" => {
+                let w = self.int("\\Y_WIDTH")?;
+                let a = ext(&self.arg(g, "A")?, w, sa);
+                match tp {
+                    "$not" => a.iter().map(|x| g.not(x)).collect(),
+                    "$pos" => a,
+                    _ => g.sub(&vec![ZERO; w], &a),
+                }
+            }
+            "$and" | "$or" | "$xor" | "$xnor" => {
+                let w = self.int("\\Y_WIDTH")?;
+                let a = ext(&self.arg(g, "A")?, w, sa);
+                let b = ext(&self.arg(g, "B")?, w, sb);
+                a.iter()
+                    .zip(&b)
+                    .map(|(x, y)| match tp {
+                        "$and" => g.and(x, y),
+                        "$or" => g.or(x, y),
+                        "$xor" => g.xor(x, y),
+                        _ => {
+                            let v = g.xor(x, y);
+                            g.not(&v)
+                        }
+                    })
+                    .collect()
+            }
+            "$reduce_and" | "$reduce_or" | "$reduce_xor" | "$reduce_xnor" | "$reduce_bool"
+            | "$logic_not" => {
+                let a = self.arg(g, "A")?;
+                let v = match tp {
+                    "$reduce_and" => g.reduce(&a, ONE, Gates::and),
+                    "$reduce_xor" => g.reduce(&a, ZERO, Gates::xor),
+                    "$reduce_xnor" => {
+                        let v = g.reduce(&a, ZERO, Gates::xor);
+                        g.not(&v)
+                    }
+                    "$logic_not" => {
+                        let v = g.reduce_or(&a);
+                        g.not(&v)
+                    }
+                    _ => g.reduce_or(&a),
+                };
+                bit(v, self.int("\\Y_WIDTH")?)
+            }
+            "$logic_and" | "$logic_or" => {
+                let a = g.reduce_or(&self.arg(g, "A")?);
+                let b = g.reduce_or(&self.arg(g, "B")?);
+                let v = if tp == "$logic_and" {
+                    g.and(&a, &b)
+                } else {
+                    g.or(&a, &b)
+                };
+                bit(v, self.int("\\Y_WIDTH")?)
+            }
+            "$shl" | "$sshl" => {
+                let w = self.int("\\Y_WIDTH")?;
+                let a = ext(&self.arg(g, "A")?, w, sa);
+                g.shift(&a, &self.arg(g, "B")?, true, &ZERO)
+            }
+            "$shr" | "$sshr" => {
+                let w = self.int("\\Y_WIDTH")?;
+                let a = self.arg(g, "A")?;
+                let a = ext(&a, w.max(a.len()), sa);
+                let fill = match a.last() {
+                    Some(s) if tp == "$sshr" && sa => s.clone(),
+                    _ => ZERO,
+                };
+                let mut v = g.shift(&a, &self.arg(g, "B")?, false, &fill);
+                v.truncate(w);
+                v
+            }
+            "$lt" | "$le" | "$gt" | "$ge" => {
+                let (a, b) = (self.arg(g, "A")?, self.arg(g, "B")?);
+                let v = match tp {
+                    "$lt" => g.lt(&a, &b, sa),
+                    "$ge" => {
+                        let v = g.lt(&a, &b, sa);
+                        g.not(&v)
+                    }
+                    "$gt" => g.lt(&b, &a, sa),
+                    _ => {
+                        let v = g.lt(&b, &a, sa);
+                        g.not(&v)
+                    }
+                };
+                bit(v, self.int("\\Y_WIDTH")?)
+            }
+            "$eq" | "$ne" => {
+                let (a, b) = (self.arg(g, "A")?, self.arg(g, "B")?);
+                let n = a.len().max(b.len());
+                let v = g.eq(&ext(&a, n, sa), &ext(&b, n, sb));
+                let v = if tp == "$ne" { g.not(&v) } else { v };
+                bit(v, self.int("\\Y_WIDTH")?)
+            }
+            "$add" | "$sub" | "$mul" => {
+                let w = self.int("\\Y_WIDTH")?;
+                let a = ext(&self.arg(g, "A")?, w, sa);
+                let b = ext(&self.arg(g, "B")?, w, sb);
+                match tp {
+                    "$add" => g.add(&a, &b, ZERO),
+                    "$sub" => g.sub(&a, &b),
+                    _ => g.mul(&a, &b),
+                }
+            }
+            "$mux" => {
+                let w = self.int("\\WIDTH")?;
+                let a = ext(&self.port(g, "\\A")?, w, false);
+                let b = ext(&self.port(g, "\\B")?, w, false);
+                let s = self.port(g, "\\S")?.remove(0);
+                a.iter().zip(&b).map(|(x, y)| g.mux(x, y, &s)).collect()
+            }
+            // the cases are assumed one-hot, like yosys' techmap does
+            "$pmux" => {
+                let w = self.int("\\WIDTH")?;
+                let a = ext(&self.port(g, "\\A")?, w, false);
+                let b = self.port(g, "\\B")?;
+                let s = self.port(g, "\\S")?;
+                let b = ext(&b, w * s.len(), false);
+                let any = g.reduce_or(&s);
+                (0..w)
+                    .map(|i| {
+                        let hits: Vec<SigBit> = s
+                            .iter()
+                            .enumerate()
+                            .map(|(j, x)| g.and(x, &b[j * w + i]))
+                            .collect();
+                        let v = g.reduce_or(&hits);
+                        g.mux(&a[i], &v, &any)
+                    })
+                    .collect()
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// Lower word-level internal cells into single-bit `$_NOT_`, `$_AND_`,
+/// `$_OR_`, `$_XOR_` and `$_MUX_` gates, like yosys' `techmap` with its
+/// default library: bitwise, reduction and logic operators, shifts by a
+/// variable amount, comparisons, `$add`, `$sub`, `$mul`, `$mux` and `$pmux`.
+/// Operands are extended following their `A_SIGNED`/`B_SIGNED` parameters;
+/// binary operators other than shifts are only signed when both are set.
+/// `x` bits are not modelled, and gates with constant inputs are folded
+/// away. The gates drive fresh wires connected to the old outputs. Other
+/// cells are kept. Returns the number of cells lowered.
+pub fn techmap(module: &mut Module) -> Result<usize> {
+    let mut wt = WireTable::new(module);
+    let cells = std::mem::take(module.cells_mut());
+    let mut count = 0;
+    for cell in cells {
+        let mut g = Gates {
+            module,
+            wt: &mut wt,
+        };
+        let y = match (Mapper { cell: &cell }).map(&mut g)? {
+            Some(y) => y,
+            None => {
+                module.cells_mut().push(cell);
+                continue;
+            }
+        };
+        if let Some(out) = cell.port("\\Y") {
+            let c = Connect::new(out.clone(), wt.sigspec(&y));
+            module.connects_mut().push(c);
+        }
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,115 @@
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashSet;
+
+fn instances(design: &Design, module: &str) -> usize {
+    design
+        .modules()
+        .iter()
+        .map(|m| m.cells().iter().filter(|c| c.i1() == module).count())
+        .sum()
+}
+
+fn fresh_name(design: &Design, parent: &str, cell: &str) -> String {
+    let base = format!("{}.{}", parent, cell.strip_prefix('\\').unwrap_or(cell));
+    let mut name = base.clone();
+    let mut i = 1;
+    while design.module(&name).is_some() {
+        name = format!("{}${}", base, i);
+        i += 1;
+    }
+    name
+}
+
+// Give the instance `cell` of `parent` its own copy of the instantiated
+// module, if it is shared. Returns the module now instantiated by the cell
+// (None for non-module cells) and whether a copy was made.
+fn uniquify_cell(design: &mut Design, parent: &str, cell: &str) -> Result<(Option<String>, bool)> {
+    let tp = design
+        .module(parent)
+        .and_then(|m| m.cell(cell))
+        .map(|c| c.i1().clone())
+        .ok_or_else(|| anyhow!("cell `{}' not found in module `{}'", cell, parent))?;
+    let m = match design.module(&tp) {
+        Some(m) => m,
+        None => return Ok((None, false)),
+    };
+    if instances(design, &tp) <= 1 {
+        return Ok((Some(tp), false));
+    }
+
+    let name = fresh_name(design, parent, cell);
+    let mut m = m.clone();
+    *m.ident_mut() = name.clone();
+    m.attrs_mut().insert("\\unique".to_string(), Const::Int(1));
+    design.modules_mut().push(m);
+    *design
+        .module_mut(parent)
+        .and_then(|m| m.cell_mut(cell))
+        .unwrap()
+        .i1_mut() = name.clone();
+    Ok((Some(name), true))
+}
+
+// Resolve `path` (top module followed by cell names) to the module
+// instantiated at its end, uniquifying every step on the way.
+fn uniquify_path(design: &mut Design, path: &[&str], count: &mut usize) -> Result<String> {
+    let (top, cells) = match path.split_first() {
+        Some(v) => v,
+        None => bail!("empty instance path"),
+    };
+    if design.module(top).is_none() {
+        bail!("module `{}' not found", top);
+    }
+    let mut module = top.to_string();
+    for cell in cells {
+        let (next, copied) = uniquify_cell(design, &module, cell)?;
+        if copied {
+            *count += 1;
+        }
+        module = next
+            .ok_or_else(|| anyhow!("cell `{}' in `{}' is not a module instance", cell, module))?;
+    }
+    Ok(module)
+}
+
+/// Make the module instantiated at `path` private to that instance, so it can
+/// be edited without affecting other instances. `path` is the top module
+/// followed by the cell names leading to the instance. Shared ancestors are
+/// uniquified as well; calling it again on the same path is a no-op.
+///
+/// Returns the name of the module now instantiated at `path`.
+pub fn uniquify(design: &mut Design, path: &[&str]) -> Result<String> {
+    uniquify_path(design, path, &mut 0)
+}
+
+/// Like [`uniquify`], but also uniquifies every instance below `path`, so the
+/// whole subtree is private. Returns the number of modules created.
+pub fn uniquify_tree(design: &mut Design, path: &[&str]) -> Result<usize> {
+    let mut count = 0;
+    let root = uniquify_path(design, path, &mut count)?;
+    let mut seen = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(module) = stack.pop() {
+        if !seen.insert(module.clone()) {
+            bail!("module `{}' is instantiated recursively", module);
+        }
+        let cells: Vec<String> = design
+            .module(&module)
+            .unwrap()
+            .cells()
+            .iter()
+            .map(|c| c.i2().clone())
+            .collect();
+        for cell in cells {
+            let (next, copied) = uniquify_cell(design, &module, &cell)?;
+            if copied {
+                count += 1;
+            }
+            if let Some(next) = next {
+                stack.push(next);
+            }
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,454 @@
+// Copyright (c) 2020 xhe
+
+//! A small CDCL SAT solver, and equivalence checking built on it.
+
+use crate::cnf::{Cnf, ModuleCnf};
+use crate::passes::miter;
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+// Index of literal `l` in the watch lists.
+fn index(l: i32) -> usize {
+    2 * l.unsigned_abs() as usize + (l < 0) as usize
+}
+
+fn var(l: i32) -> usize {
+    l.unsigned_abs() as usize
+}
+
+/// Conflict-driven clause learning solver with two watched literals per
+/// clause, first-UIP learning, activity-based decisions and restarts.
+/// Literals are numbered as in [`Cnf`].
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    clauses: Vec<Vec<i32>>,
+    /// Clauses watching each literal, by [`index`].
+    watches: Vec<Vec<usize>>,
+    /// Value of each variable: 1, -1, or 0 if unassigned.
+    values: Vec<i8>,
+    level: Vec<usize>,
+    /// Clause implying each assigned variable, whose first literal it is.
+    reason: Vec<Option<usize>>,
+    trail: Vec<i32>,
+    /// Start of each decision level in the trail.
+    levels: Vec<usize>,
+    head: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    /// Unassigned variables, and maybe some assigned ones, in a heap
+    /// ordered by activity.
+    heap: Vec<usize>,
+    /// Position of each variable in the heap.
+    slot: Vec<Option<usize>>,
+    /// Scratch marks of variables for conflict analysis.
+    seen: Vec<bool>,
+    /// Last value of each variable, tried first on decisions.
+    phase: Vec<bool>,
+    /// Cleared once a conflict is found without decisions.
+    ok: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            values: vec![0],
+            level: vec![0],
+            reason: vec![None],
+            activity: vec![0.0],
+            phase: vec![false],
+            slot: vec![None],
+            seen: vec![false],
+            watches: vec![Vec::new(); 2],
+            bump: 1.0,
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    /// Solver of the clauses of `cnf`.
+    pub fn from_cnf(cnf: &Cnf) -> Self {
+        let mut s = Self::new();
+        s.reserve(*cnf.vars());
+        for c in cnf.clauses() {
+            s.add_clause(c);
+        }
+        s
+    }
+
+    pub fn vars(&self) -> usize {
+        self.values.len() - 1
+    }
+
+    // Make room for the variables up to `n`.
+    fn reserve(&mut self, n: usize) {
+        while self.vars() < n {
+            self.values.push(0);
+            self.level.push(0);
+            self.reason.push(None);
+            self.activity.push(0.0);
+            self.phase.push(false);
+            self.slot.push(None);
+            self.seen.push(false);
+            self.watches.push(Vec::new());
+            self.watches.push(Vec::new());
+            self.insert(self.vars());
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        let v = self.heap[i];
+        while i > 0 {
+            let p = (i - 1) / 2;
+            if self.activity[self.heap[p]] >= self.activity[v] {
+                break;
+            }
+            self.heap[i] = self.heap[p];
+            self.slot[self.heap[i]] = Some(i);
+            i = p;
+        }
+        self.heap[i] = v;
+        self.slot[v] = Some(i);
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let v = self.heap[i];
+        let n = self.heap.len();
+        loop {
+            let l = 2 * i + 1;
+            if l >= n {
+                break;
+            }
+            let r = l + 1;
+            let c = if r < n && self.activity[self.heap[r]] > self.activity[self.heap[l]] {
+                r
+            } else {
+                l
+            };
+            if self.activity[self.heap[c]] <= self.activity[v] {
+                break;
+            }
+            self.heap[i] = self.heap[c];
+            self.slot[self.heap[i]] = Some(i);
+            i = c;
+        }
+        self.heap[i] = v;
+        self.slot[v] = Some(i);
+    }
+
+    fn insert(&mut self, v: usize) {
+        if self.slot[v].is_none() {
+            self.heap.push(v);
+            self.sift_up(self.heap.len() - 1);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let v = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.slot[v] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.slot[last] = Some(0);
+            self.sift_down(0);
+        }
+        Some(v)
+    }
+
+    fn bump(&mut self, v: usize) {
+        self.activity[v] += self.bump;
+        if self.activity[v] > 1e100 {
+            self.activity.iter_mut().for_each(|a| *a *= 1e-100);
+            self.bump *= 1e-100;
+        }
+        if let Some(i) = self.slot[v] {
+            self.sift_up(i);
+        }
+    }
+
+    /// Value of literal `l` in the current assignment, which is a model
+    /// after [`Solver::solve`] returned true.
+    pub fn value(&self, l: i32) -> Option<bool> {
+        match self.values.get(var(l)).copied().unwrap_or(0) {
+            0 => None,
+            v => Some((v > 0) == (l > 0)),
+        }
+    }
+
+    fn assign(&mut self, l: i32, reason: Option<usize>) {
+        let v = var(l);
+        self.values[v] = if l > 0 { 1 } else { -1 };
+        self.level[v] = self.levels.len();
+        self.reason[v] = reason;
+        self.trail.push(l);
+    }
+
+    /// Add a clause. Only valid before solving or between calls.
+    pub fn add_clause(&mut self, lits: &[i32]) {
+        self.cancel(0);
+        let mut c: Vec<i32> = Vec::new();
+        for &l in lits {
+            self.reserve(var(l));
+            if c.contains(&-l) || self.value(l) == Some(true) {
+                return;
+            }
+            if !c.contains(&l) && self.value(l) != Some(false) {
+                c.push(l);
+            }
+        }
+        match c.len() {
+            0 => self.ok = false,
+            1 => self.assign(c[0], None),
+            _ => {
+                self.attach(c);
+            }
+        }
+    }
+
+    fn attach(&mut self, c: Vec<i32>) -> usize {
+        let i = self.clauses.len();
+        self.watches[index(c[0])].push(i);
+        self.watches[index(c[1])].push(i);
+        self.clauses.push(c);
+        i
+    }
+
+    // Propagate the assignments of the trail, returning a conflicting
+    // clause if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let f = -self.trail[self.head];
+            self.head += 1;
+            let ws = std::mem::take(&mut self.watches[index(f)]);
+            let mut keep = Vec::with_capacity(ws.len());
+            for (n, &ci) in ws.iter().enumerate() {
+                let c = &mut self.clauses[ci];
+                if c[0] == f {
+                    c.swap(0, 1);
+                }
+                let first = c[0];
+                if self.values[var(first)] != 0 && (self.values[var(first)] > 0) == (first > 0) {
+                    keep.push(ci);
+                    continue;
+                }
+                let values = &self.values;
+                let free = (2..c.len()).find(|&k| {
+                    let l = c[k];
+                    values[var(l)] == 0 || (values[var(l)] > 0) == (l > 0)
+                });
+                if let Some(k) = free {
+                    c.swap(1, k);
+                    let l = c[1];
+                    self.watches[index(l)].push(ci);
+                    continue;
+                }
+                keep.push(ci);
+                if self.value(first) == Some(false) {
+                    keep.extend_from_slice(&ws[n + 1..]);
+                    self.watches[index(f)] = keep;
+                    return Some(ci);
+                }
+                self.assign(first, Some(ci));
+            }
+            self.watches[index(f)] = keep;
+        }
+        None
+    }
+
+    // Learnt clause and backtrack level for the conflict `confl`.
+    fn analyze(&mut self, mut confl: usize) -> (Vec<i32>, usize) {
+        let mut learnt = vec![0];
+        let mut count = 0;
+        let mut p: Option<i32> = None;
+        let mut i = self.trail.len();
+        let current = self.levels.len();
+        loop {
+            let start = if p.is_some() { 1 } else { 0 };
+            for k in start..self.clauses[confl].len() {
+                let q = self.clauses[confl][k];
+                let v = var(q);
+                if self.seen[v] || self.level[v] == 0 {
+                    continue;
+                }
+                self.seen[v] = true;
+                self.bump(v);
+                if self.level[v] == current {
+                    count += 1;
+                } else {
+                    learnt.push(q);
+                }
+            }
+            loop {
+                i -= 1;
+                if self.seen[var(self.trail[i])] {
+                    break;
+                }
+            }
+            let l = self.trail[i];
+            self.seen[var(l)] = false;
+            count -= 1;
+            p = Some(l);
+            if count == 0 {
+                break;
+            }
+            confl = self.reason[var(l)].unwrap();
+        }
+        learnt[0] = -p.unwrap();
+
+        // drop the literals implied by the others
+        let redundant = |s: &Self, q: i32| match s.reason[var(q)] {
+            Some(r) => s.clauses[r][1..]
+                .iter()
+                .all(|l| s.seen[var(*l)] || s.level[var(*l)] == 0),
+            None => false,
+        };
+        let keep: Vec<bool> = learnt.iter().map(|q| !redundant(self, *q)).collect();
+        for q in learnt[1..].iter() {
+            self.seen[var(*q)] = false;
+        }
+        let mut learnt: Vec<i32> = learnt
+            .into_iter()
+            .zip(keep)
+            .enumerate()
+            .filter(|(k, (_, keep))| *k == 0 || *keep)
+            .map(|(_, (q, _))| q)
+            .collect();
+
+        let mut back = 0;
+        for k in 1..learnt.len() {
+            let lv = self.level[var(learnt[k])];
+            if lv > back {
+                back = lv;
+                learnt.swap(1, k);
+            }
+        }
+        self.bump /= 0.95;
+        (learnt, back)
+    }
+
+    // Undo the assignments above decision level `level`.
+    fn cancel(&mut self, level: usize) {
+        if self.levels.len() <= level {
+            return;
+        }
+        let start = self.levels[level];
+        let undone: Vec<i32> = self.trail.drain(start..).collect();
+        for l in undone {
+            let v = var(l);
+            self.phase[v] = l > 0;
+            self.values[v] = 0;
+            self.reason[v] = None;
+            self.insert(v);
+        }
+        self.levels.truncate(level);
+        self.head = start;
+    }
+
+    fn decide(&mut self) -> Option<i32> {
+        while let Some(v) = self.pop() {
+            if self.values[v] == 0 {
+                return Some(if self.phase[v] { v as i32 } else { -(v as i32) });
+            }
+        }
+        None
+    }
+
+    /// Search for an assignment satisfying the clauses and `assumptions`.
+    pub fn solve_with(&mut self, assumptions: &[i32]) -> bool {
+        self.cancel(0);
+        if !self.ok || self.propagate().is_some() {
+            self.ok = false;
+            return false;
+        }
+        for &a in assumptions {
+            self.reserve(var(a));
+        }
+        let mut conflicts = 0;
+        let mut limit = 100;
+        loop {
+            if let Some(confl) = self.propagate() {
+                if self.levels.is_empty() {
+                    self.ok = false;
+                    return false;
+                }
+                let (learnt, back) = self.analyze(confl);
+                self.cancel(back);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let first = learnt[0];
+                    let ci = self.attach(learnt);
+                    self.assign(first, Some(ci));
+                }
+                conflicts += 1;
+                if conflicts >= limit {
+                    conflicts = 0;
+                    limit += limit / 2;
+                    self.cancel(0);
+                }
+                continue;
+            }
+            // assumptions take the first decision levels
+            let next = match assumptions.get(self.levels.len()) {
+                Some(&a) => match self.value(a) {
+                    Some(false) => return false,
+                    Some(true) => {
+                        self.levels.push(self.trail.len());
+                        continue;
+                    }
+                    None => a,
+                },
+                None => match self.decide() {
+                    Some(l) => l,
+                    None => return true,
+                },
+            };
+            self.levels.push(self.trail.len());
+            self.assign(next, None);
+        }
+    }
+
+    /// Search for an assignment satisfying the clauses.
+    pub fn solve(&mut self) -> bool {
+        self.solve_with(&[])
+    }
+}
+
+/// Check that the gate-level modules `gold` and `gate`, which must have the
+/// same ports, compute the same outputs from the same inputs. Returns an
+/// input assignment for which they differ, by port, if there is one.
+///
+/// Both modules must be combinational and built of the gate cells that
+/// [`ModuleCnf`] encodes; running [`techmap`](crate::passes::techmap)
+/// first takes word-level cells down to those.
+pub fn equiv(gold: &Module, gate: &Module) -> Result<Option<BTreeMap<String, Const>>> {
+    let m = miter(gold, gate, "\\miter")?;
+    let mut enc = ModuleCnf::new(&m)?;
+    if !enc.state().is_empty() {
+        bail!("can not check equivalence of sequential modules");
+    }
+    let trigger = enc.lit(&SigBit::Wire(("\\trigger".to_string(), 0)));
+    enc.add(&[trigger]);
+    let mut inputs = Vec::new();
+    for w in m.wires().iter().filter(|w| *w.input()) {
+        let lits = enc.lits(&SigSpec::wire(w.id()))?;
+        inputs.push((w.id().clone(), lits));
+    }
+    let mut s = Solver::from_cnf(enc.cnf());
+    if !s.solve() {
+        return Ok(None);
+    }
+    let value = |l: i32| match s.value(l) {
+        Some(true) => State::S1,
+        _ => State::S0,
+    };
+    Ok(Some(
+        inputs
+            .into_iter()
+            .map(|(id, lits)| {
+                let bits: Vec<State> = lits.iter().map(|l| value(*l)).collect();
+                (id, Const::from_bits(&bits))
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,24 @@
+// Copyright (c) 2020 xhe
+
+//! Simulation of designs.
+
+use crate::syntax::State;
+
+/// Index of a simulated net: a group of wire bits connected together.
+pub(crate) type Net = usize;
+
+// Nets 0 to 5 hold the constants, in the order of `State`.
+pub(crate) fn const_net(s: State) -> Net {
+    s as usize
+}
+
+pub(crate) mod ff;
+pub(crate) mod memory;
+mod process;
+
+mod simulator;
+pub use simulator::*;
+mod testbench;
+pub use testbench::*;
+mod vcd;
+pub use vcd::*;
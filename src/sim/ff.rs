@@ -0,0 +1,279 @@
+use super::Net;
+use crate::syntax::*;
+use anyhow::{bail, Result};
+
+use State::{Sx, S0, S1};
+
+// Value of an active-`pol` control signal: S1 when active, S0 when not.
+pub(crate) fn level(v: State, pol: bool) -> State {
+    match (v, pol) {
+        (S1, true) | (S0, false) => S1,
+        (S0, true) | (S1, false) => S0,
+        _ => Sx,
+    }
+}
+
+// Bitwise `s ? b : a`, keeping the bits `a` and `b` agree on when `s` is
+// undefined.
+pub(crate) fn select(a: &[State], b: &[State], s: State) -> Vec<State> {
+    match s {
+        S0 => a.to_vec(),
+        S1 => b.to_vec(),
+        _ => a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| if x == y { *x } else { Sx })
+            .collect(),
+    }
+}
+
+// Whether a clock moved from one defined level to the other since the last
+// call, towards its active level `pol` if given. `last` starts undefined, so
+// the first defined value seen is a baseline and never an edge.
+pub(crate) fn edge(last: &mut State, cur: State, pol: Option<bool>) -> bool {
+    let r = match pol {
+        Some(p) => (*last, cur) == (level(S0, p), level(S1, p)),
+        None => matches!((*last, cur), (S0, S1) | (S1, S0)),
+    };
+    *last = cur;
+    r
+}
+
+/// A storage cell of any of yosys' flip-flop and latch types, normalised to
+/// a single model over bits of type `N`.
+#[derive(Debug, Clone)]
+pub(crate) struct Ff<N = Net> {
+    pub q: Vec<N>,
+    pub d: Vec<N>,
+    pub clk: Option<(N, bool)>,
+    pub en: Option<(N, bool)>,
+    pub arst: Option<(N, bool, Vec<State>)>,
+    pub srst: Option<(N, bool, Vec<State>)>,
+    pub ce_over_srst: bool,
+    pub aload: Option<(N, bool, Vec<N>)>,
+    pub set: Option<(Vec<N>, bool)>,
+    pub clr: Option<(Vec<N>, bool)>,
+    /// Driven by the implicit global clock (`$ff`).
+    pub global: bool,
+    pub last_clk: State,
+}
+
+fn pol(c: char) -> bool {
+    c == 'P'
+}
+
+impl<N: Clone> Ff<N> {
+    /// Model of `cell`, or None if it is not a storage cell. `port` resolves
+    /// a port name to its bits.
+    pub fn new<F>(cell: &Cell, port: F) -> Result<Option<Self>>
+    where
+        F: Fn(&str) -> Result<Vec<N>>,
+    {
+        let tp = cell.i1().as_str();
+        let mut ff = Ff {
+            q: vec![],
+            d: vec![],
+            clk: None,
+            en: None,
+            arst: None,
+            srst: None,
+            ce_over_srst: false,
+            aload: None,
+            set: None,
+            clr: None,
+            global: false,
+            last_clk: Sx,
+        };
+        let bit = |k: &str| -> Result<N> {
+            match port(k)?.first() {
+                Some(n) => Ok(n.clone()),
+                None => bail!("port `{}' of cell `{}' is empty", k, cell.i2()),
+            }
+        };
+
+        if tp.starts_with("$_") {
+            let name = tp.trim_start_matches("$_").trim_end_matches('_');
+            let (kind, p) = match name.rfind('_') {
+                Some(i) => (&name[..i], name[i + 1..].chars().collect::<Vec<_>>()),
+                None => (name, vec![]),
+            };
+            let value = |c: char| vec![if c == '1' { S1 } else { S0 }];
+            let known = matches!(
+                kind,
+                "FF" | "DFF"
+                    | "DFFE"
+                    | "SDFF"
+                    | "SDFFE"
+                    | "SDFFCE"
+                    | "ALDFF"
+                    | "ALDFFE"
+                    | "DFFSR"
+                    | "DFFSRE"
+                    | "SR"
+                    | "DLATCH"
+                    | "DLATCHSR"
+            );
+            if !known {
+                return Ok(None);
+            }
+            ff.q = port("\\Q")?;
+            if kind != "SR" {
+                ff.d = port("\\D")?;
+            }
+            match (kind, p.as_slice()) {
+                ("FF", []) => ff.global = true,
+                ("DFF", [c]) => ff.clk = Some((bit("\\C")?, pol(*c))),
+                ("DFF", [c, r, v]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.arst = Some((bit("\\R")?, pol(*r), value(*v)));
+                }
+                ("DFFE", [c, e]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                }
+                ("DFFE", [c, r, v, e]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.arst = Some((bit("\\R")?, pol(*r), value(*v)));
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                }
+                ("SDFF", [c, r, v]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.srst = Some((bit("\\R")?, pol(*r), value(*v)));
+                }
+                ("SDFFE", [c, r, v, e]) | ("SDFFCE", [c, r, v, e]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.srst = Some((bit("\\R")?, pol(*r), value(*v)));
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                    ff.ce_over_srst = kind == "SDFFCE";
+                }
+                ("ALDFF", [c, l]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.aload = Some((bit("\\L")?, pol(*l), port("\\AD")?));
+                }
+                ("ALDFFE", [c, l, e]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.aload = Some((bit("\\L")?, pol(*l), port("\\AD")?));
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                }
+                ("DFFSR", [c, s, r]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.set = Some((port("\\S")?, pol(*s)));
+                    ff.clr = Some((port("\\R")?, pol(*r)));
+                }
+                ("DFFSRE", [c, s, r, e]) => {
+                    ff.clk = Some((bit("\\C")?, pol(*c)));
+                    ff.set = Some((port("\\S")?, pol(*s)));
+                    ff.clr = Some((port("\\R")?, pol(*r)));
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                }
+                ("SR", [s, r]) => {
+                    ff.set = Some((port("\\S")?, pol(*s)));
+                    ff.clr = Some((port("\\R")?, pol(*r)));
+                }
+                ("DLATCH", [e]) => ff.en = Some((bit("\\E")?, pol(*e))),
+                ("DLATCH", [e, r, v]) => {
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                    ff.arst = Some((bit("\\R")?, pol(*r), value(*v)));
+                }
+                ("DLATCHSR", [e, s, r]) => {
+                    ff.en = Some((bit("\\E")?, pol(*e)));
+                    ff.set = Some((port("\\S")?, pol(*s)));
+                    ff.clr = Some((port("\\R")?, pol(*r)));
+                }
+                _ => bail!("unknown cell type `{}'", tp),
+            }
+            return Ok(Some(ff));
+        }
+
+        let param = |k: &str| matches!(cell.param(k), Some(v) if v.as_bool());
+        let bits = |k: &str| cell.param(k).map(|v| v.to_bits()).unwrap_or_default();
+        let has = |k: &str| cell.port(k).is_some();
+        match tp {
+            "$ff" | "$dff" | "$dffe" | "$adff" | "$adffe" | "$aldff" | "$aldffe" | "$sdff"
+            | "$sdffe" | "$sdffce" | "$dffsr" | "$dffsre" | "$sr" | "$dlatch" | "$adlatch"
+            | "$dlatchsr" => (),
+            _ => return Ok(None),
+        }
+        ff.q = port("\\Q")?;
+        let width = ff.q.len();
+        if tp != "$sr" {
+            ff.d = port("\\D")?;
+        }
+        if tp == "$ff" {
+            ff.global = true;
+        }
+        if has("\\CLK") {
+            ff.clk = Some((bit("\\CLK")?, param("\\CLK_POLARITY")));
+        }
+        if has("\\EN") {
+            ff.en = Some((bit("\\EN")?, param("\\EN_POLARITY")));
+        }
+        if has("\\ARST") {
+            let mut v = bits("\\ARST_VALUE");
+            v.resize(width, S0);
+            ff.arst = Some((bit("\\ARST")?, param("\\ARST_POLARITY"), v));
+        }
+        if has("\\SRST") {
+            let mut v = bits("\\SRST_VALUE");
+            v.resize(width, S0);
+            ff.srst = Some((bit("\\SRST")?, param("\\SRST_POLARITY"), v));
+            ff.ce_over_srst = tp == "$sdffce";
+        }
+        if has("\\ALOAD") {
+            ff.aload = Some((bit("\\ALOAD")?, param("\\ALOAD_POLARITY"), port("\\AD")?));
+        }
+        if has("\\SET") {
+            ff.set = Some((port("\\SET")?, param("\\SET_POLARITY")));
+        }
+        if has("\\CLR") {
+            ff.clr = Some((port("\\CLR")?, param("\\CLR_POLARITY")));
+        }
+        Ok(Some(ff))
+    }
+}
+
+impl Ff {
+    /// Next value of `q`. Clock edges are detected against the clock value
+    /// seen by the previous call; `tick` advances the global clock.
+    pub fn next<F: Fn(Net) -> State>(&mut self, v: F, tick: bool) -> Vec<State> {
+        let get = |n: &[Net]| n.iter().map(|x| v(*x)).collect::<Vec<_>>();
+        let mut r = get(&self.q);
+        if let Some((c, p)) = self.clk {
+            if edge(&mut self.last_clk, v(c), Some(p)) {
+                let en = self.en.as_ref().map_or(S1, |(n, p)| level(v(*n), *p));
+                let d = get(&self.d);
+                let srst = self.srst.as_ref().map(|(n, p, rv)| (level(v(*n), *p), rv));
+                r = match srst {
+                    Some((s, rv)) if self.ce_over_srst => {
+                        let loaded = select(&r, &d, en);
+                        let reset = select(&r, rv, en);
+                        select(&loaded, &reset, s)
+                    }
+                    Some((s, rv)) => select(&select(&r, &d, en), rv, s),
+                    None => select(&r, &d, en),
+                };
+            }
+        } else if self.global {
+            if tick {
+                r = get(&self.d);
+            }
+        } else if let Some((n, p)) = self.en {
+            r = select(&r, &get(&self.d), level(v(n), p));
+        }
+
+        if let Some((n, p, ad)) = &self.aload {
+            r = select(&r, &get(ad), level(v(*n), *p));
+        }
+        if let (Some((set, sp)), Some((clr, cp))) = (&self.set, &self.clr) {
+            for (i, q) in r.iter_mut().enumerate() {
+                let s = level(v(set[i]), *sp);
+                let c = level(v(clr[i]), *cp);
+                *q = select(&select(&[*q], &[S1], s), &[S0], c)[0];
+            }
+        }
+        if let Some((n, p, rv)) = &self.arst {
+            r = select(&r, rv, level(v(*n), *p));
+        }
+        r
+    }
+}
@@ -0,0 +1,291 @@
+use super::ff::select;
+use super::Net;
+use crate::syntax::*;
+use anyhow::{anyhow, Result};
+
+use State::{Sx, S0, S1};
+
+fn addr_value(addr: &[State]) -> Option<i64> {
+    let mut v: i64 = 0;
+    for (i, b) in addr.iter().enumerate() {
+        match b {
+            S1 if i < 63 => v |= 1 << i,
+            S0 => (),
+            _ => return None,
+        }
+    }
+    Some(v)
+}
+
+/// Contents of a memory, one word per address.
+#[derive(Debug, Clone)]
+pub(crate) struct Mem {
+    pub id: String,
+    pub width: usize,
+    pub offset: i64,
+    pub data: Vec<Vec<State>>,
+}
+
+impl Mem {
+    pub fn new(id: &str, width: usize, offset: i64, size: usize) -> Self {
+        Self {
+            id: id.to_string(),
+            width,
+            offset,
+            data: vec![vec![Sx; width]; size],
+        }
+    }
+
+    fn index(&self, addr: &[State]) -> Option<usize> {
+        let a = addr_value(addr)? - self.offset;
+        if a >= 0 && (a as usize) < self.data.len() {
+            Some(a as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn read(&self, addr: &[State]) -> Vec<State> {
+        match self.index(addr) {
+            Some(i) => self.data[i].clone(),
+            None => vec![Sx; self.width],
+        }
+    }
+
+    /// Write the bits of `data` enabled by `en` to the word at `addr`,
+    /// returning whether the contents changed. `data` may span several
+    /// consecutive words.
+    pub fn write(&mut self, addr: &[State], data: &[State], en: &[State]) -> bool {
+        let base = match addr_value(addr) {
+            Some(a) => a,
+            None => return false,
+        };
+        let mut changed = false;
+        for (w, chunk) in data.chunks(self.width.max(1)).enumerate() {
+            let a = base + w as i64 - self.offset;
+            if a < 0 || a as usize >= self.data.len() {
+                continue;
+            }
+            let word = &mut self.data[a as usize];
+            for (i, b) in chunk.iter().enumerate() {
+                let e = en.get(w * self.width + i).cloned().unwrap_or(S0);
+                let v = select(&[word[i]], &[*b], e)[0];
+                changed |= word[i] != v;
+                word[i] = v;
+            }
+        }
+        changed
+    }
+}
+
+/// A memory read port over bits of type `N`. A missing enable is always
+/// active.
+#[derive(Debug, Clone)]
+pub(crate) struct RdPort<N = Net> {
+    pub mem: usize,
+    pub clk: Option<(N, bool)>,
+    pub last_clk: State,
+    pub en: Option<N>,
+    pub arst: Option<(N, Vec<State>)>,
+    pub srst: Option<(N, Vec<State>)>,
+    pub ce_over_srst: bool,
+    pub transparent: bool,
+    pub addr: Vec<N>,
+    pub data: Vec<N>,
+}
+
+/// A memory write port over bits of type `N`.
+#[derive(Debug, Clone)]
+pub(crate) struct WrPort<N = Net> {
+    pub mem: usize,
+    pub clk: Option<(N, bool)>,
+    pub last_clk: State,
+    pub en: Vec<N>,
+    pub addr: Vec<N>,
+    pub data: Vec<N>,
+}
+
+impl RdPort {
+    /// Value of a synchronous port after a clock edge.
+    pub fn clocked<F: Fn(Net) -> State>(&self, v: F, mem: &Mem) -> Vec<State> {
+        let get = |n: &[Net]| n.iter().map(|x| v(*x)).collect::<Vec<_>>();
+        let cur = get(&self.data);
+        let en = self.en.map_or(S1, &v);
+        let srst = self.srst.as_ref().map(|(n, rv)| (v(*n), rv));
+        let r = select(&cur, &mem.read(&get(&self.addr)), en);
+        match srst {
+            Some((s, rv)) if self.ce_over_srst => select(&r, &select(&cur, rv, en), s),
+            Some((s, rv)) => select(&r, rv, s),
+            None => r,
+        }
+    }
+}
+
+fn bit(v: &[State], i: usize) -> bool {
+    v.get(i) == Some(&S1)
+}
+
+fn slice<T: Clone>(v: &[T], i: usize, n: usize) -> Vec<T> {
+    v.iter().skip(i * n).take(n).cloned().collect()
+}
+
+type MemPorts<N> = (Mem, Vec<RdPort<N>>, Vec<WrPort<N>>);
+
+/// Ports of a `$mem`/`$mem_v2` cell.
+pub(crate) fn mem_cell<N, F>(cell: &Cell, mem: usize, port: F) -> Result<MemPorts<N>>
+where
+    N: Clone,
+    F: Fn(&str) -> Result<Vec<N>>,
+{
+    let int = |k: &str| -> Result<usize> {
+        cell.param(k)
+            .and_then(|v| v.as_int())
+            .map(|v| v.max(0) as usize)
+            .ok_or_else(|| anyhow!("cell `{}' has no parameter `{}'", cell.i2(), k))
+    };
+    let bits = |k: &str| cell.param(k).map(|v| v.to_bits()).unwrap_or_default();
+    let id = match cell.param("\\MEMID") {
+        Some(Const::Str(s)) => s.clone(),
+        _ => cell.i2().clone(),
+    };
+    let (width, abits, size) = (int("\\WIDTH")?, int("\\ABITS")?, int("\\SIZE")?);
+    let offset = cell.param("\\OFFSET").and_then(|v| v.as_int()).unwrap_or(0);
+    let mut m = Mem::new(&id, width, offset, size);
+    let init = bits("\\INIT");
+    for (i, w) in m.data.iter_mut().enumerate() {
+        for (j, b) in w.iter_mut().enumerate() {
+            if let Some(s) = init.get(i * width + j) {
+                *b = *s;
+            }
+        }
+    }
+
+    let v2 = cell.i1() == "$mem_v2";
+    let (nrd, nwr) = (int("\\RD_PORTS")?, int("\\WR_PORTS")?);
+    let (rclk, ren, raddr, rdata) = (
+        port("\\RD_CLK")?,
+        port("\\RD_EN")?,
+        port("\\RD_ADDR")?,
+        port("\\RD_DATA")?,
+    );
+    let (rarst, rsrst) = if v2 {
+        (port("\\RD_ARST")?, port("\\RD_SRST")?)
+    } else {
+        (vec![], vec![])
+    };
+    let (rce, rpol) = (bits("\\RD_CLK_ENABLE"), bits("\\RD_CLK_POLARITY"));
+    let (arstv, srstv) = (bits("\\RD_ARST_VALUE"), bits("\\RD_SRST_VALUE"));
+    let ce_over = bits("\\RD_CE_OVER_SRST");
+    let transp = if v2 {
+        bits("\\RD_TRANSPARENCY_MASK")
+    } else {
+        bits("\\RD_TRANSPARENT")
+    };
+    let mut rd = Vec::new();
+    for i in 0..nrd {
+        let transparent = if v2 {
+            (0..nwr).any(|j| bit(&transp, i * nwr + j))
+        } else {
+            bit(&transp, i)
+        };
+        rd.push(RdPort {
+            mem,
+            clk: match rclk.get(i) {
+                Some(c) if bit(&rce, i) => Some((c.clone(), bit(&rpol, i))),
+                _ => None,
+            },
+            last_clk: Sx,
+            en: ren.get(i).cloned(),
+            arst: rarst.get(i).map(|n| (n.clone(), slice(&arstv, i, width))),
+            srst: rsrst.get(i).map(|n| (n.clone(), slice(&srstv, i, width))),
+            ce_over_srst: bit(&ce_over, i),
+            transparent,
+            addr: slice(&raddr, i, abits),
+            data: slice(&rdata, i, width),
+        });
+    }
+
+    let (wclk, wen, waddr, wdata) = (
+        port("\\WR_CLK")?,
+        port("\\WR_EN")?,
+        port("\\WR_ADDR")?,
+        port("\\WR_DATA")?,
+    );
+    let (wce, wpol) = (bits("\\WR_CLK_ENABLE"), bits("\\WR_CLK_POLARITY"));
+    let mut wr = Vec::new();
+    for i in 0..nwr {
+        wr.push(WrPort {
+            mem,
+            clk: match wclk.get(i) {
+                Some(c) if bit(&wce, i) => Some((c.clone(), bit(&wpol, i))),
+                _ => None,
+            },
+            last_clk: Sx,
+            en: slice(&wen, i, width),
+            addr: slice(&waddr, i, abits),
+            data: slice(&wdata, i, width),
+        });
+    }
+    Ok((m, rd, wr))
+}
+
+/// Port of a `$memrd`/`$memrd_v2` cell on memory `mem`.
+pub(crate) fn rd_cell<N, F>(cell: &Cell, mem: usize, port: F) -> Result<RdPort<N>>
+where
+    N: Clone,
+    F: Fn(&str) -> Result<Vec<N>>,
+{
+    let flag = |k: &str| matches!(cell.param(k), Some(v) if v.as_bool());
+    let bits = |k: &str| cell.param(k).map(|v| v.to_bits()).unwrap_or_default();
+    let opt = |k: &str| -> Result<Option<N>> {
+        Ok(match cell.port(k) {
+            Some(_) => port(k)?.first().cloned(),
+            None => None,
+        })
+    };
+    let data = port("\\DATA")?;
+    let clk = match opt("\\CLK")? {
+        Some(c) if flag("\\CLK_ENABLE") => Some((c, flag("\\CLK_POLARITY"))),
+        _ => None,
+    };
+    let value = |k: &str| {
+        let mut v = bits(k);
+        v.resize(data.len(), Sx);
+        v
+    };
+    Ok(RdPort {
+        mem,
+        clk,
+        last_clk: Sx,
+        en: opt("\\EN")?,
+        arst: opt("\\ARST")?.map(|n| (n, value("\\ARST_VALUE"))),
+        srst: opt("\\SRST")?.map(|n| (n, value("\\SRST_VALUE"))),
+        ce_over_srst: flag("\\CE_OVER_SRST"),
+        transparent: flag("\\TRANSPARENT") || bits("\\TRANSPARENCY_MASK").contains(&S1),
+        addr: port("\\ADDR")?,
+        data,
+    })
+}
+
+/// Port of a `$memwr`/`$memwr_v2` cell on memory `mem`.
+pub(crate) fn wr_cell<N, F>(cell: &Cell, mem: usize, port: F) -> Result<WrPort<N>>
+where
+    N: Clone,
+    F: Fn(&str) -> Result<Vec<N>>,
+{
+    let flag = |k: &str| matches!(cell.param(k), Some(v) if v.as_bool());
+    let clk = match cell.port("\\CLK") {
+        Some(_) if flag("\\CLK_ENABLE") => port("\\CLK")?
+            .first()
+            .map(|c| (c.clone(), flag("\\CLK_POLARITY"))),
+        _ => None,
+    };
+    Ok(WrPort {
+        mem,
+        clk,
+        last_clk: Sx,
+        en: port("\\EN")?,
+        addr: port("\\ADDR")?,
+        data: port("\\DATA")?,
+    })
+}
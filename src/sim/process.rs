@@ -0,0 +1,206 @@
+use super::ff::{edge, level};
+use super::Net;
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+use State::{S0, S1};
+
+type Assign = (Vec<Net>, Vec<Net>);
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Case {
+    pats: Vec<Vec<Net>>,
+    assign: Vec<Assign>,
+    switch: Vec<Switch>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Switch {
+    sig: Vec<Net>,
+    cases: Vec<Case>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Trigger {
+    Always,
+    Global,
+    Init,
+    /// Active while the signal is at the given level.
+    Level(Net, bool),
+    /// Rising (true), falling (false) or any edge of the signal.
+    Edge(Net, Option<bool>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Sync {
+    pub trigger: Trigger,
+    pub updates: Vec<Assign>,
+    last: State,
+}
+
+/// A process with its signals resolved to nets.
+#[derive(Debug, Clone)]
+pub(crate) struct Proc {
+    pub root: Case,
+    pub syncs: Vec<Sync>,
+}
+
+fn read<F: Fn(Net) -> State>(v: &F, nets: &[Net]) -> Vec<State> {
+    nets.iter().map(|n| v(*n)).collect()
+}
+
+fn assigns<F>(v: &[(SigSpec, SigSpec)], resolve: &F) -> Result<Vec<Assign>>
+where
+    F: Fn(&SigSpec) -> Result<Vec<Net>>,
+{
+    v.iter()
+        .map(|(l, r)| Ok((resolve(l)?, resolve(r)?)))
+        .collect()
+}
+
+impl Case {
+    fn new<F>(c: &ProcessSwitchCase, resolve: &F) -> Result<Self>
+    where
+        F: Fn(&SigSpec) -> Result<Vec<Net>>,
+    {
+        Ok(Self {
+            pats: c.sigs().iter().map(resolve).collect::<Result<_>>()?,
+            assign: assigns(c.assign(), resolve)?,
+            switch: c
+                .switch()
+                .iter()
+                .map(|s| {
+                    Ok(Switch {
+                        sig: resolve(s.sig())?,
+                        cases: s
+                            .cases()
+                            .iter()
+                            .map(|c| Case::new(c, resolve))
+                            .collect::<Result<_>>()?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    // Whether the case is taken for switch value `sig`: `-`, `x` and `z`
+    // pattern bits match anything, as `proc` treats them, any other bit must
+    // be equal.
+    fn matches<F: Fn(Net) -> State>(&self, v: &F, sig: &[State]) -> bool {
+        self.pats.is_empty()
+            || self.pats.iter().any(|p| {
+                sig.iter().enumerate().all(|(i, s)| match p.get(i) {
+                    Some(n) => !matches!(v(*n), S0 | S1) || v(*n) == *s,
+                    None => *s == S0,
+                })
+            })
+    }
+
+    /// Values assigned by the case and the cases it selects, later
+    /// assignments overriding earlier ones. Right-hand sides read the
+    /// current values of the nets.
+    pub fn eval<F: Fn(Net) -> State>(&self, v: &F, out: &mut HashMap<Net, State>) {
+        for (l, r) in self.assign.iter() {
+            for (n, s) in l.iter().zip(read(v, r)) {
+                out.insert(*n, s);
+            }
+        }
+        for s in self.switch.iter() {
+            let sig = read(v, &s.sig);
+            if let Some(c) = s.cases.iter().find(|c| c.matches(v, &sig)) {
+                c.eval(v, out);
+            }
+        }
+    }
+
+    /// Nets read and nets assigned anywhere in the case.
+    pub fn nets(&self, ins: &mut Vec<Net>, outs: &mut Vec<Net>) {
+        for p in self.pats.iter() {
+            ins.extend(p);
+        }
+        for (l, r) in self.assign.iter() {
+            outs.extend(l);
+            ins.extend(r);
+        }
+        for s in self.switch.iter() {
+            ins.extend(&s.sig);
+            for c in s.cases.iter() {
+                c.nets(ins, outs);
+            }
+        }
+    }
+}
+
+impl Sync {
+    pub fn is_level(&self) -> bool {
+        matches!(self.trigger, Trigger::Level(_, _))
+    }
+
+    /// Values written by the sync rule if it fires. Edges are detected
+    /// against the signal value seen by the previous call.
+    pub fn fire<F: Fn(Net) -> State>(&mut self, v: &F, tick: bool) -> Vec<(Net, State)> {
+        let active = match self.trigger {
+            Trigger::Always | Trigger::Init => false,
+            Trigger::Global => tick,
+            Trigger::Level(n, p) => level(v(n), p) == S1,
+            Trigger::Edge(n, p) => edge(&mut self.last, v(n), p),
+        };
+        if active {
+            self.values(v)
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn values<F: Fn(Net) -> State>(&self, v: &F) -> Vec<(Net, State)> {
+        let mut r = Vec::new();
+        for (l, rhs) in self.updates.iter() {
+            r.extend(l.iter().cloned().zip(read(v, rhs)));
+        }
+        r
+    }
+}
+
+impl Proc {
+    pub fn new<F>(p: &Process, resolve: F) -> Result<Self>
+    where
+        F: Fn(&SigSpec) -> Result<Vec<Net>>,
+    {
+        let mut root = ProcessSwitchCase::default();
+        *root.assign_mut() = p.assign().clone();
+        *root.switch_mut() = p.switch().clone();
+        let mut syncs = Vec::new();
+        for s in p.syncs() {
+            let bit = |s: &SigSpec| -> Result<Net> {
+                match resolve(s)?.as_slice() {
+                    [n] => Ok(*n),
+                    _ => bail!(
+                        "sync signal `{}' of process `{}' is not a single bit",
+                        s,
+                        p.id()
+                    ),
+                }
+            };
+            let trigger = match s.tp() {
+                ProcessSyncType::Always => Trigger::Always,
+                ProcessSyncType::Global => Trigger::Global,
+                ProcessSyncType::Init => Trigger::Init,
+                ProcessSyncType::Low(s) => Trigger::Level(bit(s)?, false),
+                ProcessSyncType::High(s) => Trigger::Level(bit(s)?, true),
+                ProcessSyncType::Posedge(s) => Trigger::Edge(bit(s)?, Some(true)),
+                ProcessSyncType::Negedge(s) => Trigger::Edge(bit(s)?, Some(false)),
+                ProcessSyncType::Edge(s) => Trigger::Edge(bit(s)?, None),
+            };
+            syncs.push(Sync {
+                trigger,
+                updates: assigns(s.updates(), &resolve)?,
+                last: State::Sx,
+            });
+        }
+        Ok(Self {
+            root: Case::new(&root, &resolve)?,
+            syncs,
+        })
+    }
+}
@@ -0,0 +1,746 @@
+use super::ff::{edge, Ff};
+use super::memory::{mem_cell, rd_cell, wr_cell, Mem, RdPort, WrPort};
+use super::process::{Proc, Trigger};
+use super::{const_net, Net};
+use crate::celltypes::{CellTypes, PortDir};
+use crate::eval::{eval, is_evaluable};
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+use State::{Sx, S0, S1};
+
+// Passes over the combinational logic before giving up on a loop.
+const SETTLE_LIMIT: usize = 1000;
+
+/// An instance of a module in the simulated hierarchy.
+#[derive(Debug, Clone)]
+pub(crate) struct Instance {
+    /// Cell names leading to the instance from the top module.
+    pub path: Vec<String>,
+    pub module: String,
+    /// Nets of each wire, LSB first, in declaration order.
+    pub wires: Vec<(String, Vec<Net>)>,
+    /// Direction of the port wires.
+    pub ports: HashMap<String, PortDir>,
+    index: HashMap<String, usize>,
+    memories: HashMap<String, usize>,
+    pub wt: WireTable,
+}
+
+impl Instance {
+    fn wire(&self, id: &str) -> Option<&Vec<Net>> {
+        self.index.get(id).map(|i| &self.wires[*i].1)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Comb {
+    cell: Cell,
+    inputs: Vec<(String, Vec<Net>)>,
+    outputs: Vec<(String, Vec<Net>)>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Cell(Box<Comb>),
+    /// An asynchronous memory read port.
+    Read(usize),
+    /// The switch tree of a process.
+    Process(usize),
+    /// A `sync always` rule of a process.
+    Update(usize, usize),
+}
+
+/// Cycle-based simulator of a flattened design over four-valued bits.
+///
+/// Combinational cells are evaluated in topological order until the design
+/// settles. Flip-flops and synchronous memory ports react to clock edges
+/// seen between two calls of [`Simulator::update`], latches and asynchronous
+/// controls to levels. A change from or to an undefined clock is not an
+/// edge: the first defined value a clock takes only sets the level later
+/// edges are measured from, so a clock should be driven low (as
+/// [`Simulator::step`] does) before its first rising edge. Wires with an
+/// `init` attribute start with that value, everything else starts undefined.
+///
+/// Processes are interpreted directly: the switch tree is evaluated like
+/// combinational logic whenever its inputs change, each taking the first
+/// matching case, and the updates of the sync rules are applied on their
+/// edges or levels, or continuously for `sync always`. Signals a process
+/// does not assign keep their value. Level-sensitive rules take priority
+/// over edges, and `sync init` rules run once at the start.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    instances: Vec<Instance>,
+    values: Vec<State>,
+    nodes: Vec<Node>,
+    ffs: Vec<Ff>,
+    mems: Vec<Mem>,
+    rd: Vec<RdPort>,
+    wr: Vec<WrPort>,
+    procs: Vec<Proc>,
+    initstate: Vec<Net>,
+    cycle: u64,
+}
+
+// Makes `name` an RTLIL identifier, adding the leading `\` of public names.
+pub(crate) fn ident(name: &str) -> String {
+    if name.starts_with('\\') || name.starts_with('$') {
+        name.to_string()
+    } else {
+        format!("\\{}", name)
+    }
+}
+
+struct Builder<'a> {
+    design: &'a Design,
+    parent: Vec<Net>,
+    instances: Vec<Instance>,
+    cells: Vec<(usize, &'a Cell)>,
+    procs: Vec<(usize, &'a Process)>,
+    mems: Vec<Mem>,
+}
+
+impl<'a> Builder<'a> {
+    fn alloc(&mut self, n: usize) -> Vec<Net> {
+        let start = self.parent.len();
+        self.parent.extend(start..start + n);
+        (start..start + n).collect()
+    }
+
+    fn find(&mut self, mut n: Net) -> Net {
+        while self.parent[n] != n {
+            self.parent[n] = self.parent[self.parent[n]];
+            n = self.parent[n];
+        }
+        n
+    }
+
+    fn union(&mut self, a: Net, b: Net) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b || (a < 6 && b < 6) {
+            return;
+        }
+        // constants always stay representatives
+        if b < 6 {
+            self.parent[a] = b;
+        } else {
+            self.parent[b] = a;
+        }
+    }
+
+    fn resolve(&self, inst: usize, s: &SigSpec) -> Result<Vec<Net>> {
+        let i = &self.instances[inst];
+        i.wt.bits(s)?
+            .into_iter()
+            .map(|b| match b {
+                SigBit::Const(s) => Ok(const_net(s)),
+                SigBit::Wire((w, n)) => i
+                    .wire(&w)
+                    .map(|v| v[n as usize])
+                    .ok_or_else(|| anyhow!("wire `{}' not found", w)),
+            })
+            .collect()
+    }
+
+    fn instantiate(&mut self, module: &'a Module, path: Vec<String>) -> Result<usize> {
+        if path.len() > 64 {
+            bail!("module `{}' instantiates itself", module.ident());
+        }
+        let id = self.instances.len();
+        let mut inst = Instance {
+            path: path.clone(),
+            module: module.ident().clone(),
+            wires: Vec::new(),
+            ports: HashMap::new(),
+            index: HashMap::new(),
+            memories: HashMap::new(),
+            wt: WireTable::new(module),
+        };
+        for w in module.wires() {
+            let nets = self.alloc(*w.width() as usize);
+            inst.index.insert(w.id().clone(), inst.wires.len());
+            inst.wires.push((w.id().clone(), nets));
+            let dir = match (w.input(), w.output()) {
+                (true, true) => PortDir::Inout,
+                (true, false) => PortDir::Input,
+                (false, true) => PortDir::Output,
+                _ => continue,
+            };
+            inst.ports.insert(w.id().clone(), dir);
+        }
+        for m in module.memories() {
+            inst.memories.insert(m.id().clone(), self.mems.len());
+            self.mems.push(Mem::new(
+                m.id(),
+                *m.width() as usize,
+                *m.offset(),
+                *m.size() as usize,
+            ));
+        }
+        self.instances.push(inst);
+        self.procs
+            .extend(module.processes().iter().map(|p| (id, p)));
+
+        for c in module.connects() {
+            let (a, b) = (self.resolve(id, c.sig1())?, self.resolve(id, c.sig2())?);
+            for (x, y) in a.into_iter().zip(b) {
+                self.union(x, y);
+            }
+        }
+        for c in module.cells() {
+            let sub = match self.design.module(c.i1()) {
+                Some(m) => m,
+                None => {
+                    self.cells.push((id, c));
+                    continue;
+                }
+            };
+            if matches!(sub.attrs().get("\\blackbox"), Some(v) if v.as_bool()) {
+                bail!("can not simulate blackbox module `{}'", sub.ident());
+            }
+            let mut p = path.clone();
+            p.push(c.i2().clone());
+            let child = self.instantiate(sub, p)?;
+            for (k, s) in c.connects() {
+                let outer = self.resolve(id, s)?;
+                let inner = self.instances[child]
+                    .wire(k)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("module `{}' has no port `{}'", sub.ident(), k))?;
+                for (x, y) in outer.into_iter().zip(inner) {
+                    self.union(x, y);
+                }
+            }
+        }
+        Ok(id)
+    }
+}
+
+impl Simulator {
+    /// Build a simulator for module `top` of `design`, instantiating
+    /// submodules of the design recursively.
+    pub fn new(design: &Design, top: &str) -> Result<Self> {
+        let top = design
+            .module(&ident(top))
+            .ok_or_else(|| anyhow!("module `{}' not found", top))?;
+        let mut b = Builder {
+            design,
+            parent: (0..6).collect(),
+            instances: Vec::new(),
+            cells: Vec::new(),
+            procs: Vec::new(),
+            mems: Vec::new(),
+        };
+        b.instantiate(top, vec![])?;
+
+        // renumber the union-find classes densely, keeping the constants
+        let mut dense: HashMap<Net, Net> = (0..6).map(|i| (i, i)).collect();
+        let mut fixed = vec![0; b.parent.len()];
+        for (n, f) in fixed.iter_mut().enumerate() {
+            let r = b.find(n);
+            let next = dense.len();
+            *f = *dense.entry(r).or_insert(next);
+        }
+        for inst in b.instances.iter_mut() {
+            for (_, nets) in inst.wires.iter_mut() {
+                for n in nets.iter_mut() {
+                    *n = fixed[*n];
+                }
+            }
+        }
+
+        let states = [S0, S1, Sx, State::Sz, State::Sa, State::Sm];
+        let mut values = vec![Sx; dense.len()];
+        values[..6].copy_from_slice(&states);
+        let mut sim = Simulator {
+            instances: Vec::new(),
+            values,
+            nodes: Vec::new(),
+            ffs: Vec::new(),
+            mems: std::mem::take(&mut b.mems),
+            rd: Vec::new(),
+            wr: Vec::new(),
+            procs: Vec::new(),
+            initstate: Vec::new(),
+            cycle: 0,
+        };
+        let ct = CellTypes::internals();
+        let mut inits = Vec::new();
+        let mut writes = Vec::new();
+        let mut cell_mems = Vec::new();
+        for (inst, cell) in b.cells.iter() {
+            let port = |k: &str| -> Result<Vec<Net>> {
+                match cell.port(k) {
+                    Some(s) => b.resolve(*inst, s),
+                    None => Ok(vec![]),
+                }
+            };
+            let memory = || -> Result<usize> {
+                let id = match cell.param("\\MEMID") {
+                    Some(Const::Str(s)) => s.clone(),
+                    _ => bail!("cell `{}' has no parameter `\\MEMID'", cell.i2()),
+                };
+                b.instances[*inst]
+                    .memories
+                    .get(&id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("memory `{}' not found", id))
+            };
+            let tp = cell.i1().as_str();
+            if let Some(ff) = Ff::new(cell, port)? {
+                sim.ffs.push(ff);
+                continue;
+            }
+            match tp {
+                "$mem" | "$mem_v2" => {
+                    let (m, rd, wr) = mem_cell(cell, sim.mems.len(), port)?;
+                    cell_mems.push((*inst, m.id.clone(), sim.mems.len()));
+                    sim.mems.push(m);
+                    sim.rd.extend(rd);
+                    sim.wr.extend(wr);
+                }
+                "$memrd" | "$memrd_v2" => sim.rd.push(rd_cell(cell, memory()?, port)?),
+                "$memwr" | "$memwr_v2" => {
+                    let key = ["\\PORTID", "\\PRIORITY"]
+                        .iter()
+                        .find_map(|k| cell.param(k).and_then(|v| v.as_int()));
+                    let p = wr_cell(cell, memory()?, port)?;
+                    writes.push(((p.mem, key), p));
+                }
+                "$meminit" | "$meminit_v2" => inits.push((memory()?, *inst, *cell)),
+                "$initstate" => sim.initstate.extend(port("\\Y")?),
+                "$assert" | "$assume" | "$live" | "$fair" | "$cover" | "$anyconst" | "$anyseq"
+                | "$allconst" | "$allseq" => (),
+                _ if is_evaluable(tp) => {
+                    let t = ct.get(tp).unwrap();
+                    let mut comb = Comb {
+                        cell: (*cell).clone(),
+                        inputs: Vec::new(),
+                        outputs: Vec::new(),
+                    };
+                    for k in t.inputs() {
+                        comb.inputs.push((k.clone(), port(k)?));
+                    }
+                    for k in t.outputs() {
+                        comb.outputs.push((k.clone(), port(k)?));
+                    }
+                    sim.nodes.push(Node::Cell(Box::new(comb)));
+                }
+                _ => bail!("can not simulate cell `{}' of type `{}'", cell.i2(), tp),
+            }
+        }
+        // later write ports of a memory win, in priority order
+        writes.sort_by_key(|(k, _)| *k);
+        sim.wr.extend(writes.into_iter().map(|(_, p)| p));
+        for (i, p) in sim.rd.iter().enumerate() {
+            if p.clk.is_none() {
+                sim.nodes.push(Node::Read(i));
+            }
+        }
+        for (inst, p) in b.procs.iter() {
+            let i = sim.procs.len();
+            let p = Proc::new(p, |s: &SigSpec| b.resolve(*inst, s))?;
+            sim.nodes.push(Node::Process(i));
+            for (j, s) in p.syncs.iter().enumerate() {
+                if matches!(s.trigger, Trigger::Always) {
+                    sim.nodes.push(Node::Update(i, j));
+                }
+            }
+            sim.procs.push(p);
+        }
+
+        // memory contents given by `$meminit` cells, in priority order
+        inits.sort_by_key(|(_, _, c)| c.param("\\PRIORITY").and_then(|v| v.as_int()));
+        for (m, inst, cell) in inits {
+            let wt = &b.instances[inst].wt;
+            let value = |k: &str| -> Result<Vec<State>> {
+                match cell.port(k) {
+                    Some(s) => Ok(wt
+                        .bits(s)?
+                        .iter()
+                        .map(|b| b.state().unwrap_or(Sx))
+                        .collect()),
+                    None => Ok(vec![]),
+                }
+            };
+            let data = value("\\DATA")?;
+            let mut en = value("\\EN")?;
+            let width = sim.mems[m].width;
+            if en.is_empty() {
+                en = vec![S1; width];
+            }
+            let en: Vec<State> = (0..data.len()).map(|i| en[i % en.len()]).collect();
+            sim.mems[m].write(&value("\\ADDR")?, &data, &en);
+        }
+
+        // `$mem` cells are found by their `MEMID` in their own instance
+        for (inst, id, m) in cell_mems {
+            b.instances[inst].memories.insert(id, m);
+        }
+        sim.instances = b.instances;
+        sim.sort_nodes();
+        for inst in 0..sim.instances.len() {
+            let module = design.module(&sim.instances[inst].module).unwrap();
+            for w in module.wires() {
+                if let Some(init) = w.attrs().get("\\init") {
+                    let nets = sim.instances[inst].wire(w.id()).unwrap().clone();
+                    for (n, s) in nets.into_iter().zip(init.to_bits()) {
+                        if n >= 6 && s != Sx {
+                            sim.values[n] = s;
+                        }
+                    }
+                }
+            }
+        }
+        let mut init = Vec::new();
+        for p in sim.procs.iter() {
+            for s in p.syncs.iter() {
+                if matches!(s.trigger, Trigger::Init) {
+                    init.extend(s.values(&|n| sim.values[n]));
+                }
+            }
+        }
+        for (n, v) in init {
+            sim.write(&[n], &[v]);
+        }
+        sim.update()?;
+        Ok(sim)
+    }
+
+    fn node_nets(&self, n: &Node) -> (Vec<Net>, Vec<Net>) {
+        match n {
+            Node::Cell(c) => (
+                c.inputs.iter().flat_map(|(_, v)| v.clone()).collect(),
+                c.outputs.iter().flat_map(|(_, v)| v.clone()).collect(),
+            ),
+            Node::Read(i) => (self.rd[*i].addr.clone(), self.rd[*i].data.clone()),
+            Node::Process(i) => {
+                let (mut ins, mut outs) = (Vec::new(), Vec::new());
+                self.procs[*i].root.nets(&mut ins, &mut outs);
+                (ins, outs)
+            }
+            Node::Update(i, j) => {
+                let u = &self.procs[*i].syncs[*j].updates;
+                (
+                    u.iter().flat_map(|(_, r)| r.clone()).collect(),
+                    u.iter().flat_map(|(l, _)| l.clone()).collect(),
+                )
+            }
+        }
+    }
+
+    // Order the combinational nodes so that drivers come before their
+    // loads; nodes on loops are left at the end.
+    fn sort_nodes(&mut self) {
+        let nets: Vec<(Vec<Net>, Vec<Net>)> =
+            self.nodes.iter().map(|n| self.node_nets(n)).collect();
+        let mut driver: HashMap<Net, usize> = HashMap::new();
+        for (i, (_, outs)) in nets.iter().enumerate() {
+            for n in outs {
+                driver.insert(*n, i);
+            }
+        }
+        let mut loads: Vec<Vec<usize>> = vec![vec![]; nets.len()];
+        let mut deps = vec![0; nets.len()];
+        for (i, (ins, _)) in nets.iter().enumerate() {
+            let mut seen: Vec<usize> = ins.iter().filter_map(|n| driver.get(n).cloned()).collect();
+            seen.sort_unstable();
+            seen.dedup();
+            for d in seen {
+                loads[d].push(i);
+                deps[i] += 1;
+            }
+        }
+        let mut order = Vec::new();
+        let mut ready: Vec<usize> = (0..nets.len()).filter(|i| deps[*i] == 0).rev().collect();
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            for l in loads[i].iter() {
+                deps[*l] -= 1;
+                if deps[*l] == 0 {
+                    ready.push(*l);
+                }
+            }
+        }
+        let mut done = vec![false; nets.len()];
+        for i in order.iter() {
+            done[*i] = true;
+        }
+        order.extend((0..nets.len()).filter(|i| !done[*i]));
+        let mut nodes: Vec<Option<Node>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.nodes = order
+            .into_iter()
+            .map(|i| nodes[i].take().unwrap())
+            .collect();
+    }
+
+    fn write(&mut self, nets: &[Net], v: &[State]) -> bool {
+        let mut changed = false;
+        for (i, n) in nets.iter().enumerate() {
+            let s = v.get(i).cloned().unwrap_or(Sx);
+            if *n >= 6 && self.values[*n] != s {
+                self.values[*n] = s;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn read(&self, nets: &[Net]) -> Vec<State> {
+        nets.iter().map(|n| self.values[*n]).collect()
+    }
+
+    fn eval_node(&self, n: &Node) -> Result<Vec<(Vec<Net>, Vec<State>)>> {
+        match n {
+            Node::Cell(c) => {
+                let input = |k: &str| {
+                    c.inputs
+                        .iter()
+                        .find(|(p, _)| p == k)
+                        .filter(|(_, v)| !v.is_empty())
+                        .map(|(_, v)| self.read(v))
+                };
+                let mut r = Vec::new();
+                for (k, v) in eval(&c.cell, input)? {
+                    if let Some((_, nets)) = c.outputs.iter().find(|(p, _)| *p == k) {
+                        r.push((nets.clone(), v));
+                    }
+                }
+                Ok(r)
+            }
+            Node::Read(i) => {
+                let p = &self.rd[*i];
+                let v = self.mems[p.mem].read(&self.read(&p.addr));
+                Ok(vec![(p.data.clone(), v)])
+            }
+            Node::Process(i) => {
+                let mut out = HashMap::new();
+                self.procs[*i].root.eval(&|n| self.values[n], &mut out);
+                Ok(out.into_iter().map(|(n, v)| (vec![n], vec![v])).collect())
+            }
+            Node::Update(i, j) => {
+                let s = &self.procs[*i].syncs[*j];
+                Ok(s.values(&|n| self.values[n])
+                    .into_iter()
+                    .map(|(n, v)| (vec![n], vec![v]))
+                    .collect())
+            }
+        }
+    }
+
+    // Evaluate the combinational logic until no value changes.
+    fn settle(&mut self) -> Result<()> {
+        let init = if self.cycle == 0 { S1 } else { S0 };
+        for n in self.initstate.clone() {
+            self.write(&[n], &[init]);
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        let mut r = Ok(());
+        for _ in 0..SETTLE_LIMIT {
+            let mut changed = false;
+            for n in nodes.iter() {
+                match self.eval_node(n) {
+                    Ok(outs) => {
+                        for (nets, v) in outs {
+                            changed |= self.write(&nets, &v);
+                        }
+                    }
+                    Err(e) => {
+                        r = Err(e);
+                        break;
+                    }
+                }
+            }
+            if !changed || r.is_err() {
+                self.nodes = nodes;
+                return r;
+            }
+        }
+        self.nodes = nodes;
+        bail!("combinational logic does not settle")
+    }
+
+    // Update storage cells once, returning whether anything changed.
+    fn clock_cells(&mut self, tick: bool) -> bool {
+        let mut writes: Vec<(Vec<Net>, Vec<State>)> = Vec::new();
+        let values = &self.values;
+        for ff in self.ffs.iter_mut() {
+            let v = ff.next(|n| values[n], tick);
+            writes.push((ff.q.clone(), v));
+        }
+
+        let mut transparent = Vec::new();
+        for (i, p) in self.rd.iter_mut().enumerate() {
+            if let Some((c, pol)) = p.clk {
+                if edge(&mut p.last_clk, values[c], Some(pol)) {
+                    if p.transparent {
+                        transparent.push(i);
+                    } else {
+                        writes.push((p.data.clone(), p.clocked(|n| values[n], &self.mems[p.mem])));
+                    }
+                }
+            }
+        }
+        let mut changed = false;
+        for p in self.wr.iter_mut() {
+            let active = match p.clk {
+                Some((c, pol)) => edge(&mut p.last_clk, values[c], Some(pol)),
+                None => true,
+            };
+            if active {
+                let get = |n: &[Net]| n.iter().map(|x| values[*x]).collect::<Vec<_>>();
+                changed |= self.mems[p.mem].write(&get(&p.addr), &get(&p.data), &get(&p.en));
+            }
+        }
+        for i in transparent {
+            let p = &self.rd[i];
+            writes.push((p.data.clone(), p.clocked(|n| values[n], &self.mems[p.mem])));
+        }
+        for p in self.rd.iter() {
+            if let Some((n, v)) = &p.arst {
+                if values[*n] == S1 {
+                    writes.push((p.data.clone(), v.clone()));
+                }
+            }
+        }
+        for level in [false, true].iter() {
+            for p in self.procs.iter_mut() {
+                for s in p.syncs.iter_mut().filter(|s| s.is_level() == *level) {
+                    for (n, v) in s.fire(&|n| values[n], tick) {
+                        writes.push((vec![n], vec![v]));
+                    }
+                }
+            }
+        }
+
+        for (nets, v) in writes {
+            changed |= self.write(&nets, &v);
+        }
+        changed
+    }
+
+    fn run(&mut self, mut tick: bool) -> Result<()> {
+        for _ in 0..SETTLE_LIMIT {
+            self.settle()?;
+            if !self.clock_cells(tick) {
+                return Ok(());
+            }
+            tick = false;
+        }
+        bail!("storage cells do not settle")
+    }
+
+    /// Propagate the current inputs through the design, updating storage
+    /// cells on the clock edges and levels seen since the last update.
+    pub fn update(&mut self) -> Result<()> {
+        self.run(false)
+    }
+
+    /// Advance the implicit global clock driving `$ff` cells by one cycle.
+    pub fn tick(&mut self) -> Result<()> {
+        self.run(true)?;
+        self.cycle += 1;
+        Ok(())
+    }
+
+    /// Run one cycle of clock input `clk`: drive it low, then high, updating
+    /// the design after each change.
+    pub fn step(&mut self, clk: &str) -> Result<()> {
+        self.set(clk, &[S0])?;
+        self.update()?;
+        self.set(clk, &[S1])?;
+        self.update()?;
+        self.cycle += 1;
+        Ok(())
+    }
+
+    pub(crate) fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub(crate) fn value(&self, n: Net) -> State {
+        self.values[n]
+    }
+
+    /// Number of cycles run by [`Simulator::step`] and [`Simulator::tick`].
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub(crate) fn instance(&self, path: &[&str]) -> Result<&Instance> {
+        self.instances
+            .iter()
+            .find(|i| i.path.iter().map(|s| s.as_str()).eq(path.iter().cloned()))
+            .ok_or_else(|| anyhow!("instance `{}' not found", path.join(".")))
+    }
+
+    fn nets(&self, path: &[&str], wire: &str) -> Result<&Vec<Net>> {
+        let inst = self.instance(path)?;
+        inst.wire(&ident(wire))
+            .ok_or_else(|| anyhow!("wire `{}' not found in `{}'", wire, inst.module))
+    }
+
+    /// Drive wire `wire` of the top module with `value` (LSB first, padded
+    /// with `0`). The new value takes effect on the next update. The leading
+    /// `\` of public names may be left out.
+    pub fn set(&mut self, wire: &str, value: &[State]) -> Result<()> {
+        let nets = self.nets(&[], wire)?.clone();
+        let mut v = value.to_vec();
+        v.resize(nets.len(), S0);
+        self.write(&nets, &v);
+        Ok(())
+    }
+
+    pub fn set_u64(&mut self, wire: &str, value: u64) -> Result<()> {
+        let v: Vec<State> = (0..64)
+            .map(|i| if (value >> i) & 1 == 1 { S1 } else { S0 })
+            .collect();
+        let width = self.nets(&[], wire)?.len();
+        self.set(wire, &v[..width.min(64)])
+    }
+
+    /// Value of wire `wire` of the top module, LSB first.
+    pub fn get(&self, wire: &str) -> Result<Vec<State>> {
+        self.get_in(&[], wire)
+    }
+
+    /// Value of wire `wire` of the instance reached through the cells named
+    /// in `path`.
+    pub fn get_in(&self, path: &[&str], wire: &str) -> Result<Vec<State>> {
+        Ok(self.read(self.nets(path, wire)?))
+    }
+
+    /// Value of wire `wire` of the top module as an integer, or None if it
+    /// has undefined bits or is wider than 64 bits.
+    pub fn get_u64(&self, wire: &str) -> Result<Option<u64>> {
+        let v = self.get(wire)?;
+        if v.len() > 64 {
+            return Ok(None);
+        }
+        let mut r = 0;
+        for (i, s) in v.iter().enumerate() {
+            match s {
+                S1 => r |= 1 << i,
+                S0 => (),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(r))
+    }
+
+    /// Contents of memory `id` of the instance at `path`, one word per
+    /// address starting at the memory's offset. Also finds memories
+    /// collected into a `$mem` cell of that instance by their `MEMID`.
+    pub fn memory(&self, path: &[&str], id: &str) -> Result<&[Vec<State>]> {
+        let id = ident(id);
+        let inst = self.instance(path)?;
+        inst.memories
+            .get(&id)
+            .map(|m| self.mems[*m].data.as_slice())
+            .ok_or_else(|| anyhow!("memory `{}' not found in `{}'", id, inst.module))
+    }
+}
@@ -0,0 +1,285 @@
+use super::simulator::{ident, Simulator};
+use crate::celltypes::PortDir;
+use crate::syntax::*;
+use anyhow::{anyhow, bail, Result};
+use getset::*;
+use std::collections::HashMap;
+use std::fmt;
+
+use State::{Sx, S0, S1};
+
+/// Values of some signals at one point of a trace, LSB first.
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct Vector {
+    time: u64,
+    values: Vec<(String, Vec<State>)>,
+}
+
+/// A trace to replay against a simulated design: input ports of the top
+/// module are driven from it, output ports are checked against it.
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct Stimulus {
+    vectors: Vec<Vector>,
+}
+
+/// First difference found by [`Stimulus::replay`].
+#[derive(Debug, Clone, Getters)]
+#[get = "pub"]
+pub struct Mismatch {
+    /// Index of the vector in the stimulus.
+    cycle: usize,
+    time: u64,
+    signal: String,
+    expected: Vec<State>,
+    actual: Vec<State>,
+}
+
+fn bits_str(v: &[State]) -> String {
+    v.iter().rev().map(|s| s.to_string()).collect()
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cycle {} (time {}): `{}' is {}, expected {}",
+            self.cycle,
+            self.time,
+            self.signal,
+            bits_str(&self.actual),
+            bits_str(&self.expected)
+        )
+    }
+}
+
+fn state(c: char) -> Option<State> {
+    match c.to_ascii_lowercase() {
+        '0' => Some(S0),
+        '1' => Some(S1),
+        'x' => Some(Sx),
+        'z' => Some(State::Sz),
+        '-' => Some(State::Sa),
+        _ => None,
+    }
+}
+
+// Bits of a binary string, MSB first as written.
+fn binary(s: &str) -> Result<Vec<State>> {
+    s.chars()
+        .rev()
+        .map(|c| state(c).ok_or_else(|| anyhow!("invalid bit `{}' in `{}'", c, s)))
+        .collect()
+}
+
+// A vector value: binary digits, or hexadecimal and decimal numbers written
+// like `'h1f` and `'d31`.
+fn value(s: &str) -> Result<Vec<State>> {
+    let num = |radix: u32, digits: &str| -> Result<Vec<State>> {
+        let v = u64::from_str_radix(digits, radix)
+            .map_err(|e| anyhow!("invalid value `{}': {}", s, e))?;
+        Ok((0..64 - v.leading_zeros().min(63))
+            .map(|i| if (v >> i) & 1 == 1 { S1 } else { S0 })
+            .collect())
+    };
+    match s.get(..2) {
+        Some("'h") => num(16, &s[2..]),
+        Some("'d") => num(10, &s[2..]),
+        Some("'b") => binary(&s[2..]),
+        _ => binary(s),
+    }
+}
+
+fn unescape(name: &str) -> &str {
+    name.strip_prefix('\\').unwrap_or(name)
+}
+
+// Extend `v` to `width` bits like a VCD value: with `x` or `z` if that is
+// the top bit, with `0` otherwise.
+fn extend(mut v: Vec<State>, width: usize) -> Vec<State> {
+    let fill = match v.last() {
+        Some(s) if *s == Sx || *s == State::Sz => *s,
+        _ => S0,
+    };
+    v.resize(width, fill);
+    v
+}
+
+impl Stimulus {
+    /// Read a table of vectors, one per cycle. The first line names the
+    /// signals, the following lines give their values in the same order,
+    /// separated by commas or whitespace. Values are binary MSB first, or
+    /// numbers like `'h1f` and `'d31`. Text after `#` is ignored.
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.split('#').next().unwrap().trim()))
+            .filter(|(_, l)| !l.is_empty());
+        let fields = |l: &str| -> Vec<String> {
+            l.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect()
+        };
+        let names = match lines.next() {
+            Some((_, l)) => fields(l),
+            None => bail!("missing header line"),
+        };
+        let mut r = Self::default();
+        for (line, l) in lines {
+            let f = fields(l);
+            if f.len() != names.len() {
+                bail!(
+                    "line {}: {} values for {} signals",
+                    line,
+                    f.len(),
+                    names.len()
+                );
+            }
+            let values = names
+                .iter()
+                .zip(f.iter())
+                .map(|(n, v)| {
+                    Ok((
+                        n.clone(),
+                        value(v).map_err(|e| anyhow!("line {}: {}", line, e))?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            r.vectors.push(Vector {
+                time: r.vectors.len() as u64,
+                values,
+            });
+        }
+        Ok(r)
+    }
+
+    /// Read the value changes of the variables in `scope` of a VCD file,
+    /// e.g. `["tb", "dut"]`, one vector per timestamp. Names escaped with a
+    /// `\`, as yosys writes those starting with `$`, are unescaped.
+    pub fn from_vcd(text: &str, scope: &[&str]) -> Result<Self> {
+        let mut tokens = text.split_whitespace();
+        let mut path: Vec<&str> = Vec::new();
+        let mut vars: HashMap<&str, (String, usize)> = HashMap::new();
+        while let Some(t) = tokens.next() {
+            let mut args = Vec::new();
+            for a in tokens.by_ref() {
+                if a == "$end" {
+                    break;
+                }
+                args.push(a);
+            }
+            match (t, args.as_slice()) {
+                ("$scope", [_, name, ..]) => path.push(unescape(name)),
+                ("$upscope", _) => {
+                    path.pop();
+                }
+                ("$var", [_, width, code, name, ..]) if path == scope => {
+                    let width = width
+                        .parse()
+                        .map_err(|_| anyhow!("invalid width of variable `{}'", name))?;
+                    vars.entry(code)
+                        .or_insert((unescape(name).to_string(), width));
+                }
+                ("$enddefinitions", _) => break,
+                (t, _) if t.starts_with('$') => (),
+                (t, _) => bail!("unexpected `{}' in VCD header", t),
+            }
+        }
+        if vars.is_empty() {
+            bail!("no variables in scope `{}'", scope.join("."));
+        }
+
+        let mut r = Self::default();
+        let mut cur = Vector::default();
+        let change = |cur: &mut Vector, code: &str, v: Vec<State>| {
+            if let Some((name, width)) = vars.get(code) {
+                cur.values.push((name.clone(), extend(v, *width)));
+            }
+        };
+        while let Some(t) = tokens.next() {
+            let mut c = t.chars();
+            match c.next() {
+                Some('#') => {
+                    let time = c
+                        .as_str()
+                        .parse()
+                        .map_err(|_| anyhow!("invalid timestamp `{}'", t))?;
+                    if !cur.values.is_empty() {
+                        r.vectors.push(std::mem::take(&mut cur));
+                    }
+                    cur.time = time;
+                }
+                Some('$') if t == "$comment" => {
+                    tokens.by_ref().find(|t| *t == "$end");
+                }
+                Some('$') => (),
+                Some('b') | Some('B') => {
+                    let code = tokens
+                        .next()
+                        .ok_or_else(|| anyhow!("missing identifier after `{}'", t))?;
+                    change(&mut cur, code, binary(c.as_str())?);
+                }
+                Some('r') | Some('R') => {
+                    tokens.next();
+                }
+                Some(s) => match state(s) {
+                    Some(s) => change(&mut cur, c.as_str(), vec![s]),
+                    None => bail!("unexpected `{}' in VCD value changes", t),
+                },
+                None => (),
+            }
+        }
+        if !cur.values.is_empty() {
+            r.vectors.push(cur);
+        }
+        Ok(r)
+    }
+
+    /// Replay the vectors on the top module of `sim`: drive the input ports
+    /// given by each vector, run one cycle of `clock` if given or just
+    /// update the design otherwise, then compare the output ports against
+    /// their last given value. `x` and `-` bits of expected values match
+    /// anything, and signals that are not ports are ignored.
+    pub fn replay(&self, sim: &mut Simulator, clock: Option<&str>) -> Result<Option<Mismatch>> {
+        let ports = sim.instance(&[])?.ports.clone();
+        let dir = |name: &str| ports.get(&ident(name)).cloned();
+        let mut expected: Vec<(String, Vec<State>)> = Vec::new();
+        for (cycle, v) in self.vectors.iter().enumerate() {
+            for (name, value) in v.values.iter() {
+                match dir(name) {
+                    Some(PortDir::Input) | Some(PortDir::Inout) => sim.set(name, value)?,
+                    Some(PortDir::Output) => match expected.iter_mut().find(|(n, _)| n == name) {
+                        Some(e) => e.1 = value.clone(),
+                        None => expected.push((name.clone(), value.clone())),
+                    },
+                    None => (),
+                }
+            }
+            match clock {
+                Some(clk) => sim.step(clk)?,
+                None => sim.update()?,
+            }
+            for (name, value) in expected.iter() {
+                let actual = sim.get(name)?;
+                let value = extend(value.clone(), actual.len());
+                let differs = value
+                    .iter()
+                    .zip(actual.iter())
+                    .any(|(e, a)| *e != Sx && *e != State::Sa && e != a);
+                if differs {
+                    return Ok(Some(Mismatch {
+                        cycle,
+                        time: v.time,
+                        signal: name.clone(),
+                        expected: value,
+                        actual,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,154 @@
+use super::{Net, Simulator};
+use crate::syntax::*;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+// Name of a scope or variable as yosys' `sim` writes it: without the
+// leading `\` of public names, and escaped with a `\` if it starts with a
+// `$`, so that it can not be taken for a keyword.
+fn display(name: &str) -> String {
+    let name = name.strip_prefix('\\').unwrap_or(name);
+    if name.starts_with('$') {
+        format!("\\{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+// Name of the variable of a wire, with its range unless it is a single bit
+// at index 0, since GTKWave reads a trailing `[...]` as the range.
+fn var_name(id: &str, (msb, lsb): (i64, i64)) -> String {
+    if id.contains('[') || (msb, lsb) != (0, 0) {
+        format!("{} [{}:{}]", display(id), msb, lsb)
+    } else {
+        display(id)
+    }
+}
+
+// Short identifier code of the `i`-th variable, over the printable ASCII
+// characters.
+fn code(mut i: usize) -> String {
+    let mut s = String::new();
+    loop {
+        s.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return s;
+        }
+        i -= 1;
+    }
+}
+
+fn state(s: State) -> char {
+    match s {
+        State::S0 => '0',
+        State::S1 => '1',
+        State::Sz => 'z',
+        _ => 'x',
+    }
+}
+
+/// Writer of a simulation trace as a Value Change Dump, as read by GTKWave
+/// and friends.
+///
+/// Every wire of every instance is dumped, in a scope per instance named
+/// after its cell. Wires sharing the same nets share one variable. Names are
+/// written like yosys' `sim` does: names starting with `$` are escaped with
+/// a `\`, and variables are followed by the range of their wire unless it
+/// is a single bit at index 0.
+pub struct Vcd<W: Write> {
+    w: W,
+    vars: Vec<(String, Vec<Net>)>,
+    last: Vec<Option<Vec<State>>>,
+    time: Option<u64>,
+}
+
+impl<W: Write> Vcd<W> {
+    /// Write the header for the design simulated by `sim`. `timescale` is
+    /// the duration of one time unit, e.g. `1ns`.
+    pub fn new(mut w: W, sim: &Simulator, timescale: &str) -> Result<Self> {
+        writeln!(w, "$version rtlil-rs $end")?;
+        writeln!(w, "$timescale {} $end", timescale)?;
+        let mut codes: HashMap<Vec<Net>, usize> = HashMap::new();
+        let mut vars = Vec::new();
+        // path of the innermost open scope; instances come parents first
+        let mut open: Option<&[String]> = None;
+        for inst in sim.instances() {
+            let name = match open {
+                None => display(&inst.module),
+                Some(mut p) => {
+                    while !(inst.path.len() == p.len() + 1 && inst.path.starts_with(p)) {
+                        writeln!(w, "$upscope $end")?;
+                        p = &p[..p.len() - 1];
+                    }
+                    display(inst.path.last().unwrap())
+                }
+            };
+            writeln!(w, "$scope module {} $end", name)?;
+            open = Some(&inst.path);
+            for (id, nets) in inst.wires.iter().filter(|(_, nets)| !nets.is_empty()) {
+                let i = *codes.entry(nets.clone()).or_insert_with(|| {
+                    vars.push((code(vars.len()), nets.clone()));
+                    vars.len() - 1
+                });
+                let range = inst.wt.range(id).unwrap_or((0, 0));
+                writeln!(
+                    w,
+                    "$var wire {} {} {} $end",
+                    nets.len(),
+                    vars[i].0,
+                    var_name(id, range)
+                )?;
+            }
+        }
+        for _ in 0..open.map_or(0, |p| p.len() + 1) {
+            writeln!(w, "$upscope $end")?;
+        }
+        writeln!(w, "$enddefinitions $end")?;
+        let last = vec![None; vars.len()];
+        Ok(Self {
+            w,
+            vars,
+            last,
+            time: None,
+        })
+    }
+
+    /// Record the current values of `sim` at `time`, writing the variables
+    /// that changed since the last sample. Times must not decrease.
+    pub fn sample(&mut self, sim: &Simulator, time: u64) -> Result<()> {
+        let mut changes = Vec::new();
+        for (i, (code, nets)) in self.vars.iter().enumerate() {
+            let v: Vec<State> = nets.iter().map(|n| sim.value(*n)).collect();
+            if self.last[i].as_ref() != Some(&v) {
+                changes.push(match v.as_slice() {
+                    [s] => format!("{}{}", state(*s), code),
+                    _ => format!(
+                        "b{} {}",
+                        v.iter().rev().map(|s| state(*s)).collect::<String>(),
+                        code
+                    ),
+                });
+                self.last[i] = Some(v);
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if self.time != Some(time) {
+            writeln!(self.w, "#{}", time)?;
+            self.time = Some(time);
+        }
+        for c in changes {
+            writeln!(self.w, "{}", c)?;
+        }
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright (c) 2020 xhe
+
+//! Size statistics of designs, in the spirit of yosys' `stat`.
+
+use crate::syntax::*;
+use anyhow::{bail, Result};
+use getset::*;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Getters)]
+#[get = "pub"]
+pub struct ModuleStats {
+    wires: usize,
+    wire_bits: usize,
+    public_wires: usize,
+    public_wire_bits: usize,
+    memories: usize,
+    memory_bits: usize,
+    processes: usize,
+    cells: usize,
+    /// Number of cells of each type.
+    cell_types: BTreeMap<String, usize>,
+    /// Sum of the costs of the cells, if a cost table was given. Cells of
+    /// types missing from the table count for nothing.
+    area: Option<f64>,
+    /// Cell types missing from the cost table, instances of modules of the
+    /// design aside.
+    uncosted: BTreeSet<String>,
+}
+
+impl ModuleStats {
+    fn new(m: &Module, design: &Design, costs: Option<&HashMap<String, f64>>) -> Self {
+        let mut r = Self::default();
+        for w in m.wires() {
+            r.wires += 1;
+            r.wire_bits += *w.width() as usize;
+            if w.id().starts_with('\\') {
+                r.public_wires += 1;
+                r.public_wire_bits += *w.width() as usize;
+            }
+        }
+        for mem in m.memories() {
+            r.memories += 1;
+            r.memory_bits += (*mem.width() * *mem.size()) as usize;
+        }
+        r.processes = m.processes().len();
+        r.cells = m.cells().len();
+        for c in m.cells() {
+            *r.cell_types.entry(c.i1().clone()).or_insert(0) += 1;
+            // collected memories are both cells and memories, as in yosys
+            if c.i1() == "$mem" || c.i1() == "$mem_v2" {
+                let int = |k: &str| c.param(k).and_then(|v| v.as_int()).unwrap_or(0).max(0);
+                r.memories += 1;
+                r.memory_bits += (int("\\WIDTH") * int("\\SIZE")) as usize;
+            }
+        }
+        if let Some(t) = costs {
+            let mut area = 0.0;
+            for (tp, n) in r.cell_types.iter() {
+                match t.get(tp) {
+                    Some(c) => area += c * *n as f64,
+                    None if design.module(tp).is_some_and(|sub| !is_blackbox(sub)) => (),
+                    None => {
+                        r.uncosted.insert(tp.clone());
+                    }
+                }
+            }
+            r.area = Some(area);
+        }
+        r
+    }
+
+    // Add `n` times the numbers of `other`.
+    fn add(&mut self, other: &Self, n: usize) {
+        self.wires += other.wires * n;
+        self.wire_bits += other.wire_bits * n;
+        self.public_wires += other.public_wires * n;
+        self.public_wire_bits += other.public_wire_bits * n;
+        self.memories += other.memories * n;
+        self.memory_bits += other.memory_bits * n;
+        self.processes += other.processes * n;
+        self.cells += other.cells * n;
+        for (tp, k) in other.cell_types.iter() {
+            *self.cell_types.entry(tp.clone()).or_insert(0) += k * n;
+        }
+        if let (Some(a), Some(b)) = (self.area.as_mut(), other.area) {
+            *a += b * n as f64;
+        }
+        self.uncosted.extend(other.uncosted.iter().cloned());
+    }
+
+    fn json(&self) -> Value {
+        let mut v = json!({
+            "num_wires": self.wires,
+            "num_wire_bits": self.wire_bits,
+            "num_pub_wires": self.public_wires,
+            "num_pub_wire_bits": self.public_wire_bits,
+            "num_memories": self.memories,
+            "num_memory_bits": self.memory_bits,
+            "num_processes": self.processes,
+            "num_cells": self.cells,
+            "num_cells_by_type": self.cell_types,
+        });
+        if let Some(a) = self.area {
+            v["area"] = json!(a);
+            v["uncosted_cell_types"] = json!(self.uncosted);
+        }
+        v
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        let rows = [
+            ("wires", self.wires),
+            ("wire bits", self.wire_bits),
+            ("public wires", self.public_wires),
+            ("public wire bits", self.public_wire_bits),
+            ("memories", self.memories),
+            ("memory bits", self.memory_bits),
+            ("processes", self.processes),
+            ("cells", self.cells),
+        ];
+        for (k, n) in rows.iter() {
+            writeln!(f, "   Number of {:<22}{:>6}", format!("{}:", k), n)?;
+        }
+        for (tp, n) in self.cell_types.iter() {
+            writeln!(f, "     {:<30}{:>6}", tp, n)?;
+        }
+        if let Some(a) = self.area {
+            writeln!(f)?;
+            writeln!(f, "   Chip area for {}: {}", name, a)?;
+            for tp in self.uncosted.iter() {
+                writeln!(f, "   Area for cell type {} is unknown!", tp)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Getters)]
+#[get = "pub"]
+pub struct DesignStats {
+    modules: BTreeMap<String, ModuleStats>,
+    /// The top module: the one marked `top`, or else the only module not
+    /// instantiated by another.
+    top: Option<String>,
+    /// Numbers of the whole tree below the top module, where each instance
+    /// of a module of the design is replaced by the contents of the module.
+    /// Blackbox modules are counted as cells.
+    hierarchy: Option<ModuleStats>,
+    /// Number of instances of each module below the top module, the top
+    /// module included.
+    instances: BTreeMap<String, usize>,
+}
+
+impl DesignStats {
+    /// The statistics as a JSON document.
+    pub fn to_json(&self) -> String {
+        let mut v = json!({
+            "modules": self
+                .modules
+                .iter()
+                .map(|(k, s)| (k.clone(), s.json()))
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        if let (Some(top), Some(h)) = (&self.top, &self.hierarchy) {
+            let mut d = h.json();
+            d["top"] = json!(top);
+            d["instances"] = json!(self.instances);
+            v["design"] = d;
+        }
+        serde_json::to_string_pretty(&v).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for DesignStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (k, s) in self.modules.iter() {
+            writeln!(f, "=== {} ===", k)?;
+            writeln!(f)?;
+            s.write(f, &format!("module '{}'", k))?;
+            writeln!(f)?;
+        }
+        if let (Some(top), Some(h)) = (&self.top, &self.hierarchy) {
+            writeln!(f, "=== design hierarchy ===")?;
+            writeln!(f)?;
+            writeln!(f, "   {:<32}{:>6}", top, 1)?;
+            for (k, n) in self.instances.iter().filter(|(k, _)| *k != top) {
+                writeln!(f, "     {:<30}{:>6}", k, n)?;
+            }
+            writeln!(f)?;
+            h.write(f, "design")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_blackbox(m: &Module) -> bool {
+    matches!(m.attrs().get("\\blackbox"), Some(v) if v.as_bool())
+}
+
+fn find_top(design: &Design) -> Option<String> {
+    let is_top = |m: &&Module| matches!(m.attrs().get("\\top"), Some(v) if v.as_bool());
+    if let Some(m) = design.modules().iter().find(is_top) {
+        return Some(m.ident().clone());
+    }
+    let mut roots = design.modules().iter().filter(|m| {
+        !is_blackbox(m)
+            && !design
+                .modules()
+                .iter()
+                .any(|o| o.cells().iter().any(|c| c.i1() == m.ident()))
+    });
+    match (roots.next(), roots.next()) {
+        (Some(m), None) => Some(m.ident().clone()),
+        _ => None,
+    }
+}
+
+// Hierarchical numbers of `m`, counting the instances of each module below
+// it into `instances`.
+fn flatten(
+    design: &Design,
+    modules: &BTreeMap<String, ModuleStats>,
+    costs: Option<&HashMap<String, f64>>,
+    m: &Module,
+    stack: &mut Vec<String>,
+    instances: &mut BTreeMap<String, usize>,
+) -> Result<ModuleStats> {
+    if stack.contains(m.ident()) {
+        bail!("module `{}' instantiates itself", m.ident());
+    }
+    stack.push(m.ident().clone());
+    let mut r = modules[m.ident()].clone();
+    let subs: Vec<(String, usize)> = r
+        .cell_types
+        .iter()
+        .filter(|(tp, _)| matches!(design.module(tp), Some(sub) if !is_blackbox(sub)))
+        .map(|(tp, n)| (tp.clone(), *n))
+        .collect();
+    for (tp, n) in subs {
+        r.cell_types.remove(&tp);
+        r.cells -= n;
+        if let (Some(a), Some(t)) = (r.area.as_mut(), costs) {
+            // instances cost what their contents cost
+            *a -= t.get(&tp).copied().unwrap_or(0.0) * n as f64;
+        }
+        let mut below = BTreeMap::new();
+        let sub = design.module(&tp).unwrap();
+        let sub = flatten(design, modules, costs, sub, stack, &mut below)?;
+        r.add(&sub, n);
+        *instances.entry(tp).or_insert(0) += n;
+        for (k, i) in below {
+            *instances.entry(k).or_insert(0) += i * n;
+        }
+    }
+    stack.pop();
+    Ok(r)
+}
+
+fn collect(design: &Design, costs: Option<&HashMap<String, f64>>) -> Result<DesignStats> {
+    let modules: BTreeMap<String, ModuleStats> = design
+        .modules()
+        .iter()
+        .map(|m| (m.ident().clone(), ModuleStats::new(m, design, costs)))
+        .collect();
+    let top = find_top(design);
+    let mut instances = BTreeMap::new();
+    let hierarchy = match top.as_deref().and_then(|t| design.module(t)) {
+        Some(m) => {
+            instances.insert(m.ident().clone(), 1);
+            Some(flatten(
+                design,
+                &modules,
+                costs,
+                m,
+                &mut Vec::new(),
+                &mut instances,
+            )?)
+        }
+        None => None,
+    };
+    Ok(DesignStats {
+        modules,
+        top,
+        hierarchy,
+        instances,
+    })
+}
+
+/// Count the wires, memories, processes and cells of each module of
+/// `design`, and of the hierarchy below its top module.
+pub fn stats(design: &Design) -> Result<DesignStats> {
+    collect(design, None)
+}
+
+/// Like [`stats`], also estimating the area of modules as the sum of the
+/// costs in `costs` of their cells, by cell type.
+pub fn stats_with(design: &Design, costs: &HashMap<String, f64>) -> Result<DesignStats> {
+    collect(design, Some(costs))
+}
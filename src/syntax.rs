@@ -3,10 +3,15 @@
 use anyhow::Result;
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub static AUTOIDX: AtomicUsize = AtomicUsize::new(0);
 
+/// A fresh private identifier, `$<tag>$<autoidx>`.
+pub fn new_id(tag: &str) -> String {
+    format!("${}${}", tag, AUTOIDX.fetch_add(1, Ordering::SeqCst))
+}
+
 mod design;
 pub use design::*;
 
@@ -37,6 +42,12 @@ pub use constant::*;
 mod signal;
 pub use signal::*;
 
+mod sigbit;
+pub use sigbit::*;
+
+mod sigmap;
+pub use sigmap::*;
+
 macro_rules! define_type {
     ( $($x: ident),* ) => {
         #[derive(Debug)]
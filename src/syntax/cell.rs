@@ -75,6 +75,23 @@ impl Cell {
         }
         r
     }
+
+    pub fn param(&self, k: &str) -> Option<&Const> {
+        self.params.get(k).map(|p| p.val())
+    }
+
+    pub fn set_param(&mut self, k: &str, v: Const) {
+        self.params
+            .insert(k.to_string(), CellParam::new(v, CellFlag::empty()));
+    }
+
+    pub fn port(&self, k: &str) -> Option<&SigSpec> {
+        self.connects.get(k)
+    }
+
+    pub fn set_port(&mut self, k: &str, v: SigSpec) {
+        self.connects.insert(k.to_string(), v);
+    }
 }
 
 impl Visit for Cell {
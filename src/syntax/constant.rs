@@ -8,6 +8,85 @@ pub enum Const {
     Int(i64),
 }
 
+impl Const {
+    /// Bits of the constant, LSB first. Integers are 32 bits wide and
+    /// strings 8 bits per character, like yosys does.
+    pub fn to_bits(&self) -> Vec<State> {
+        match self {
+            Const::Empty => vec![],
+            Const::Int(n) => (0..32)
+                .map(|i| {
+                    if (n >> i) & 1 == 1 {
+                        State::S1
+                    } else {
+                        State::S0
+                    }
+                })
+                .collect(),
+            Const::Str(s) => s
+                .bytes()
+                .rev()
+                .flat_map(|c| {
+                    (0..8).map(move |i| {
+                        if (c >> i) & 1 == 1 {
+                            State::S1
+                        } else {
+                            State::S0
+                        }
+                    })
+                })
+                .collect(),
+            Const::Sig(s) => {
+                let width = (*s.width()).max(0) as usize;
+                let mut r: Vec<State> = s.bits().iter().rev().cloned().collect();
+                r.truncate(width);
+                let pad = match r.last() {
+                    Some(State::Sx) => State::Sx,
+                    Some(State::Sz) => State::Sz,
+                    _ => State::S0,
+                };
+                r.resize(width, pad);
+                r
+            }
+        }
+    }
+
+    /// Build a constant from bits given LSB first.
+    pub fn from_bits(bits: &[State]) -> Self {
+        Const::Sig(Signal::new(
+            bits.len() as i64,
+            bits.iter().rev().cloned().collect(),
+        ))
+    }
+
+    /// Value of a fully defined constant of at most 64 bits, read unsigned.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Const::Int(n) => Some(*n),
+            Const::Sig(_) => {
+                let bits = self.to_bits();
+                if bits.len() > 64 {
+                    return None;
+                }
+                let mut v: i64 = 0;
+                for b in bits.iter().rev() {
+                    v = match b {
+                        State::S0 => v << 1,
+                        State::S1 => (v << 1) | 1,
+                        _ => return None,
+                    };
+                }
+                Some(v)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        self.to_bits().contains(&State::S1)
+    }
+}
+
 impl fmt::Display for Const {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
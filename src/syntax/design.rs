@@ -14,6 +14,14 @@ impl Design {
     pub fn new() -> Self {
         Self::default()
     }
+
+    pub fn module(&self, ident: &str) -> Option<&Module> {
+        self.modules.iter().find(|m| m.ident() == ident)
+    }
+
+    pub fn module_mut(&mut self, ident: &str) -> Option<&mut Module> {
+        self.modules.iter_mut().find(|m| m.ident() == ident)
+    }
 }
 
 impl Visit for Design {
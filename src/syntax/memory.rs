@@ -23,6 +23,7 @@ impl Memory {
     pub fn new(i: String, o: Vec<MemoryOption>) -> Self {
         let mut r = Self {
             id: i,
+            width: 1,
             ..Self::default()
         };
         for opt in o {
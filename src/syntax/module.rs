@@ -19,7 +19,8 @@ pub enum ModuleStmt {
 pub struct Module {
     ident: String,
     attrs: HashMap<String, Const>,
-    params: HashMap<String, Const>,
+    /// Parameters with their default values, in declaration order.
+    params: Vec<(String, Const)>,
     wires: Vec<Wire>,
     cells: Vec<Cell>,
     processes: Vec<Process>,
@@ -35,12 +36,8 @@ impl Module {
         };
         for stmt in stmts {
             match stmt {
-                ModuleStmt::Param(n) => {
-                    r.params.insert(n, Const::Empty);
-                }
-                ModuleStmt::ParamVal((k, v)) => {
-                    r.params.insert(k, v);
-                }
+                ModuleStmt::Param(n) => r.set_param(&n, Const::Empty),
+                ModuleStmt::ParamVal((k, v)) => r.set_param(&k, v),
                 ModuleStmt::Wire(n) => r.wires.push(n),
                 ModuleStmt::Cell(n) => r.cells.push(n),
                 ModuleStmt::Process(n) => r.processes.push(n),
@@ -51,6 +48,63 @@ impl Module {
         }
         r
     }
+
+    pub fn wire(&self, id: &str) -> Option<&Wire> {
+        self.wires.iter().find(|w| w.id() == id)
+    }
+
+    pub fn cell(&self, id: &str) -> Option<&Cell> {
+        self.cells.iter().find(|c| c.i2() == id)
+    }
+
+    pub fn cell_mut(&mut self, id: &str) -> Option<&mut Cell> {
+        self.cells.iter_mut().find(|c| c.i2() == id)
+    }
+
+    /// Default value of parameter `k`.
+    pub fn param(&self, k: &str) -> Option<&Const> {
+        self.params.iter().find(|(n, _)| n == k).map(|(_, v)| v)
+    }
+
+    /// Set the default value of parameter `k`, declaring it after the others
+    /// if it is new.
+    pub fn set_param(&mut self, k: &str, v: Const) {
+        match self.params.iter_mut().find(|(n, _)| n == k) {
+            Some((_, old)) => *old = v,
+            None => self.params.push((k.to_string(), v)),
+        }
+    }
+
+    /// Add a fresh private wire and return a SigSpec covering it.
+    pub fn new_wire(&mut self, tag: &str, width: i64) -> SigSpec {
+        let id = new_id(tag);
+        self.wires
+            .push(Wire::new(id.clone(), vec![WireOption::Width(width)]));
+        SigSpec::wire(&id)
+    }
+
+    /// Add a cell with a fresh private name and return it.
+    pub fn new_cell(&mut self, tp: &str, tag: &str) -> &mut Cell {
+        self.cells
+            .push(Cell::new(tp.to_string(), new_id(tag), vec![]));
+        self.cells.last_mut().unwrap()
+    }
+
+    /// Call `f` on every SigSpec of the module body.
+    pub fn rewrite_sigs<F: FnMut(&mut SigSpec)>(&mut self, mut f: F) {
+        for c in self.connects.iter_mut() {
+            f(c.sig1_mut());
+            f(c.sig2_mut());
+        }
+        for c in self.cells.iter_mut() {
+            for s in c.connects_mut().values_mut() {
+                f(s);
+            }
+        }
+        for p in self.processes.iter_mut() {
+            p.rewrite_sigs(&mut f);
+        }
+    }
 }
 
 impl Visit for Module {
@@ -59,12 +113,18 @@ impl Visit for Module {
         for n in self.wires.iter_mut() {
             n.visit(f)?;
         }
+        for n in self.memories.iter_mut() {
+            n.visit(f)?;
+        }
         for n in self.cells.iter_mut() {
             n.visit(f)?;
         }
         for n in self.processes.iter_mut() {
             n.visit(f)?;
         }
+        for n in self.connects.iter_mut() {
+            n.visit(f)?;
+        }
         f.leave(Node::Module(self))?;
         Ok(())
     }
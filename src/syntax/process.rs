@@ -30,6 +30,19 @@ impl ProcessSwitchCase {
         }
         r
     }
+
+    pub fn rewrite_sigs<F: FnMut(&mut SigSpec)>(&mut self, f: &mut F) {
+        for s in self.sigs.iter_mut() {
+            f(s);
+        }
+        for (l, r) in self.assign.iter_mut() {
+            f(l);
+            f(r);
+        }
+        for s in self.switch.iter_mut() {
+            s.rewrite_sigs(f);
+        }
+    }
 }
 
 impl Visit for ProcessSwitchCase {
@@ -60,6 +73,13 @@ impl ProcessSwitch {
             attrs: HashMap::new(),
         }
     }
+
+    pub fn rewrite_sigs<F: FnMut(&mut SigSpec)>(&mut self, f: &mut F) {
+        f(&mut self.sig);
+        for c in self.cases.iter_mut() {
+            c.rewrite_sigs(f);
+        }
+    }
 }
 
 impl Visit for ProcessSwitch {
@@ -108,6 +128,21 @@ impl ProcessSync {
             attrs: HashMap::new(),
         }
     }
+
+    pub fn rewrite_sigs<F: FnMut(&mut SigSpec)>(&mut self, f: &mut F) {
+        match &mut self.tp {
+            ProcessSyncType::Low(s)
+            | ProcessSyncType::High(s)
+            | ProcessSyncType::Posedge(s)
+            | ProcessSyncType::Negedge(s)
+            | ProcessSyncType::Edge(s) => f(s),
+            _ => (),
+        }
+        for (l, r) in self.updates.iter_mut() {
+            f(l);
+            f(r);
+        }
+    }
 }
 
 impl Visit for ProcessSync {
@@ -149,6 +184,19 @@ impl Process {
         }
         r
     }
+
+    pub fn rewrite_sigs<F: FnMut(&mut SigSpec)>(&mut self, f: &mut F) {
+        for (l, r) in self.assign.iter_mut() {
+            f(l);
+            f(r);
+        }
+        for s in self.switch.iter_mut() {
+            s.rewrite_sigs(f);
+        }
+        for s in self.syncs.iter_mut() {
+            s.rewrite_sigs(f);
+        }
+    }
 }
 
 impl Visit for Process {
@@ -0,0 +1,197 @@
+use super::*;
+use anyhow::{anyhow, bail};
+
+/// A single bit of a SigSpec: a constant or bit `i` (counted from the LSB,
+/// regardless of `offset`/`upto`) of a wire.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SigBit {
+    Const(State),
+    Wire((String, i64)),
+}
+
+impl SigBit {
+    pub fn is_const(&self) -> bool {
+        matches!(self, SigBit::Const(_))
+    }
+
+    pub fn state(&self) -> Option<State> {
+        match self {
+            SigBit::Const(s) => Some(*s),
+            _ => None,
+        }
+    }
+
+    pub fn wire(&self) -> Option<&str> {
+        match self {
+            SigBit::Wire((w, _)) => Some(w),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SigBit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigBit::Const(s) => write!(f, "1'{}", s),
+            SigBit::Wire((w, i)) => write!(f, "{} [{}]", w, i),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WireShape {
+    width: i64,
+    offset: i64,
+    upto: bool,
+}
+
+impl WireShape {
+    fn index(&self, hdl: i64) -> i64 {
+        if self.upto {
+            self.width - 1 - (hdl - self.offset)
+        } else {
+            hdl - self.offset
+        }
+    }
+
+    fn hdl(&self, index: i64) -> i64 {
+        if self.upto {
+            self.width - 1 - index + self.offset
+        } else {
+            index + self.offset
+        }
+    }
+}
+
+/// Wire shapes of a module, used to convert between SigSpecs and bits.
+#[derive(Debug, Clone, Default)]
+pub struct WireTable {
+    wires: HashMap<String, WireShape>,
+}
+
+impl WireTable {
+    pub fn new(m: &Module) -> Self {
+        let mut r = Self::default();
+        for w in m.wires() {
+            r.insert(w);
+        }
+        r
+    }
+
+    pub fn insert(&mut self, w: &Wire) {
+        self.wires.insert(
+            w.id().clone(),
+            WireShape {
+                width: *w.width(),
+                offset: *w.offset(),
+                upto: *w.upto(),
+            },
+        );
+    }
+
+    pub fn width(&self, id: &str) -> Option<i64> {
+        self.wires.get(id).map(|w| w.width)
+    }
+
+    /// Indices of the MSB and LSB of wire `id`, as its `[msb:lsb]` range
+    /// is written in HDL.
+    pub fn range(&self, id: &str) -> Option<(i64, i64)> {
+        self.wires.get(id).map(|w| (w.hdl(w.width - 1), w.hdl(0)))
+    }
+
+    /// Bits of `s`, LSB first.
+    pub fn bits(&self, s: &SigSpec) -> Result<Vec<SigBit>> {
+        let mut r = Vec::new();
+        self.collect(s, &mut r)?;
+        Ok(r)
+    }
+
+    pub fn width_of(&self, s: &SigSpec) -> Result<i64> {
+        Ok(self.bits(s)?.len() as i64)
+    }
+
+    fn collect(&self, s: &SigSpec, r: &mut Vec<SigBit>) -> Result<()> {
+        match s {
+            SigSpec::Const((c, range)) => {
+                let bits = c.to_bits();
+                let (lo, hi) = match range {
+                    None => (0, bits.len() as i64 - 1),
+                    Some((l, h)) => (*l.min(h), *l.max(h)),
+                };
+                if lo < 0 || hi >= bits.len() as i64 {
+                    bail!("slice of `{}' out of range", s);
+                }
+                for b in &bits[lo as usize..(hi + 1) as usize] {
+                    r.push(SigBit::Const(*b));
+                }
+            }
+            SigSpec::Refer((n, range)) => {
+                let w = self
+                    .wires
+                    .get(n)
+                    .ok_or_else(|| anyhow!("wire `{}' not found", n))?;
+                let (lo, hi) = match range {
+                    None => (0, w.width - 1),
+                    Some((l, h)) => {
+                        let (a, b) = (w.index(*l), w.index(*h));
+                        (a.min(b), a.max(b))
+                    }
+                };
+                if lo < 0 || hi >= w.width {
+                    bail!("slice of `{}' out of range", s);
+                }
+                for i in lo..=hi {
+                    r.push(SigBit::Wire((n.clone(), i)));
+                }
+            }
+            // the first item of a concatenation holds the most significant bits
+            SigSpec::List(v) => {
+                for m in v.iter().rev() {
+                    self.collect(m, r)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Shortest SigSpec for `bits` (LSB first).
+    pub fn sigspec(&self, bits: &[SigBit]) -> SigSpec {
+        let mut chunks: Vec<SigSpec> = Vec::new();
+        let mut i = 0;
+        while i < bits.len() {
+            let mut j = i + 1;
+            match &bits[i] {
+                SigBit::Const(_) => {
+                    while j < bits.len() && bits[j].is_const() {
+                        j += 1;
+                    }
+                    let states: Vec<State> =
+                        bits[i..j].iter().map(|b| b.state().unwrap()).collect();
+                    chunks.push(SigSpec::constant(&states));
+                }
+                SigBit::Wire((w, lo)) => {
+                    while j < bits.len()
+                        && bits[j] == SigBit::Wire((w.clone(), lo + (j - i) as i64))
+                    {
+                        j += 1;
+                    }
+                    let hi = lo + (j - i) as i64 - 1;
+                    let range = match self.wires.get(w) {
+                        Some(s) if *lo == 0 && hi == s.width - 1 => None,
+                        Some(s) => Some((s.hdl(hi), s.hdl(*lo))),
+                        None => Some((hi, *lo)),
+                    };
+                    chunks.push(SigSpec::Refer((w.clone(), range)));
+                }
+            }
+            i = j;
+        }
+        match chunks.len() {
+            1 => chunks.pop().unwrap(),
+            _ => {
+                chunks.reverse();
+                SigSpec::List(chunks)
+            }
+        }
+    }
+}
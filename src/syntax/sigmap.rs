@@ -0,0 +1,66 @@
+use super::*;
+
+/// Classes of bits connected together by a module's `connect` statements.
+/// Each class is represented by a constant if it holds one, else by a public
+/// wire bit if any, else by the smallest bit.
+#[derive(Debug, Clone, Default)]
+pub struct SigMap {
+    parent: HashMap<SigBit, SigBit>,
+}
+
+fn better(a: &SigBit, b: &SigBit) -> bool {
+    let rank = |s: &SigBit| match s {
+        SigBit::Const(_) => 0,
+        SigBit::Wire((w, _)) if w.starts_with('\\') => 1,
+        _ => 2,
+    };
+    (rank(a), a) < (rank(b), b)
+}
+
+impl SigMap {
+    pub fn new(module: &Module, wt: &WireTable) -> Result<Self> {
+        let mut r = Self::default();
+        for c in module.connects() {
+            r.add(&wt.bits(c.sig1())?, &wt.bits(c.sig2())?);
+        }
+        Ok(r)
+    }
+
+    /// Connect `a` and `b` bit by bit.
+    pub fn add(&mut self, a: &[SigBit], b: &[SigBit]) {
+        for (x, y) in a.iter().zip(b) {
+            let (x, y) = (self.map_bit(x), self.map_bit(y));
+            if x == y || (x.is_const() && y.is_const()) {
+                continue;
+            }
+            if better(&x, &y) {
+                self.parent.insert(y, x);
+            } else {
+                self.parent.insert(x, y);
+            }
+        }
+    }
+
+    /// Make `b` the representative of its class, unless the class holds a
+    /// constant.
+    pub fn promote(&mut self, b: &SigBit) {
+        let r = self.map_bit(b);
+        if r == *b || r.is_const() {
+            return;
+        }
+        self.parent.remove(b);
+        self.parent.insert(r, b.clone());
+    }
+
+    pub fn map_bit(&self, b: &SigBit) -> SigBit {
+        let mut b = b;
+        while let Some(p) = self.parent.get(b) {
+            b = p;
+        }
+        b.clone()
+    }
+
+    pub fn map(&self, bits: &[SigBit]) -> Vec<SigBit> {
+        bits.iter().map(|b| self.map_bit(b)).collect()
+    }
+}
@@ -1,37 +1,49 @@
 use super::*;
 
+/// A SigSpec either covers a whole wire/constant (`None`) or the slice
+/// `[l:r]` of it, with `[i]` stored as `[i:i]`.
 #[derive(Debug, Clone)]
 pub enum SigSpec {
-    Const((Const, i64, i64)),
-    Refer((String, i64, i64)),
+    Const((Const, Option<(i64, i64)>)),
+    Refer((String, Option<(i64, i64)>)),
     List(Vec<SigSpec>),
 }
 
+impl SigSpec {
+    pub fn wire(id: &str) -> Self {
+        SigSpec::Refer((id.to_string(), None))
+    }
+
+    pub fn constant(bits: &[State]) -> Self {
+        SigSpec::Const((Const::from_bits(bits), None))
+    }
+}
+
+fn fmt_range(f: &mut fmt::Formatter<'_>, r: &Option<(i64, i64)>) -> fmt::Result {
+    match r {
+        Some((l, r)) if l == r => write!(f, " [{}]", l),
+        Some((l, r)) => write!(f, " [{}:{}]", l, r),
+        None => Ok(()),
+    }
+}
+
 impl fmt::Display for SigSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SigSpec::Const((n, l, r)) => {
+            SigSpec::Const((n, r)) => {
                 write!(f, "{}", n)?;
-                if *r != 0 {
-                    write!(f, "[{}:{}]", l, r)?;
-                } else if *l != 0 {
-                    write!(f, "[{}]", l)?;
-                }
+                fmt_range(f, r)?;
             }
-            SigSpec::Refer((n, l, r)) => {
+            SigSpec::Refer((n, r)) => {
                 write!(f, "{}", n)?;
-                if *r != 0 {
-                    write!(f, "[{}:{}]", l, r)?;
-                } else if *l != 0 {
-                    write!(f, "[{}]", l)?;
-                }
+                fmt_range(f, r)?;
             }
             SigSpec::List(n) => {
                 write!(f, "{{")?;
                 for m in n.iter() {
-                    write!(f, "{}", m)?;
+                    write!(f, " {}", m)?;
                 }
-                write!(f, "}}")?;
+                write!(f, " }}")?;
             }
         };
         Ok(())
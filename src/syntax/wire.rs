@@ -31,6 +31,7 @@ impl Wire {
     pub fn new(i: String, o: Vec<WireOption>) -> Self {
         let mut r = Self {
             id: i,
+            width: 1,
             ..Self::default()
         };
         for opt in o {
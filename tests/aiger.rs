@@ -0,0 +1,32 @@
+use rtlil::aiger::read_aiger;
+
+fn read(symbols: &str, outputs: usize) -> anyhow::Result<rtlil::syntax::Module> {
+    // y = i0 & i1, on every output
+    let mut s = format!("aag 3 2 0 {} 1\n2\n4\n", outputs);
+    s += &"6\n".repeat(outputs);
+    s += "6 2 4\n";
+    s += symbols;
+    read_aiger(s.as_bytes(), "\\top")
+}
+
+#[test]
+fn grouped_symbols() {
+    let m = read("i0 a[1]\ni1 a[2]\no0 y\n", 1).unwrap();
+    let a = m.wires().iter().find(|w| w.id() == "\\a").unwrap();
+    assert_eq!(*a.width(), 2);
+    assert_eq!(*a.offset(), 1);
+    assert!(m.wires().iter().any(|w| w.id() == "\\y"));
+}
+
+#[test]
+fn conflicting_symbols() {
+    // a wire and one of its bits
+    assert!(read("i0 a\ni1 a[0]\no0 y\n", 1).is_err());
+    assert!(read("i0 a[0]\ni1 a\no0 y\n", 1).is_err());
+    // an input and an output
+    assert!(read("i0 a\ni1 b\no0 a\n", 1).is_err());
+    // the same output twice
+    assert!(read("i0 a\ni1 b\no0 y\no1 y\n", 2).is_err());
+    assert!(read("i0 a[0]\ni1 a[0]\no0 y\n", 1).is_err());
+    assert!(read("i0 a\ni1 b\no0 y\no1 z\n", 2).is_ok());
+}
@@ -0,0 +1,49 @@
+use rtlil::backends::write_blif;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+
+fn write(s: &str) -> anyhow::Result<String> {
+    let d = Parser::new().parse(Lexer::new(s.chars())).unwrap();
+    let mut out = Vec::new();
+    write_blif(&mut out, &d)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+fn lut(width: usize, table: &str) -> String {
+    format!(
+        r#"
+module \top
+  wire width {w} input 1 \a
+  wire output 2 \y
+  cell $lut $lut$1
+    parameter \WIDTH {w}
+    parameter \LUT {t}
+    connect \A \a
+    connect \Y \y
+  end
+end
+"#,
+        w = width,
+        t = table
+    )
+}
+
+#[test]
+fn lut_rows() {
+    // two input xor
+    let v = write(&lut(2, "4'0110")).unwrap();
+    let rows: Vec<&str> = v
+        .lines()
+        .skip_while(|l| !l.starts_with(".names a[0] a[1] y"))
+        .skip(1)
+        .take_while(|l| !l.starts_with('.'))
+        .collect();
+    assert_eq!(rows, ["10 1", "01 1"]);
+}
+
+#[test]
+fn wide_lut() {
+    assert!(write(&lut(20, "1'1")).is_ok());
+    assert!(write(&lut(21, "1'1")).is_err());
+    assert!(write(&lut(64, "1'1")).is_err());
+}
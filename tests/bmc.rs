@@ -0,0 +1,145 @@
+#![cfg(feature = "sat")]
+
+use rtlil::bmc::{bmc, cover};
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+
+// 2-bit counter from 0, counting while `\en` is set, with `\q != 3`
+// asserted and `\q == 2` covered
+const COUNTER: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \en
+  attribute \init 2'00
+  wire width 2 output 3 \q
+  wire width 2 $next
+  wire width 2 $d
+  wire $ok
+  wire $two
+  cell $add $add$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \q
+    connect \B 2'01
+    connect \Y $next
+  end
+  cell $mux $mux$2
+    parameter \WIDTH 2
+    connect \A \q
+    connect \B $next
+    connect \S \en
+    connect \Y $d
+  end
+  cell $dff $dff$3
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D $d
+    connect \Q \q
+  end
+  cell $ne $ne$4
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 1
+    connect \A \q
+    connect \B 2'11
+    connect \Y $ok
+  end
+  cell $assert $assert$5
+    connect \A $ok
+    connect \EN 1'1
+  end
+  cell $eq $eq$6
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 1
+    connect \A \q
+    connect \B 2'10
+    connect \Y $two
+  end
+  cell $cover $cover$7
+    connect \A $two
+    connect \EN 1'1
+  end
+end
+"#;
+
+fn top(d: &Design) -> &Module {
+    d.module("\\top").unwrap()
+}
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn bmc_depth_boundary() {
+    let d = parse(COUNTER);
+    let m = top(&d);
+    // \q is 3 in the fourth step at the earliest
+    for depth in 0..4 {
+        assert!(bmc(m, depth).unwrap().is_none(), "depth {}", depth);
+    }
+    for depth in 4..6 {
+        let w = bmc(m, depth).unwrap().expect("counterexample");
+        assert_eq!(w.steps(), 4, "depth {}", depth);
+        assert_eq!(w.cells(), &["$assert$5".to_string()]);
+        assert_eq!(w.inputs().len(), 4);
+        for step in w.inputs() {
+            assert_eq!(step["\\en"].as_int(), Some(1));
+        }
+    }
+}
+
+#[test]
+fn cover_depth_boundary() {
+    let d = parse(COUNTER);
+    let m = top(&d);
+    for depth in 0..3 {
+        let r = cover(m, depth).unwrap();
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].0, "$cover$7");
+        assert!(r[0].1.is_none(), "depth {}", depth);
+    }
+    let r = cover(m, 3).unwrap();
+    assert_eq!(r[0].1.as_ref().expect("trace").steps(), 3);
+}
+
+#[test]
+fn witness_vcd_ends_with_failing_assert() {
+    // make the asserted signal public so that it is dumped
+    let d = parse(&COUNTER.replace("$ok", "\\ok"));
+    let w = bmc(top(&d), 6).unwrap().expect("counterexample");
+    let vcd = String::from_utf8(w.write_vcd(Vec::new()).unwrap()).unwrap();
+    let code = vcd
+        .lines()
+        .find_map(|l| match l.split(' ').collect::<Vec<_>>()[..] {
+            ["$var", "wire", "1", code, "ok", "$end"] => Some(code.to_string()),
+            _ => None,
+        })
+        .expect("variable of `ok'");
+    // value of `ok' at each timestamp it changes
+    let (mut time, mut values) = (None, Vec::new());
+    for l in vcd.lines() {
+        if let Some(t) = l.strip_prefix('#') {
+            time = Some(t.parse::<u64>().unwrap());
+        } else if l.len() == 1 + code.len() && l.ends_with(&code) {
+            values.push((time, l[..1].to_string()));
+        }
+    }
+    let last = vcd.lines().rev().find(|l| l.starts_with('#')).unwrap();
+    assert_eq!(last, format!("#{}", w.steps() - 1));
+    assert_eq!(values.first(), Some(&(Some(0), "1".to_string())));
+    assert_eq!(
+        values.last(),
+        Some(&(Some(w.steps() as u64 - 1), "0".to_string()))
+    );
+}
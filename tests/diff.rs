@@ -0,0 +1,148 @@
+use rtlil::diff::*;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+const A: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 2 output 2 \y
+  wire width 2 $n1
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \Y $n1
+  end
+  cell $and \g
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B $n1
+    connect \Y \y
+  end
+end
+module \sub
+end
+"#;
+
+// `A` as written back by a tool renaming auto names
+const RENAMED: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 2 output 2 \y
+  wire width 2 $auto$7
+  cell $not $auto$8
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \Y $auto$7
+  end
+  cell $and \g
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B $auto$7
+    connect \Y \y
+  end
+end
+module \sub
+end
+"#;
+
+// `A` with real changes
+const CHANGED: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 3 output 2 \y
+  wire \extra
+  wire width 2 $n1
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \Y $n1
+  end
+  cell $or \g
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 3
+    connect \A \a
+    connect \B $n1
+    connect \Y \y
+  end
+end
+"#;
+
+fn lines(a: &str, b: &str, structural: bool) -> Vec<String> {
+    diff(&parse(a), &parse(b), structural)
+        .iter()
+        .map(|d| d.to_string())
+        .collect()
+}
+
+#[test]
+fn identical() {
+    assert!(lines(A, A, false).is_empty());
+    assert!(lines(A, A, true).is_empty());
+}
+
+#[test]
+fn renamed_auto_names() {
+    assert_eq!(
+        lines(A, RENAMED, false),
+        [
+            "- wire \\top $n1",
+            "+ wire \\top $auto$7",
+            "- cell \\top $not$1",
+            "~ cell \\top \\g: port \\B: $n1 -> $auto$7",
+            "+ cell \\top $auto$8",
+        ]
+    );
+    // paired by structure instead of by name
+    assert!(lines(A, RENAMED, true).is_empty());
+}
+
+#[test]
+fn changes() {
+    assert_eq!(
+        lines(A, CHANGED, true),
+        [
+            "~ wire \\top \\y: width: 2 -> 3",
+            "+ wire \\top \\extra",
+            "~ cell \\top \\g: type: $and -> $or",
+            "~ cell \\top \\g: parameter \\Y_WIDTH: 2 -> 3",
+            "- module \\sub",
+        ]
+    );
+    let d = diff(&parse(A), &parse(CHANGED), false);
+    assert_eq!(*d[0].kind(), Kind::Wire);
+    assert_eq!(d[0].name(), "\\y");
+    assert_eq!(
+        *d[0].change(),
+        Change::Changed {
+            what: "width".to_string(),
+            old: Some("2".to_string()),
+            new: Some("3".to_string()),
+        }
+    );
+    // the other way round
+    let back = lines(CHANGED, A, false);
+    assert!(back.contains(&"+ module \\sub".to_string()));
+    assert!(back.contains(&"- wire \\top \\extra".to_string()));
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "sat")]
+
+use rtlil::backends::write_dimacs;
+use rtlil::cnf::ModuleCnf;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::techmap;
+use rtlil::sat::Solver;
+use rtlil::syntax::*;
+use std::collections::HashMap;
+
+// 2-bit accumulator: `\y = \a + \q`, registered into `\q` from 1
+const ACC: &str = r#"
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \a
+  wire width 2 output 3 \y
+  attribute \init 2'01
+  wire width 2 output 4 \q
+  cell $add $add$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B \q
+    connect \Y \y
+  end
+  cell $_DFF_P_ $dff$2
+    connect \C \clk
+    connect \D \y [0]
+    connect \Q \q [0]
+  end
+  cell $_DFF_P_ $dff$3
+    connect \C \clk
+    connect \D \y [1]
+    connect \Q \q [1]
+  end
+end
+"#;
+
+// Clauses and comment lines of a DIMACS file.
+#[derive(Default)]
+struct Dimacs {
+    vars: usize,
+    clauses: Vec<Vec<i32>>,
+    ports: HashMap<String, Vec<i32>>,
+    // current and next state literal of each flip-flop bit
+    state: Vec<(String, i32, i32)>,
+    init: Vec<i32>,
+}
+
+fn lits(v: &[&str]) -> Vec<i32> {
+    v.iter().map(|l| l.parse().unwrap()).collect()
+}
+
+fn read(s: &str) -> Dimacs {
+    let mut d = Dimacs::default();
+    let mut count = 0;
+    for line in s.lines() {
+        let v: Vec<&str> = line.split_whitespace().collect();
+        match v.as_slice() {
+            ["c", "input", name, rest @ ..] | ["c", "output", name, rest @ ..] => {
+                d.ports.insert(name.to_string(), lits(rest));
+            }
+            ["c", "state", name, q, next] => {
+                d.state
+                    .push((name.to_string(), q.parse().unwrap(), next.parse().unwrap()));
+            }
+            ["c", "init", rest @ ..] => d.init = lits(rest),
+            ["p", "cnf", vars, n] => {
+                d.vars = vars.parse().unwrap();
+                count = n.parse().unwrap();
+            }
+            [clause @ .., "0"] => d.clauses.push(lits(clause)),
+            _ => panic!("unexpected line `{}'", line),
+        }
+    }
+    assert_eq!(d.clauses.len(), count);
+    d
+}
+
+fn module() -> Module {
+    let mut d = Parser::new().parse(Lexer::new(ACC.chars())).unwrap();
+    let m = d.module_mut("\\top").unwrap();
+    techmap(m).unwrap();
+    m.clone()
+}
+
+// Assumptions setting the literals `l` to the bits of `v`.
+fn assign(l: &[i32], v: u64) -> Vec<i32> {
+    l.iter()
+        .enumerate()
+        .map(|(i, l)| if (v >> i) & 1 == 1 { *l } else { -l })
+        .collect()
+}
+
+fn value(s: &Solver, l: &[i32]) -> u64 {
+    l.iter()
+        .enumerate()
+        .map(|(i, l)| (s.value(*l).unwrap() as u64) << i)
+        .sum()
+}
+
+#[test]
+fn round_trip_through_solver() {
+    let m = module();
+    let mut out = Vec::new();
+    write_dimacs(&mut out, &m).unwrap();
+    let d = read(&String::from_utf8(out).unwrap());
+
+    let mut enc = ModuleCnf::new(&m).unwrap();
+    let (a, y, q) = (
+        enc.lits(&SigSpec::wire("\\a")).unwrap(),
+        enc.lits(&SigSpec::wire("\\y")).unwrap(),
+        enc.lits(&SigSpec::wire("\\q")).unwrap(),
+    );
+    assert_eq!(d.ports["\\a"], a);
+    assert_eq!(d.ports["\\y"], y);
+    assert_eq!(d.ports["\\q"], q);
+    let cur: Vec<i32> = d.state.iter().map(|s| s.1).collect();
+    let next: Vec<i32> = d.state.iter().map(|s| s.2).collect();
+    let names: Vec<&str> = d.state.iter().map(|s| s.0.as_str()).collect();
+    assert_eq!(names, ["\\q[0]", "\\q[1]"]);
+    assert_eq!(cur, q);
+    // `\q` starts at 1
+    assert_eq!(d.init, [q[0], -q[1]]);
+
+    let mut file = Solver::new();
+    for c in d.clauses.iter() {
+        file.add_clause(c);
+    }
+    assert_eq!(file.vars(), d.vars);
+    let mut direct = Solver::from_cnf(enc.cnf());
+    for av in 0..4 {
+        for qv in 0..4 {
+            let mut assume = assign(&a, av);
+            assume.extend(assign(&q, qv));
+            assert!(file.solve_with(&assume));
+            assert!(direct.solve_with(&assume));
+            let sum = (av + qv) & 3;
+            assert_eq!(value(&file, &y), sum);
+            assert_eq!(value(&direct, &y), sum);
+            // the next state is the `D` input
+            assert_eq!(value(&file, &next), sum);
+        }
+    }
+    // no output other than the sum
+    let mut assume = assign(&a, 1);
+    assume.extend(assign(&q, 1));
+    assume.push(-y[1]);
+    assert!(!file.solve_with(&assume));
+    assert!(!direct.solve_with(&assume));
+}
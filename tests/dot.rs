@@ -0,0 +1,64 @@
+use rtlil::backends::to_dot;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+
+// an adder driving a concatenation of slices, and a process reading and
+// driving slices
+const SLICES: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 3 output 2 \y
+  wire width 2 output 3 \q
+  wire \lo
+  cell $add $add$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 1
+    parameter \B_WIDTH 1
+    parameter \Y_WIDTH 2
+    connect \A \a [0]
+    connect \B \a [1]
+    connect \Y { \y [2] \lo }
+  end
+  process $proc$2
+    assign \q [1] \a [0]
+    sync always
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn edges_follow_the_signal() {
+    let d = parse(SLICES);
+    let dot = to_dot(d.module("\\top").unwrap(), true).unwrap();
+    let lines: Vec<&str> = dot.lines().collect();
+    for edge in [
+        // read slices: from the wire to the record, to the reader
+        "n0 -> x1:s0 [ label=\"\" ];",
+        "x1:e -> c0:p0:w [ label=\"\" ];",
+        "n0 -> x4:s0 [ label=\"\" ];",
+        "x4:e -> p0 [ label=\"\" ];",
+        // driven slices: from the driver to the record, to the wires
+        "c0:p2:e -> x3:w [ label=\"2\", style=\"setlinewidth(3)\" ];",
+        "x3:s0 -> n1 [ label=\"\" ];",
+        "x3:s1 -> n3 [ label=\"\" ];",
+        "p0 -> x5:w [ label=\"\" ];",
+        "x5:s0 -> n2 [ label=\"\" ];",
+    ]
+    .iter()
+    {
+        assert!(lines.contains(edge), "missing `{}' in\n{}", edge, dot);
+    }
+    for n in ["n1", "n2", "n3"].iter() {
+        let from = format!("{} -> ", n);
+        assert!(!dot.contains(&from), "edge from driven `{}' in\n{}", n, dot);
+    }
+
+    let dot = to_dot(d.module("\\top").unwrap(), false).unwrap();
+    assert!(!dot.contains("PROC"));
+}
@@ -0,0 +1,102 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::process_drivers;
+use rtlil::syntax::*;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn bits(name: &str, n: i64) -> Vec<SigBit> {
+    (0..n)
+        .map(|i| SigBit::Wire((name.to_string(), i)))
+        .collect()
+}
+
+// `always @* if (en) q = d;` as read by yosys' Verilog frontend
+const LATCH: &str = r#"
+module \top
+  wire input 1 \en
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  wire width 2 $0\q[1:0]
+  process $proc$latch.v:5$1
+    assign $0\q[1:0] \q
+    switch \en
+      case 1'1
+        assign $0\q[1:0] \d
+      case
+    end
+    sync always
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+// `always @* if (en) q = d; else q = 0;`
+const COMB: &str = r#"
+module \top
+  wire input 1 \en
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  wire width 2 $0\q[1:0]
+  process $proc$comb.v:5$1
+    assign $0\q[1:0] \q
+    switch \en
+      case 1'1
+        assign $0\q[1:0] \d
+      case
+        assign $0\q[1:0] 2'00
+    end
+    sync always
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+// `always @* begin y = a; if (en) y = y; end`
+const HOLD: &str = r#"
+module \top
+  wire input 1 \en
+  wire input 2 \a
+  wire output 3 \y
+  process $proc$hold.v:5$1
+    assign \y \a
+    switch \en
+      case 1'1
+        assign \y \y
+      case
+    end
+  end
+end
+"#;
+
+#[test]
+fn latch_from_yosys_frontend() {
+    let d = parse(LATCH);
+    let r = process_drivers(d.module("\\top").unwrap()).unwrap();
+    assert_eq!(r.len(), 1);
+    assert_eq!(r[0].comb(), &bits("\\q", 2));
+    assert_eq!(r[0].partial(), &bits("\\q", 2));
+    assert!(r[0].registered().is_empty());
+    // the root self-assignment is not a driver
+    assert!(r[0].cases()[0].assigned().is_empty());
+    assert_eq!(r[0].cases()[1].assigned(), &bits("$0\\q[1:0]", 2));
+}
+
+#[test]
+fn no_latch_when_always_assigned() {
+    let d = parse(COMB);
+    let r = process_drivers(d.module("\\top").unwrap()).unwrap();
+    assert_eq!(r[0].comb(), &bits("\\q", 2));
+    assert!(r[0].partial().is_empty());
+}
+
+#[test]
+fn self_assignment_on_some_path() {
+    let d = parse(HOLD);
+    let r = process_drivers(d.module("\\top").unwrap()).unwrap();
+    assert_eq!(r[0].comb(), &bits("\\y", 1));
+    assert_eq!(r[0].partial(), &bits("\\y", 1));
+    assert_eq!(r[0].assigned(), &bits("\\y", 1));
+}
@@ -0,0 +1,70 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+use std::process::Command;
+
+// single bits: the defaults the dumper leaves out, and a `[0]` slice that
+// must stay a slice
+const BITS: &str = r#"
+module \top
+  wire \a
+  wire width 2 \b
+  wire output 1 \y
+  memory size 4 \m
+  memory width 2 size 4 \n
+  connect \y \b [0]
+  connect \b [1] \a
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+// Output of the `parse` binary on `text`, which dumps what it parsed.
+fn dump(text: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rtlil-dumper-{}.il", std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_parse"))
+        .arg(&path)
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stderr).unwrap()
+}
+
+#[test]
+fn single_bits_round_trip() {
+    let text = dump(BITS);
+    let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+    for l in [
+        "wire \\a",
+        "wire width 2 \\b",
+        "wire output 1 \\y",
+        "memory size 4 \\m",
+        "memory width 2 size 4 \\n",
+        "connect \\y \\b [0]",
+        "connect \\b [1] \\a",
+    ]
+    .iter()
+    {
+        assert!(lines.contains(l), "missing `{}' in\n{}", l, text);
+    }
+
+    let d = parse(&text);
+    let top = d.module("\\top").unwrap();
+    let widths: Vec<i64> = top.wires().iter().map(|w| *w.width()).collect();
+    assert_eq!(widths, [1, 2, 1]);
+    let widths: Vec<i64> = top.memories().iter().map(|m| *m.width()).collect();
+    assert_eq!(widths, [1, 2]);
+    let connects: Vec<String> = top
+        .connects()
+        .iter()
+        .map(|c| format!("{} {}", c.sig1(), c.sig2()))
+        .collect();
+    assert_eq!(connects, ["\\y \\b [0]", "\\b [1] \\a"]);
+    let wt = WireTable::new(top);
+    assert_eq!(wt.width_of(top.connects()[0].sig2()).unwrap(), 1);
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "sat")]
+
+use rtlil::cnf::Cnf;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::techmap;
+use rtlil::sat::{equiv, Solver};
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+fn binary(tp: &str, a: &str, b: &str, y: &str) -> String {
+    format!(
+        "  cell {} {}$cell\n    parameter \\A_SIGNED 0\n    parameter \\B_SIGNED 0\n    \
+         parameter \\A_WIDTH 3\n    parameter \\B_WIDTH 3\n    parameter \\Y_WIDTH 3\n    \
+         connect \\A {}\n    connect \\B {}\n    connect \\Y {}\n  end\n",
+        tp, y, a, b, y
+    )
+}
+
+// Modules with inputs `\a` and `\b` and output `\y` made of `cells`.
+fn design(modules: &[(&str, String)]) -> Design {
+    let mut s = String::new();
+    for (name, cells) in modules {
+        s += &format!(
+            "module \\{}\n  wire width 3 input 1 \\a\n  wire width 3 input 2 \\b\n  \
+             wire width 3 output 3 \\y\n  wire width 3 \\t\n{}end\n",
+            name, cells
+        );
+    }
+    let mut d = Parser::new().parse(Lexer::new(s.chars())).unwrap();
+    for m in d.modules_mut().iter_mut() {
+        techmap(m).unwrap();
+    }
+    d
+}
+
+fn int(c: &Const) -> u64 {
+    c.to_bits()
+        .iter()
+        .enumerate()
+        .map(|(i, s)| ((*s == State::S1) as u64) << i)
+        .sum()
+}
+
+#[test]
+fn equivalent() {
+    // a + b against a - (0 - b)
+    let d = design(&[
+        ("gold", binary("$add", "\\a", "\\b", "\\y")),
+        (
+            "gate",
+            binary("$sub", "3'000", "\\b", "\\t") + &binary("$sub", "\\a", "\\t", "\\y"),
+        ),
+    ]);
+    let (gold, gate) = (d.module("\\gold").unwrap(), d.module("\\gate").unwrap());
+    assert!(equiv(gold, gate).unwrap().is_none());
+}
+
+#[test]
+fn counterexample() {
+    // a + b against a - b, which differ whenever b is not 0 or 4
+    let d = design(&[
+        ("gold", binary("$add", "\\a", "\\b", "\\y")),
+        ("gate", binary("$sub", "\\a", "\\b", "\\y")),
+    ]);
+    let (gold, gate) = (d.module("\\gold").unwrap(), d.module("\\gate").unwrap());
+    let cex = equiv(gold, gate).unwrap().unwrap();
+    let (a, b) = (int(&cex["\\a"]), int(&cex["\\b"]));
+    assert!(b != 0 && b != 4);
+
+    let mut outputs = Vec::new();
+    for m in ["\\gold", "\\gate"].iter() {
+        let mut sim = Simulator::new(&d, m).unwrap();
+        sim.set_u64("a", a).unwrap();
+        sim.set_u64("b", b).unwrap();
+        sim.update().unwrap();
+        outputs.push(sim.get_u64("y").unwrap());
+    }
+    assert_ne!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn solver() {
+    // three pigeons in two holes
+    let mut cnf = Cnf::new();
+    let p: Vec<Vec<i32>> = (0..3)
+        .map(|_| (0..2).map(|_| cnf.new_var()).collect())
+        .collect();
+    for row in p.iter() {
+        cnf.add(row);
+    }
+    for i in 0..3 {
+        for j in i + 1..3 {
+            for (x, y) in p[i].iter().zip(p[j].iter()) {
+                cnf.add(&[-x, -y]);
+            }
+        }
+    }
+    assert!(!Solver::from_cnf(&cnf).solve());
+
+    // x0 ^ x1 ^ x2 with x0 set
+    let mut cnf = Cnf::new();
+    let x: Vec<i32> = (0..3).map(|_| cnf.new_var()).collect();
+    let t = cnf.xor(x[0], x[1]);
+    let y = cnf.xor(t, x[2]);
+    cnf.add(&[y]);
+    cnf.add(&[x[0]]);
+    let mut s = Solver::from_cnf(&cnf);
+    assert!(s.solve());
+    let v: Vec<bool> = x.iter().map(|l| s.value(*l).unwrap()).collect();
+    assert!(v[0]);
+    assert!(v[0] ^ v[1] ^ v[2]);
+}
@@ -0,0 +1,120 @@
+use rtlil::eval::eval;
+use rtlil::syntax::*;
+use std::collections::HashMap;
+
+// Parse a value written MSB first, like an RTLIL constant.
+fn bits(s: &str) -> Vec<State> {
+    s.chars()
+        .rev()
+        .map(|c| match c {
+            '0' => State::S0,
+            '1' => State::S1,
+            'z' => State::Sz,
+            _ => State::Sx,
+        })
+        .collect()
+}
+
+fn show(v: &[State]) -> String {
+    v.iter().rev().map(|s| s.to_string()).collect()
+}
+
+fn run(tp: &str, params: &[(&str, i64)], inputs: &[(&str, &str)]) -> anyhow::Result<String> {
+    let mut cell = Cell::new(tp.to_string(), "$c".to_string(), vec![]);
+    for (k, v) in params {
+        cell.set_param(k, Const::Int(*v));
+    }
+    let inputs: HashMap<String, Vec<State>> = inputs
+        .iter()
+        .map(|(k, v)| (k.to_string(), bits(v)))
+        .collect();
+    let out = eval(&cell, |k| inputs.get(k).cloned())?;
+    Ok(show(&out.into_iter().find(|(k, _)| k == "\\Y").unwrap().1))
+}
+
+fn binary(tp: &str, signed: bool, w: i64, a: &str, b: &str) -> String {
+    mixed(tp, (signed, signed), w, a, b)
+}
+
+fn mixed(tp: &str, (sa, sb): (bool, bool), w: i64, a: &str, b: &str) -> String {
+    let params = [
+        ("\\A_SIGNED", sa as i64),
+        ("\\B_SIGNED", sb as i64),
+        ("\\A_WIDTH", a.len() as i64),
+        ("\\B_WIDTH", b.len() as i64),
+        ("\\Y_WIDTH", w),
+    ];
+    run(tp, &params, &[("\\A", a), ("\\B", b)]).unwrap()
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(binary("$add", false, 4, "0111", "0011"), "1010");
+    assert_eq!(binary("$sub", false, 4, "0011", "0111"), "1100");
+    assert_eq!(binary("$mul", false, 8, "1111", "1111"), "11100001");
+    // -1 * 3, sign extended
+    assert_eq!(binary("$mul", true, 8, "1111", "0011"), "11111101");
+    assert_eq!(binary("$div", false, 4, "1101", "0011"), "0100");
+    assert_eq!(binary("$mod", true, 4, "1001", "0011"), "1111");
+    // division by zero is undefined
+    assert_eq!(binary("$div", false, 4, "1101", "0000"), "xxxx");
+    // an undefined operand spoils the whole sum
+    assert_eq!(binary("$add", false, 4, "000x", "0001"), "xxxx");
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(binary("$lt", false, 1, "1111", "0001"), "0");
+    assert_eq!(binary("$lt", true, 1, "1111", "0001"), "1");
+    assert_eq!(binary("$ge", true, 1, "1000", "0111"), "0");
+    assert_eq!(binary("$eq", false, 1, "10x1", "0011"), "0");
+    assert_eq!(binary("$eq", false, 1, "10x1", "1011"), "x");
+    assert_eq!(binary("$eqx", false, 1, "10x1", "10x1"), "1");
+}
+
+#[test]
+fn mixed_signedness() {
+    // operands are unsigned unless both are signed
+    for flags in [(true, false), (false, true)].iter() {
+        assert_eq!(mixed("$lt", *flags, 1, "1111", "0001"), "0");
+        assert_eq!(mixed("$gt", *flags, 1, "1111", "0001"), "1");
+        assert_eq!(mixed("$add", *flags, 4, "11", "0001"), "0100");
+        assert_eq!(mixed("$sub", *flags, 4, "0001", "11"), "1110");
+        assert_eq!(mixed("$mul", *flags, 8, "1111", "0011"), "00101101");
+        assert_eq!(mixed("$div", *flags, 4, "1101", "0011"), "0100");
+        assert_eq!(mixed("$eq", *flags, 1, "11", "1111"), "0");
+        assert_eq!(mixed("$and", *flags, 4, "10", "1111"), "0010");
+    }
+    // shifts extend `A' by its own signedness
+    assert_eq!(mixed("$shl", (true, false), 4, "10", "1"), "1100");
+    assert_eq!(mixed("$sshr", (true, false), 4, "1000", "10"), "1110");
+}
+
+#[test]
+fn logic_and_shifts() {
+    assert_eq!(binary("$and", false, 4, "x10x", "0011"), "000x");
+    assert_eq!(binary("$or", false, 4, "x10x", "1100"), "110x");
+    assert_eq!(binary("$shl", false, 4, "0011", "10"), "1100");
+    assert_eq!(binary("$sshr", true, 4, "1000", "10"), "1110");
+    assert_eq!(binary("$shr", false, 4, "1000", "1x"), "xxxx");
+}
+
+#[test]
+fn muxes() {
+    let mux = |tp: &str, a: &str, s: &str| {
+        let p = [("\\WIDTH", 2), ("\\S_WIDTH", s.len() as i64)];
+        run(tp, &p, &[("\\A", a), ("\\S", s)])
+    };
+    assert_eq!(mux("$bmux", "11100100", "10").unwrap(), "10");
+    assert_eq!(mux("$bmux", "11100100", "x0").unwrap(), "xx");
+    assert_eq!(mux("$demux", "11", "01").unwrap(), "00001100");
+    // a select this wide can not be evaluated
+    assert!(mux("$bmux", "11", &"0".repeat(64)).is_err());
+    assert!(mux("$demux", "11", &"0".repeat(100)).is_err());
+
+    let p = [("\\WIDTH", 2), ("\\S_WIDTH", 2)];
+    let pmux = |s| run("$pmux", &p, &[("\\A", "00"), ("\\B", "1001"), ("\\S", s)]);
+    assert_eq!(pmux("00").unwrap(), "00");
+    assert_eq!(pmux("10").unwrap(), "10");
+    assert_eq!(pmux("11").unwrap(), "xx");
+}
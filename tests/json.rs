@@ -0,0 +1,216 @@
+use rtlil::json::{read_json, write_json};
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+const DESIGN: &str = r#"
+attribute \top 1
+attribute \src "counter.v:3"
+module \top
+  parameter \DEPTH 4
+  wire input 1 \clk
+  wire input 3 \rst
+  wire width 4 upto offset 2 input 2 \en
+  wire width 4 output 4 \q
+  wire width 4 signed output 5 \rd
+  wire width 4 $next
+  wire width 4 \alias
+  attribute \init 4'0000
+  wire width 4 \tmp
+  memory width 4 size 4 \mem
+  cell \inc \u0
+    connect \i \q
+    connect \o $next
+  end
+  attribute \keep "01 "
+  cell $adff $ff0
+    parameter \WIDTH 4
+    parameter \CLK_POLARITY 1'1
+    parameter \ARST_POLARITY 1'1
+    parameter \ARST_VALUE 4'01x0
+    connect \CLK \clk
+    connect \ARST \rst
+    connect \D \tmp
+    connect \Q \q
+  end
+  cell $mux $mux0
+    parameter \WIDTH 4
+    connect \A \q
+    connect \B $next
+    connect \S \en [2]
+    connect \Y \tmp
+  end
+  connect \alias { \q [3:2] 2'10 }
+  cell $memwr $w0
+    parameter \MEMID "\\mem"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1'1
+    parameter \CLK_POLARITY 1'1
+    parameter \PRIORITY 0
+    connect \CLK \clk
+    connect \EN 4'1111
+    connect \ADDR \q [1:0]
+    connect \DATA \q
+  end
+  cell $memrd $r0
+    parameter \MEMID "\\mem"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1'0
+    parameter \CLK_POLARITY 1'1
+    parameter \TRANSPARENT 1'0
+    connect \CLK 1'x
+    connect \EN 1'1
+    connect \ADDR \q [3:2]
+    connect \DATA \rd
+  end
+end
+module \inc
+  wire width 4 input 1 \i
+  wire width 4 output 2 \o
+  cell $add $add0
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 4
+    connect \A \i
+    connect \B 4'0001
+    connect \Y \o
+  end
+end
+"#;
+
+fn parse(text: &str) -> Design {
+    Parser::new().parse(Lexer::new(text.chars())).unwrap()
+}
+
+fn json(d: &Design) -> String {
+    let mut w = Vec::new();
+    write_json(&mut w, d).unwrap();
+    String::from_utf8(w).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let d = parse(DESIGN);
+    let text = json(&d);
+    let d2 = read_json(&text).unwrap();
+    assert_eq!(json(&d2), text);
+
+    let top = d2.module("\\top").unwrap();
+    let mut ports: Vec<&Wire> = top.wires().iter().filter(|w| *w.port() > 0).collect();
+    ports.sort_by_key(|w| *w.port());
+    let names: Vec<&str> = ports.iter().map(|w| w.id().as_str()).collect();
+    assert_eq!(names, ["\\clk", "\\en", "\\rst", "\\q", "\\rd"]);
+    let en = top.wire("\\en").unwrap();
+    assert!(*en.input() && *en.upto() && *en.offset() == 2 && *en.width() == 4);
+    assert!(*top.wire("\\rd").unwrap().signed());
+    assert_eq!(top.memories()[0].size(), &4);
+    assert_eq!(top.param("\\DEPTH").and_then(|v| v.as_int()), Some(4));
+    assert_eq!(top.attrs()["\\src"].to_string(), "\"counter.v:3\"");
+    assert_eq!(
+        top.wire("\\tmp").unwrap().attrs()["\\init"].to_bits(),
+        vec![State::S0; 4]
+    );
+
+    let ff = top.cell("$ff0").unwrap();
+    assert_eq!(ff.param("\\WIDTH").unwrap().as_int(), Some(4));
+    assert_eq!(ff.param("\\ARST_VALUE").unwrap().to_string(), "4'01x0");
+    assert_eq!(ff.attrs()["\\keep"].to_string(), "\"01 \"");
+    assert_eq!(top.cell("\\u0").unwrap().i1(), "\\inc");
+}
+
+#[test]
+fn round_trip_simulates_alike() {
+    let a = parse(DESIGN);
+    let b = read_json(&json(&a)).unwrap();
+    let mut a = Simulator::new(&a, "\\top").unwrap();
+    let mut b = Simulator::new(&b, "\\top").unwrap();
+    for cycle in 0..20u64 {
+        for sim in [&mut a, &mut b].iter_mut() {
+            sim.set_u64("rst", (cycle == 0) as u64).unwrap();
+            sim.set_u64("en", (cycle % 3) << 2).unwrap();
+            sim.step("clk").unwrap();
+        }
+        for w in ["q", "rd", "alias", "tmp"].iter() {
+            assert_eq!(a.get(w).unwrap(), b.get(w).unwrap(), "{} at {}", w, cycle);
+        }
+    }
+}
+
+// As written by yosys, with a net not named by any wire.
+const YOSYS: &str = r#"{
+  "creator": "Yosys 0.9",
+  "modules": {
+    "and2": {
+      "attributes": {
+        "src": "and2.v:1"
+      },
+      "ports": {
+        "a": { "direction": "input", "bits": [ 2 ] },
+        "b": { "direction": "input", "bits": [ 3 ] },
+        "y": { "direction": "output", "bits": [ 4 ] }
+      },
+      "cells": {
+        "$and$and2.v:4$1": {
+          "hide_name": 1,
+          "type": "$_AND_",
+          "parameters": { },
+          "attributes": { "src": "and2.v:4" },
+          "port_directions": { "A": "input", "B": "input", "Y": "output" },
+          "connections": { "A": [ 2 ], "B": [ 3 ], "Y": [ 5 ] }
+        },
+        "$not$and2.v:4$2": {
+          "hide_name": 1,
+          "type": "$_NOT_",
+          "parameters": { },
+          "attributes": { },
+          "port_directions": { "A": "input", "Y": "output" },
+          "connections": { "A": [ 5 ], "Y": [ 6 ] }
+        },
+        "$not$and2.v:4$3": {
+          "hide_name": 1,
+          "type": "$_NOT_",
+          "parameters": { },
+          "attributes": { },
+          "port_directions": { "A": "input", "Y": "output" },
+          "connections": { "A": [ 6 ], "Y": [ 4 ] }
+        }
+      },
+      "netnames": {
+        "a": { "hide_name": 0, "bits": [ 2 ], "attributes": { "src": "and2.v:1" } },
+        "b": { "hide_name": 0, "bits": [ 3 ], "attributes": { } },
+        "n": { "hide_name": 0, "bits": [ 5 ], "attributes": { } },
+        "y": { "hide_name": 0, "bits": [ 4 ], "attributes": { } },
+        "y2": { "hide_name": 0, "bits": [ 4 ], "attributes": { } }
+      }
+    }
+  }
+}"#;
+
+#[test]
+fn read_yosys() {
+    let d = read_json(YOSYS).unwrap();
+    let m = d.module("\\and2").unwrap();
+    assert_eq!(m.cells().len(), 3);
+    assert_eq!(m.connects().len(), 1);
+    assert_eq!(m.connects()[0].sig1().to_string(), "\\y2");
+    assert_eq!(m.connects()[0].sig2().to_string(), "\\y");
+    assert_eq!(
+        m.wire("\\a").unwrap().attrs()["\\src"].to_string(),
+        "\"and2.v:1\""
+    );
+    // net 6 has no name and gets a fresh wire
+    assert_eq!(m.wires().len(), 6);
+
+    let mut sim = Simulator::new(&d, "\\and2").unwrap();
+    for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)].iter() {
+        sim.set_u64("a", *a).unwrap();
+        sim.set_u64("b", *b).unwrap();
+        sim.update().unwrap();
+        assert_eq!(sim.get_u64("y2").unwrap(), Some(a & b));
+    }
+}
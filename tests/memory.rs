@@ -0,0 +1,329 @@
+use rtlil::lexer::Lexer;
+use rtlil::memory::MemoryPorts;
+use rtlil::parser::Parser;
+use rtlil::passes::{memory_collect, memory_unpack};
+use rtlil::syntax::*;
+
+// version 1 cells: two write ports on \clk and one on \clk2, a transparent
+// and a plain read port on \clk and an asynchronous one
+const V1: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \clk2
+  wire width 2 input 3 \addr
+  wire width 4 input 4 \wdata
+  wire width 4 output 5 \a
+  wire width 4 output 6 \b
+  wire width 4 output 7 \c
+  memory width 4 size 4 \m
+  cell $memwr $memwr$2
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PRIORITY 2
+    connect \CLK \clk
+    connect \EN 4'1111
+    connect \ADDR \addr
+    connect \DATA \wdata
+  end
+  cell $memwr $memwr$1
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PRIORITY 1
+    connect \CLK \clk
+    connect \EN 4'0011
+    connect \ADDR 2'00
+    connect \DATA \wdata
+  end
+  cell $memwr $memwr$3
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PRIORITY 3
+    connect \CLK \clk2
+    connect \EN 4'1111
+    connect \ADDR 2'11
+    connect \DATA \wdata
+  end
+  cell $memrd $memrd$4
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENT 1
+    connect \CLK \clk
+    connect \EN 1'1
+    connect \ADDR \addr
+    connect \DATA \a
+  end
+  cell $memrd $memrd$5
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENT 0
+    connect \CLK \clk
+    connect \EN 1'1
+    connect \ADDR \addr
+    connect \DATA \b
+  end
+  cell $memrd $memrd$6
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 0
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENT 0
+    connect \CLK 1'x
+    connect \EN 1'1
+    connect \ADDR \addr
+    connect \DATA \c
+  end
+  cell $meminit $meminit$7
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 1
+    parameter \PRIORITY 8
+    connect \ADDR 2'01
+    connect \DATA 4'1010
+  end
+  cell $meminit $meminit$8
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 2
+    parameter \PRIORITY 7
+    connect \ADDR 2'00
+    connect \DATA 8'01011111
+  end
+end
+"#;
+
+// version 2 cells: the second write port wins over the first, the read port
+// is transparent with the first one; the initializers overlap and the
+// last one only sets the low half of a word
+const V2: &str = r#"
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \addr
+  wire width 4 input 3 \wdata
+  wire width 4 output 4 \a
+  memory width 4 offset 0 size 4 \m
+  cell $memwr_v2 $memwr$1
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PORTID 0
+    parameter \PRIORITY_MASK 2'00
+    connect \CLK \clk
+    connect \EN 4'1111
+    connect \ADDR \addr
+    connect \DATA \wdata
+  end
+  cell $memwr_v2 $memwr$2
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 0
+    parameter \PORTID 1
+    parameter \PRIORITY_MASK 2'01
+    connect \CLK \clk
+    connect \EN 4'1100
+    connect \ADDR 2'10
+    connect \DATA \wdata
+  end
+  cell $memrd_v2 $memrd$3
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENCY_MASK 2'01
+    parameter \COLLISION_X_MASK 2'10
+    parameter \CE_OVER_SRST 1
+    parameter \ARST_VALUE 4'0000
+    parameter \SRST_VALUE 4'0110
+    parameter \INIT_VALUE 4'1001
+    connect \CLK \clk
+    connect \EN 1'1
+    connect \ARST 1'0
+    connect \SRST \wdata [0]
+    connect \ADDR \addr
+    connect \DATA \a
+  end
+  cell $meminit_v2 $meminit$4
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 2
+    parameter \PRIORITY 1
+    connect \ADDR 2'00
+    connect \DATA 8'00010010
+    connect \EN 4'1111
+  end
+  cell $meminit_v2 $meminit$5
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 2
+    parameter \PRIORITY 0
+    connect \ADDR 2'01
+    connect \DATA 8'01110111
+    connect \EN 4'1111
+  end
+  cell $meminit_v2 $meminit$6
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 1
+    parameter \PRIORITY 2
+    connect \ADDR 2'11
+    connect \DATA 4'1111
+    connect \EN 4'0011
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+// Bits written MSB first, as in RTLIL, LSB first.
+fn states(s: &str) -> Vec<State> {
+    s.chars()
+        .rev()
+        .map(|c| match c {
+            '0' => State::S0,
+            '1' => State::S1,
+            _ => State::Sx,
+        })
+        .collect()
+}
+
+type Params = Vec<(String, String)>;
+type Ports = Vec<(String, Vec<SigBit>)>;
+
+// Parameters and port bits of the `$mem_v2` cell of `module`, sorted.
+fn mem_cell(module: &Module) -> (Params, Ports) {
+    let cells: Vec<&Cell> = module
+        .cells()
+        .iter()
+        .filter(|c| c.i1() == "$mem_v2")
+        .collect();
+    assert_eq!(cells.len(), 1);
+    let wt = WireTable::new(module);
+    let mut params: Params = cells[0]
+        .params()
+        .iter()
+        .map(|(k, v)| (k.clone(), format!("{}", v.val())))
+        .collect();
+    params.sort();
+    let mut ports: Ports = cells[0]
+        .connects()
+        .iter()
+        .map(|(k, s)| (k.clone(), wt.bits(s).unwrap()))
+        .collect();
+    ports.sort();
+    (params, ports)
+}
+
+fn round_trip(src: &str) {
+    let mut d = parse(src);
+    let top = d.module_mut("\\top").unwrap();
+    assert_eq!(memory_collect(top).unwrap(), 1);
+    let first = mem_cell(top);
+    assert_eq!(memory_unpack(top).unwrap(), 1);
+    assert!(top.cells().iter().all(|c| c.i1() != "$mem_v2"));
+    assert_eq!(top.memories().len(), 1);
+    assert_eq!(memory_collect(top).unwrap(), 1);
+    assert!(top.memories().is_empty());
+    assert_eq!(mem_cell(top), first);
+}
+
+#[test]
+fn collect_unpack_round_trip_v1() {
+    round_trip(V1);
+}
+
+#[test]
+fn collect_unpack_round_trip_v2() {
+    round_trip(V2);
+}
+
+#[test]
+fn v1_masks_follow_clocks() {
+    let d = parse(V1);
+    let m = MemoryPorts::from_cells(d.module("\\top").unwrap(), "\\m").unwrap();
+    // write ports are ordered by PRIORITY
+    let addrs: Vec<String> = m.wr().iter().map(|p| format!("{}", p.addr())).collect();
+    assert_eq!(addrs, ["2'00", "\\addr", "2'11"]);
+    let prio: Vec<&Vec<bool>> = m.wr().iter().map(|p| p.priority()).collect();
+    assert_eq!(
+        prio,
+        [
+            &vec![false, false, false],
+            &vec![true, false, false],
+            &vec![false, false, false],
+        ]
+    );
+    let transparency: Vec<&Vec<bool>> = m.rd().iter().map(|p| p.transparency()).collect();
+    assert_eq!(
+        transparency,
+        [
+            &vec![true, true, false],
+            &vec![false, false, false],
+            &vec![false, false, false],
+        ]
+    );
+    assert!(m.rd().iter().all(|p| p.collision_x() == &[false; 3]));
+}
+
+#[test]
+fn v2_masks_are_kept() {
+    let d = parse(V2);
+    let m = MemoryPorts::from_cells(d.module("\\top").unwrap(), "\\m").unwrap();
+    assert_eq!(m.wr()[0].priority(), &[false, false]);
+    assert_eq!(m.wr()[1].priority(), &[true, false]);
+    assert!(m.wr()[0].clk().as_ref().unwrap().1);
+    assert!(!m.wr()[1].clk().as_ref().unwrap().1);
+    let r = &m.rd()[0];
+    assert_eq!(r.transparency(), &[true, false]);
+    assert_eq!(r.collision_x(), &[false, true]);
+    assert!(*r.ce_over_srst());
+    assert_eq!(r.srst_value(), &states("0110"));
+    assert_eq!(r.init_value(), &states("1001"));
+}
+
+#[test]
+fn meminit_priority_and_enable() {
+    // the higher priority initializer wins, whatever the cell order
+    let d = parse(V1);
+    let m = MemoryPorts::from_cells(d.module("\\top").unwrap(), "\\m").unwrap();
+    assert_eq!(m.init(), &states("xxxxxxxx10101111"));
+
+    // word 1 is set by both, priority 1 wins; word 3 only gets its low half
+    let d = parse(V2);
+    let m = MemoryPorts::from_cells(d.module("\\top").unwrap(), "\\m").unwrap();
+    assert_eq!(m.init(), &states("xx11011100010010"));
+
+    // unpacking keeps the merged contents
+    let mut d = parse(V2);
+    let top = d.module_mut("\\top").unwrap();
+    memory_collect(top).unwrap();
+    memory_unpack(top).unwrap();
+    let m = MemoryPorts::from_cells(top, "\\m").unwrap();
+    assert_eq!(m.init(), &states("xx11011100010010"));
+}
@@ -0,0 +1,176 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::memory_map;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+// 4x4 memory with one write port, an asynchronous and a synchronous read
+// port, and the first two words initialized
+const RAM: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \wen
+  wire width 2 input 3 \waddr
+  wire width 4 input 4 \wdata
+  wire width 2 input 5 \raddr
+  wire width 4 output 6 \rdata
+  wire width 4 output 7 \rdata_q
+  memory width 4 size 4 \m
+  cell $meminit_v2 $meminit$1
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \WORDS 2
+    parameter \PRIORITY 0
+    connect \ADDR 2'00
+    connect \DATA 8'10010110
+    connect \EN 4'1111
+  end
+  cell $memwr_v2 $memwr$2
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PORTID 0
+    parameter \PRIORITY_MASK 0
+    connect \CLK \clk
+    connect \EN { \wen \wen \wen \wen }
+    connect \ADDR \waddr
+    connect \DATA \wdata
+  end
+  cell $memrd_v2 $memrd$3
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 0
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENCY_MASK 1'0
+    parameter \COLLISION_X_MASK 1'0
+    parameter \CE_OVER_SRST 0
+    parameter \ARST_VALUE 4'0000
+    parameter \SRST_VALUE 4'0000
+    parameter \INIT_VALUE 4'xxxx
+    connect \CLK 1'x
+    connect \EN 1'1
+    connect \ARST 1'0
+    connect \SRST 1'0
+    connect \ADDR \raddr
+    connect \DATA \rdata
+  end
+  cell $memrd_v2 $memrd$4
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \TRANSPARENCY_MASK 1'0
+    parameter \COLLISION_X_MASK 1'0
+    parameter \CE_OVER_SRST 0
+    parameter \ARST_VALUE 4'0000
+    parameter \SRST_VALUE 4'0000
+    parameter \INIT_VALUE 4'xxxx
+    connect \CLK \clk
+    connect \EN 1'1
+    connect \ARST 1'0
+    connect \SRST 1'0
+    connect \ADDR \raddr
+    connect \DATA \rdata_q
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn mapped_memory_reads_back_like_the_original() {
+    let d = parse(RAM);
+    let mut mapped = parse(RAM);
+    assert_eq!(memory_map(mapped.module_mut("\\top").unwrap()).unwrap(), 1);
+    let top = mapped.module("\\top").unwrap();
+    assert!(top.memories().is_empty());
+    assert!(top.cells().iter().all(|c| !c.i1().starts_with("$mem")));
+
+    let mut a = Simulator::new(&d, "\\top").unwrap();
+    let mut b = Simulator::new(&mapped, "\\top").unwrap();
+    // (wen, waddr, wdata, raddr), then rdata and rdata_q after the edge;
+    // the synchronous port reads the word before the write
+    let steps = [
+        ((0, 0, 0, 0), Some(6), Some(6)),
+        ((0, 0, 0, 1), Some(9), Some(9)),
+        ((1, 2, 5, 2), Some(5), None),
+        ((1, 0, 12, 2), Some(5), Some(5)),
+        ((0, 3, 7, 0), Some(12), Some(12)),
+        ((1, 3, 9, 3), Some(9), None),
+        ((1, 1, 3, 1), Some(3), Some(9)),
+        ((0, 1, 0, 3), Some(9), Some(9)),
+        ((0, 0, 0, 1), Some(3), Some(3)),
+    ];
+    for (i, &((wen, waddr, wdata, raddr), rdata, rdata_q)) in steps.iter().enumerate() {
+        for sim in [&mut a, &mut b].iter_mut() {
+            sim.set_u64("wen", wen).unwrap();
+            sim.set_u64("waddr", waddr).unwrap();
+            sim.set_u64("wdata", wdata).unwrap();
+            sim.set_u64("raddr", raddr).unwrap();
+            sim.step("clk").unwrap();
+        }
+        for (port, v) in [("rdata", rdata), ("rdata_q", rdata_q)].iter() {
+            assert_eq!(a.get_u64(port).unwrap(), *v, "`{}' in step {}", port, i);
+            assert_eq!(
+                b.get_u64(port).unwrap(),
+                *v,
+                "mapped `{}' in step {}",
+                port,
+                i
+            );
+        }
+    }
+}
+
+#[test]
+fn unmappable_memory_leaves_module_unchanged() {
+    // make the write port asynchronous
+    let mut d = parse(&RAM.replacen("\\CLK_ENABLE 1", "\\CLK_ENABLE 0", 1));
+    let before = format!("{:?}", d.module("\\top").unwrap());
+    let e = memory_map(d.module_mut("\\top").unwrap()).unwrap_err();
+    assert!(e.to_string().contains("asynchronous write ports"), "{}", e);
+    assert_eq!(format!("{:?}", d.module("\\top").unwrap()), before);
+}
+
+#[test]
+fn write_priority_over_later_port_is_rejected() {
+    let second = r#"  cell $memwr_v2 $memwr$5
+    parameter \MEMID "\\m"
+    parameter \ABITS 2
+    parameter \WIDTH 4
+    parameter \CLK_ENABLE 1
+    parameter \CLK_POLARITY 1
+    parameter \PORTID 1
+    parameter \PRIORITY_MASK 2'01
+    connect \CLK \clk
+    connect \EN 4'1111
+    connect \ADDR \raddr
+    connect \DATA 4'0000
+  end
+end
+"#;
+    // the second port may win over the first
+    let ok = RAM.trim_end().strip_suffix("end").unwrap().to_string() + second;
+    let mut d = parse(&ok);
+    assert_eq!(memory_map(d.module_mut("\\top").unwrap()).unwrap(), 1);
+
+    // but not the other way round
+    let bad = ok
+        .replacen("\\PRIORITY_MASK 0", "\\PRIORITY_MASK 2'10", 1)
+        .replacen("\\PRIORITY_MASK 2'01", "\\PRIORITY_MASK 2'00", 1);
+    let mut d = parse(&bad);
+    let e = memory_map(d.module_mut("\\top").unwrap()).unwrap_err();
+    assert!(
+        e.to_string().contains("priority over a later port"),
+        "{}",
+        e
+    );
+    assert!(!d.module("\\top").unwrap().memories().is_empty());
+}
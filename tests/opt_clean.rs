@@ -0,0 +1,92 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::opt_clean;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+const DESIGN: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 2 output 2 \y
+  wire width 2 \b
+  wire width 2 $t
+  wire width 2 $u
+  wire width 2 $d
+  wire width 2 $k
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \Y $t
+  end
+  cell $and $and$2
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B $t
+    connect \Y $d
+  end
+  attribute \keep 1
+  cell $or $or$3
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B $t
+    connect \Y $k
+  end
+  connect $u $t
+  connect \y $u
+  connect \b \a
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn removes_unused_cells_and_wires() {
+    let mut d = parse(DESIGN);
+    let m = d.module_mut("\\top").unwrap();
+    let stats = opt_clean(m).unwrap();
+    assert_eq!(*stats.cells(), 1);
+    let mut cells: Vec<&str> = m.cells().iter().map(|c| c.i2().as_str()).collect();
+    cells.sort_unstable();
+    assert_eq!(cells, ["$not$1", "$or$3"]);
+    let mut wires: Vec<&str> = m.wires().iter().map(|w| w.id().as_str()).collect();
+    wires.sort_unstable();
+    assert_eq!(wires, ["$k", "\\a", "\\b", "\\y"]);
+
+    // the inverter drives the output port directly, public aliases stay
+    let not = m.cell("$not$1").unwrap();
+    assert_eq!(not.port("\\Y").unwrap().to_string(), "\\y");
+    let connects: Vec<String> = m
+        .connects()
+        .iter()
+        .map(|c| format!("{} {}", c.sig1(), c.sig2()))
+        .collect();
+    assert_eq!(connects, ["\\b \\a"]);
+
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("a", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("y").unwrap(), Some(2));
+}
+
+#[test]
+fn nothing_left_to_clean() {
+    let mut d = parse(DESIGN);
+    let m = d.module_mut("\\top").unwrap();
+    opt_clean(m).unwrap();
+    let stats = opt_clean(m).unwrap();
+    assert_eq!(*stats.cells(), 0);
+    assert_eq!(*stats.wires(), 0);
+    assert_eq!(*stats.connects(), 0);
+}
@@ -0,0 +1,83 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::opt_const;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+// identities on a signed `\a` with unsigned constant operands
+const MIXED: &str = r#"
+module \top
+  wire width 2 input 1 \a
+  wire width 4 output 2 \add
+  wire width 4 output 3 \sub
+  wire width 4 output 4 \and
+  wire width 4 output 5 \sshr
+  cell $add $add$1
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B 4'0000
+    connect \Y \add
+  end
+  cell $sub $sub$2
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B 4'0000
+    connect \Y \sub
+  end
+  cell $and $and$3
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B 4'1111
+    connect \Y \and
+  end
+  cell $sshr $sshr$4
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 1
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B 1'0
+    connect \Y \sshr
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn outputs(d: &Design, a: u64) -> Vec<Option<u64>> {
+    let mut sim = Simulator::new(d, "\\top").unwrap();
+    sim.set_u64("a", a).unwrap();
+    sim.update().unwrap();
+    ["add", "sub", "and", "sshr"]
+        .iter()
+        .map(|w| sim.get_u64(w).unwrap())
+        .collect()
+}
+
+#[test]
+fn mixed_signedness_identities_keep_behaviour() {
+    let d = parse(MIXED);
+    let mut opt = parse(MIXED);
+    assert_eq!(opt_const(opt.module_mut("\\top").unwrap()).unwrap(), 4);
+    assert!(opt.module("\\top").unwrap().cells().is_empty());
+    for a in 0..4 {
+        assert_eq!(outputs(&d, a), outputs(&opt, a), "a = {}", a);
+    }
+    // the unsigned operand makes `\a` zero extended, except when shifted
+    assert_eq!(outputs(&opt, 3), [Some(3), Some(3), Some(3), Some(15)]);
+}
@@ -0,0 +1,139 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::opt_merge;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+// `\g` and `$and$2` only differ in operand order, and so do the inverters
+// reading them once they are merged. `$sub$4` is not commutative and the
+// flip-flop driving `\q1` is kept apart by its `init` attribute.
+const DESIGN: &str = r#"
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \a
+  wire width 2 input 3 \b
+  wire width 2 output 4 \y1
+  wire width 2 output 5 \y2
+  wire width 2 output 6 \d1
+  wire width 2 output 7 \d2
+  wire width 2 output 8 \s1
+  wire width 2 output 9 \s2
+  attribute \init 2'00
+  wire width 2 output 10 \q1
+  wire width 2 output 11 \q2
+  wire width 2 $x
+  wire width 2 $y
+  cell $and \g
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B \b
+    connect \Y $x
+  end
+  cell $and $and$2
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \b
+    connect \B \a
+    connect \Y $y
+  end
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A $x
+    connect \Y \y1
+  end
+  cell $not $not$2
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A $y
+    connect \Y \y2
+  end
+  cell $sub $sub$3
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B \b
+    connect \Y \s1
+  end
+  cell $sub $sub$4
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \b
+    connect \B \a
+    connect \Y \s2
+  end
+  cell $dff $dff$5
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \a
+    connect \Q \q1
+  end
+  cell $dff $dff$6
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \a
+    connect \Q \q2
+  end
+  cell $dff $dff$7
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \a
+    connect \Q \d1
+  end
+  cell $dff $dff$8
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \a
+    connect \Q \d2
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn merges_identical_cells() {
+    let mut d = parse(DESIGN);
+    let m = d.module_mut("\\top").unwrap();
+    assert_eq!(opt_merge(m).unwrap(), 4);
+    let mut cells: Vec<&str> = m.cells().iter().map(|c| c.i2().as_str()).collect();
+    cells.sort_unstable();
+    assert_eq!(
+        cells,
+        ["$dff$5", "$dff$6", "$not$1", "$sub$3", "$sub$4", "\\g"]
+    );
+    assert_eq!(opt_merge(m).unwrap(), 0);
+
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("clk", 0).unwrap();
+    sim.set_u64("a", 3).unwrap();
+    sim.set_u64("b", 1).unwrap();
+    sim.step("clk").unwrap();
+    for (k, v) in [("y1", 2), ("y2", 2), ("s1", 2), ("s2", 2), ("q1", 3)].iter() {
+        assert_eq!(sim.get_u64(k).unwrap(), Some(*v), "{}", k);
+    }
+    for k in ["q2", "d1", "d2"].iter() {
+        assert_eq!(sim.get_u64(k).unwrap(), Some(3), "{}", k);
+    }
+}
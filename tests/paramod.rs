@@ -0,0 +1,287 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::{derive, derive_all, derive_all_with, derive_with, paramod_name};
+use rtlil::syntax::*;
+use std::collections::HashMap;
+
+use State::{S0, S1};
+
+// `write_rtlil` output of yosys for
+//
+//   module top(output [31:0] a, b, output [7:0] c);
+//     sub #(.INIT(42)) u0 (.o(a));
+//     sub u1 (.o(b));
+//     wide #(.WIDTH(8)) u2 (.o(c));
+//   endmodule
+//   module sub #(parameter INIT = 7) (output [31:0] o);
+//     assign o = INIT;
+//   endmodule
+//   module wide #(parameter WIDTH = 8) (output [WIDTH-1:0] o);
+//     assign o = {WIDTH{1'b1}};
+//   endmodule
+//
+// read without `hierarchy`, so the parameters are only in the cells.
+const DESIGN: &str = r#"
+# Generated by Yosys 0.38 (git sha1 543faed9c8c, clang++ 17.0.6 -fPIC -Os)
+autoidx 1
+attribute \src "top.v:1.1-5.10"
+module \top
+  attribute \src "top.v:1.25-1.26"
+  wire width 32 output 1 \a
+  attribute \src "top.v:1.28-1.29"
+  wire width 32 output 2 \b
+  attribute \src "top.v:1.44-1.45"
+  wire width 8 output 3 \c
+  attribute \module_not_derived 1
+  attribute \src "top.v:2.20-2.31"
+  cell \sub \u0
+    parameter \INIT 42
+    connect \o \a
+  end
+  attribute \module_not_derived 1
+  attribute \src "top.v:3.7-3.18"
+  cell \sub \u1
+    connect \o \b
+  end
+  attribute \module_not_derived 1
+  attribute \src "top.v:4.21-4.32"
+  cell \wide \u2
+    parameter \WIDTH 8
+    connect \o \c
+  end
+end
+attribute \src "top.v:6.1-8.10"
+module \sub
+  parameter \INIT 7
+  attribute \src "top.v:6.50-6.51"
+  wire width 32 output 1 \o
+  connect \o 7
+end
+attribute \src "top.v:9.1-11.10"
+module \wide
+  parameter \WIDTH 8
+  attribute \src "top.v:9.55-9.56"
+  wire width 8 output 1 \o
+  connect \o 8'11111111
+end
+"#;
+
+// Hand-written RTLIL may refer to a parameter in place of a wire.
+const REFERENCE: &str = r#"
+module \sub
+  parameter \INIT 7
+  wire width 32 output 1 \o
+  connect \o \INIT
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn params(v: &[(&str, Const)]) -> Vec<(String, Const)> {
+    v.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+#[test]
+fn names() {
+    // in the given, declaration order
+    let p = params(&[("\\W", Const::Int(8)), ("\\A", Const::Str("x".into()))]);
+    assert_eq!(paramod_name("\\sub", &p), "$paramod\\sub\\W=8\\A=\"x\"");
+    assert_eq!(
+        paramod_name("$abstract\\sub", &params(&[("\\W", Const::Int(8))])),
+        "$paramod\\sub\\W=8"
+    );
+
+    // 32-bit values are signed, like yosys prints them
+    let mut bits = vec![S1; 32];
+    assert_eq!(
+        paramod_name("\\sub", &params(&[("\\W", Const::from_bits(&bits))])),
+        "$paramod\\sub\\W=-1"
+    );
+    bits[0] = S0;
+    bits[31] = S0;
+    assert_eq!(
+        paramod_name("\\sub", &params(&[("\\W", Const::from_bits(&bits))])),
+        "$paramod\\sub\\W=2147483646"
+    );
+}
+
+#[test]
+fn hashed_names() {
+    let p = params(&[
+        ("\\ADDRESS_WIDTH", Const::Int(12)),
+        ("\\DATA_WIDTH", Const::Int(32)),
+        ("\\INIT_FILE", Const::Str("mem.hex".into())),
+    ]);
+    assert_eq!(
+        paramod_name("\\ram", &p),
+        "$paramod\\ram\\ADDRESS_WIDTH=12\\DATA_WIDTH=32\\INIT_FILE=\"mem.hex\""
+    );
+    // longer than 60 characters, spanning two SHA-1 blocks
+    let p = params(&[
+        ("\\ADDRESS_WIDTH", Const::Int(12)),
+        ("\\DATA_WIDTH", Const::Int(32)),
+        ("\\INIT_FILE", Const::Str("memory_contents.hex".into())),
+    ]);
+    assert_eq!(
+        paramod_name("\\ram", &p),
+        "$paramod$d7325558a947b436230badb2459a8fdb4adfecd9\\ram"
+    );
+}
+
+fn values(v: &[(&str, Const)]) -> HashMap<String, Const> {
+    v.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+}
+
+#[test]
+fn derive_keeps_default_values() {
+    let mut d = parse(DESIGN);
+    let p = values(&[("\\INIT", Const::Int(7))]);
+    let name = derive(&mut d, "\\sub", &p).unwrap();
+    assert_eq!(name, "$paramod\\sub\\INIT=7");
+    // derived once per set of values
+    assert_eq!(derive(&mut d, "\\sub", &p).unwrap(), name);
+    assert_eq!(d.modules().len(), 4);
+    let m = d.module(&name).unwrap();
+    assert_eq!(m.connects()[0].sig2().to_string(), "7");
+
+    let p = values(&[("\\WIDTH", Const::Int(8))]);
+    assert_eq!(
+        derive(&mut d, "\\wide", &p).unwrap(),
+        "$paramod\\wide\\WIDTH=8"
+    );
+}
+
+#[test]
+fn derive_rejects_values_it_can_not_elaborate() {
+    let mut d = parse(DESIGN);
+    // the body holds the default value, not a reference to `\INIT'
+    let p = values(&[("\\INIT", Const::Int(42))]);
+    assert!(derive(&mut d, "\\sub", &p).is_err());
+    // the width of `\o' depends on `\WIDTH'
+    let p = values(&[("\\WIDTH", Const::Int(16))]);
+    assert!(derive(&mut d, "\\wide", &p).is_err());
+    let p = values(&[("\\W", Const::Int(1))]);
+    assert!(derive(&mut d, "\\sub", &p).is_err());
+    assert!(derive(&mut d, "\\top", &p).is_err());
+    assert_eq!(d.modules().len(), 3);
+}
+
+#[test]
+fn derive_with_custom_elaborator() {
+    let mut d = parse(DESIGN);
+    let mut rebuild = |m: &mut Module, p: &HashMap<String, Const>| -> anyhow::Result<()> {
+        let v = p["\\INIT"].clone();
+        *m.connects_mut()[0].sig2_mut() = SigSpec::Const((v, None));
+        Ok(())
+    };
+    let p = values(&[("\\INIT", Const::Int(42))]);
+    let name = derive_with(&mut d, "\\sub", &p, &mut rebuild).unwrap();
+    assert_eq!(name, "$paramod\\sub\\INIT=42");
+    let m = d.module(&name).unwrap();
+    assert_eq!(m.param("\\INIT").and_then(|v| v.as_int()), Some(42));
+    assert_eq!(m.connects()[0].sig2().to_string(), "42");
+    // the base module is untouched
+    let base = d.module("\\sub").unwrap();
+    assert_eq!(base.connects()[0].sig2().to_string(), "7");
+}
+
+#[test]
+fn derive_substitutes_references() {
+    let mut d = parse(REFERENCE);
+    let p = values(&[("\\INIT", Const::Int(42))]);
+    let name = derive(&mut d, "\\sub", &p).unwrap();
+    let m = d.module(&name).unwrap();
+    assert_eq!(m.connects()[0].sig2().to_string(), "42");
+    assert_eq!(
+        d.module("\\sub").unwrap().connects()[0].sig2().to_string(),
+        "\\INIT"
+    );
+    // a reference can not change width
+    let p = values(&[("\\INIT", Const::from_bits(&[S1; 4]))]);
+    assert!(derive(&mut d, "\\sub", &p).is_err());
+}
+
+#[test]
+fn derive_all_retargets_cells() {
+    let mut d = parse(DESIGN);
+    // `\u0' overrides a value the default elaborator can not apply
+    assert!(derive_all(&mut d).is_err());
+
+    let mut d = parse(DESIGN);
+    let mut rebuild = |m: &mut Module, p: &HashMap<String, Const>| -> anyhow::Result<()> {
+        if let Some(v) = p.get("\\INIT") {
+            *m.connects_mut()[0].sig2_mut() = SigSpec::Const((v.clone(), None));
+        }
+        Ok(())
+    };
+    assert_eq!(derive_all_with(&mut d, &mut rebuild).unwrap(), 2);
+    let top = d.module("\\top").unwrap();
+    assert_eq!(top.cell("\\u0").unwrap().i1(), "$paramod\\sub\\INIT=42");
+    assert!(top.cell("\\u0").unwrap().params().is_empty());
+    assert_eq!(top.cell("\\u1").unwrap().i1(), "\\sub");
+    assert_eq!(top.cell("\\u2").unwrap().i1(), "$paramod\\wide\\WIDTH=8");
+}
+
+// `write_rtlil` output of yosys for
+//
+//   module ram #(parameter WIDTH = 8, DEPTH = 16) (output [31:0] o);
+//     assign o = WIDTH * DEPTH;
+//   endmodule
+const ORDER: &str = r#"
+attribute \src "ram.v:1.1-3.10"
+module \ram
+  parameter \WIDTH 8
+  parameter \DEPTH 16
+  attribute \src "ram.v:1.58-1.59"
+  wire width 32 output 1 \o
+  connect \o 128
+end
+"#;
+
+#[test]
+fn derive_names_follow_declaration_order() {
+    let mut d = parse(ORDER);
+    let mut keep = |_: &mut Module, _: &HashMap<String, Const>| -> anyhow::Result<()> { Ok(()) };
+    let p = values(&[("\\DEPTH", Const::Int(4)), ("\\WIDTH", Const::Int(2))]);
+    let name = derive_with(&mut d, "\\ram", &p, &mut keep).unwrap();
+    assert_eq!(name, "$paramod\\ram\\WIDTH=2\\DEPTH=4");
+    let m = d.module(&name).unwrap();
+    let order: Vec<&str> = m.params().iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(order, ["\\WIDTH", "\\DEPTH"]);
+    assert_eq!(m.param("\\DEPTH").and_then(|v| v.as_int()), Some(4));
+    // only overridden parameters are named
+    let p = values(&[("\\DEPTH", Const::Int(4))]);
+    let name = derive_with(&mut d, "\\ram", &p, &mut keep).unwrap();
+    assert_eq!(name, "$paramod\\ram\\DEPTH=4");
+}
+
+// A module instantiating itself, one level deeper each time.
+const RECURSIVE: &str = r#"
+module \top
+  cell \r \u
+    parameter \N 0
+  end
+end
+module \r
+  parameter \N 0
+  cell \r \u
+    parameter \N 0
+  end
+end
+"#;
+
+#[test]
+fn derive_all_stops_at_recursion() {
+    let mut d = parse(RECURSIVE);
+    let mut deeper = |m: &mut Module, p: &HashMap<String, Const>| -> anyhow::Result<()> {
+        let n = p["\\N"].as_int().unwrap();
+        m.cell_mut("\\u")
+            .unwrap()
+            .set_param("\\N", Const::Int(n + 1));
+        Ok(())
+    };
+    let e = derive_all_with(&mut d, &mut deeper).unwrap_err();
+    assert!(e.to_string().contains("64 levels"), "{}", e);
+}
@@ -0,0 +1,210 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::proc_design;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn cells<'a>(d: &'a Design, tp: &str) -> Vec<&'a Cell> {
+    d.module("\\top")
+        .unwrap()
+        .cells()
+        .iter()
+        .filter(|c| c.i1() == tp)
+        .collect()
+}
+
+// `always @* if (en) q = d;` as read by yosys' Verilog frontend
+const LATCH: &str = r#"
+module \top
+  wire input 1 \en
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  wire width 2 $0\q[1:0]
+  attribute \src "latch.v:5.3-6.21"
+  process $proc$latch.v:5$1
+    assign $0\q[1:0] \q
+    attribute \src "latch.v:6.5-6.21"
+    switch \en
+      attribute \src "latch.v:6.9-6.11"
+      case 1'1
+        assign $0\q[1:0] \d
+      case
+    end
+    sync always
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+#[test]
+fn latch_from_yosys_frontend() {
+    let mut d = parse(LATCH);
+    proc_design(&mut d).unwrap();
+    let m = d.module("\\top").unwrap();
+    assert!(m.processes().is_empty());
+    let latches = cells(&d, "$dlatch");
+    assert_eq!(latches.len(), 1);
+    assert_eq!(latches[0].port("\\Q").unwrap().to_string(), "\\q");
+    // `\q` is only driven by the latch
+    assert!(!m.connects().iter().any(|c| c.sig1().to_string() == "\\q"));
+    // and does not feed back into its data
+    for c in cells(&d, "$mux") {
+        for p in ["\\A", "\\B"].iter() {
+            assert!(!c.port(p).unwrap().to_string().contains("\\q"));
+        }
+    }
+
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("en", 1).unwrap();
+    sim.set_u64("d", 2).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+    sim.set_u64("en", 0).unwrap();
+    sim.set_u64("d", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+    sim.set_u64("en", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(1));
+}
+
+// `always @* begin q = d; if (en) q[0] = 0; else if (set) q[1] = 1; end`
+// with a root default for q[0] only
+const PARTIAL: &str = r#"
+module \top
+  wire input 1 \en
+  wire input 2 \set
+  wire width 2 input 3 \d
+  wire width 2 output 4 \q
+  wire width 2 $0\q[1:0]
+  process $proc$partial.v:5$1
+    assign $0\q[1:0] \q
+    assign $0\q[1:0] [0] \d [0]
+    switch \en
+      case 1'1
+        assign $0\q[1:0] [0] 1'0
+      case
+        switch \set
+          case 1'1
+            assign $0\q[1:0] [1] 1'1
+          case
+        end
+    end
+    sync always
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+#[test]
+fn latch_only_held_bits() {
+    let mut d = parse(PARTIAL);
+    proc_design(&mut d).unwrap();
+    let latches = cells(&d, "$dlatch");
+    assert_eq!(latches.len(), 1);
+    assert_eq!(latches[0].port("\\Q").unwrap().to_string(), "\\q [1]");
+
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("en", 0).unwrap();
+    sim.set_u64("d", 1).unwrap();
+    sim.set_u64("set", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(3));
+    sim.set_u64("set", 0).unwrap();
+    sim.set_u64("en", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+}
+
+// `always @* if (en) q = d; else q = 0;`
+const COMB: &str = r#"
+module \top
+  wire input 1 \en
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  wire width 2 $0\q[1:0]
+  process $proc$comb.v:5$1
+    assign $0\q[1:0] \q
+    switch \en
+      case 1'1
+        assign $0\q[1:0] \d
+      case
+        assign $0\q[1:0] 2'00
+    end
+    sync always
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+#[test]
+fn no_latch_when_always_assigned() {
+    let mut d = parse(COMB);
+    proc_design(&mut d).unwrap();
+    assert!(cells(&d, "$dlatch").is_empty());
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("en", 0).unwrap();
+    sim.set_u64("d", 3).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(0));
+    sim.set_u64("en", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(3));
+}
+
+// `always @(posedge clk, posedge rst) if (rst) q <= 0; else if (en) q <= d;`
+const FLOP: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \rst
+  wire input 3 \en
+  wire width 2 input 4 \d
+  wire width 2 output 5 \q
+  wire width 2 $0\q[1:0]
+  process $proc$flop.v:5$1
+    assign $0\q[1:0] \q
+    switch \rst
+      case 1'1
+        assign $0\q[1:0] 2'00
+      case
+        switch \en
+          case 1'1
+            assign $0\q[1:0] \d
+          case
+        end
+    end
+    sync posedge \clk
+      update \q $0\q[1:0]
+    sync posedge \rst
+      update \q $0\q[1:0]
+  end
+end
+"#;
+
+#[test]
+fn flop_with_async_reset() {
+    let mut d = parse(FLOP);
+    proc_design(&mut d).unwrap();
+    assert!(cells(&d, "$dlatch").is_empty());
+    let ffs = cells(&d, "$adff");
+    assert_eq!(ffs.len(), 1);
+    assert_eq!(ffs[0].port("\\ARST").unwrap().to_string(), "\\rst");
+
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("clk", 0).unwrap();
+    sim.set_u64("en", 0).unwrap();
+    sim.set_u64("rst", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(0));
+    sim.set_u64("rst", 0).unwrap();
+    sim.set_u64("d", 2).unwrap();
+    sim.step("clk").unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(0));
+    sim.set_u64("en", 1).unwrap();
+    sim.step("clk").unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+}
@@ -0,0 +1,63 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::{analyze_switches, proc_design, proc_rmdead};
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+const DESIGN: &str = r#"
+module \top
+  wire width 2 input 1 \s
+  wire width 2 output 2 \y
+  process $proc$top.v:4$1
+    switch \s
+      case 2'1x
+        assign \y 2'01
+      case 2'0z
+        assign \y 2'10
+      case 2'11
+        assign \y 2'11
+      case
+        assign \y 2'00
+    end
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn undefined_pattern_bits_match_anything() {
+    let d = parse(DESIGN);
+    let r = analyze_switches(d.module("\\top").unwrap()).unwrap();
+    assert_eq!(r.len(), 1);
+    let cases = r[0].cases();
+    assert!(!cases[0].unreachable());
+    assert!(cases[1].always());
+    assert!(cases[2].unreachable());
+    assert!(cases[3].unreachable());
+    assert!(r[0].full());
+
+    let mut m = d.module("\\top").unwrap().clone();
+    assert_eq!(proc_rmdead(&mut m).unwrap(), 2);
+    assert_eq!(m.processes()[0].switch()[0].cases().len(), 2);
+}
+
+#[test]
+fn processes_and_netlist_agree() {
+    let d = parse(DESIGN);
+    let mut n = d.clone();
+    proc_design(&mut n).unwrap();
+    let mut sims = [
+        Simulator::new(&d, "\\top").unwrap(),
+        Simulator::new(&n, "\\top").unwrap(),
+    ];
+    for (s, y) in [(0, 2), (1, 2), (2, 1), (3, 1)].iter() {
+        for sim in sims.iter_mut() {
+            sim.set_u64("s", *s).unwrap();
+            sim.update().unwrap();
+            assert_eq!(sim.get_u64("y").unwrap(), Some(*y));
+        }
+    }
+}
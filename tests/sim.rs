@@ -0,0 +1,199 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+use State::*;
+
+const DFF: &str = r#"
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  cell $dff $dff$1
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \d
+    connect \Q \q
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn first_rising_edge_is_seen() {
+    let d = parse(DFF);
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("d", 2).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), None);
+    sim.step("clk").unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+    sim.set_u64("d", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+    sim.step("clk").unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(1));
+}
+
+#[test]
+fn first_defined_clock_is_a_baseline() {
+    let d = parse(DFF);
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("d", 2).unwrap();
+    // undefined to high is not an edge
+    sim.set_u64("clk", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), None);
+    sim.set_u64("clk", 0).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), None);
+    sim.set_u64("clk", 1).unwrap();
+    sim.update().unwrap();
+    assert_eq!(sim.get_u64("q").unwrap(), Some(2));
+}
+
+// `\m` only exists in the submodule
+const SUB_MEMORY: &str = r#"
+module \sub
+  memory width 4 size 2 \m
+  cell $meminit_v2 $meminit$1
+    parameter \MEMID "\\m"
+    parameter \ABITS 1
+    parameter \WIDTH 4
+    parameter \WORDS 2
+    parameter \PRIORITY 0
+    connect \ADDR 1'0
+    connect \DATA 8'01100101
+    connect \EN 4'1111
+  end
+end
+module \top
+  cell \sub \u
+  end
+end
+"#;
+
+#[test]
+fn memory_is_looked_up_by_instance() {
+    let mut d = parse(SUB_MEMORY);
+    for collect in [false, true].iter() {
+        if *collect {
+            rtlil::passes::memory_collect(d.module_mut("\\sub").unwrap()).unwrap();
+        }
+        let sim = Simulator::new(&d, "\\top").unwrap();
+        let data = sim.memory(&["\\u"], "m").unwrap();
+        assert_eq!(data, [vec![S1, S0, S1, S0], vec![S0, S1, S1, S0]]);
+        assert!(sim.memory(&[], "m").is_err());
+        assert!(sim.memory(&["\\v"], "m").is_err());
+    }
+}
+
+// A register with an enable, one with an asynchronous reset, as left by
+// `proc_arst`, and a counter with an initial value, still as processes
+const PROCESSES: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \rst
+  wire input 3 \en
+  wire width 2 input 4 \d
+  wire width 2 output 5 \q1
+  wire width 2 output 6 \q2
+  wire width 2 output 7 \q3
+  wire width 2 $0\q1[1:0]
+  wire width 2 $0\q2[1:0]
+  wire width 2 $0\q3[1:0]
+  wire width 2 $inc
+  cell $add $add$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \q3
+    connect \B 2'01
+    connect \Y $inc
+  end
+  process $proc$enable$2
+    assign $0\q1[1:0] \q1
+    switch \en
+      case 1'1
+        assign $0\q1[1:0] \d
+      case
+    end
+    sync posedge \clk
+      update \q1 $0\q1[1:0]
+  end
+  process $proc$reset$3
+    assign $0\q2[1:0] \d
+    sync high \rst
+      update \q2 2'00
+    sync posedge \clk
+      update \q2 $0\q2[1:0]
+  end
+  process $proc$init$4
+    assign $0\q3[1:0] $inc
+    sync posedge \clk
+      update \q3 $0\q3[1:0]
+    sync init
+      update \q3 2'01
+  end
+end
+"#;
+
+#[test]
+fn processes_simulate_like_proc() {
+    let d = parse(PROCESSES);
+    let mut p = parse(PROCESSES);
+    rtlil::passes::proc_design(&mut p).unwrap();
+    assert!(p.module("\\top").unwrap().processes().is_empty());
+
+    let mut a = Simulator::new(&d, "\\top").unwrap();
+    let mut b = Simulator::new(&p, "\\top").unwrap();
+    let check = |a: &Simulator, b: &Simulator, step: usize| {
+        for q in ["q1", "q2", "q3"].iter() {
+            assert_eq!(
+                a.get(q).unwrap(),
+                b.get(q).unwrap(),
+                "`{}' in step {}",
+                q,
+                step
+            );
+        }
+    };
+    // (rst, en, d), each followed by a clock cycle
+    let steps = [
+        (0, 0, 2),
+        (0, 1, 2),
+        (1, 0, 3),
+        (1, 1, 3),
+        (0, 0, 1),
+        (0, 1, 3),
+    ];
+    for (i, &(rst, en, v)) in steps.iter().enumerate() {
+        for sim in [&mut a, &mut b].iter_mut() {
+            sim.set_u64("rst", rst).unwrap();
+            sim.set_u64("en", en).unwrap();
+            sim.set_u64("d", v).unwrap();
+            sim.update().unwrap();
+        }
+        check(&a, &b, i);
+        for sim in [&mut a, &mut b].iter_mut() {
+            sim.step("clk").unwrap();
+        }
+        check(&a, &b, i);
+    }
+    // six edges from the initial value
+    assert_eq!(a.get_u64("q3").unwrap(), Some(3));
+
+    // the level sync resets without a clock edge
+    for sim in [&mut a, &mut b].iter_mut() {
+        sim.set_u64("rst", 1).unwrap();
+        sim.update().unwrap();
+        assert_eq!(sim.get_u64("q2").unwrap(), Some(0));
+    }
+    check(&a, &b, steps.len());
+}
@@ -0,0 +1,138 @@
+use rtlil::backends::write_smt2;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn smt2(s: &str) -> String {
+    let d = parse(s);
+    let mut out = Vec::new();
+    write_smt2(&mut out, &d.modules()[0]).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// `$lt` and `$add` of a signed `\a` and an unsigned `\b`
+const MIXED: &str = r#"
+module \top
+  wire width 4 input 1 \a
+  wire width 4 input 2 \b
+  wire output 3 \lt
+  wire width 6 output 4 \sum
+  cell $lt $lt$1
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 1
+    connect \A \a
+    connect \B \b
+    connect \Y \lt
+  end
+  cell $add $add$2
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 4
+    parameter \Y_WIDTH 6
+    connect \A \a
+    connect \B \b
+    connect \Y \sum
+  end
+end
+"#;
+
+#[test]
+fn operands_are_signed_only_when_both_are() {
+    let out = smt2(MIXED);
+    assert!(out.contains("(bvult (|top#0| state) (|top#1| state))"));
+    assert!(out.contains(
+        "(bvadd ((_ zero_extend 2) (|top#0| state)) ((_ zero_extend 2) (|top#1| state)))"
+    ));
+
+    let out = smt2(&MIXED.replace("\\B_SIGNED 0", "\\B_SIGNED 1"));
+    assert!(out.contains("(bvslt (|top#0| state) (|top#1| state))"));
+    assert!(out.contains(
+        "(bvadd ((_ sign_extend 2) (|top#0| state)) ((_ sign_extend 2) (|top#1| state)))"
+    ));
+}
+
+// 2-bit register `\q` from 1, adding a constant `$k` and a free `\r`
+const COUNTER: &str = r#"
+module \top
+  wire input 1 \clk
+  attribute \init 2'01
+  wire width 2 output 2 \q
+  wire width 2 output 3 \r
+  wire width 2 $k
+  wire width 2 $s
+  wire width 2 $d
+  cell $anyconst $anyconst$1
+    parameter \WIDTH 2
+    connect \Y $k
+  end
+  cell $anyseq $anyseq$2
+    parameter \WIDTH 2
+    connect \Y \r
+  end
+  cell $add $add$3
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \q
+    connect \B $k
+    connect \Y $s
+  end
+  cell $add $add$4
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A $s
+    connect \B \r
+    connect \Y $d
+  end
+  cell $dff $dff$5
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D $d
+    connect \Q \q
+  end
+end
+"#;
+
+#[test]
+fn register_and_free_values() {
+    let expected = r#"; SMT-LIBv2 description generated by rtlil-rs
+; yosys-smt2-module top
+(declare-sort |top_s| 0)
+; yosys-smt2-input clk 1
+; yosys-smt2-register $dff$5 2
+(declare-fun |top#0| (|top_s|) (_ BitVec 1)) ; \clk
+(declare-fun |top#1| (|top_s|) (_ BitVec 2)) ; $anyconst$1
+(declare-fun |top#2| (|top_s|) (_ BitVec 2)) ; $anyseq$2
+(declare-fun |top#5| (|top_s|) (_ BitVec 2)) ; $dff$5
+(define-fun |top#3| ((state |top_s|)) (_ BitVec 2) (bvadd (|top#5| state) (|top#1| state))) ; $add$3
+(define-fun |top#4| ((state |top_s|)) (_ BitVec 2) (bvadd (|top#3| state) (|top#2| state))) ; $add$4
+; yosys-smt2-wire clk 1
+(define-fun |top_n clk| ((state |top_s|)) (_ BitVec 1) (|top#0| state))
+; yosys-smt2-output q 2
+; yosys-smt2-wire q 2
+(define-fun |top_n q| ((state |top_s|)) (_ BitVec 2) (|top#5| state))
+; yosys-smt2-output r 2
+; yosys-smt2-wire r 2
+(define-fun |top_n r| ((state |top_s|)) (_ BitVec 2) (|top#2| state))
+(define-fun |top_a| ((state |top_s|)) Bool true)
+(define-fun |top_u| ((state |top_s|)) Bool true)
+(define-fun |top_i| ((state |top_s|)) Bool (and (= ((_ extract 0 0) (|top#5| state)) #b1) (= ((_ extract 1 1) (|top#5| state)) #b0)))
+(define-fun |top_t| ((state |top_s|) (next_state |top_s|)) Bool (and (= (|top#4| state) (|top#5| next_state)) (= (|top#1| state) (|top#1| next_state))))
+; end of module top
+"#;
+    assert_eq!(smt2(COUNTER), expected);
+}
@@ -0,0 +1,192 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::stats::{stats, stats_with};
+use rtlil::syntax::*;
+use std::collections::HashMap;
+
+// `\top` instantiates `\mid` three times, which instantiates `\leaf`
+// twice; `\leaf` has a collected 8x4 memory
+const DESIGN: &str = r#"
+attribute \blackbox 1
+module \bb
+  wire input 1 \i
+end
+module \leaf
+  wire input 1 \a
+  wire output 2 \y
+  cell $and $and$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 1
+    parameter \B_WIDTH 1
+    parameter \Y_WIDTH 1
+    connect \A \a
+    connect \B \a
+    connect \Y \y
+  end
+  cell $mem_v2 \m
+    parameter \MEMID "\\m"
+    parameter \WIDTH 4
+    parameter \SIZE 8
+  end
+end
+module \mid
+  wire input 1 \a
+  wire $n
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 1
+    parameter \Y_WIDTH 1
+    connect \A \a
+    connect \Y $n
+  end
+  cell \leaf \l0
+    connect \a $n
+  end
+  cell \leaf \l1
+    connect \a \a
+  end
+end
+module \top
+  wire width 2 input 1 \a
+  wire width 4 $p
+  cell \mid \m0
+    connect \a \a [0]
+  end
+  cell \mid \m1
+    connect \a \a [1]
+  end
+  cell \mid \m2
+    connect \a \a [0]
+  end
+  cell \bb \b
+    connect \i \a [1]
+  end
+  cell $mul $mul$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B \a
+    connect \Y $p
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn costs() -> HashMap<String, f64> {
+    [
+        ("$and", 1.5),
+        ("$not", 0.5),
+        ("$mem_v2", 10.0),
+        ("\\bb", 7.0),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), *v))
+    .collect()
+}
+
+// the `\top` section and the hierarchy, where each module counts as many
+// times as it is instantiated
+const TEXT: &str = r#"=== \top ===
+
+   Number of wires:                     2
+   Number of wire bits:                 6
+   Number of public wires:              1
+   Number of public wire bits:          2
+   Number of memories:                  0
+   Number of memory bits:               0
+   Number of processes:                 0
+   Number of cells:                     5
+     $mul                               1
+     \bb                                1
+     \mid                               3
+
+   Chip area for module '\top': 7
+   Area for cell type $mul is unknown!
+
+=== design hierarchy ===
+
+   \top                                 1
+     \leaf                              6
+     \mid                               3
+
+   Number of wires:                    20
+   Number of wire bits:                24
+   Number of public wires:             16
+   Number of public wire bits:         17
+   Number of memories:                  6
+   Number of memory bits:             192
+   Number of processes:                 0
+   Number of cells:                    17
+     $and                               6
+     $mem_v2                            6
+     $mul                               1
+     $not                               3
+     \bb                                1
+
+   Chip area for design: 77.5
+   Area for cell type $mul is unknown!
+"#;
+
+#[test]
+fn text_report() {
+    let d = parse(DESIGN);
+    let text = stats_with(&d, &costs()).unwrap().to_string();
+    let top = text.find("=== \\top ===").unwrap();
+    assert_eq!(&text[top..], TEXT);
+    assert!(text.contains("   Chip area for module '\\leaf': 11.5\n"));
+
+    // without costs, there is no area
+    let text = stats(&d).unwrap().to_string();
+    assert!(!text.contains("area"));
+    assert!(!text.contains("unknown"));
+}
+
+#[test]
+fn json_report() {
+    let d = parse(DESIGN);
+    let s = stats_with(&d, &costs()).unwrap();
+    let v: serde_json::Value = serde_json::from_str(&s.to_json()).unwrap();
+    let leaf = &v["modules"]["\\leaf"];
+    assert_eq!(leaf["num_memories"], 1);
+    assert_eq!(leaf["num_memory_bits"], 32);
+    assert_eq!(leaf["num_cells_by_type"]["$mem_v2"], 1);
+    assert_eq!(leaf["area"], 11.5);
+    // instances of modules of the design are not missing costs
+    assert_eq!(
+        v["modules"]["\\mid"]["uncosted_cell_types"],
+        serde_json::json!([])
+    );
+    assert_eq!(
+        v["modules"]["\\top"]["uncosted_cell_types"],
+        serde_json::json!(["$mul"])
+    );
+
+    let h = &v["design"];
+    assert_eq!(h["top"], "\\top");
+    assert_eq!(
+        h["instances"],
+        serde_json::json!({"\\top": 1, "\\mid": 3, "\\leaf": 6})
+    );
+    assert_eq!(h["num_cells"], 17);
+    assert_eq!(h["num_cells_by_type"]["$and"], 6);
+    assert_eq!(h["num_cells_by_type"]["$not"], 3);
+    assert_eq!(h["num_cells_by_type"]["\\bb"], 1);
+    assert!(h["num_cells_by_type"].get("\\mid").is_none());
+    assert_eq!(h["num_memories"], 6);
+    assert_eq!(h["num_memory_bits"], 192);
+    assert_eq!(h["num_wires"], 20);
+    assert_eq!(h["num_pub_wire_bits"], 17);
+    assert_eq!(h["area"], 77.5);
+    assert_eq!(h["uncosted_cell_types"], serde_json::json!(["$mul"]));
+
+    let v: serde_json::Value = serde_json::from_str(&stats(&d).unwrap().to_json()).unwrap();
+    assert!(v["design"].get("area").is_none());
+    assert_eq!(v["design"]["num_memory_bits"], 192);
+}
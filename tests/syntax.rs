@@ -0,0 +1,107 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::syntax::*;
+
+use State::{Sx, S0, S1};
+
+const DESIGN: &str = r#"
+module \top
+  wire \a
+  wire width 4 \b
+  wire width 4 offset 2 \c
+  memory size 4 \mem
+  connect \a \b [0]
+  connect \b [3:1] { \c [5] 2'1x }
+  connect \c { }
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn sigspec_ranges() {
+    let d = parse(DESIGN);
+    let m = d.module("\\top").unwrap();
+    let c: Vec<String> = m
+        .connects()
+        .iter()
+        .map(|c| format!("{} {}", c.sig1(), c.sig2()))
+        .collect();
+    assert_eq!(c, ["\\a \\b [0]", "\\b [3:1] { \\c [5] 2'1x }", "\\c { }",]);
+    match &m.connects()[0].sig2() {
+        SigSpec::Refer((id, r)) => assert_eq!((id.as_str(), *r), ("\\b", Some((0, 0)))),
+        s => panic!("unexpected {:?}", s),
+    }
+    match &m.connects()[0].sig1() {
+        SigSpec::Refer((_, r)) => assert_eq!(*r, None),
+        s => panic!("unexpected {:?}", s),
+    }
+}
+
+#[test]
+fn default_widths() {
+    let d = parse(DESIGN);
+    let m = d.module("\\top").unwrap();
+    assert_eq!(*m.wire("\\a").unwrap().width(), 1);
+    assert_eq!(*m.memories()[0].width(), 1);
+}
+
+#[test]
+fn wire_table_bits() {
+    let d = parse(DESIGN);
+    let m = d.module("\\top").unwrap();
+    let wt = WireTable::new(m);
+    let c = m.connects();
+    assert_eq!(
+        wt.bits(c[0].sig2()).unwrap(),
+        [SigBit::Wire(("\\b".to_string(), 0))]
+    );
+    // indices of wires with an offset are shifted, lists are MSB first
+    assert_eq!(
+        wt.bits(c[1].sig2()).unwrap(),
+        [
+            SigBit::Const(Sx),
+            SigBit::Const(S1),
+            SigBit::Wire(("\\c".to_string(), 3)),
+        ]
+    );
+    assert_eq!(wt.width_of(c[1].sig1()).unwrap(), 3);
+    assert!(wt.bits(c[2].sig2()).unwrap().is_empty());
+    assert!(wt
+        .bits(&SigSpec::Refer(("\\b".to_string(), Some((7, 7)))))
+        .is_err());
+
+    let bits = wt.bits(c[1].sig1()).unwrap();
+    assert_eq!(wt.sigspec(&bits).to_string(), "\\b [3:1]");
+}
+
+#[test]
+fn const_bits() {
+    assert_eq!(Const::Int(5).to_bits()[..4], [S1, S0, S1, S0]);
+    assert_eq!(Const::Int(-1).to_bits(), vec![S1; 32]);
+    assert_eq!(Const::Str("A".to_string()).to_bits().len(), 8);
+    let c = Const::from_bits(&[S0, S1, S1]);
+    assert_eq!(c.to_string(), "3'110");
+    assert_eq!(c.as_int(), Some(6));
+    assert_eq!(Const::from_bits(&[S0, Sx]).as_int(), None);
+    assert!(Const::Int(2).as_bool());
+}
+
+#[test]
+fn fresh_ids() {
+    let mut m = Module::new("\\top".to_string(), vec![]);
+    let s = m.new_wire("tmp", 3);
+    let id = match &s {
+        SigSpec::Refer((id, None)) => id.clone(),
+        s => panic!("unexpected {:?}", s),
+    };
+    assert!(id.starts_with("$tmp$"));
+    assert_eq!(*m.wire(&id).unwrap().width(), 3);
+    let c = m.new_cell("$not", "not");
+    c.set_port("\\A", s.clone());
+    assert!(c.i2().starts_with("$not$"));
+    assert_eq!(c.port("\\A").unwrap().to_string(), id);
+    assert_ne!(new_id("tmp"), new_id("tmp"));
+}
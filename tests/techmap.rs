@@ -0,0 +1,156 @@
+use rtlil::eval::eval;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::techmap;
+use rtlil::sim::Simulator;
+use rtlil::syntax::*;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn bits(v: u64, w: usize) -> Vec<State> {
+    (0..w)
+        .map(|i| {
+            if (v >> i) & 1 == 1 {
+                State::S1
+            } else {
+                State::S0
+            }
+        })
+        .collect()
+}
+
+fn value(v: &[State]) -> u64 {
+    v.iter()
+        .enumerate()
+        .map(|(i, s)| ((*s == State::S1) as u64) << i)
+        .sum()
+}
+
+// Check the gates `techmap` gives for `cell` against `eval` of the cell
+// for every value of the inputs in `widths` where the result is defined.
+fn check(cell: &str, widths: &[(&str, usize)], y: usize) {
+    let mut wires = String::new();
+    for (i, (k, w)) in widths.iter().enumerate() {
+        wires += &format!(
+            "  wire width {} input {} \\{}\n",
+            w,
+            i + 1,
+            k.to_lowercase()
+        );
+    }
+    let src = format!(
+        "module \\top\n{}  wire width {} output {} \\y\n{}end\n",
+        wires,
+        y,
+        widths.len() + 1,
+        cell
+    );
+    let d = parse(&src);
+    let orig = d.module("\\top").unwrap().cells()[0].clone();
+    let mut n = d.clone();
+    let m = n.module_mut("\\top").unwrap();
+    assert_eq!(techmap(m).unwrap(), 1, "{}", orig.i1());
+    assert!(m.cells().iter().all(|c| c.i1().starts_with("$_")));
+
+    let mut sim = Simulator::new(&n, "\\top").unwrap();
+    let total: usize = widths.iter().map(|(_, w)| w).sum();
+    for v in 0..1u64 << total {
+        let mut inputs = Vec::new();
+        let mut shift = 0;
+        for (k, w) in widths {
+            let x = (v >> shift) & ((1 << w) - 1);
+            shift += w;
+            sim.set_u64(&k.to_lowercase(), x).unwrap();
+            inputs.push((format!("\\{}", k), bits(x, *w)));
+        }
+        sim.update().unwrap();
+        let input = |k: &str| inputs.iter().find(|(n, _)| n == k).map(|(_, v)| v.clone());
+        let want = eval(&orig, input).unwrap().remove(0).1;
+        if want.iter().any(|s| *s != State::S0 && *s != State::S1) {
+            continue;
+        }
+        assert_eq!(
+            sim.get_u64("y").unwrap(),
+            Some(value(&want)),
+            "{} with inputs {:?}",
+            orig.i1(),
+            inputs
+        );
+    }
+}
+
+fn binary(tp: &str, (sa, sb): (bool, bool), y: usize) -> String {
+    format!(
+        "  cell {} $c\n    parameter \\A_SIGNED {}\n    parameter \\B_SIGNED {}\n    \
+         parameter \\A_WIDTH 3\n    parameter \\B_WIDTH 2\n    parameter \\Y_WIDTH {}\n    \
+         connect \\A \\a\n    connect \\B \\b\n    connect \\Y \\y\n  end\n",
+        tp, sa as i32, sb as i32, y
+    )
+}
+
+#[test]
+fn binary_cells() {
+    let arith = [
+        "$and", "$or", "$xor", "$xnor", "$shl", "$shr", "$sshl", "$sshr", "$add", "$sub", "$mul",
+    ];
+    let logic = [
+        "$lt",
+        "$le",
+        "$eq",
+        "$ne",
+        "$ge",
+        "$gt",
+        "$logic_and",
+        "$logic_or",
+    ];
+    // mixed flags leave both operands unsigned, except for shifted values
+    let flags = [(false, false), (true, false), (false, true), (true, true)];
+    for (sa, sb) in flags.iter() {
+        for tp in arith.iter() {
+            // shift amounts stay unsigned
+            if *sb && tp.contains("sh") {
+                continue;
+            }
+            check(&binary(tp, (*sa, *sb), 4), &[("A", 3), ("B", 2)], 4);
+        }
+        for tp in logic.iter() {
+            check(&binary(tp, (*sa, *sb), 1), &[("A", 3), ("B", 2)], 1);
+        }
+    }
+}
+
+#[test]
+fn unary_cells() {
+    let cells = [
+        ("$not", 4),
+        ("$reduce_and", 1),
+        ("$reduce_or", 1),
+        ("$reduce_xor", 1),
+        ("$reduce_xnor", 1),
+        ("$reduce_bool", 1),
+        ("$logic_not", 1),
+    ];
+    for signed in [false, true].iter() {
+        for (tp, y) in cells.iter() {
+            let cell = format!(
+                "  cell {} $c\n    parameter \\A_SIGNED {}\n    parameter \\A_WIDTH 3\n    \
+                 parameter \\Y_WIDTH {}\n    connect \\A \\a\n    connect \\Y \\y\n  end\n",
+                tp, *signed as i32, y
+            );
+            check(&cell, &[("A", 3)], *y);
+        }
+    }
+}
+
+#[test]
+fn muxes() {
+    let mux = "  cell $mux $c\n    parameter \\WIDTH 2\n    connect \\A \\a\n    \
+               connect \\B \\b\n    connect \\S \\s\n    connect \\Y \\y\n  end\n";
+    check(mux, &[("A", 2), ("B", 2), ("S", 1)], 2);
+    let pmux = "  cell $pmux $c\n    parameter \\WIDTH 2\n    parameter \\S_WIDTH 2\n    \
+                connect \\A \\a\n    connect \\B \\b\n    connect \\S \\s\n    \
+                connect \\Y \\y\n  end\n";
+    check(pmux, &[("A", 2), ("B", 4), ("S", 2)], 2);
+}
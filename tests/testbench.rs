@@ -0,0 +1,202 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::sim::{Simulator, Stimulus, Vcd};
+use rtlil::syntax::*;
+use State::*;
+
+// accumulator of `\d` into `\q` from 0, and `\n` the inverse of `\d [0]`
+// computed by a submodule
+const ACC: &str = r#"
+module \inv
+  wire input 1 \i
+  wire output 2 \o
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 1
+    parameter \Y_WIDTH 1
+    connect \A \i
+    connect \Y \o
+  end
+end
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \d
+  attribute \init 2'00
+  wire width 2 output 3 \q
+  wire output 4 \n
+  wire width 2 $sum
+  cell $add $add$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \q
+    connect \B \d
+    connect \Y $sum
+  end
+  cell $dff $dff$2
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D $sum
+    connect \Q \q
+  end
+  cell \inv $inv$3
+    connect \i \d [0]
+    connect \o \n
+  end
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn values(v: &[(String, Vec<State>)]) -> Vec<(&str, &[State])> {
+    v.iter().map(|(n, s)| (n.as_str(), s.as_slice())).collect()
+}
+
+#[test]
+fn csv_values() {
+    let s = Stimulus::from_csv(
+        "# header\n\
+         d, q n\n\
+         01, 'h3, 1 # binary, hex\n\
+         \n\
+         'd2 x0 -\n",
+    )
+    .unwrap();
+    assert_eq!(s.vectors().len(), 2);
+    assert_eq!(*s.vectors()[1].time(), 1);
+    assert_eq!(
+        values(s.vectors()[0].values()),
+        [("d", &[S1, S0][..]), ("q", &[S1, S1]), ("n", &[S1])]
+    );
+    assert_eq!(
+        values(s.vectors()[1].values()),
+        [("d", &[S0, S1][..]), ("q", &[S0, Sx]), ("n", &[Sa])]
+    );
+}
+
+#[test]
+fn csv_replay_checks_every_cycle() {
+    let d = parse(ACC);
+    // d, then q after the clock edge
+    let table = "d q n\n01 01 0\n10 11 1\n11 10 0\n00 10 1\n";
+    let s = Stimulus::from_csv(table).unwrap();
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    assert!(s.replay(&mut sim, Some("clk")).unwrap().is_none());
+    assert_eq!(sim.cycle(), 4);
+
+    // undefined expected bits match anything
+    let s = Stimulus::from_csv("d q\n01 x1\n10 xx\n").unwrap();
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    assert!(s.replay(&mut sim, Some("clk")).unwrap().is_none());
+
+    let s = Stimulus::from_csv(&table.replace("11 10 0", "11 01 0")).unwrap();
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    let m = s.replay(&mut sim, Some("clk")).unwrap().expect("mismatch");
+    assert_eq!(*m.cycle(), 2);
+    assert_eq!(m.signal(), "q");
+    assert_eq!(m.expected(), &[S1, S0]);
+    assert_eq!(m.actual(), &[S0, S1]);
+    assert_eq!(m.to_string(), "cycle 2 (time 2): `q' is 10, expected 01");
+}
+
+// Simulate `ACC` with `d` following `inputs`, dumping it as a VCD.
+fn record(inputs: &[u64]) -> String {
+    let d = parse(ACC);
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    sim.set_u64("clk", 0).unwrap();
+    sim.update().unwrap();
+    let mut vcd = Vcd::new(Vec::new(), &sim, "1ns").unwrap();
+    for (i, v) in inputs.iter().enumerate() {
+        sim.set_u64("d", *v).unwrap();
+        sim.set_u64("clk", 0).unwrap();
+        sim.update().unwrap();
+        vcd.sample(&sim, 10 * i as u64).unwrap();
+        sim.set_u64("clk", 1).unwrap();
+        sim.update().unwrap();
+        vcd.sample(&sim, 10 * i as u64 + 5).unwrap();
+    }
+    String::from_utf8(vcd.finish().unwrap()).unwrap()
+}
+
+#[test]
+fn vcd_replay() {
+    let d = parse(ACC);
+    let text = record(&[1, 2, 2, 3, 0]);
+    let s = Stimulus::from_vcd(&text, &["top"]).unwrap();
+    // a vector per timestamp, each half cycle
+    assert_eq!(s.vectors().len(), 10);
+    assert_eq!(*s.vectors()[3].time(), 15);
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    assert!(s.replay(&mut sim, None).unwrap().is_none());
+    assert_eq!(sim.get_u64("q").unwrap(), Some(0));
+
+    // the submodule alone, in a scope escaped for its `$` name
+    assert!(text.contains("$scope module \\$inv$3 $end"));
+    let s = Stimulus::from_vcd(&text, &["top", "$inv$3"]).unwrap();
+    let mut sim = Simulator::new(&d, "\\inv").unwrap();
+    assert!(s.replay(&mut sim, None).unwrap().is_none());
+
+    // the recorded `q' does not match another design
+    let other = parse(&ACC.replace("connect \\B \\d", "connect \\B 2'01"));
+    let s = Stimulus::from_vcd(&text, &["top"]).unwrap();
+    let mut sim = Simulator::new(&other, "\\top").unwrap();
+    let m = s.replay(&mut sim, None).unwrap().expect("mismatch");
+    assert_eq!((*m.cycle(), *m.time(), m.signal().as_str()), (3, 15, "q"));
+}
+
+#[test]
+fn malformed_input() {
+    let err = |r: anyhow::Result<Stimulus>| r.unwrap_err().to_string();
+    assert_eq!(
+        err(Stimulus::from_csv("# nothing\n")),
+        "missing header line"
+    );
+    assert_eq!(
+        err(Stimulus::from_csv("d q\n1 1\n1\n")),
+        "line 3: 1 values for 2 signals"
+    );
+    assert_eq!(
+        err(Stimulus::from_csv("d\n12\n")),
+        "line 2: invalid bit `2' in `12'"
+    );
+    assert!(err(Stimulus::from_csv("d\n'hg\n")).starts_with("line 2: invalid value `'hg'"));
+
+    let header = "$scope module top $end\n$var wire 1 ! clk $end\n$upscope $end\n";
+    let vcd = |body: &str| Stimulus::from_vcd(&format!("{}{}", header, body), &["top"]);
+    assert_eq!(
+        err(Stimulus::from_vcd(header, &["dut"])),
+        "no variables in scope `dut'"
+    );
+    assert_eq!(
+        err(Stimulus::from_vcd(
+            "$scope module top $end $var wire w ! clk $end",
+            &["top"]
+        )),
+        "invalid width of variable `clk'"
+    );
+    assert_eq!(
+        err(Stimulus::from_vcd("clk $end", &["top"])),
+        "unexpected `clk' in VCD header"
+    );
+    assert_eq!(
+        err(vcd("$enddefinitions $end\n#1x\n")),
+        "invalid timestamp `#1x'"
+    );
+    assert_eq!(
+        err(vcd("$enddefinitions $end\n#0\n2!\n")),
+        "unexpected `2!' in VCD value changes"
+    );
+    assert_eq!(
+        err(vcd("$enddefinitions $end\n#0\nb01\n")),
+        "missing identifier after `b01'"
+    );
+    assert_eq!(
+        err(vcd("$enddefinitions $end\n#0\nb02 !\n")),
+        "invalid bit `2' in `02'"
+    );
+}
@@ -0,0 +1,68 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::passes::{uniquify, uniquify_tree};
+use rtlil::syntax::*;
+
+const DESIGN: &str = r#"
+module \top
+  cell \mid \a
+  end
+  cell \mid \b
+  end
+end
+module \mid
+  cell \leaf \x
+  end
+  cell \leaf \y
+  end
+  cell $not $n
+  end
+end
+module \leaf
+end
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+fn tp(d: &Design, module: &str, cell: &str) -> String {
+    d.module(module).unwrap().cell(cell).unwrap().i1().clone()
+}
+
+#[test]
+fn uniquify_path() {
+    let mut d = parse(DESIGN);
+    let name = uniquify(&mut d, &["\\top", "\\a", "\\x"]).unwrap();
+    assert_eq!(name, "\\top.a.x");
+    // the shared parent is copied on the way, other instances are untouched
+    assert_eq!(tp(&d, "\\top", "\\a"), "\\top.a");
+    assert_eq!(tp(&d, "\\top", "\\b"), "\\mid");
+    assert_eq!(tp(&d, "\\top.a", "\\x"), "\\top.a.x");
+    assert_eq!(tp(&d, "\\top.a", "\\y"), "\\leaf");
+    assert_eq!(tp(&d, "\\mid", "\\x"), "\\leaf");
+    assert!(d
+        .module("\\top.a")
+        .unwrap()
+        .attrs()
+        .contains_key("\\unique"));
+
+    let n = d.modules().len();
+    assert_eq!(uniquify(&mut d, &["\\top", "\\a", "\\x"]).unwrap(), name);
+    assert_eq!(d.modules().len(), n);
+
+    assert!(uniquify(&mut d, &["\\top", "\\c"]).is_err());
+    assert!(uniquify(&mut d, &["\\top", "\\b", "$n"]).is_err());
+    assert!(uniquify(&mut d, &[]).is_err());
+}
+
+#[test]
+fn uniquify_subtree() {
+    let mut d = parse(DESIGN);
+    // \mid for \a, then both leaves below it
+    assert_eq!(uniquify_tree(&mut d, &["\\top", "\\a"]).unwrap(), 3);
+    assert_eq!(tp(&d, "\\top.a", "\\x"), "\\top.a.x");
+    assert_eq!(tp(&d, "\\top.a", "\\y"), "\\top.a.y");
+    assert_eq!(tp(&d, "\\mid", "\\x"), "\\leaf");
+    assert_eq!(uniquify_tree(&mut d, &["\\top", "\\a"]).unwrap(), 0);
+}
@@ -0,0 +1,99 @@
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+use rtlil::sim::{Simulator, Vcd};
+use rtlil::syntax::*;
+
+const DESIGN: &str = r#"
+module \sub
+  wire input 1 \i
+  wire output 2 \o
+  wire $n
+  cell $not $not$1
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 1
+    parameter \Y_WIDTH 1
+    connect \A \i
+    connect \Y $n
+  end
+  connect \o $n
+end
+module \top
+  wire input 1 \clk
+  wire width 2 input 2 \d
+  wire width 2 output 3 \q
+  wire \a[3]
+  wire width 2 upto offset 4 \v
+  wire $tmp
+  cell $dff $dff$1
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \d
+    connect \Q \q
+  end
+  cell \sub $sub$2
+    connect \i \clk
+    connect \o \a[3]
+  end
+  connect $tmp \q [0]
+  connect \v \d
+end
+"#;
+
+// Wires sharing nets, like `\d` and `\v` or `\a[3]` and the output of the
+// submodule, share a variable
+const GOLDEN: &str = r#"$version rtlil-rs $end
+$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 2 " d [1:0] $end
+$var wire 2 # q [1:0] $end
+$var wire 1 $ a[3] [0:0] $end
+$var wire 2 " v [4:5] $end
+$var wire 1 % \$tmp $end
+$scope module \$sub$2 $end
+$var wire 1 ! i $end
+$var wire 1 $ o $end
+$var wire 1 $ \$n $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b10 "
+bxx #
+1$
+x%
+#5
+1!
+b10 #
+0$
+0%
+#10
+b01 "
+"#;
+
+fn parse(s: &str) -> Design {
+    Parser::new().parse(Lexer::new(s.chars())).unwrap()
+}
+
+#[test]
+fn names_are_escaped_like_yosys() {
+    let d = parse(DESIGN);
+    let mut sim = Simulator::new(&d, "\\top").unwrap();
+    let mut vcd = Vcd::new(Vec::new(), &sim, "1ns").unwrap();
+    sim.set_u64("clk", 0).unwrap();
+    sim.set_u64("d", 2).unwrap();
+    sim.update().unwrap();
+    vcd.sample(&sim, 0).unwrap();
+    sim.set_u64("clk", 1).unwrap();
+    sim.update().unwrap();
+    vcd.sample(&sim, 5).unwrap();
+    // nothing changes, so nothing is written
+    vcd.sample(&sim, 7).unwrap();
+    sim.set_u64("d", 1).unwrap();
+    sim.update().unwrap();
+    vcd.sample(&sim, 10).unwrap();
+    let out = String::from_utf8(vcd.finish().unwrap()).unwrap();
+    assert_eq!(out, GOLDEN);
+}
@@ -0,0 +1,285 @@
+use rtlil::backends::write_verilog;
+use rtlil::lexer::Lexer;
+use rtlil::parser::Parser;
+
+fn write(s: &str) -> anyhow::Result<String> {
+    let d = Parser::new().parse(Lexer::new(s.chars())).unwrap();
+    let mut out = Vec::new();
+    write_verilog(&mut out, &d)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+const ALL: &str = r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \rst
+  wire width 4 input 3 \a
+  wire width 2 input 4 \b
+  wire input 5 \s
+  wire width 2 output 6 \y1
+  wire width 2 output 7 \y2
+  wire width 4 output 8 \x
+  wire width 4 output 9 \co
+  wire width 4 output 10 \sum
+  wire width 4 output 11 \lco
+  wire width 4 output 12 \df
+  wire width 4 output 13 \mf
+  wire width 2 output 14 \bw
+  wire width 2 output 15 \eqx
+  wire output 16 \m4
+  wire width 2 output 17 \gq
+  attribute \init 2'01
+  wire width 2 output 18 \q
+  wire width 2 $q
+  wire width 2 output 19 \pq
+  wire width 4 output 20 \any
+  cell $shiftx $shiftx$1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A \a
+    connect \B \b
+    connect \Y \y1
+  end
+  cell $shiftx $shiftx$2
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 1
+    parameter \A_WIDTH 3
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A { \b \s }
+    connect \B \b
+    connect \Y \y2
+  end
+  cell $alu $alu$3
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 1
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B \b
+    connect \BI \s
+    connect \CI \s
+    connect \X \x
+    connect \Y \sum
+    connect \CO \co
+  end
+  cell $lcu $lcu$4
+    parameter \WIDTH 4
+    connect \P \a
+    connect \G \x
+    connect \CI \s
+    connect \CO \lco
+  end
+  cell $divfloor $divfloor$5
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 1
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B \b
+    connect \Y \df
+  end
+  cell $modfloor $modfloor$6
+    parameter \A_SIGNED 1
+    parameter \B_SIGNED 1
+    parameter \A_WIDTH 4
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 4
+    connect \A \a
+    connect \B \b
+    connect \Y \mf
+  end
+  cell $bwmux $bwmux$7
+    parameter \WIDTH 2
+    connect \A \a [1:0]
+    connect \B \a [3:2]
+    connect \S \b
+    connect \Y \bw
+  end
+  cell $bweqx $bweqx$8
+    parameter \WIDTH 2
+    connect \A \a [1:0]
+    connect \B \b
+    connect \Y \eqx
+  end
+  cell $_MUX4_ $mux$9
+    connect \A \a [0]
+    connect \B \a [1]
+    connect \C \a [2]
+    connect \D \a [3]
+    connect \S \b [0]
+    connect \T \b [1]
+    connect \Y \m4
+  end
+  cell $ff $ff$10
+    parameter \WIDTH 2
+    connect \D \b
+    connect \Q \gq
+  end
+  cell $dff $dff$11
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \D \b
+    connect \Q $q
+  end
+  connect \q $q
+  cell $assert $assert$12
+    connect \A \s
+    connect \EN \rst
+  end
+  cell $anyconst $anyconst$13
+    parameter \WIDTH 4
+    connect \Y \any
+  end
+  process $proc$top.v:1$14
+    sync posedge \clk
+      update \pq \b
+    sync high \rst
+      update \pq 2'00
+  end
+end
+"#;
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|l| l.trim().to_string()).collect()
+}
+
+fn has(v: &[String], l: &str) -> bool {
+    v.iter().any(|x| x == l)
+}
+
+#[test]
+fn cells_without_instances() {
+    let v = write(ALL).unwrap();
+    let l = lines(&v);
+    // out of range bits of `$shiftx` read as `x`
+    assert!(has(&l, "assign y1 = a[b +: 2];"));
+    assert!(has(&l, "wire [2:0] \\$shiftx$2$a ;"));
+    assert!(has(&l, "assign y2 = \\$shiftx$2$a [$signed(b) +: 2];"));
+
+    let b = "({ b[1], b[1], b } ^ {4{s}})";
+    assert!(has(&l, &format!("assign x = a ^ {};", b)));
+    assert!(has(&l, &format!("assign sum = a + {} + s;", b)));
+    assert!(has(&l, "assign \\$lcu$4$co [0] = x[0] | (a[0] & s);"));
+    assert!(has(
+        &l,
+        "assign \\$lcu$4$co [3] = x[3] | (a[3] & \\$lcu$4$co [2]);"
+    ));
+    assert!(has(&l, "assign lco = \\$lcu$4$co ;"));
+    assert!(has(
+        &l,
+        "assign df = $signed(\\$divfloor$5$div ) / $signed(b);"
+    ));
+    assert!(has(
+        &l,
+        "assign \\$modfloor$6$mod  = $signed(a) % $signed(b);"
+    ));
+    assert!(has(&l, "assign bw = (a[1:0] & ~b) | (a[3:2] & b);"));
+    assert!(has(&l, "assign eqx = { a[1] === b[1], a[0] === b[0] };"));
+    assert!(has(
+        &l,
+        "assign m4 = b[1] ? (b[0] ? a[3] : a[2]) : (b[0] ? a[1] : a[0]);"
+    ));
+    assert!(has(&l, "always @* if (rst) assert(s);"));
+    assert!(has(&l, "assign any = $anyconst;"));
+    // nothing is left as an instance
+    assert!(!l.iter().any(|x| x.ends_with(" (")));
+}
+
+#[test]
+fn storage() {
+    let l = lines(&write(ALL).unwrap());
+    assert!(has(&l, "output reg [1:0] gq;"));
+    assert!(has(&l, "always @($global_clock)"));
+    assert!(has(&l, "gq <= b;"));
+    // the initial value of `\q` moves to the register driving it
+    assert!(has(&l, "reg [1:0] \\$q  = 2'b01;"));
+    assert!(has(&l, "assign q = \\$q ;"));
+
+    // one block for the level and edge rules of the process
+    let i = l
+        .iter()
+        .position(|x| x == "always @(posedge clk, posedge rst) begin")
+        .unwrap();
+    assert_eq!(
+        l[i + 1..i + 7],
+        [
+            "if (rst) begin",
+            "pq <= 2'b00;",
+            "end else begin",
+            "pq <= b;",
+            "end",
+            "end"
+        ]
+    );
+    assert_eq!(l.iter().filter(|x| x.contains("pq <=")).count(), 2);
+}
+
+#[test]
+fn partial_register_init() {
+    let v = write(
+        r#"
+module \top
+  wire input 1 \clk
+  wire input 2 \d
+  attribute \init 2'10
+  wire width 2 output 3 \q
+  cell $dff $dff$1
+    parameter \CLK_POLARITY 1
+    parameter \WIDTH 1
+    connect \CLK \clk
+    connect \D \d
+    connect \Q \q [1]
+  end
+  cell $pos $pos$2
+    parameter \A_SIGNED 0
+    parameter \A_WIDTH 0
+    parameter \Y_WIDTH 1
+    connect \A { }
+    connect \Y \q [0]
+  end
+end
+"#,
+    )
+    .unwrap();
+    let l = lines(&v);
+    assert!(has(&l, "reg \\$dff$1$reg  = 1'b1;"));
+    assert!(has(&l, "assign q[1] = \\$dff$1$reg ;"));
+    // `\q` itself is no register and keeps the attribute
+    assert!(has(&l, "(* init = 2'b10 *)"));
+    assert!(has(&l, "assign q[0] = {0{1'b0}};"));
+}
+
+#[test]
+fn unsupported() {
+    let cell = |tp: &str| {
+        format!(
+            "module \\top\n  wire \\a\n  cell {} $c\n    connect \\A \\a\n  end\nend\n",
+            tp
+        )
+    };
+    assert!(write(&cell("$macc")).is_err());
+    assert!(write(&cell("$live")).is_err());
+    assert!(write(&cell("\\sub")).is_ok());
+
+    let mixed = r#"
+module \top
+  wire input 1 \clk
+  wire \a
+  wire \q
+  process $proc$1
+    sync always
+      update \q \a
+    sync posedge \clk
+      update \q \a
+  end
+end
+"#;
+    assert!(write(mixed).is_err());
+}